@@ -0,0 +1,54 @@
+//! Compares sending a scalar command with a new index map per command with the
+//! commands of `Actuator`, which reuse their index map, on a device with two features
+//!
+//! cargo bench --bench commands
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use bp_fakes::get_test_client;
+use bp_scheduler::actuator::Actuators;
+use buttplug::{
+    client::ScalarCommand,
+    core::message::{
+        ActuatorType, ClientDeviceMessageAttributesBuilder, ClientGenericDeviceMessageAttributes, DeviceAdded,
+    },
+};
+
+const ITERATIONS: u32 = 10_000;
+
+async fn measure<F, R>(name: &str, send: F) -> Duration
+where
+    F: Fn(f64) -> R,
+    R: Future,
+{
+    let started = Instant::now();
+    for i in 0..ITERATIONS {
+        send(i as f64 / ITERATIONS as f64).await;
+    }
+    let elapsed = started.elapsed();
+    println!("{:<16} {:>8.1} us/command", name, elapsed.as_micros() as f64 / ITERATIONS as f64);
+    elapsed
+}
+
+#[tokio::main]
+async fn main() {
+    let attribute = |name: &str| ClientGenericDeviceMessageAttributes::new(name, 20, ActuatorType::Vibrate);
+    let mut attributes = ClientDeviceMessageAttributesBuilder::default();
+    attributes.scalar_cmd(&[attribute("vib1"), attribute("vib2")]);
+    let client = get_test_client(vec![DeviceAdded::new(1, "vib", &None, &None, &attributes.finish())]).await;
+    let actuator = client.created_devices.flatten_actuators().remove(1);
+
+    let new_map = measure("new map", |value| {
+        actuator.device.scalar(&ScalarCommand::ScalarMap(HashMap::from([(
+            actuator.index_in_device,
+            (value, actuator.actuator),
+        )])))
+    })
+    .await;
+    let reused_map = measure("Actuator::scalar", |value| actuator.scalar(value)).await;
+    println!("speedup {:.2}x", new_map.as_secs_f64() / reused_map.as_secs_f64());
+}
@@ -1,19 +1,40 @@
-use buttplug::client::ButtplugClientDevice;
-use buttplug::core::message::ActuatorType;
+use buttplug::client::{ButtplugClientDevice, ButtplugClientError, LinearCommand, RotateCommand, ScalarCommand};
+use futures::future::BoxFuture;
+use buttplug::core::message::{ActuatorType, ClientDeviceMessageAttributes};
 use tracing::trace;
 use std::{
-    collections::HashMap, fmt::{self, Display}, ops::Deref, sync::Arc
+    collections::HashMap, fmt::{self, Display}, ops::Deref, sync::{Arc, Mutex}
 };
 
-use crate::actuators::{ActuatorConfig, ActuatorSettings};
+use crate::actuators::{suggest_body_parts, ActuatorConfig, ActuatorSettings};
+use crate::config::connection::Transport;
+use crate::config::logging::redact;
+use crate::config::linear::{apply_profiles, ScalingProfile};
+use crate::config::scalar::RotatePlayback;
+use crate::ActuatorLimits;
 
 #[derive(Clone)]
 pub struct Actuator {
     pub device: Arc<ButtplugClientDevice>,
     pub actuator: ActuatorType,
     pub index_in_device: u32,
+    /// message type that controls the actuator
+    pub command: ActuatorCommand,
     pub config: Option<ActuatorConfig>,
+    /// transport of the device, only XInput is known before the client sets it
+    pub transport: Transport,
+    /// name of the remote server the device is connected to, if any
+    pub server_name: Option<String>,
     identifier: String,
+    commands: Arc<Mutex<CommandBuffer>>,
+}
+
+/// Index map commands of an actuator that shares its device with other features
+#[derive(Default)]
+struct CommandBuffer {
+    scalar: Option<ScalarCommand>,
+    linear: Option<LinearCommand>,
+    rotate: Option<RotateCommand>,
 }
 
 impl Actuator {
@@ -21,17 +42,47 @@ impl Actuator {
         device: &Arc<ButtplugClientDevice>,
         actuator: ActuatorType,
         index_in_device: usize
+    ) -> Self {
+        let command = match actuator {
+            ActuatorType::Position => ActuatorCommand::Linear,
+            ActuatorType::Rotate if !is_scalar_rotation(device.message_attributes(), index_in_device) => ActuatorCommand::Rotate,
+            _ => ActuatorCommand::Scalar,
+        };
+        Actuator::with_command(device, actuator, index_in_device, command)
+    }
+
+    /// Like `new` for an actuator that is known to be controlled with 'command'
+    pub fn with_command(
+        device: &Arc<ButtplugClientDevice>,
+        actuator: ActuatorType,
+        index_in_device: usize,
+        command: ActuatorCommand,
     ) -> Self {
         let identifier = Actuator::get_identifier(device, actuator, index_in_device);
+        let transport = match Transport::is_xinput_name(device.name()) {
+            true => Transport::XInput,
+            false => Transport::Unknown,
+        };
         Actuator {
             device: device.clone(),
             actuator,
             index_in_device: index_in_device as u32,
+            command,
             identifier,
-            config: None
+            config: None,
+            transport,
+            server_name: None,
+            commands: Default::default(),
         }
     }
 
+    /// Sets the transport of the device and the remote server it is connected to
+    pub fn with_transport(mut self, transport: Transport, server_name: Option<String>) -> Self {
+        self.transport = transport;
+        self.server_name = server_name;
+        self
+    }
+
     pub fn identifier(&self) -> &str {
         &self.identifier
     }
@@ -54,17 +105,74 @@ impl Actuator {
         }
     }
 
+    /// True if the actuator can play positional patterns, either as
+    /// linear actuator or as rotate actuator with alternating playback
+    pub fn plays_positions(&self) -> bool {
+        match self.actuator {
+            ActuatorType::Position => true,
+            ActuatorType::Rotate => matches!(
+                self.get_config().limits,
+                ActuatorLimits::Scalar(range) if range.rotate_playback == RotatePlayback::Alternating
+            ),
+            _ => false,
+        }
+    }
+
+    /// Sets the scalar value of this actuator. Devices with a single scalar feature
+    /// are addressed directly, others with an index map that is reused for all
+    /// commands of the actuator
+    pub fn scalar(&self, value: f64) -> BoxFuture<'static, Result<(), ButtplugClientError>> {
+        if self.is_only_feature(self.device.message_attributes().scalar_cmd()) {
+            return self.device.scalar(&ScalarCommand::Scalar((value, self.actuator)));
+        }
+        let mut commands = self.commands.lock().unwrap();
+        let command = commands.scalar.get_or_insert_with(|| ScalarCommand::ScalarMap(HashMap::with_capacity(1)));
+        if let ScalarCommand::ScalarMap(map) = command {
+            map.insert(self.index_in_device, (value, self.actuator));
+        }
+        self.device.scalar(command)
+    }
+
+    /// Moves this actuator to 'position', see `scalar`
+    pub fn linear(&self, duration_ms: u32, position: f64) -> BoxFuture<'static, Result<(), ButtplugClientError>> {
+        if self.is_only_feature(self.device.message_attributes().linear_cmd()) {
+            return self.device.linear(&LinearCommand::Linear(duration_ms, position));
+        }
+        let mut commands = self.commands.lock().unwrap();
+        let command = commands.linear.get_or_insert_with(|| LinearCommand::LinearMap(HashMap::with_capacity(1)));
+        if let LinearCommand::LinearMap(map) = command {
+            map.insert(self.index_in_device, (duration_ms, position));
+        }
+        self.device.linear(command)
+    }
+
+    /// Rotates this actuator, see `scalar`
+    pub fn rotate(&self, speed: f64, clockwise: bool) -> BoxFuture<'static, Result<(), ButtplugClientError>> {
+        if self.is_only_feature(self.device.message_attributes().rotate_cmd()) {
+            return self.device.rotate(&RotateCommand::Rotate(speed, clockwise));
+        }
+        let mut commands = self.commands.lock().unwrap();
+        let command = commands.rotate.get_or_insert_with(|| RotateCommand::RotateMap(HashMap::with_capacity(1)));
+        if let RotateCommand::RotateMap(map) = command {
+            map.insert(self.index_in_device, (speed, clockwise));
+        }
+        self.device.rotate(command)
+    }
+
+    fn is_only_feature<T>(&self, attributes: &Option<Vec<T>>) -> bool {
+        self.index_in_device == 0 && attributes.as_ref().is_some_and(|x| x.len() == 1)
+    }
 }
 
 impl Display for Actuator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.identifier)
+        write!(f, "{}", redact(&self.identifier))
     }
 }
 
 impl fmt::Debug for Actuator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Actuator({})", self.identifier)
+        write!(f, "Actuator({})", redact(&self.identifier))
     }
 }
 
@@ -80,32 +188,138 @@ impl Actuators for Vec<Arc<ButtplugClientDevice>> {
 
 impl Actuators for &Arc<ButtplugClientDevice> {
     fn flatten_actuators(&self) -> Vec<Arc<Actuator>> {
-        let mut actuators = vec![];
-        if let Some(scalar_cmd) = self.message_attributes().scalar_cmd() {
-            for (idx, scalar_cmd) in scalar_cmd.iter().enumerate() {
-                actuators.push(Actuator::new(self, *scalar_cmd.actuator_type(), idx))
+        self.message_attributes()
+            .actuator_features()
+            .into_iter()
+            .map(|feature| Arc::new(Actuator::with_command(self, feature.actuator, feature.index, feature.command)))
+            .collect()
+    }
+}
+
+/// Whether the rotate actuator at 'index' is controlled with scalar commands
+fn is_scalar_rotation(attributes: &ClientDeviceMessageAttributes, index: usize) -> bool {
+    attributes
+        .scalar_cmd()
+        .as_ref()
+        .and_then(|x| x.get(index))
+        .is_some_and(|x| *x.actuator_type() == ActuatorType::Rotate)
+}
+
+/// Message type that is used to control an actuator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActuatorCommand {
+    Scalar,
+    Linear,
+    Rotate,
+}
+
+/// Protocol independent description of a single actuator of a device
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActuatorFeature {
+    pub actuator: ActuatorType,
+    pub command: ActuatorCommand,
+    /// index of the actuator within the commands of its message type
+    pub index: usize,
+    pub step_count: u32,
+    pub descriptor: String,
+}
+
+/// Anything that describes the actuators of a device. Changes to the buttplug
+/// device model only require a new implementation, not changes to Filter/DeviceAccess
+pub trait ActuatorFeatures {
+    fn actuator_features(&self) -> Vec<ActuatorFeature>;
+}
+
+/// Spec v3: one attribute list per message type
+impl ActuatorFeatures for ClientDeviceMessageAttributes {
+    fn actuator_features(&self) -> Vec<ActuatorFeature> {
+        let mut features = vec![];
+        let lists = [
+            (self.scalar_cmd(), ActuatorCommand::Scalar),
+            (self.linear_cmd(), ActuatorCommand::Linear),
+            (self.rotate_cmd(), ActuatorCommand::Rotate),
+        ];
+        for (attributes, command) in lists {
+            for (index, attribute) in attributes.iter().flatten().enumerate() {
+                features.push(ActuatorFeature {
+                    actuator: match command {
+                        ActuatorCommand::Scalar => *attribute.actuator_type(),
+                        ActuatorCommand::Linear => ActuatorType::Position,
+                        ActuatorCommand::Rotate => ActuatorType::Rotate,
+                    },
+                    command,
+                    index,
+                    step_count: *attribute.step_count(),
+                    descriptor: attribute.feature_descriptor().clone(),
+                });
             }
         }
-        if let Some(linear_cmd) = self.message_attributes().linear_cmd() {
-            for (idx, _) in linear_cmd.iter().enumerate() {
-                actuators.push(Actuator::new(self, ActuatorType::Position, idx));
+        features
+    }
+}
+
+/// A feature of the feature-based device model (spec v4), where each
+/// feature lists the commands that can address it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceFeature {
+    pub description: String,
+    pub feature_type: ActuatorType,
+    pub step_count: u32,
+    pub commands: Vec<ActuatorCommand>,
+}
+
+/// Spec v4: actuator indices are counted per command in feature order
+impl ActuatorFeatures for Vec<DeviceFeature> {
+    fn actuator_features(&self) -> Vec<ActuatorFeature> {
+        let mut features = vec![];
+        for command in [ActuatorCommand::Scalar, ActuatorCommand::Linear, ActuatorCommand::Rotate] {
+            let addressable = self.iter().filter(|x| x.commands.contains(&command));
+            for (index, feature) in addressable.enumerate() {
+                features.push(ActuatorFeature {
+                    actuator: feature.feature_type,
+                    command,
+                    index,
+                    step_count: feature.step_count,
+                    descriptor: feature.description.clone(),
+                });
             }
         }
-        if let Some(rotate_cmd) = self.message_attributes().rotate_cmd() {
-            for (idx, _) in rotate_cmd.iter().enumerate() {
-                actuators.push(Actuator::new(self, ActuatorType::Rotate, idx))
-            }
+        features
+    }
+}
+
+/// Either device representation, buttplug 7 clients only provide message attributes
+#[derive(Debug, Clone)]
+pub enum DeviceModel {
+    MessageAttributes(ClientDeviceMessageAttributes),
+    Features(Vec<DeviceFeature>),
+}
+
+impl ActuatorFeatures for DeviceModel {
+    fn actuator_features(&self) -> Vec<ActuatorFeature> {
+        match self {
+            DeviceModel::MessageAttributes(attributes) => attributes.actuator_features(),
+            DeviceModel::Features(features) => features.actuator_features(),
         }
-        actuators.into_iter().map(Arc::new).collect()
     }
 }
 
 pub trait ActuatorConfigLoader {
+    /// Attaches the config of each actuator, configs that are created get the
+    /// body parts suggested for the device
     fn load_config(self, config: &mut ActuatorSettings) -> Vec<Arc<Actuator>>;
+
+    /// Like `load_config`, configs that are created are pre-filled by the matching
+    /// profiles, see `apply_profiles`
+    fn load_config_with_profiles(self, config: &mut ActuatorSettings, profiles: &[ScalingProfile]) -> Vec<Arc<Actuator>>;
 }
 
 impl ActuatorConfigLoader for Vec<Arc<Actuator>> {
     fn load_config(self, config: &mut ActuatorSettings) -> Vec<Arc<Actuator>> {
+        self.load_config_with_profiles(config, &[])
+    }
+
+    fn load_config_with_profiles(self, config: &mut ActuatorSettings, profiles: &[ScalingProfile]) -> Vec<Arc<Actuator>> {
         fn get_dedup_index(map: &mut HashMap<String, u32>, actuator_id: &str) -> u32 {
             let new_value = if let Some(i) = map.get(actuator_id) {
                 i + 1
@@ -125,8 +339,18 @@ impl ActuatorConfigLoader for Vec<Arc<Actuator>> {
             } else {
                 actuator.identifier.to_owned()
             };
+            let actuator_config = match config.find(&actuator_config_id) {
+                Some(existing) => existing.clone(),
+                None => {
+                    let mut actuator_config = ActuatorConfig::created(&actuator_config_id);
+                    actuator_config.suggested_body_parts = suggest_body_parts(actuator.device.name());
+                    apply_profiles(profiles, actuator.device.name(), actuator.actuator, &mut actuator_config);
+                    config.update_device(actuator_config.clone());
+                    actuator_config
+                }
+            };
             results.push(Arc::new( Actuator {
-                config: Some(config.get_or_create(&actuator_config_id)),
+                config: Some(actuator_config),
                 .. actuator.deref().clone()
             } ));
         }
@@ -137,4 +361,48 @@ impl ActuatorConfigLoader for Vec<Arc<Actuator>> {
         }
         results
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use buttplug::core::message::{
+        ClientDeviceMessageAttributesBuilder, ClientGenericDeviceMessageAttributes,
+    };
+
+    use super::*;
+
+    fn feature(description: &str, feature_type: ActuatorType, commands: Vec<ActuatorCommand>) -> DeviceFeature {
+        DeviceFeature {
+            description: description.into(),
+            feature_type,
+            step_count: 20,
+            commands,
+        }
+    }
+
+    #[test]
+    fn message_attributes_and_features_describe_same_actuators() {
+        let attribute = |name: &str, actuator| ClientGenericDeviceMessageAttributes::new(name, 20, actuator);
+        let mut builder = ClientDeviceMessageAttributesBuilder::default();
+        builder.scalar_cmd(&[
+            attribute("vib", ActuatorType::Vibrate),
+            attribute("rot", ActuatorType::Rotate),
+        ]);
+        builder.linear_cmd(&[attribute("lin", ActuatorType::Position)]);
+        builder.rotate_cmd(&[attribute("rot", ActuatorType::Rotate)]);
+        let v3 = DeviceModel::MessageAttributes(builder.finish());
+
+        let v4 = DeviceModel::Features(vec![
+            feature("vib", ActuatorType::Vibrate, vec![ActuatorCommand::Scalar]),
+            feature("rot", ActuatorType::Rotate, vec![ActuatorCommand::Scalar, ActuatorCommand::Rotate]),
+            feature("lin", ActuatorType::Position, vec![ActuatorCommand::Linear]),
+        ]);
+
+        let features = v3.actuator_features();
+        assert_eq!(features, v4.actuator_features());
+        assert_eq!(features.len(), 4);
+        assert_eq!(features[1].actuator, ActuatorType::Rotate);
+        assert_eq!(features[1].command, ActuatorCommand::Scalar);
+        assert_eq!(features[3].command, ActuatorCommand::Rotate);
+        assert_eq!(features[3].index, 0);
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+use tracing::info;
+
+use crate::{config::actions::{Action, Strength}, player::options::Transposition, speed::Speed};
+
+use super::{BpClient, DispatchResult};
+
+/// Arguments of a single `dispatch_refs` call
+#[derive(Debug, Clone)]
+pub struct DispatchRequest {
+    pub actions: Vec<(Strength, Action)>,
+    pub body_parts: Vec<String>,
+    pub speed: Speed,
+    pub duration: Duration,
+    pub transposition: Transposition,
+}
+
+pub type ExecutionResult = DispatchResult;
+
+impl BpClient {
+    /// Dispatches several requests at once, e.g. events that were fired in the same frame.
+    ///
+    /// All requests are resolved against the same device snapshot and
+    /// spawned on the runtime together, results are in request order
+    pub fn execute_batch(&mut self, requests: Vec<DispatchRequest>) -> Vec<ExecutionResult> {
+        info!(count = requests.len(), "execute_batch");
+        let snapshot = self.device_snapshot();
+        let mut results = vec![];
+        let mut tasks = vec![];
+        for request in requests {
+            let (result, request_tasks) = self.prepare_refs(
+                request.actions,
+                request.body_parts,
+                request.speed,
+                request.duration,
+                request.transposition,
+                &snapshot,
+            );
+            results.push(result);
+            tasks.extend(request_tasks);
+        }
+        self.runtime.spawn(async move {
+            for task in tasks {
+                Handle::current().spawn(task);
+            }
+        });
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use buttplug::core::message::ActuatorType;
+
+    use bp_fakes::*;
+
+    use crate::client::tests::wait_for_connection;
+    use crate::client::{Control, ScalarActuator, Selector};
+
+    use super::*;
+
+    #[test]
+    fn execute_batch_dispatches_all_requests() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Inflate),
+            ],
+            None,
+            None,
+        );
+        let request = |actuator: ScalarActuator, speed: i32| DispatchRequest {
+            actions: vec![(
+                Strength::Constant(speed),
+                Action::new("foobar", vec![Control::Scalar(Selector::All, vec![actuator])]),
+            )],
+            body_parts: vec![],
+            speed: Speed::max(),
+            transposition: Transposition::default(),
+            duration: Duration::from_millis(1),
+        };
+
+        // act
+        let results = tk.execute_batch(vec![
+            request(ScalarActuator::Vibrate, 100),
+            request(ScalarActuator::Inflate, 50),
+        ]);
+        thread::sleep(Duration::from_secs(1));
+
+        // assert
+        assert_eq!(results.len(), 2);
+        assert_ne!(results[0].handle, results[1].handle);
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.get_device(2)[0].assert_strenth(0.5);
+    }
+}
@@ -0,0 +1,90 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use buttplug::client::ButtplugClient;
+use crossbeam_channel::Sender;
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+use crate::{
+    config::{client::BatterySettings, logging::redact},
+    player::access::Degradation,
+    speed::Speed,
+    DeviceLimiter,
+};
+
+use super::events::ClientEvent;
+
+/// Whether a device with the battery level 'pct' should be limited, devices
+/// that are already limited stay limited until the level reaches `restore_pct`
+fn is_low(settings: &BatterySettings, pct: u32, degraded: bool) -> bool {
+    if degraded {
+        pct < settings.restore_pct.max(settings.low_pct)
+    } else {
+        pct < settings.low_pct
+    }
+}
+
+/// Periodically reads the battery level of all devices that report one and limits
+/// the output of devices with a low battery, so that they run for longer
+pub async fn run_battery_monitor(
+    buttplug: Arc<ButtplugClient>,
+    settings: BatterySettings,
+    limiter: DeviceLimiter,
+    event_sender: Sender<ClientEvent>,
+) {
+    let degradation = Degradation {
+        max_speed: Speed::new(settings.max_speed.into()),
+        min_interval: Duration::from_millis(settings.min_command_interval_ms),
+    };
+    let mut degraded = HashSet::new();
+    loop {
+        sleep(Duration::from_millis(settings.interval_ms)).await;
+        if !buttplug.connected() {
+            continue;
+        }
+        for device in buttplug.devices().iter().filter(|x| x.connected() && x.has_battery_level()) {
+            let pct = match device.battery_level().await {
+                Ok(level) => (level * 100.0).round() as u32,
+                Err(err) => {
+                    debug!(?err, "failed reading battery level");
+                    continue;
+                }
+            };
+            let was_low = degraded.contains(&device.index());
+            let low = is_low(&settings, pct, was_low);
+            if low == was_low {
+                continue;
+            }
+            let name = redact(device.name());
+            if low {
+                info!(device = name, pct, "battery low, limiting output");
+                degraded.insert(device.index());
+                limiter.set_degradation(device.index(), Some(degradation));
+                let _ = event_sender.send(ClientEvent::BatteryLow(name, pct));
+            } else {
+                info!(device = name, pct, "battery recovered, lifting limits");
+                degraded.remove(&device.index());
+                limiter.set_degradation(device.index(), None);
+                let _ = event_sender.send(ClientEvent::BatteryRestored(name, pct));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_battery_has_hysteresis() {
+        let settings = BatterySettings {
+            low_pct: 20,
+            restore_pct: 25,
+            ..Default::default()
+        };
+        assert!(!is_low(&settings, 20, false));
+        assert!(is_low(&settings, 19, false));
+        assert!(is_low(&settings, 22, true));
+        assert!(!is_low(&settings, 25, true));
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use buttplug::client::{ButtplugClientDevice, ButtplugClientEvent};
+use crossbeam_channel::Sender;
+use futures::{Stream, StreamExt};
+use tracing::{debug, error, info};
+
+use crate::{
+    actuator::{Actuator, ActuatorConfigLoader, Actuators},
+    actuators::{ActuatorConfig, SettingsCache},
+    config::logging::redact,
+};
+
+use super::{events::ClientEvent, BpClient};
+
+/// Devices that were added since the client created their configs last
+pub(super) type AddedDevices = Arc<Mutex<Vec<Arc<ButtplugClientDevice>>>>;
+
+/// Time each actuator (by identifier) disconnected, see `disable_idle_actuators`
+pub(super) type DisconnectedSince = Arc<Mutex<HashMap<String, Instant>>>;
+
+/// Raises the device events of the server until the client is dropped, remembers added
+/// devices, so that the client creates their configs, and the time their actuators disconnect
+pub(super) async fn run_device_events<S>(
+    events: S,
+    added: AddedDevices,
+    disconnected_since: DisconnectedSince,
+    event_sender: Sender<ClientEvent>,
+) where
+    S: Stream<Item = ButtplugClientEvent> + Unpin,
+{
+    let mut events = events;
+    // actuator identifiers of the connected devices by device index
+    let mut connected: HashMap<u32, Vec<String>> = HashMap::new();
+    let identifiers = |device: &Arc<ButtplugClientDevice>| {
+        device.flatten_actuators().iter().map(|x| x.identifier().to_owned()).collect::<Vec<_>>()
+    };
+    let disconnect = |ids: Vec<String>| {
+        let now = Instant::now();
+        let mut disconnected_since = disconnected_since.lock().unwrap();
+        for id in ids {
+            disconnected_since.entry(id).or_insert(now);
+        }
+    };
+    while let Some(event) = events.next().await {
+        let event = match event {
+            ButtplugClientEvent::DeviceAdded(device) => {
+                let name = redact(device.name());
+                info!(device = name, "device added");
+                let ids = identifiers(&device);
+                disconnected_since.lock().unwrap().retain(|id, _| !ids.contains(id));
+                connected.insert(device.index(), ids);
+                added.lock().unwrap().push(device);
+                ClientEvent::DeviceAdded(name)
+            }
+            ButtplugClientEvent::DeviceRemoved(device) => {
+                let name = redact(device.name());
+                info!(device = name, "device removed");
+                disconnect(connected.remove(&device.index()).unwrap_or_else(|| identifiers(&device)));
+                ClientEvent::DeviceRemoved(name)
+            }
+            ButtplugClientEvent::ServerDisconnect => {
+                error!("server disconnected");
+                disconnect(connected.drain().flat_map(|(_, ids)| ids).collect());
+                ClientEvent::ServerDisconnected
+            }
+            event => {
+                debug!(?event, "client event");
+                continue;
+            }
+        };
+        if event_sender.send(event).is_err() {
+            break;
+        }
+    }
+    debug!("device events stopped");
+}
+
+/// Reason of actuators that are disabled until the user enables them
+pub const DISCOVERED_REASON: &str = "new device";
+
+impl BpClient {
+    /// Creates the configs of the actuators of all devices that were added since the
+    /// last call, see `load_configs`.
+    ///
+    /// Called before each dispatch, returns the ids of the created configs
+    pub fn load_added_devices(&mut self) -> Vec<String> {
+        let added = std::mem::take(&mut *self.added_devices.lock().unwrap());
+        if added.is_empty() {
+            return vec![];
+        }
+        let known = self.known_config_ids();
+        self.load_configs(added.flatten_actuators())
+            .iter()
+            .filter_map(|x| x.config.as_ref())
+            .filter(|x| !known.contains(&x.actuator_config_id))
+            .map(|x| x.actuator_config_id.clone())
+            .collect()
+    }
+
+    /// Loads the configs of 'actuators' and rebuilds the settings cache. Configs of
+    /// actuators that were never seen before are pre-filled by the matching device profile, then
+    /// created disabled and limited according to `settings.safe_mode`, each of them raises
+    /// `DeviceDiscovered` and `SettingsChanged`
+    pub(super) fn load_configs(&mut self, actuators: Vec<Arc<Actuator>>) -> Vec<Arc<Actuator>> {
+        let known = self.known_config_ids();
+        let loaded = actuators
+            .load_config_with_profiles(&mut self.device_settings, &self.scaling_profiles)
+            .into_iter()
+            .map(|actuator| match &actuator.config {
+                Some(config) if !known.contains(&config.actuator_config_id) => {
+                    let config = self.discovered(&actuator, config.clone());
+                    let mut actuator = Actuator::clone(&actuator);
+                    actuator.config = Some(config);
+                    Arc::new(actuator)
+                }
+                _ => actuator,
+            })
+            .collect::<Vec<_>>();
+        self.settings_cache = SettingsCache::from(&self.device_settings);
+        loaded
+    }
+
+    fn known_config_ids(&self) -> HashSet<String> {
+        self.device_settings
+            .0
+            .iter()
+            .map(|x| x.actuator_config_id.clone())
+            .collect()
+    }
+
+    fn discovered(&mut self, actuator: &Actuator, mut config: ActuatorConfig) -> ActuatorConfig {
+        let actuator_id = config.actuator_config_id.clone();
+        if let Some(safe_mode) = &self.settings.safe_mode {
+            info!(actuator = redact(&actuator_id), "new actuator in safe mode");
+            config.enabled = false;
+            config.disabled_reason = Some(DISCOVERED_REASON.to_owned());
+            config.limits = config.limits.capped(actuator.actuator, safe_mode.max_speed.into());
+            self.device_settings.update_device(config.clone());
+        }
+        let _ = self.event_sender.send(ClientEvent::DeviceDiscovered(redact(&actuator_id)));
+        self.settings_changed(&actuator_id, "created");
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use buttplug::core::message::ActuatorType;
+
+    use bp_fakes::*;
+
+    use crate::ActuatorLimits;
+    use crate::client::tests::{assert_timeout, test_cmd, wait_for_connection};
+    use crate::client::{ScalarActuator, Strength};
+
+    use super::*;
+
+    #[test]
+    fn disconnects_are_timed_from_the_server_event() {
+        // arrange
+        let (tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+        assert!(tk.disconnected_since.lock().unwrap().is_empty());
+
+        // act
+        let before = Instant::now();
+        tk.disconnect();
+
+        // assert
+        assert_timeout!(
+            tk.disconnected_since.lock().unwrap().contains_key("vib1 (Vibrate)"),
+            "Awaiting disconnect event"
+        );
+        assert!(tk.disconnected_since.lock().unwrap()["vib1 (Vibrate)"] >= before);
+    }
+
+    #[test]
+    fn added_devices_raise_events_and_get_configs() {
+        // arrange
+        let (connector, _) = FakeDeviceConnector::new(vec![scalar(1, "vib1", ActuatorType::Vibrate)]);
+        let mut tk = BpClient::connect_with(|| async move { connector }, None, None).unwrap();
+        tk.await_connect(1);
+
+        // act
+        let mut added = vec![];
+        assert_timeout!(
+            {
+                added.extend(tk.events.try_iter().filter(|x| matches!(x, ClientEvent::DeviceAdded(_))));
+                !added.is_empty()
+            },
+            "Awaiting device event"
+        );
+        let created = tk.load_added_devices();
+
+        // assert
+        assert!(matches!(&added[0], ClientEvent::DeviceAdded(name) if name == "vib1"));
+        assert_eq!(created, vec![String::from("vib1 (Vibrate)")]);
+        assert!(!tk.device_settings.get_enabled("vib1 (Vibrate)"));
+        assert!(tk.load_added_devices().is_empty());
+    }
+
+    #[test]
+    fn new_actuators_start_in_safe_mode() {
+        // arrange
+        let (connector, call_registry) = FakeDeviceConnector::new(vec![scalar(1, "vib1", ActuatorType::Vibrate)]);
+        let mut tk = BpClient::connect_with(|| async move { connector }, None, None).unwrap();
+        tk.await_connect(1);
+
+        // act
+        let vibrate = |tk: &mut BpClient| {
+            test_cmd(tk, Strength::Constant(100), Duration::from_millis(1), vec![], None, &[ScalarActuator::Vibrate]);
+            thread::sleep(Duration::from_millis(500));
+        };
+        vibrate(&mut tk);
+        let discovered = tk
+            .events
+            .try_iter()
+            .filter(|x| matches!(x, ClientEvent::DeviceDiscovered(_)))
+            .count();
+        let config = tk.device_settings.get_config("vib1 (Vibrate)").unwrap();
+        tk.set_enabled("vib1 (Vibrate)", true);
+        vibrate(&mut tk);
+
+        // assert
+        assert_eq!(discovered, 1);
+        assert!(!config.enabled);
+        assert_eq!(config.disabled_reason.as_deref(), Some(DISCOVERED_REASON));
+        assert!(matches!(config.limits, ActuatorLimits::Scalar(range) if range.max_speed == 50));
+        call_registry.get_device(1)[0].assert_strenth(0.5);
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+    }
+}
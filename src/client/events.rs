@@ -0,0 +1,183 @@
+use buttplug::{
+    client::ButtplugClientError,
+    core::errors::{ButtplugDeviceError, ButtplugError},
+};
+
+use crossbeam_channel::Sender;
+use tokio::sync::mpsc::Receiver;
+use tracing::warn;
+
+use crate::{
+    config::logging::redact,
+    player::{
+        lifecycle::{SchedulerEvent, SCHEDULER_EVENT_CAPACITY},
+        worker::WorkerError,
+    },
+    CapacityError,
+};
+
+use super::state::ConnectionState;
+
+/// Events that the client raises for the host application,
+/// received through `BpClient::events`
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    /// The client connected, disconnected or lost its connection
+    ConnectionStateChanged(ConnectionState),
+    /// The server did not answer a ping in time, commands might not reach the devices
+    ConnectionDegraded(String),
+    /// The server answers pings again after the connection was degraded
+    ConnectionRestored,
+    /// The battery of a device is low (device name, percent), its output is
+    /// capped and updated less often until `BatteryRestored`
+    BatteryLow(String, u32),
+    /// The battery of a limited device recovered (device name, percent)
+    BatteryRestored(String, u32),
+    /// An actuator was disabled automatically (actuator id, reason),
+    /// the id is redacted according to the logging settings
+    ActuatorDisabled(String, String),
+    /// An action name was requested that is not loaded
+    UnknownAction(String),
+    /// A command of a running action failed on the device
+    CommandFailed(CommandFailure),
+    /// A task was refused because the actuator ran longer than allowed
+    /// (actuator id, "hourly" or "daily")
+    RuntimeCapReached(String, String),
+    /// Settings of an actuator changed through the client (actuator id, changed field)
+    SettingsChanged(String, String),
+    /// An actuator was enabled (true) or disabled (false), raised after `SettingsChanged`
+    EnabledChanged(String, bool),
+    /// The body parts of an actuator changed, raised after `SettingsChanged`
+    BodyPartsChanged(String, Vec<String>),
+    /// The server added a device (device name), the configs of its actuators
+    /// are created with the next dispatch or `BpClient::load_added_devices`
+    DeviceAdded(String),
+    /// The config of an actuator that was never seen before was created (actuator id),
+    /// it stays disabled until the user enables it, see `ClientSettings::safe_mode`
+    DeviceDiscovered(String),
+    /// The server removed a device (device name)
+    DeviceRemoved(String),
+    /// The server closed the connection
+    ServerDisconnected,
+    /// A dispatch was refused because too many tasks are running, see `ClientSettings::resource_limits`
+    CapacityExceeded(CapacityError),
+    /// A dispatch was refused because a control can't be played with the strength
+    /// (action name, strength), see `Control::supports`
+    UnsupportedStrength(String, String),
+    /// Lifecycle of a dispatched handle, forwarded from [crate::ButtplugScheduler::create_with_events]
+    Scheduler(SchedulerEvent),
+}
+
+/// Raises the lifecycle events of the scheduler until the scheduler is dropped. They are
+/// dropped while `SCHEDULER_EVENT_CAPACITY` client events wait to be received
+pub(super) async fn forward_scheduler_events(
+    mut scheduler_events: Receiver<SchedulerEvent>,
+    event_sender: Sender<ClientEvent>,
+) {
+    while let Some(event) = scheduler_events.recv().await {
+        if event_sender.len() >= SCHEDULER_EVENT_CAPACITY {
+            warn!(?event, "client events are not received, dropped");
+            continue;
+        }
+        if event_sender.send(ClientEvent::Scheduler(event)).is_err() {
+            break;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandErrorKind {
+    /// connection to the server was lost or broke
+    Connection,
+    /// the device is not connected anymore
+    NotConnected,
+    /// the device did not respond or returned an error
+    Communication,
+    /// the device does not support the command
+    Unsupported,
+    Other,
+}
+
+/// Details about a failed device command
+#[derive(Debug, Clone)]
+pub struct CommandFailure {
+    /// actuator id, redacted according to the logging settings
+    pub actuator: String,
+    pub action: String,
+    pub handle: i32,
+    pub kind: CommandErrorKind,
+    pub message: String,
+    /// whether sending the command again might succeed
+    pub retriable: bool,
+}
+
+impl CommandFailure {
+    pub fn new(err: &WorkerError, action: &str, handle: i32) -> Self {
+        let kind = match &err.bp_error {
+            ButtplugClientError::ButtplugConnectorError(_) => CommandErrorKind::Connection,
+            ButtplugClientError::ButtplugError(ButtplugError::ButtplugPingError(_)) => CommandErrorKind::Connection,
+            ButtplugClientError::ButtplugError(ButtplugError::ButtplugDeviceError(err)) => match err {
+                ButtplugDeviceError::DeviceNotConnected(_) | ButtplugDeviceError::DeviceNotAvailable(_) => {
+                    CommandErrorKind::NotConnected
+                }
+                ButtplugDeviceError::DeviceConnectionError(_) | ButtplugDeviceError::DeviceCommunicationError(_) => {
+                    CommandErrorKind::Communication
+                }
+                ButtplugDeviceError::MessageNotSupported(_)
+                | ButtplugDeviceError::DeviceFeatureCountMismatch(_, _)
+                | ButtplugDeviceError::DeviceFeatureIndexError(_, _)
+                | ButtplugDeviceError::DeviceActuatorTypeMismatch(_, _, _) => CommandErrorKind::Unsupported,
+                _ => CommandErrorKind::Other,
+            },
+            ButtplugClientError::ButtplugError(_) => CommandErrorKind::Other,
+        };
+        CommandFailure {
+            actuator: redact(err.actuator.identifier()),
+            action: action.to_owned(),
+            handle,
+            kind,
+            message: err.bp_error.to_string(),
+            retriable: matches!(kind, CommandErrorKind::Connection | CommandErrorKind::Communication),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use buttplug::core::message::ActuatorType;
+
+    use bp_fakes::*;
+
+    use crate::client::tests::{test_cmd, wait_for_connection};
+    use crate::client::{ScalarActuator, Strength};
+
+    use super::*;
+
+    #[test]
+    fn scheduler_events_are_raised_as_client_events() {
+        // arrange
+        let (mut tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+
+        // act
+        let handle = test_cmd(
+            &mut tk,
+            Strength::Constant(50),
+            Duration::from_millis(50),
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        let events = tk.events.try_iter().collect::<Vec<_>>();
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, ClientEvent::Scheduler(SchedulerEvent::Started(x)) if *x == handle)));
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, ClientEvent::Scheduler(SchedulerEvent::Finished(x)) if *x == handle)));
+    }
+}
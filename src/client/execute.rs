@@ -0,0 +1,133 @@
+use std::{sync::Arc, time::Duration};
+
+use tracing::{error, info};
+
+use crate::{actuator::Actuator, config::actions::{Action, Strength}, speed::Speed};
+
+use super::{events::ClientEvent, BpClient};
+
+/// Outcome of a single entry of `execute_actions`
+#[derive(Debug, Clone)]
+pub enum ExecutionStatus {
+    Dispatched(Vec<Arc<Actuator>>),
+    /// the action is known but none of its controls matched an actuator
+    NoActuators,
+    /// the action is not loaded and there is no fallback action
+    UnknownAction,
+}
+
+#[derive(Debug)]
+pub struct ExecuteActionsResult {
+    pub handle: i32,
+    /// requested action name and its status, in request order
+    pub entries: Vec<(String, ExecutionStatus)>,
+}
+
+impl BpClient {
+    /// Dispatches loaded actions by name. Unknown names raise `ClientEvent::UnknownAction`
+    /// and are replaced with `settings.fallback_action` if there is one
+    pub fn execute_actions(
+        &mut self,
+        actions: Vec<(Strength, String)>,
+        body_parts: Vec<String>,
+        speed: Speed,
+        duration: Duration,
+    ) -> ExecuteActionsResult {
+        info!(?actions, "execute_actions");
+        let mut resolved: Vec<(String, Option<Action>)> = vec![];
+        let mut refs = vec![];
+        for (strength, name) in actions {
+            let action = match self.actions.get(&name) {
+                Some(action) => Some(action.clone()),
+                None => {
+                    error!(name, "unknown action");
+                    let _ = self.event_sender.send(ClientEvent::UnknownAction(name.clone()));
+                    self.settings
+                        .fallback_action
+                        .as_ref()
+                        .and_then(|fallback| self.actions.get(fallback))
+                        .cloned()
+                }
+            };
+            if let Some(action) = &action {
+                refs.push((strength, action.clone()));
+            }
+            resolved.push((name, action));
+        }
+
+        let result = self.dispatch_refs(refs, body_parts, speed, duration);
+        let entries = resolved
+            .into_iter()
+            .map(|(name, action)| {
+                let status = match action {
+                    Some(action) => {
+                        let actuators = result
+                            .actions
+                            .iter()
+                            .filter(|(started, _)| *started == action.name)
+                            .flat_map(|(_, actuators)| actuators.clone())
+                            .collect::<Vec<_>>();
+                        if actuators.is_empty() {
+                            ExecutionStatus::NoActuators
+                        } else {
+                            ExecutionStatus::Dispatched(actuators)
+                        }
+                    }
+                    None => ExecutionStatus::UnknownAction,
+                };
+                (name, status)
+            })
+            .collect();
+        ExecuteActionsResult {
+            handle: result.handle,
+            entries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use buttplug::core::message::ActuatorType;
+
+    use bp_fakes::*;
+
+    use crate::client::tests::wait_for_connection;
+    use crate::client::{Actions, Control, ScalarActuator, Selector};
+    use crate::config::client::ClientSettings;
+
+    use super::*;
+
+    #[test]
+    fn execute_unknown_action_reports_status_and_uses_fallback() {
+        // arrange
+        let settings = ClientSettings {
+            fallback_action: Some("vibrate".into()),
+            ..Default::default()
+        };
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+        tk.actions = Actions(vec![
+            Action::new("vibrate", vec![Control::Scalar(Selector::All, vec![ScalarActuator::Vibrate])]),
+            Action::new("inflate", vec![Control::Scalar(Selector::All, vec![ScalarActuator::Inflate])]),
+        ]);
+        let actions = || vec![
+            (Strength::Constant(100), "inflate".to_owned()),
+            (Strength::Constant(100), "does not exist".to_owned()),
+        ];
+
+        // act
+        let without_fallback = tk.execute_actions(actions(), vec![], Speed::max(), Duration::from_millis(1));
+        tk.settings = settings;
+        let with_fallback = tk.execute_actions(actions(), vec![], Speed::max(), Duration::from_millis(1));
+        thread::sleep(Duration::from_millis(500));
+
+        // assert
+        assert!(matches!(without_fallback.entries[0].1, ExecutionStatus::NoActuators));
+        assert!(matches!(without_fallback.entries[1].1, ExecutionStatus::UnknownAction));
+        assert!(matches!(with_fallback.entries[1].1, ExecutionStatus::Dispatched(_)));
+        assert!(tk.events.try_iter().any(|x| matches!(x, ClientEvent::UnknownAction(_))));
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+    }
+}
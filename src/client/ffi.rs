@@ -0,0 +1,338 @@
+//! C-compatible interface to `BpClient` for non-Rust hosts. Build the crate as
+//! `cdylib` or `staticlib` with the `ffi` feature to export these functions.
+//!
+//! All functions block until the request was handed to the client, none of them
+//! wait for an action to finish. Strings are UTF-8 and null-terminated, strings
+//! returned by the library must be released with `bp_free_string`.
+//!
+//! Handles returned by `bp_execute_action` are positive and never reused during
+//! the lifetime of a client, 0 means that nothing was started. Updating a handle
+//! that already finished returns false, stopping it does nothing.
+
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::RecvTimeoutError;
+use serde_json::json;
+use tracing::error;
+
+use crate::{
+    actuators::ActuatorSettings,
+    config::{actions::Strength, client::ClientSettings},
+    speed::Speed,
+};
+
+use super::{events::ClientEvent, BpClient};
+
+/// Receives every client event as name (e.g. "ActuatorDisabled") and detail text
+pub type BpEventCallback = extern "C" fn(event: *const c_char, detail: *const c_char, user_data: *mut c_void);
+
+pub struct BpFfiClient {
+    client: BpClient,
+    event_forwarder: Option<Arc<AtomicBool>>,
+}
+
+impl Drop for BpFfiClient {
+    fn drop(&mut self) {
+        if let Some(running) = self.event_forwarder.take() {
+            running.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+struct UserData(*mut c_void);
+
+// the host is responsible for making 'user_data' usable from the event thread
+unsafe impl Send for UserData {}
+
+unsafe fn read_str<'a>(value: *const c_char) -> Option<&'a str> {
+    if value.is_null() {
+        return None;
+    }
+    CStr::from_ptr(value).to_str().ok()
+}
+
+fn to_c_string(value: String) -> *mut c_char {
+    CString::new(value).map(|x| x.into_raw()).unwrap_or(ptr::null_mut())
+}
+
+fn describe(event: &ClientEvent) -> (&'static str, String) {
+    match event {
+        ClientEvent::ConnectionStateChanged(state) => ("ConnectionStateChanged", format!("{:?}", state)),
+        ClientEvent::ConnectionDegraded(reason) => ("ConnectionDegraded", reason.clone()),
+        ClientEvent::ConnectionRestored => ("ConnectionRestored", String::new()),
+        ClientEvent::BatteryLow(device, pct) => ("BatteryLow", format!("{}: {}%", device, pct)),
+        ClientEvent::BatteryRestored(device, pct) => ("BatteryRestored", format!("{}: {}%", device, pct)),
+        ClientEvent::ActuatorDisabled(actuator, reason) => ("ActuatorDisabled", format!("{}: {}", actuator, reason)),
+        ClientEvent::UnknownAction(name) => ("UnknownAction", name.clone()),
+        ClientEvent::CommandFailed(failure) => ("CommandFailed", format!("{}: {}", failure.actuator, failure.message)),
+        ClientEvent::RuntimeCapReached(actuator, window) => ("RuntimeCapReached", format!("{}: {}", actuator, window)),
+        ClientEvent::SettingsChanged(actuator, field) => ("SettingsChanged", format!("{}: {}", actuator, field)),
+        ClientEvent::EnabledChanged(actuator, enabled) => ("EnabledChanged", format!("{}: {}", actuator, enabled)),
+        ClientEvent::BodyPartsChanged(actuator, body_parts) => {
+            ("BodyPartsChanged", format!("{}: {}", actuator, body_parts.join(",")))
+        }
+        ClientEvent::DeviceAdded(device) => ("DeviceAdded", device.clone()),
+        ClientEvent::DeviceDiscovered(actuator) => ("DeviceDiscovered", actuator.clone()),
+        ClientEvent::DeviceRemoved(device) => ("DeviceRemoved", device.clone()),
+        ClientEvent::ServerDisconnected => ("ServerDisconnected", String::new()),
+        ClientEvent::CapacityExceeded(err) => ("CapacityExceeded", err.to_string()),
+        ClientEvent::UnsupportedStrength(action, strength) => ("UnsupportedStrength", format!("{}: {}", action, strength)),
+        ClientEvent::Scheduler(event) => ("Scheduler", format!("{:?}", event)),
+    }
+}
+
+/// Connects to the server configured in 'settings_json' (`ClientSettings`),
+/// both json arguments may be null to use the defaults. Returns null on failure.
+///
+/// # Safety
+/// All arguments must be null or valid null-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn bp_connect(
+    settings_json: *const c_char,
+    device_settings_json: *const c_char,
+    pattern_path: *const c_char,
+) -> *mut BpFfiClient {
+    let settings = match read_str(settings_json).map(serde_json::from_str::<ClientSettings>) {
+        Some(Ok(settings)) => settings,
+        Some(Err(err)) => {
+            error!(?err, "invalid client settings");
+            return ptr::null_mut();
+        }
+        None => ClientSettings::default(),
+    };
+    let device_settings = match read_str(device_settings_json).map(serde_json::from_str::<ActuatorSettings>) {
+        Some(Ok(settings)) => settings,
+        Some(Err(err)) => {
+            error!(?err, "invalid device settings");
+            return ptr::null_mut();
+        }
+        None => ActuatorSettings::default(),
+    };
+    let settings = ClientSettings {
+        pattern_path: read_str(pattern_path).unwrap_or_default().to_owned(),
+        ..settings
+    };
+    match BpClient::connect(settings, device_settings) {
+        Ok(client) => Box::into_raw(Box::new(BpFfiClient {
+            client,
+            event_forwarder: None,
+        })),
+        Err(err) => {
+            error!(?err, "connection failed");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Stops all devices, disconnects and releases the client
+///
+/// # Safety
+/// 'client' must be null or returned by `bp_connect` and not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn bp_disconnect(client: *mut BpFfiClient) {
+    if client.is_null() {
+        return;
+    }
+    let client = Box::from_raw(client);
+    client.client.emergency_stop();
+    client.client.disconnect();
+}
+
+/// # Safety
+/// 'value' must be null or returned by this library
+#[no_mangle]
+pub unsafe extern "C" fn bp_free_string(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+/// # Safety
+/// 'client' must be returned by `bp_connect`
+#[no_mangle]
+pub unsafe extern "C" fn bp_scan_for_devices(client: *mut BpFfiClient) -> bool {
+    let Some(client) = client.as_mut() else { return false };
+    client.client.scan_for_devices()
+}
+
+/// Json array of all actuators as `{"id": .., "enabled": .., "connected": .., "transport": .., "server": ..}`,
+/// release it with `bp_free_string`
+///
+/// # Safety
+/// 'client' must be returned by `bp_connect`
+#[no_mangle]
+pub unsafe extern "C" fn bp_list_actuators(client: *mut BpFfiClient) -> *mut c_char {
+    let Some(client) = client.as_mut() else { return ptr::null_mut() };
+    let client = &mut client.client;
+    let actuators = client
+        .all_actuators()
+        .iter()
+        .map(|x| {
+            json!({
+                "id": x.identifier(),
+                "enabled": client.device_settings.get_enabled(x.identifier()),
+                "connected": x.device.connected(),
+                "transport": x.transport,
+                "server": x.server_name,
+            })
+        })
+        .collect::<Vec<_>>();
+    to_c_string(json!(actuators).to_string())
+}
+
+/// Json of the `LatencyStats` of 'samples' commands to the actuator, null if it is
+/// not available. Blocks until all commands are acknowledged, release it with `bp_free_string`
+///
+/// # Safety
+/// 'client' must be returned by `bp_connect`, 'actuator_id' a valid string
+#[no_mangle]
+pub unsafe extern "C" fn bp_measure_latency(client: *mut BpFfiClient, actuator_id: *const c_char, samples: u32) -> *mut c_char {
+    let (Some(client), Some(actuator_id)) = (client.as_mut(), read_str(actuator_id)) else {
+        return ptr::null_mut();
+    };
+    match client.client.measure_latency(actuator_id, samples as usize) {
+        Some(stats) => to_c_string(json!(stats).to_string()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// 'client' must be returned by `bp_connect`, 'actuator_id' a valid string
+#[no_mangle]
+pub unsafe extern "C" fn bp_set_enabled(client: *mut BpFfiClient, actuator_id: *const c_char, enabled: bool) -> bool {
+    let (Some(client), Some(actuator_id)) = (client.as_mut(), read_str(actuator_id)) else {
+        return false;
+    };
+    client.client.set_enabled(actuator_id, enabled);
+    true
+}
+
+/// Loads all action files in 'action_path'
+///
+/// # Safety
+/// 'client' must be returned by `bp_connect`, 'action_path' a valid string
+#[no_mangle]
+pub unsafe extern "C" fn bp_read_actions(client: *mut BpFfiClient, action_path: *const c_char) -> bool {
+    let (Some(client), Some(action_path)) = (client.as_mut(), read_str(action_path)) else {
+        return false;
+    };
+    client.client.read_actions(action_path);
+    true
+}
+
+/// Starts the loaded action 'name' with 'speed' (0-100) for 'duration_ms',
+/// 'body_parts' is a comma separated list or null. Returns the handle or 0
+///
+/// # Safety
+/// 'client' must be returned by `bp_connect`, the strings must be null or valid
+#[no_mangle]
+pub unsafe extern "C" fn bp_execute_action(
+    client: *mut BpFfiClient,
+    name: *const c_char,
+    speed: i32,
+    duration_ms: u64,
+    body_parts: *const c_char,
+) -> i32 {
+    let (Some(client), Some(name)) = (client.as_mut(), read_str(name)) else {
+        return 0;
+    };
+    let body_parts = read_str(body_parts)
+        .map(|x| x.split(',').map(|x| x.trim().to_owned()).filter(|x| !x.is_empty()).collect())
+        .unwrap_or_default();
+    let result = client.client.execute_actions(
+        vec![(Strength::Constant(speed), name.to_owned())],
+        body_parts,
+        Speed::max(),
+        Duration::from_millis(duration_ms),
+    );
+    result.handle.max(0)
+}
+
+/// # Safety
+/// 'client' must be returned by `bp_connect`
+#[no_mangle]
+pub unsafe extern "C" fn bp_update(client: *mut BpFfiClient, handle: i32, speed: i32) -> bool {
+    let Some(client) = client.as_mut() else { return false };
+    client.client.update(handle, Speed::new(speed.into()))
+}
+
+/// # Safety
+/// 'client' must be returned by `bp_connect`
+#[no_mangle]
+pub unsafe extern "C" fn bp_stop(client: *mut BpFfiClient, handle: i32) -> bool {
+    let Some(client) = client.as_mut() else { return false };
+    client.client.stop(handle)
+}
+
+/// # Safety
+/// 'client' must be returned by `bp_connect`
+#[no_mangle]
+pub unsafe extern "C" fn bp_stop_all(client: *mut BpFfiClient) -> bool {
+    let Some(client) = client.as_mut() else { return false };
+    client.client.stop_all()
+}
+
+/// Calls 'callback' from a background thread for every client event until the
+/// client is disconnected or another callback is set, null only stops forwarding
+///
+/// # Safety
+/// 'client' must be returned by `bp_connect`, 'user_data' must be safe to use
+/// from another thread
+#[no_mangle]
+pub unsafe extern "C" fn bp_set_event_callback(
+    client: *mut BpFfiClient,
+    callback: Option<BpEventCallback>,
+    user_data: *mut c_void,
+) -> bool {
+    let Some(client) = client.as_mut() else { return false };
+    if let Some(running) = client.event_forwarder.take() {
+        running.store(false, Ordering::Relaxed);
+    }
+    let Some(callback) = callback else { return true };
+    let running = Arc::new(AtomicBool::new(true));
+    client.event_forwarder = Some(running.clone());
+    let events = client.client.events.clone();
+    let user_data = UserData(user_data);
+    thread::spawn(move || {
+        let user_data = user_data;
+        while running.load(Ordering::Relaxed) {
+            match events.recv_timeout(Duration::from_millis(100)) {
+                Ok(event) => {
+                    let (name, detail) = describe(&event);
+                    let name = CString::new(name).unwrap_or_default();
+                    let detail = CString::new(detail).unwrap_or_default();
+                    callback(name.as_ptr(), detail.as_ptr(), user_data.0);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        let settings = CString::new("{ not json").unwrap();
+        unsafe {
+            assert!(bp_connect(settings.as_ptr(), ptr::null(), ptr::null()).is_null());
+            assert_eq!(bp_execute_action(ptr::null_mut(), ptr::null(), 100, 1000, ptr::null()), 0);
+            assert!(!bp_stop(ptr::null_mut(), 1));
+            assert!(bp_list_actuators(ptr::null_mut()).is_null());
+            bp_free_string(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+use tracing::info;
+
+use crate::{actuator::Actuators, config::logging::redact};
+
+use super::{events::ClientEvent, BpClient};
+
+impl BpClient {
+    /// Disables every enabled actuator that has been disconnected or failing for
+    /// longer than `auto_disable_after_mins` and stores the reason in its settings.
+    ///
+    /// Called before each dispatch, returns the ids of the actuators that were disabled
+    pub fn disable_idle_actuators(&mut self) -> Vec<String> {
+        let Some(mins) = self.settings.auto_disable_after_mins else {
+            return vec![];
+        };
+        let timeout = Duration::from_secs(mins * 60);
+        let now = Instant::now();
+        let connected = self
+            .buttplug
+            .devices()
+            .into_iter()
+            .filter(|x| x.connected())
+            .collect::<Vec<_>>()
+            .flatten_actuators()
+            .iter()
+            .map(|x| x.identifier().to_owned())
+            .collect::<Vec<_>>();
+        let failing = self.failing_since.lock().unwrap().clone();
+        let disconnected = self.disconnected_since.clone();
+        let mut disconnected_since = disconnected.lock().unwrap();
+
+        let mut disabled = vec![];
+        for config in self.device_settings.get_enabled_devices() {
+            let id = config.actuator_config_id;
+            // actuators that were never connected count from their first check
+            let (since, reason) = if !connected.contains(&id) {
+                let since = *disconnected_since.entry(id.clone()).or_insert(now);
+                (since, "disconnected")
+            } else if let Some(since) = failing.get(&id) {
+                disconnected_since.remove(&id);
+                (*since, "failing")
+            } else {
+                disconnected_since.remove(&id);
+                continue;
+            };
+            if now.duration_since(since) >= timeout {
+                let reason = format!("{} for more than {} minutes", reason, mins);
+                info!(actuator = redact(&id), reason, "disabling idle actuator");
+                self.device_settings.disable_with_reason(&id, &reason);
+                disconnected_since.remove(&id);
+                self.failing_since.lock().unwrap().remove(&id);
+                let _ = self.event_sender.send(ClientEvent::ActuatorDisabled(redact(&id), reason));
+                self.settings_changed(&id, "enabled");
+                disabled.push(id);
+            }
+        }
+        disabled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buttplug::core::message::ActuatorType;
+
+    use bp_fakes::*;
+
+    use crate::client::tests::wait_for_connection;
+    use crate::config::client::ClientSettings;
+
+    use super::*;
+
+    #[test]
+    fn disconnected_actuators_are_disabled_after_timeout() {
+        // arrange
+        let settings = ClientSettings {
+            auto_disable_after_mins: Some(0),
+            ..Default::default()
+        };
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings), None);
+        tk.device_settings.set_enabled("gone (Vibrate)", true);
+
+        // act
+        let disabled = tk.disable_idle_actuators();
+
+        // assert
+        assert_eq!(disabled, vec!["gone (Vibrate)".to_owned()]);
+        assert!(tk.device_settings.get_enabled("vib1 (Vibrate)"));
+        let config = tk.device_settings.get_config("gone (Vibrate)").unwrap();
+        assert!(!config.enabled);
+        assert!(config.disabled_reason.is_some());
+        assert!(tk.events.try_iter().any(|x| matches!(x, ClientEvent::ActuatorDisabled(_, _))));
+    }
+}
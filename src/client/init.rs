@@ -0,0 +1,135 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::time::sleep;
+use tracing::{error, info};
+
+use crate::{
+    actuator::{Actuator, ActuatorCommand, Actuators},
+    actuators::InitCommand,
+    filter::Filter,
+    player::worker::WorkerResult,
+    speed::Speed,
+    WorkerChannel,
+};
+
+use super::BpClient;
+
+/// Actuators whose init sequence still has to run, see `BpClient::take_uninitialized`
+pub(super) struct PendingInit {
+    actuators: Vec<Arc<Actuator>>,
+    /// only created if there is an actuator to initialize
+    channel: Option<WorkerChannel>,
+}
+
+impl BpClient {
+    /// Runs the init sequences of all connected and enabled actuators that were
+    /// not initialized yet, e.g. right after a device connected
+    pub fn initialize_devices(&mut self) {
+        let loaded = self.load_configs(self.buttplug.devices().flatten_actuators());
+        let actuators = Filter::from_actuators(self.settings_cache.clone(), loaded)
+            .connected()
+            .enabled()
+            .result();
+        let pending = self.take_uninitialized(&actuators);
+        self.runtime.block_on(pending.run());
+    }
+
+    /// Actuators with an init sequence that did not run since they connected,
+    /// they count as initialized from now on
+    pub(super) fn take_uninitialized(&mut self, actuators: &[Arc<Actuator>]) -> PendingInit {
+        let actuators = actuators
+            .iter()
+            .filter(|x| !x.get_config().init_sequence.is_empty())
+            .filter(|x| self.initialized.insert(x.identifier().to_owned()))
+            .cloned()
+            .collect::<Vec<_>>();
+        let channel = (!actuators.is_empty()).then(|| self.scheduler().worker_channel());
+        PendingInit { actuators, channel }
+    }
+
+    /// Forgets actuators that are not connected anymore, so that they
+    /// are initialized again when they reconnect
+    pub(super) fn forget_disconnected(&mut self, connected: &[Arc<Actuator>]) {
+        self.initialized
+            .retain(|id| connected.iter().any(|x| x.identifier() == id));
+    }
+}
+
+impl PendingInit {
+    pub(super) async fn run(self) {
+        let Some(mut channel) = self.channel else {
+            return;
+        };
+        for actuator in self.actuators {
+            info!(actuator=%actuator, "running init sequence");
+            if let Err(err) = run_init_sequence(&mut channel, &actuator).await {
+                error!(actuator=%actuator, ?err, "init sequence failed");
+            }
+        }
+    }
+}
+
+async fn run_init_sequence(channel: &mut WorkerChannel, actuator: &Arc<Actuator>) -> WorkerResult {
+    for command in actuator.get_config().init_sequence {
+        match (&command, actuator.command) {
+            (InitCommand::Wait(ms), _) => sleep(Duration::from_millis(*ms)).await,
+            (InitCommand::Move(pos, ms), ActuatorCommand::Linear) => {
+                channel.probe(actuator, pos.clamp(0.0, 1.0), *ms).await?;
+            }
+            (InitCommand::Speed(_), ActuatorCommand::Linear) | (InitCommand::Move(_, _), _) => {
+                error!(actuator=%actuator, ?command, "init command not supported by actuator");
+            }
+            (InitCommand::Speed(speed), _) => {
+                channel.probe(actuator, Speed::new(*speed).as_float(), 0).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use buttplug::core::message::ActuatorType;
+
+    use bp_fakes::*;
+
+    use crate::client::tests::{test_cmd, wait_for_connection};
+    use crate::client::{ScalarActuator, Strength};
+
+    use super::*;
+
+    #[test]
+    fn init_sequence_runs_once_before_first_use() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+        let mut config = tk.device_settings.get_config("vib1 (Vibrate)").unwrap();
+        config.init_sequence = vec![InitCommand::Speed(10), InitCommand::Wait(50), InitCommand::Speed(0)];
+        tk.update_actuator_config(config);
+
+        // act
+        for _ in 0..2 {
+            test_cmd(
+                &mut tk,
+                Strength::Constant(100),
+                Duration::from_millis(100),
+                vec![],
+                None,
+                &[ScalarActuator::Vibrate],
+            );
+            thread::sleep(Duration::from_millis(400));
+        }
+
+        // assert
+        let calls = call_registry.get_device(1);
+        calls[0].assert_strenth(0.1);
+        calls[1].assert_strenth(0.0);
+        calls[2].assert_strenth(1.0);
+        calls[3].assert_strenth(0.0);
+        calls[4].assert_strenth(1.0);
+        calls[5].assert_strenth(0.0);
+        assert_eq!(calls.len(), 6);
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tokio::time::sleep;
+use tracing::{error, info};
+
+use crate::{
+    actuator::ActuatorCommand,
+    config::{linear::LinearRange, logging::redact},
+    PlayerSettings,
+};
+
+use super::BpClient;
+
+/// Scalar speed of the measurement commands
+const LATENCY_SPEED: f64 = 0.05;
+/// Time between the start of two measurement commands
+const LATENCY_INTERVAL_MS: u64 = 50;
+/// Distance of the linear moves, relative to the actuators position range
+const LATENCY_STROKE: f64 = 0.05;
+
+/// Time from sending a command to an actuator until the server acknowledged it,
+/// all times in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyStats {
+    pub actuator: String,
+    /// commands that were acknowledged
+    pub samples: usize,
+    /// commands that failed, they are not part of the times
+    pub failed: usize,
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    /// standard deviation
+    pub jitter_ms: f64,
+}
+
+impl LatencyStats {
+    /// None if there is no sample
+    pub fn from_samples(actuator: &str, samples: &[Duration], failed: usize) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut ms = samples.iter().map(|x| x.as_secs_f64() * 1000.0).collect::<Vec<_>>();
+        ms.sort_by(f64::total_cmp);
+        let mean_ms = ms.iter().sum::<f64>() / ms.len() as f64;
+        let variance = ms.iter().map(|x| (x - mean_ms).powi(2)).sum::<f64>() / ms.len() as f64;
+        let p95_index = ((ms.len() as f64 * 0.95).ceil() as usize).clamp(1, ms.len()) - 1;
+        Some(LatencyStats {
+            actuator: actuator.to_owned(),
+            samples: ms.len(),
+            failed,
+            min_ms: ms[0],
+            mean_ms,
+            p95_ms: ms[p95_index],
+            max_ms: ms[ms.len() - 1],
+            jitter_ms: variance.sqrt(),
+        })
+    }
+}
+
+impl BpClient {
+    /// Sends 'samples' small alternating commands to the connected and enabled actuator
+    /// and measures how long the server takes to acknowledge each of them. The actuator
+    /// is stopped afterwards.
+    ///
+    /// The mean feeds the round trip time of the jitter buffer and lookahead. With
+    /// `settings.latency_sets_scalar_resolution` the slowest measured actuator sets the
+    /// scalar resolution of tasks that start afterwards.
+    /// Returns None if the actuator is not available or no command succeeded
+    pub fn measure_latency(&mut self, actuator_id: &str, samples: usize) -> Option<LatencyStats> {
+        info!(actuator = redact(actuator_id), samples, "measure latency");
+        let actuator = self
+            .all_actuators()
+            .into_iter()
+            .find(|x| x.identifier() == actuator_id && x.device.connected())
+            .filter(|_| self.device_settings.get_enabled(actuator_id))?;
+        let range = self
+            .device_settings
+            .find(actuator_id)
+            .map(|x| x.limits.linear_or_max())
+            .unwrap_or_else(LinearRange::max);
+        let positions = [range.apply_pos(range.min_pos), range.apply_pos(range.min_pos + LATENCY_STROKE)];
+
+        let mut channel = self.scheduler().worker_channel();
+        let (times, failed) = self.runtime.block_on(async move {
+            let mut times = vec![];
+            let mut failed = 0;
+            for i in 0..samples {
+                let value = match actuator.command {
+                    ActuatorCommand::Linear => positions[(i + 1) % 2],
+                    _ if i % 2 == 0 => LATENCY_SPEED,
+                    _ => 0.0,
+                };
+                let sent = Instant::now();
+                match channel.probe(&actuator, value, LATENCY_INTERVAL_MS as u32).await {
+                    Ok(()) => times.push(sent.elapsed()),
+                    Err(err) => {
+                        error!(actuator=%actuator, ?err, "latency command failed");
+                        failed += 1;
+                    }
+                }
+                sleep(Duration::from_millis(LATENCY_INTERVAL_MS).saturating_sub(sent.elapsed())).await;
+            }
+            if actuator.command != ActuatorCommand::Linear {
+                let _ = channel.probe(&actuator, 0.0, 0).await;
+            }
+            (times, failed)
+        });
+
+        let stats = LatencyStats::from_samples(actuator_id, &times, failed)?;
+        info!(actuator = redact(actuator_id), ?stats, "measured latency");
+        self.rtt_ms.store(stats.mean_ms.round() as u64, Ordering::Relaxed);
+        self.latency.insert(actuator_id.to_owned(), stats.clone());
+        if self.settings.latency_sets_scalar_resolution {
+            let slowest = self.latency.values().map(|x| x.p95_ms.ceil() as i32).max().unwrap_or_default();
+            let resolution_ms = PlayerSettings::default().scalar_resolution_ms.max(slowest);
+            self.scheduler().set_scalar_resolution(resolution_ms);
+        }
+        Some(stats)
+    }
+
+    /// Results of the last `measure_latency` of each actuator
+    pub fn latency_stats(&self) -> HashMap<String, LatencyStats> {
+        self.latency.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use buttplug::core::message::ActuatorType;
+
+    use bp_fakes::*;
+
+    use crate::client::tests::wait_for_connection;
+    use crate::config::client::ClientSettings;
+
+    use super::*;
+
+    #[test]
+    fn stats_summarize_samples() {
+        let samples = (1..=20).map(Duration::from_millis).collect::<Vec<_>>();
+        let stats = LatencyStats::from_samples("vib1", &samples, 2).unwrap();
+
+        assert_eq!(stats.samples, 20);
+        assert_eq!(stats.failed, 2);
+        assert_eq!(stats.min_ms, 1.0);
+        assert_eq!(stats.mean_ms, 10.5);
+        assert_eq!(stats.p95_ms, 19.0);
+        assert_eq!(stats.max_ms, 20.0);
+        assert!(LatencyStats::from_samples("vib1", &[], 3).is_none());
+    }
+
+    #[test]
+    fn latency_is_measured_on_enabled_actuators() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![scalar(1, "vib1", ActuatorType::Vibrate), scalar(2, "vib2", ActuatorType::Vibrate)],
+            None,
+            None,
+        );
+        tk.device_settings.set_enabled("vib2 (Vibrate)", false);
+
+        // act
+        let stats = tk.measure_latency("vib1 (Vibrate)", 4).unwrap();
+
+        // assert
+        assert_eq!(stats.samples, 4);
+        assert_eq!(stats.failed, 0);
+        assert!(stats.min_ms <= stats.p95_ms && stats.p95_ms <= stats.max_ms);
+        assert_eq!(tk.latency_stats().len(), 1);
+        assert!(tk.measure_latency("vib2 (Vibrate)", 4).is_none());
+        call_registry.get_device(1)[0].assert_strenth(0.05);
+        call_registry.get_device(1)[4].assert_strenth(0.0);
+        call_registry.assert_unused(2);
+        assert_eq!(
+            tk.scheduler().settings.scalar_resolution_ms,
+            PlayerSettings::default().scalar_resolution_ms
+        );
+    }
+
+    #[test]
+    fn latency_sets_scalar_resolution_if_enabled() {
+        // arrange
+        let settings = ClientSettings {
+            latency_sets_scalar_resolution: true,
+            ..Default::default()
+        };
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings), None);
+
+        // act
+        let stats = tk.measure_latency("vib1 (Vibrate)", 4).unwrap();
+
+        // assert
+        assert_eq!(
+            tk.scheduler().settings.scalar_resolution_ms,
+            PlayerSettings::default().scalar_resolution_ms.max(stats.p95_ms.ceil() as i32)
+        );
+    }
+}
@@ -1,42 +1,98 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex, MutexGuard,
+};
+use std::mem::ManuallyDrop;
+use std::thread;
 use std::time::Duration;
 use std::{
     fmt::{self},
     time::Instant,
 };
 
-use actuators::ActuatorSettings;
+use actuators::{ActuatorSettings, SettingsCache};
 use anyhow::anyhow;
 use anyhow::Error;
 
-use connection::ConnectionType;
-use rand::Rng;
+use connection::{ConnectionType, Transport};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use funscript::FScript;
+use rand::{seq::SliceRandom, Rng};
 
-use futures::Future;
+use futures::{future::join_all, Future};
 use tracing::{debug, error, info, span, Instrument, Level};
 
-use tokio::runtime::Runtime;
+use tokio::runtime::{Handle, Runtime};
+use tokio::time::timeout;
+use tokio::sync::mpsc::UnboundedSender;
 
-use buttplug::client::{ButtplugClient, ButtplugClientError};
+use buttplug::client::{ButtplugClient, ButtplugClientDevice, ButtplugClientError};
 use buttplug::server::device::hardware::communication::serialport::SerialPortCommunicationManagerBuilder;
 use buttplug::server::device::hardware::communication::xinput::XInputDeviceCommunicationManagerBuilder;
 use buttplug::{
     core::{connector::*, message::*},
     server::{
-        device::hardware::communication::btleplug::BtlePlugCommunicationManagerBuilder,
+        device::{hardware::communication::btleplug::BtlePlugCommunicationManagerBuilder, ServerDeviceManager},
         ButtplugServerBuilder,
     },
 };
 use util::trim_lower_str_list;
 
+use crate::actuator::Actuators;
 use crate::filter::Filter;
+use crate::player::options::Transposition;
+use crate::dynamic_tracking::DynamicTrackingHandle;
+use scan::ScanLimiter;
+use state::ConnectionState;
+use crate::player::jitter::JitterBuffer;
+use crate::player::lookahead::Lookahead;
+use crate::player::trigger::SamplingTrigger;
+use crate::player::stats::{ActionStats, ACTION_STATS_FILE};
+use crate::player::strokes::StrokeMilestones;
+use crate::player::worker::combine_results;
+use crate::player::PatternPlayer;
 use crate::*;
 
 use actions::*;
 use config::client::*;
 use config::linear::*;
-use pattern::read_pattern;
+use config::manager::{SettingsDocument, SettingsManager};
+use config::linear::{read_scaling_profiles, ScalingProfile};
+use pattern::{copy_actions, fit_to_duration, load_pattern_name, read_bundle, validate, PatternCacheStats, PatternInfo, PatternIssue, PatternLibrary};
+#[cfg(feature = "pattern-watch")]
+use pattern::PatternWatcher;
 use read::read_config_dir;
 
+pub mod batch;
+pub mod battery;
+pub mod devices;
+pub mod events;
+pub mod execute;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod idle;
+pub mod init;
+pub mod latency;
+pub mod runtime;
+pub mod scan;
+pub mod self_test;
+pub mod settings;
+pub mod state;
+pub mod tracking;
+pub mod watchdog;
+
+use devices::{run_device_events, AddedDevices, DisconnectedSince};
+use events::{forward_scheduler_events, ClientEvent, CommandFailure};
+use runtime::{enforce_runtime_cap, record_runtime, unix_ms, RuntimeLedger, RUNTIME_LEDGER_FILE};
+use latency::LatencyStats;
+use settings::spawn_settings_writer;
+use battery::run_battery_monitor;
+use watchdog::{run_rtt_probe, run_watchdog};
+
+/// how long dropping the client waits for the devices to stop, see `settings.on_drop`
+const DROP_TIMEOUT_MS: u64 = 2_000;
+
 #[cfg(feature = "testing")]
 use bp_fakes::FakeDeviceConnector;
 
@@ -58,10 +114,58 @@ pub struct BpClient {
     pub settings: ClientSettings,
     pub device_settings: ActuatorSettings,
     pub actions: Actions,
-    pub buttplug: ButtplugClient,
-    pub runtime: Runtime,
+    pub buttplug: Arc<ButtplugClient>,
+    /// shut down by `drop`, on a background thread if the client is dropped within an async context
+    pub runtime: ManuallyDrop<Runtime>,
     pub connection_result: Result<(), ButtplugClientError>,
-    pub scheduler: ButtplugScheduler,
+    /// shared by all methods that control tasks, see `scheduler()`
+    scheduler: Mutex<ButtplugScheduler>,
+    pub events: Receiver<ClientEvent>,
+    event_sender: Sender<ClientEvent>,
+    /// time the actuators disconnected, set by the device events
+    disconnected_since: DisconnectedSince,
+    /// first failure of an actuator since its last successful action
+    failing_since: Arc<Mutex<HashMap<String, Instant>>>,
+    /// active time of the actuators, only tracked with `settings.runtime_caps`
+    runtime_ledger: Arc<Mutex<RuntimeLedger>>,
+    /// receives the settings after each change, see `settings.settings_persistence`
+    settings_writer: Option<UnboundedSender<ActuatorSettings>>,
+    /// actuators that ran their init sequence since they connected
+    initialized: HashSet<String>,
+    /// tracking tasks started with `start_tracking`, ended by `stop_all`
+    tracking: Mutex<Vec<DynamicTrackingHandle>>,
+    /// spaces out start and stop scanning, see `settings.scan_limit`
+    scan_limiter: Arc<Mutex<ScanLimiter>>,
+    /// guards all device commands, see `ensure_connected`
+    connection_state: Arc<Mutex<ConnectionState>>,
+    /// keeps `settings.reconnect` from undoing `disconnect`
+    disconnect_requested: Arc<AtomicBool>,
+    /// parsed patterns shared by all dispatches
+    pattern_library: Arc<Mutex<PatternLibrary>>,
+    /// invalidates changed patterns, see `settings.pattern_cache.watch`
+    #[cfg(feature = "pattern-watch")]
+    pattern_watcher: Option<PatternWatcher>,
+    /// devices without configs yet, see `load_added_devices`
+    added_devices: AddedDevices,
+    /// round trip time to the server in milliseconds, measured by the rtt probe
+    /// and `measure_latency`
+    rtt_ms: Arc<AtomicU64>,
+    /// last `measure_latency` result of each actuator
+    latency: HashMap<String, LatencyStats>,
+    /// stores the settings after changes and on drop, see `connect_managed`
+    settings_manager: Option<SettingsManager>,
+    /// stores the document of `settings_manager` after changes
+    managed_writer: Option<UnboundedSender<SettingsDocument>>,
+    /// `settings.scaling_profiles` followed by the ones read from
+    /// `settings.device_profile_path` when connecting
+    scaling_profiles: Vec<ScalingProfile>,
+    /// index of `device_settings` for the filters, rebuilt by `load_configs` so that
+    /// direct edits of `device_settings` apply to the next dispatch
+    settings_cache: SettingsCache,
+    /// see `in_process_connector`
+    in_process_devices: InProcessDevices,
+    /// actuators of each device tagged with their transport by device index, see `with_transports`
+    transport_cache: Mutex<TransportCache>,
 }
 
 impl BpClient {
@@ -77,34 +181,160 @@ impl BpClient {
             + 'static,
     {
         let settings = client_settings.unwrap_or_default();
-        let (scheduler, mut worker) = ButtplugScheduler::create(PlayerSettings {
+        settings.logging.apply_redaction();
+        let (scheduler, mut worker, scheduler_events) = ButtplugScheduler::create_with_events(PlayerSettings {
             scalar_resolution_ms: 100,
+            ramp_in_ms: settings.ramp_in_ms,
+            ramp_out_ms: settings.ramp_out_ms,
         });
 
         let runtime = Runtime::new()?;
-        let (buttplug, connection_result) = runtime.block_on(async move {
+        let (buttplug, device_events, connection_result) = runtime.block_on(async move {
             info!("connecting");
             let buttplug = ButtplugClient::new("BpClient");
+            // subscribe first to receive the devices that are known when connecting
+            let device_events = buttplug.event_stream();
             let result = buttplug.connect(connect_action().await).await;
-            (buttplug, result)
+            (Arc::new(buttplug), device_events, result)
         });
         if let Err(err) = connection_result.as_ref() {
             error!("connection error: {:?}", err)
         }
-        let client = BpClient {
-            runtime,
+        let (event_sender, events) = unbounded::<ClientEvent>();
+        let runtime_ledger = settings
+            .runtime_caps
+            .as_ref()
+            .and_then(|x| x.state_path.as_ref())
+            .map(|path| settings.config_store.read_or_default::<RuntimeLedger>(path, RUNTIME_LEDGER_FILE))
+            .unwrap_or_default();
+        let mut client = BpClient {
+            runtime: ManuallyDrop::new(runtime),
             settings: settings.clone(),
-            scheduler,
+            scheduler: Mutex::new(scheduler),
             actions: Actions(vec![]),
             buttplug,
+            connection_state: Arc::new(Mutex::new(ConnectionState::from_result(&connection_result))),
+            disconnect_requested: Arc::new(AtomicBool::new(false)),
+            pattern_library: Arc::new(Mutex::new(PatternLibrary::new(
+                settings.pattern_cache.max_entries,
+                settings.pattern_cache.max_kb * 1024,
+            ))),
+            #[cfg(feature = "pattern-watch")]
+            pattern_watcher: None,
             connection_result,
+            settings_cache: SettingsCache::default(),
+            in_process_devices: InProcessDevices::default(),
+            transport_cache: Mutex::new(HashMap::new()),
             device_settings: device_settings.unwrap_or_default(),
+            events,
+            event_sender,
+            disconnected_since: Arc::new(Mutex::new(HashMap::new())),
+            failing_since: Arc::new(Mutex::new(HashMap::new())),
+            runtime_ledger: Arc::new(Mutex::new(runtime_ledger)),
+            settings_writer: None,
+            initialized: HashSet::new(),
+            tracking: Mutex::new(vec![]),
+            scan_limiter: Arc::new(Mutex::new(ScanLimiter::new(Duration::from_millis(
+                settings.scan_limit.min_interval_ms,
+            )))),
+            added_devices: Arc::new(Mutex::new(vec![])),
+            rtt_ms: Arc::new(AtomicU64::new(0)),
+            latency: HashMap::new(),
+            settings_manager: None,
+            managed_writer: None,
+            scaling_profiles: settings
+                .scaling_profiles
+                .iter()
+                .cloned()
+                .chain(settings.device_profile_path.as_deref().map(read_scaling_profiles).unwrap_or_default())
+                .collect(),
         };
         client.runtime.spawn(async move {
             debug!("starting worker thread");
             worker.run_worker_thread().await;
             debug!("worked thread stopped");
         });
+        client.runtime.spawn(forward_scheduler_events(scheduler_events, client.event_sender.clone()));
+        client.runtime.spawn(run_device_events(
+            device_events,
+            client.added_devices.clone(),
+            client.disconnected_since.clone(),
+            client.event_sender.clone(),
+        ));
+        if let Some(path) = &settings.action_stats_path {
+            client
+                .scheduler()
+                .action_stats
+                .load(settings.config_store.read_or_default(path, ACTION_STATS_FILE));
+        }
+        if let Some(persistence) = settings.settings_persistence.clone() {
+            client.settings_writer = Some(spawn_settings_writer(&client, persistence));
+        }
+        #[cfg(feature = "pattern-watch")]
+        if settings.pattern_cache.watch && !settings.pattern_path.is_empty() {
+            client.pattern_watcher = PatternWatcher::watch(client.pattern_library.clone(), &settings.pattern_path)
+                .map_err(|err| error!(?err, "patterns can't be watched"))
+                .ok();
+        }
+        client.scheduler().set_variable_deadband(settings.variable_deadband);
+        if let Some(window_ms) = settings.loop_crossfade_ms {
+            client.scheduler().set_loop_crossfade(Some(Duration::from_millis(window_ms)));
+        }
+        if let Some(gap_ms) = settings.loop_gap_ms {
+            client.scheduler().set_loop_gap(Some(Duration::from_millis(gap_ms)));
+        }
+        client.scheduler().set_speed_ladder(settings.speed_ladder.clone());
+        client.scheduler().set_resource_limits(settings.resource_limits);
+        if let Some(window_ms) = settings.takeover_crossfade_ms {
+            client.scheduler().set_takeover_crossfade(Some(Duration::from_millis(window_ms)));
+        }
+        if !settings.command_budgets.is_empty() {
+            client.scheduler().set_command_budgets(settings.command_budgets.clone());
+        }
+        if let ConnectionType::WebSocket(_) = &settings.connection {
+            let rtt_ms = client.rtt_ms.clone();
+            if let Some(jitter) = &settings.jitter_buffer {
+                client.scheduler().set_jitter_buffer(Some(JitterBuffer::new(
+                    Duration::from_millis(jitter.added_latency_ms),
+                    rtt_ms.clone(),
+                )));
+            }
+            if let Some(lookahead) = &settings.lookahead {
+                client.scheduler().set_lookahead(Some(Lookahead::new(
+                    Duration::from_millis(lookahead.max_lead_ms),
+                    rtt_ms.clone(),
+                )));
+            }
+            let probe_interval_ms = [
+                settings.jitter_buffer.as_ref().map(|x| x.probe_interval_ms),
+                settings.lookahead.as_ref().map(|x| x.probe_interval_ms),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+            if let Some(interval_ms) = probe_interval_ms {
+                client.runtime.spawn(run_rtt_probe(
+                    client.buttplug.clone(),
+                    Duration::from_millis(interval_ms),
+                    rtt_ms,
+                ));
+            }
+        }
+        if let Some(watchdog) = settings.watchdog {
+            client.runtime.spawn(run_watchdog(
+                client.buttplug.clone(),
+                watchdog,
+                client.event_sender.clone(),
+            ));
+        }
+        if let Some(battery) = settings.battery {
+            client.runtime.spawn(run_battery_monitor(
+                client.buttplug.clone(),
+                battery,
+                client.scheduler().device_limiter(),
+                client.event_sender.clone(),
+            ));
+        }
         Ok(client)
     }
 }
@@ -114,8 +344,16 @@ pub struct DispatchResult {
     pub actions: Vec<(String, Vec<Arc<Actuator>>)>
 }
 
+/// Tagged actuators by device index, with the device they were created from
+type TransportCache = HashMap<u32, (Arc<ButtplugClientDevice>, Vec<Arc<Actuator>>)>;
+
+/// Device manager of the current in-process server, it knows the hardware address of each device
+type InProcessDevices = Arc<Mutex<Option<Arc<ServerDeviceManager>>>>;
+
+/// Creates a server with the comm managers of 'features', its device manager replaces the one in 'devices'
 fn in_process_connector(
     features: InProcessFeatures,
+    devices: &InProcessDevices,
 ) -> impl ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage> {
     info!(?features, "connecting in process");
     let mut builder = ButtplugServerBuilder::default();
@@ -131,12 +369,22 @@ fn in_process_connector(
     let server = builder
         .finish()
         .expect("Could not create in-process-server.");
+    *devices.lock().unwrap() = Some(server.device_manager());
     ButtplugInProcessClientConnectorBuilder::default()
         .server(server)
         .finish()
 }
 
 impl BpClient {
+    /// Connects with the settings of 'manager', which stores them again shortly after
+    /// changes made through the client and when the client is dropped
+    pub fn connect_managed(manager: SettingsManager) -> Result<BpClient, Error> {
+        let mut client = BpClient::connect(manager.client().clone(), manager.devices().clone())?;
+        client.managed_writer = Some(spawn_settings_writer(&client, manager.persistence()));
+        client.settings_manager = Some(manager);
+        Ok(client)
+    }
+
     pub fn connect(
         settings: ClientSettings,
         actuator_settings: ActuatorSettings,
@@ -145,21 +393,38 @@ impl BpClient {
         match settings.connection {
             ConnectionType::WebSocket(endpoint) => {
                 let uri = format!("ws://{}", endpoint);
-                BpClient::connect_with(
+                let reconnect_uri = uri.clone();
+                let client = BpClient::connect_with(
                     || async move { new_json_ws_client_connector(&uri) },
                     Some(settings_clone),
                     Some(actuator_settings),
-                )
+                )?;
+                client.spawn_reconnect(move || new_json_ws_client_connector(&reconnect_uri));
+                Ok(client)
+            }
+            ConnectionType::InProcess => {
+                let features = settings.in_process_features;
+                let devices = InProcessDevices::default();
+                let connect_devices = devices.clone();
+                let mut client = BpClient::connect_with(
+                    move || async move { in_process_connector(features, &connect_devices) },
+                    Some(settings),
+                    Some(actuator_settings),
+                )?;
+                client.in_process_devices = devices.clone();
+                client.spawn_reconnect(move || in_process_connector(features, &devices));
+                Ok(client)
             }
-            ConnectionType::InProcess => BpClient::connect_with(
-                move || async move { in_process_connector(settings.in_process_features) },
-                Some(settings),
-                Some(actuator_settings),
-            ),
             ConnectionType::Test => get_test_connection(settings),
         }
     }
 
+    /// Replaces the logging settings, the redaction applies to everything logged afterwards
+    pub fn set_logging(&mut self, logging: LoggingSettings) {
+        logging.apply_redaction();
+        self.settings.logging = logging;
+    }
+
     pub fn read_actions(&mut self, action_path: &str) {
         self.actions = Actions(read_config_dir(action_path.into()));
         info!("read {} actions...", self.actions.0.len());
@@ -168,34 +433,51 @@ impl BpClient {
         }
     }
 
-    pub fn scan_for_devices(&self) -> bool {
-        info!("start scan");
-        let result = self
-            .runtime
-            .block_on(async move { self.buttplug.start_scanning().await });
-        if let Err(err) = result {
-            error!("Failed to start scan {:?}", err);
-            return false;
-        }
-        true
+
+    /// Locks the scheduler. Methods that only control tasks take `&self` and hold the lock
+    /// for a single call, so the client can be shared across threads (e.g. in an `Arc`).
+    ///
+    /// The lock is not reentrant: drop the guard before calling other methods of the client
+    /// and never keep it across an `.await` or a `block_on`, otherwise the thread deadlocks.
+    /// Use it for single calls like `client.scheduler().stop_all()`
+    pub fn scheduler(&self) -> MutexGuard<'_, ButtplugScheduler> {
+        self.scheduler.lock().unwrap()
     }
 
-    pub fn stop_scan(&self) -> bool {
-        info!("stop scan");
-        let result = self
-            .runtime
-            .block_on(async move { self.buttplug.stop_scanning().await });
-        if let Err(err) = result {
-            error!("Failed to stop scan {:?}", err);
+    /// Stops all devices, in stages if `staged_stop` is configured
+    pub fn stop_all(&self) -> bool {
+        let stages = match &self.settings.staged_stop {
+            Some(staged_stop) if !staged_stop.stages.is_empty() => staged_stop.stages(),
+            _ => return self.emergency_stop(),
+        };
+        info!(?stages, "stop all devices in stages");
+        self.stop_tracking();
+        let wind_down = self.scheduler().wind_down(stages);
+        if !self.is_connected() {
+            error!("stop_all while not connected");
+            self.runtime.spawn(wind_down);
             return false;
         }
+        let buttplug = self.buttplug.clone();
+        self.runtime.spawn(async move {
+            wind_down.await;
+            if let Err(err) = buttplug.stop_all_devices().await {
+                error!("Failed to queue stop_all {:?}", err);
+            }
+        });
         true
     }
 
-    pub fn stop_all(&mut self) -> bool {
+    /// Stops all devices right away, regardless of `staged_stop`
+    pub fn emergency_stop(&self) -> bool {
         info!("stop all devices");
 
-        self.scheduler.stop_all();
+        self.scheduler().stop_all();
+        self.stop_tracking();
+        if !self.is_connected() {
+            error!("stop_all while not connected");
+            return false;
+        }
         let buttplug = &self.buttplug;
         let result = self
             .runtime
@@ -208,8 +490,9 @@ impl BpClient {
         true
     }
 
-    pub fn disconnect(&mut self) {
+    pub fn disconnect(&self) {
         info!("disconnect");
+        self.disconnect_requested.store(true, Ordering::Relaxed);
         let buttplug = &self.buttplug;
         let result = self
             .runtime
@@ -217,20 +500,204 @@ impl BpClient {
         if let Err(err) = result {
             error!("Failed to send disconnect {:?}", err);
         }
+        self.set_connection_state(ConnectionState::Disconnected);
+    }
+
+    /// see [ButtplugScheduler::describe_handle]
+    pub fn describe_handle(&self, handle: i32) -> Option<HandleDescription> {
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.describe_handle(handle)
+    }
+
+    /// see [ButtplugScheduler::pause_task]
+    pub fn pause(&self, handle: i32) -> bool {
+        info!(handle, "pause");
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.pause_task(handle)
+    }
+
+    /// see [ButtplugScheduler::resume_task]
+    pub fn resume(&self, handle: i32) -> bool {
+        info!(handle, "resume");
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.resume_task(handle)
+    }
+
+    /// Plays a running task on the connected actuators with the given identifiers,
+    /// see [ButtplugScheduler::retarget_task]
+    pub fn retarget(&mut self, handle: i32, actuator_ids: &[String]) -> bool {
+        info!(handle, ?actuator_ids, "retarget");
+        let snapshot = self.device_snapshot();
+        let actuators = self
+            .load_configs(snapshot)
+            .into_iter()
+            .filter(|x| actuator_ids.iter().any(|id| id == x.identifier()))
+            .collect::<Vec<_>>();
+        self.scheduler().retarget_task(handle, actuators)
     }
 
-    pub fn update(&mut self, handle: i32, speed: Speed) -> bool {
+    pub fn update(&self, handle: i32, speed: Speed) -> bool {
         info!("update");
-        self.scheduler.clean_finished_tasks();
-        self.scheduler.update_task(handle, speed)
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.update_task(handle, speed)
+    }
+
+    /// Updates the speed of several handles in the same scheduler pass
+    pub fn update_many(&self, updates: &[(i32, Speed)]) -> Vec<bool> {
+        info!(?updates, "update many");
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.update_tasks(updates)
+    }
+
+    pub fn boost(&self, handle: i32, speed: Speed, duration: Duration) -> bool {
+        info!(handle, ?speed, ?duration, "boost");
+        self.scheduler().boost_task(handle, speed, duration)
+    }
+
+    /// Changes the tempo of a handle that plays `Strength::Metronome`
+    pub fn update_tempo(&self, handle: i32, bpm: f64) -> bool {
+        info!(handle, bpm, "update tempo");
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.update_tempo(handle, bpm)
+    }
+
+    /// see [ButtplugScheduler::step_up]
+    pub fn step_up(&self, handle: i32) -> Option<Speed> {
+        info!(handle, "step up");
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.step_up(handle)
+    }
+
+    /// see [ButtplugScheduler::step_down]
+    pub fn step_down(&self, handle: i32) -> Option<Speed> {
+        info!(handle, "step down");
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.step_down(handle)
+    }
+
+    /// see [ButtplugScheduler::set_stroke_milestones]
+    pub fn set_stroke_milestones(&self, handle: i32, milestones: StrokeMilestones) -> bool {
+        info!(handle, ?milestones, "set stroke milestones");
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.set_stroke_milestones(handle, milestones)
+    }
+
+    /// see [ButtplugScheduler::stroke_count]
+    pub fn stroke_count(&self, handle: i32) -> Option<u64> {
+        self.scheduler().stroke_count(handle)
+    }
+
+    /// see [ButtplugScheduler::update_loop_gap]
+    pub fn update_loop_gap(&self, handle: i32, gap: Duration) -> bool {
+        info!(handle, ?gap, "update loop gap");
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.update_loop_gap(handle, gap)
+    }
+
+    /// see [ButtplugScheduler::set_auto_pause]
+    pub fn set_auto_pause(&self, handle: i32, timeout: Option<Duration>) -> bool {
+        info!(handle, ?timeout, "set_auto_pause");
+        self.scheduler().set_auto_pause(handle, timeout)
+    }
+
+    pub fn update_lanes(&self, handle: i32, lanes: HashMap<String, Speed>) -> bool {
+        info!("update lanes");
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.update_task_lanes(handle, lanes)
+    }
+
+    /// Makes variable and expression strengths of future dispatches follow
+    /// the host's frame loop, see `SamplingTrigger`
+    pub fn set_sampling_trigger(&self, trigger: Option<SamplingTrigger>) {
+        info!(enabled = trigger.is_some(), "sampling trigger");
+        self.scheduler().set_sampling_trigger(trigger);
+    }
+
+    /// Caps all scalar outputs and slows down strokes according
+    /// to `settings.quiet_mode`, applies to running handles
+    pub fn set_quiet_mode(&self, enabled: bool) {
+        info!(enabled, "quiet mode");
+        let quiet_mode = enabled.then(|| self.settings.quiet_mode.clone());
+        self.scheduler().set_quiet_mode(quiet_mode);
+    }
+
+    /// see [ButtplugScheduler::set_global_intensity]
+    pub fn set_global_intensity(&self, intensity: Speed) {
+        info!(?intensity, "global intensity");
+        self.scheduler().set_global_intensity(intensity);
+    }
+
+    /// Applies changes made to `device_settings` (limits, factors...)
+    /// to the running handles
+    pub fn apply_device_settings(&self) {
+        info!("apply device settings");
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.update_actuator_configs(&self.device_settings.0);
     }
 
-    pub fn stop(&mut self, handle: i32) -> bool {
+    pub fn stop(&self, handle: i32) -> bool {
         info!("stop");
-        self.scheduler.stop_task(handle);
+        self.scheduler().stop_task(handle);
+        true
+    }
+
+    /// Usage statistics of all actions by name
+    pub fn action_stats(&self) -> HashMap<String, ActionStats> {
+        self.scheduler().action_stats.get_all()
+    }
+
+    pub fn pattern_cache_stats(&self) -> PatternCacheStats {
+        self.pattern_library.lock().unwrap().stats()
+    }
+
+    /// Infos of all patterns in `settings.pattern_path`, see `PatternLibrary::list_patterns`
+    pub fn list_patterns(&self) -> Vec<PatternInfo> {
+        self.pattern_library.lock().unwrap().list_patterns(&self.settings.pattern_path)
+    }
+
+    /// Issues of the pattern 'pattern_name' in `settings.pattern_path`, see `pattern::validate`.
+    /// Patterns that are refused for fatal issues are read as well, None if it cannot be read
+    pub fn validate_pattern(&self, pattern_name: &str, vibration_pattern: bool) -> Option<Vec<PatternIssue>> {
+        load_pattern_name(&self.settings.pattern_path, pattern_name, vibration_pattern)
+            .ok()
+            .map(|x| validate(&x))
+    }
+
+    pub fn reset_action_stats(&self) {
+        info!("reset action stats");
+        self.scheduler().action_stats.reset();
+        if let Some(path) = &self.settings.action_stats_path {
+            self.settings.config_store.try_write(&self.scheduler().action_stats.get_all(), path, ACTION_STATS_FILE);
+        }
+    }
+
+    /// Stops several handles in the same scheduler pass
+    pub fn stop_many(&self, handles: &[i32]) -> bool {
+        info!(?handles, "stop many");
+        self.scheduler().stop_tasks(handles);
         true
     }
 
+    /// Stops all tasks that were started by the action 'name', returns the stopped handles
+    pub fn stop_action(&self, name: &str) -> Vec<i32> {
+        info!(name, "stop_action");
+        let mut scheduler = self.scheduler();
+        scheduler.clean_finished_tasks();
+        scheduler.stop_action(name)
+    }
+
     pub fn dispatch_refs(
         &mut self,
         actions: Vec<(Strength, Action)>,
@@ -238,17 +705,59 @@ impl BpClient {
         speed: Speed,
         duration: Duration,
     ) -> DispatchResult {
-        info!(?actions, "dispatch_refs");
+        self.dispatch_refs_transposed(actions, body_parts, speed, duration, Transposition::default())
+    }
+
+    /// Like `dispatch_refs`, with 'transposition' applied to the values of all played funscripts
+    pub fn dispatch_refs_transposed(
+        &mut self,
+        actions: Vec<(Strength, Action)>,
+        body_parts: Vec<String>,
+        speed: Speed,
+        duration: Duration,
+        transposition: Transposition,
+    ) -> DispatchResult {
+        info!(?actions, ?transposition, "dispatch_refs");
+        if !self.is_connected() {
+            error!("dispatch while not connected");
+            return DispatchResult { handle: -1, actions: vec![] };
+        }
+        let snapshot = self.device_snapshot();
+        let (result, tasks) =
+            self.prepare_refs(actions, body_parts, speed, duration, transposition, &snapshot);
+        for task in tasks {
+            self.runtime.spawn(task);
+        }
+        result
+    }
+
+    fn prepare_refs(
+        &mut self,
+        actions: Vec<(Strength, Action)>,
+        body_parts: Vec<String>,
+        speed: Speed,
+        duration: Duration,
+        transposition: Transposition,
+        snapshot: &[Arc<Actuator>],
+    ) -> (DispatchResult, Vec<impl Future<Output = ()> + Send + 'static>) {
+        let supported = actions
+            .iter()
+            .all(|(strength, action)| action.control.iter().all(|x| self.supports(x, strength, &action.name)));
+        if !supported {
+            return (DispatchResult { handle: -1, actions: vec![] }, vec![]);
+        }
         let mut handle = -1;
         let mut started_actions = vec![];
+        let mut tasks = vec![];
         for action in actions {
-            let strength = action.0.multiply(&speed);
+            let strength = action.0;
             for control in action.1.control.clone() {
                 let ext_selector = Selector::from(&body_parts);
                 let used_actuators;
+                let task;
 
                 let action_name = action.1.name.clone();
-                (handle, used_actuators) = self.dispatch(
+                let prepared = self.prepare(
                     match control {
                         Control::Scalar(selector, actuators) => {
                             Control::Scalar(selector.and(ext_selector), actuators)
@@ -258,17 +767,122 @@ impl BpClient {
                         }
                     },
                     strength.clone(),
+                    speed,
                     duration,
                     handle,
                     action_name.clone(),
+                    transposition,
+                    snapshot,
                 );
+                let Some(prepared) = prepared else {
+                    // the players of the previous controls never start
+                    if handle > 0 {
+                        self.scheduler().stop_task(handle);
+                    }
+                    return (DispatchResult { handle: -1, actions: vec![] }, vec![]);
+                };
+                (handle, used_actuators, task) = prepared;
                 started_actions.push( (action_name, used_actuators ) );
+                tasks.push(task);
             }
         }
 
-        DispatchResult {
-            handle,
-            actions: started_actions
+        (
+            DispatchResult {
+                handle,
+                actions: started_actions
+            },
+            tasks,
+        )
+    }
+
+    /// Whether 'players' new players of 'handle' fit within `settings.resource_limits`,
+    /// raises `CapacityExceeded` if they don't
+    fn has_capacity(&self, handle: i32, players: usize) -> bool {
+        match self.scheduler().check_capacity(handle, players) {
+            Ok(()) => true,
+            Err(err) => {
+                error!(%err, "refusing dispatch");
+                let _ = self.event_sender.send(ClientEvent::CapacityExceeded(err));
+                false
+            }
+        }
+    }
+
+    /// Whether 'control' can be played with 'strength', raises `UnsupportedStrength` if it can't
+    fn supports(&self, control: &Control, strength: &Strength, action_name: &str) -> bool {
+        if control.supports(strength) {
+            return true;
+        }
+        error!(action_name, %strength, "refusing dispatch, strokes can't follow the strength");
+        let _ = self
+            .event_sender
+            .send(ClientEvent::UnsupportedStrength(action_name.to_owned(), strength.to_string()));
+        false
+    }
+
+    /// Does the housekeeping for a new dispatch and returns all connected actuators
+    fn device_snapshot(&mut self) -> Vec<Arc<Actuator>> {
+        self.scheduler().clean_finished_tasks();
+        self.load_added_devices();
+        self.disable_idle_actuators();
+        let connected = self
+            .buttplug
+            .devices()
+            .into_iter()
+            .filter(|x| x.connected())
+            .collect::<Vec<_>>();
+        let snapshot = self.with_transports(connected);
+        self.forget_disconnected(&snapshot);
+        snapshot
+    }
+
+    /// Actuators of all devices the server knows about, including disconnected ones
+    pub fn all_actuators(&self) -> Vec<Arc<Actuator>> {
+        self.with_transports(self.buttplug.devices())
+    }
+
+    /// Actuators of 'devices' tagged with the transport of their device and the remote server,
+    /// cached until a reconnect replaces the device
+    fn with_transports(&self, devices: Vec<Arc<ButtplugClientDevice>>) -> Vec<Arc<Actuator>> {
+        let server_name = match self.settings.connection {
+            ConnectionType::WebSocket(_) => self.buttplug.server_name(),
+            _ => None,
+        };
+        let mut cache = self.transport_cache.lock().unwrap();
+        let mut actuators = vec![];
+        for device in &devices {
+            match cache.get(&device.index()) {
+                Some((cached, tagged)) if Arc::ptr_eq(cached, device) => actuators.extend(tagged.iter().cloned()),
+                _ => {
+                    let transport = self.transport(device);
+                    let tagged = device
+                        .flatten_actuators()
+                        .into_iter()
+                        .map(|x| Arc::new(Actuator::clone(&x).with_transport(transport, server_name.clone())))
+                        .collect::<Vec<_>>();
+                    actuators.extend(tagged.iter().cloned());
+                    cache.insert(device.index(), (device.clone(), tagged));
+                }
+            }
+        }
+        actuators
+    }
+
+    /// Reads the transport from the address of the device if it is connected to the
+    /// in-process server, guesses it from the connection otherwise
+    fn transport(&self, device: &ButtplugClientDevice) -> Transport {
+        let address = self
+            .in_process_devices
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|manager| manager.device_info(device.index()))
+            .and_then(|info| serde_json::to_value(info.identifier()).ok())
+            .and_then(|identifier| identifier["address"].as_str().map(String::from));
+        match address {
+            Some(address) => Transport::from_address(&address),
+            None => Transport::detect(device.name(), &self.settings.connection, &self.settings.in_process_features),
         }
     }
 
@@ -280,59 +894,202 @@ impl BpClient {
         handle: i32,
         action_name: String, // just for diagnosis
     ) -> (i32, Vec<Arc<Actuator>>) {
+        if !self.is_connected() {
+            error!("dispatch while not connected");
+            return (-1, vec![]);
+        }
+        if !self.supports(&control, &strength, &action_name) {
+            return (-1, vec![]);
+        }
+        let snapshot = self.device_snapshot();
+        let Some((handle, actuators, task)) = self.prepare(
+            control,
+            strength,
+            Speed::max(),
+            duration,
+            handle,
+            action_name,
+            Transposition::default(),
+            &snapshot,
+        ) else {
+            return (-1, vec![]);
+        };
+        self.runtime.spawn(task);
+        (handle, actuators)
+    }
+
+    /// Selects the actuators for 'control' from 'snapshot' and creates the players,
+    /// the returned task plays the action once it is spawned. None if the players
+    /// exceed `settings.resource_limits`
+    #[allow(clippy::too_many_arguments)]
+    fn prepare(
+        &mut self,
+        control: Control,
+        strength: Strength,
+        scale: Speed,
+        duration: Duration,
+        handle: i32,
+        action_name: String,
+        transposition: Transposition,
+        snapshot: &[Arc<Actuator>],
+    ) -> Option<(i32, Vec<Arc<Actuator>>, impl Future<Output = ()> + Send + 'static)> {
         info!(handle, "dispatch");
-        self.scheduler.clean_finished_tasks();
-        let body_parts = trim_lower_str_list(
-            &control
-                .get_selector()
-                .as_vec()
-                .iter()
-                .map(|x| x.as_str())
-                .collect::<Vec<_>>(),
-        );
-        info!(?body_parts);
-        let (updated_settings, actuators) =
-            Filter::new(self.device_settings.clone(), &self.buttplug.devices())
-                .load_config(&mut self.device_settings)
-                .connected()
-                .enabled()
-                .with_actuator_types(&control.get_actuators())
-                .with_body_parts(&body_parts)
-                .result();
+        let loaded = self.load_configs(snapshot.to_vec());
+        let excluded_transports = &self.settings.body_part_excluded_transports;
+        let actuators = Filter::from_actuators(self.settings_cache.clone(), loaded)
+            .connected()
+            .enabled()
+            .with_actuator_types(&control.get_actuators())
+            .with_position_playback(matches!(control, Control::Stroke(_, _)))
+            .with_first_match(&control.get_selector().alternatives(), |filter, selector| {
+                let body_parts =
+                    trim_lower_str_list(&selector.as_vec().iter().map(|x| x.as_str()).collect::<Vec<_>>());
+                info!(?body_parts);
+                filter
+                    .with_body_parts(&body_parts)
+                    .without_transports(match body_parts.is_empty() {
+                        true => &[],
+                        false => excluded_transports,
+                    })
+                    .with_namespace(selector.namespace().as_deref())
+                    .with_role(selector.role().as_deref())
+                    .retain(|actuator| !self.refuse_capped_actuator(actuator))
+            })
+            .result();
         let ret_actuators = actuators.clone();
 
-        self.device_settings = updated_settings;
-        let pattern_path = self.settings.pattern_path.clone();
+        self.scheduler().sync_actuator_configs(&self.device_settings.0);
+        let pattern_source = PatternSource {
+            path: self.settings.pattern_path.clone(),
+            library: self.pattern_library.clone(),
+            fit: Some(duration).filter(|x| transposition.fit_duration && !x.is_zero()),
+        };
+        let one_shot = duration.is_zero() && self.settings.zero_duration == ZeroDurationBehaviour::OneShot;
+        let per_loop = self.settings.random_patterns == RandomPatternMode::PerLoop;
+        let metronome_pulse = Duration::from_millis(self.settings.metronome_pulse_ms);
 
-        let player = self.scheduler.create_player(actuators, handle);
+        let mut bundle_players = match (&control, &strength) {
+            (Control::Scalar(_, _), Strength::Bundle(_, bundle)) if !one_shot => {
+                self.create_bundle_players(&actuators, bundle, &pattern_source, handle, &action_name)?
+            }
+            _ => vec![],
+        };
+        let (player, bundle_pattern) = if bundle_players.is_empty() {
+            if !self.has_capacity(handle, 1) {
+                return None;
+            }
+            (self.scheduler().create_action_player(actuators, handle, &action_name), None)
+        } else {
+            let (player, fscript) = bundle_players.remove(0);
+            (player, Some(fscript))
+        };
+        let player = player.with_transposition(transposition);
+        let bundle_players = bundle_players
+            .into_iter()
+            .map(|(player, fscript)| (player.with_transposition(transposition), fscript))
+            .collect::<Vec<_>>();
+        let pending_init = self.take_uninitialized(&ret_actuators);
+        let ret_actuators = match bundle_pattern {
+            Some(_) => player
+                .actuators
+                .iter()
+                .chain(bundle_players.iter().flat_map(|(player, _)| player.actuators.iter()))
+                .cloned()
+                .collect(),
+            None => ret_actuators,
+        };
+        let actuator_ids = ret_actuators.iter().map(|x| x.identifier().to_owned()).collect::<Vec<_>>();
         let handle = player.handle;
-
-        self.runtime.spawn(async move {
+        self.scheduler().session_log.set_action(handle, &action_name);
+        self.scheduler().action_stats.start(handle, &action_name);
+        let action_stats = self.scheduler().action_stats.clone();
+        let action_stats_path = self.settings.action_stats_path.clone();
+        let config_store = self.settings.config_store.clone();
+        let failing_since = self.failing_since.clone();
+        let event_sender = self.event_sender.clone();
+        let runtime_ledger = self.runtime_ledger.clone();
+        let runtime_caps = self.settings.runtime_caps.clone();
+        let stop_tokens = std::iter::once(&player)
+            .chain(bundle_players.iter().map(|(player, _)| player))
+            .map(|player| player.stop_token())
+            .collect::<Vec<_>>();
+
+        let task = async move {
             let now = Instant::now();
+            let start_ms = unix_ms();
+            let runtime_guard = runtime_caps.clone().map(|caps| {
+                tokio::spawn(enforce_runtime_cap(
+                    runtime_ledger.clone(),
+                    caps,
+                    config_store.clone(),
+                    actuator_ids.clone(),
+                    (start_ms, now),
+                    event_sender.clone(),
+                    stop_tokens,
+                ))
+            });
             let handle = player.handle;
             let actuators = &player.actuators;
             let sp = span!(Level::INFO, "dispatching", handle, action_name);
-            info!(?actuators, ?body_parts);
+            info!(?actuators);
             async move {
+                pending_init.run().await;
                 let result = match control {
+                    Control::Scalar(_, _) if one_shot => player.play_scalar_once(one_shot_speed(&strength, scale)).await,
+                    Control::Stroke(_, range) if one_shot => {
+                        player
+                            .play_linear_once(
+                                one_shot_speed(&strength, scale),
+                                LinearRange {
+                                    min_ms: range.min_ms,
+                                    max_ms: range.max_ms,
+                                    min_pos: range.min_pos,
+                                    max_pos: range.max_pos,
+                                    invert: false,
+                                    scaling: LinearSpeedScaling::Linear,
+                                    profile: StrokeProfile::Constant,
+                                },
+                            )
+                            .await
+                    }
                     Control::Scalar(_, _) => match strength {
                         Strength::Constant(speed) => {
-                            player.play_scalar(duration, Speed::new(speed.into())).await
+                            player.play_scalar(duration, Speed::new(speed.into()) * scale).await
                         }
                         Strength::Funscript(speed, pattern) => {
-                            match read_pattern(&pattern_path, &pattern, true) {
+                            match pattern_source.read(&pattern, true) {
                                 Some(fscript) => {
                                     player
                                         .play_scalar_pattern(
                                             duration,
                                             fscript,
-                                            Speed::new(speed.into()),
+                                            Speed::new(speed.into()) * scale,
                                         )
                                         .await
                                 }
                                 None => {
                                     error!("error reading pattern {}", pattern);
-                                    player.play_scalar(duration, Speed::new(speed.into())).await
+                                    player.play_scalar(duration, Speed::new(speed.into()) * scale).await
+                                }
+                            }
+                        }
+                        Strength::RandomFunscript(speed, patterns) if per_loop => {
+                            let fscripts = pattern_source.read_all(&patterns, true);
+                            let first = fscripts.choose(&mut rand::thread_rng()).map(copy_actions);
+                            match first {
+                                Some(fscript) => {
+                                    player
+                                        .play_scalar_patterns(
+                                            duration,
+                                            fscript,
+                                            Speed::new(speed.into()) * scale,
+                                            random_loop(fscripts),
+                                        )
+                                        .await
+                                }
+                                None => {
+                                    error!(?patterns, "error reading patterns");
+                                    player.play_scalar(duration, Speed::new(speed.into()) * scale).await
                                 }
                             }
                         }
@@ -341,30 +1098,56 @@ impl BpClient {
                                 .get(rand::thread_rng().gen_range(0..patterns.len() - 1))
                                 .unwrap()
                                 .clone();
-                            match read_pattern(&pattern_path, &pattern, true) {
+                            match pattern_source.read(&pattern, true) {
                                 Some(fscript) => {
                                     player
                                         .play_scalar_pattern(
                                             duration,
                                             fscript,
-                                            Speed::new(speed.into()),
+                                            Speed::new(speed.into()) * scale,
                                         )
                                         .await
                                 }
                                 None => {
                                     error!("error reading pattern {}", pattern);
-                                    player.play_scalar(duration, Speed::new(speed.into())).await
+                                    player.play_scalar(duration, Speed::new(speed.into()) * scale).await
+                                }
+                            }
+                        }
+                        Strength::Metronome(speed, bpm) => {
+                            player
+                                .play_scalar_metronome(duration, bpm, metronome_pulse, Speed::new(speed.into()) * scale)
+                                .await
+                        }
+                        Strength::Bundle(speed, bundle) => {
+                            let speed = Speed::new(speed.into()) * scale;
+                            match bundle_pattern {
+                                Some(fscript) => {
+                                    let mut plays = vec![player.play_scalar_pattern(duration, fscript, speed)];
+                                    plays.extend(
+                                        bundle_players
+                                            .into_iter()
+                                            .map(|(player, fscript)| player.play_scalar_pattern(duration, fscript, speed)),
+                                    );
+                                    combine_results(join_all(plays).await)
+                                }
+                                None => {
+                                    error!("error reading bundle {}", bundle);
+                                    player.play_scalar(duration, speed).await
                                 }
                             }
                         }
                         Strength::Variable(arc) => player.play_scalar_var(duration, arc).await,
+                        Strength::Expression(expression) => {
+                            player.play_scalar_expression(duration, expression).await
+                        }
                     },
                     Control::Stroke(_, range) => match strength {
                         Strength::Constant(speed) => {
                             player
                                 .play_linear_stroke(
                                     duration,
-                                    Speed::new(speed.into()),
+                                    Speed::new(speed.into()) * scale,
                                     LinearRange {
                                         min_ms: range.min_ms,
                                         max_ms: range.max_ms,
@@ -372,19 +1155,37 @@ impl BpClient {
                                         max_pos: range.max_pos,
                                         invert: false,
                                         scaling: LinearSpeedScaling::Linear,
+                                        profile: StrokeProfile::Constant,
                                     },
                                 )
                                 .await
                         }
                         Strength::Funscript(speed, pattern) => {
-                            match read_pattern(&pattern_path, &pattern, true) {
+                            match pattern_source.read(&pattern, true) {
                                 Some(fscript) => player.play_linear(duration, fscript).await,
                                 None => {
                                     error!("error reading pattern {}", pattern);
                                     player
                                         .play_linear_stroke(
                                             duration,
-                                            Speed::new(speed.into()),
+                                            Speed::new(speed.into()) * scale,
+                                            LinearRange::max(),
+                                        )
+                                        .await
+                                }
+                            }
+                        }
+                        Strength::RandomFunscript(speed, patterns) if per_loop => {
+                            let fscripts = pattern_source.read_all(&patterns, false);
+                            let first = fscripts.choose(&mut rand::thread_rng()).map(copy_actions);
+                            match first {
+                                Some(fscript) => player.play_linear_patterns(duration, fscript, random_loop(fscripts)).await,
+                                None => {
+                                    error!(?patterns, "error reading patterns");
+                                    player
+                                        .play_linear_stroke(
+                                            duration,
+                                            Speed::new(speed.into()) * scale,
                                             LinearRange::max(),
                                         )
                                         .await
@@ -396,68 +1197,265 @@ impl BpClient {
                                 .get(rand::thread_rng().gen_range(0..patterns.len() - 1))
                                 .unwrap()
                                 .clone();
-                            match read_pattern(&pattern_path, &pattern, false) {
+                            match pattern_source.read(&pattern, false) {
                                 Some(fscript) => player.play_linear(duration, fscript).await,
                                 None => {
                                     error!("error reading pattern {}", pattern);
                                     player
                                         .play_linear_stroke(
                                             duration,
-                                            Speed::new(speed.into()),
+                                            Speed::new(speed.into()) * scale,
                                             LinearRange::max(),
                                         )
                                         .await
                                 }
                             }
                         }
-                        Strength::Variable(_) => panic!("dynamic not supported"),
+                        Strength::Metronome(_, bpm) => {
+                            player
+                                .play_linear_metronome(
+                                    duration,
+                                    bpm,
+                                    LinearRange {
+                                        min_ms: range.min_ms,
+                                        max_ms: range.max_ms,
+                                        min_pos: range.min_pos,
+                                        max_pos: range.max_pos,
+                                        invert: false,
+                                        scaling: LinearSpeedScaling::Linear,
+                                        profile: StrokeProfile::Constant,
+                                    },
+                                )
+                                .await
+                        }
+                        Strength::Bundle(speed, bundle) => {
+                            error!(bundle, "bundles only drive scalar actuators");
+                            player
+                                .play_linear_stroke(
+                                    duration,
+                                    Speed::new(speed.into()) * scale,
+                                    LinearRange::max(),
+                                )
+                                .await
+                        }
+                        Strength::Variable(_) | Strength::Expression(_) => {
+                            // refused by `BpClient::supports`
+                            error!("strokes can't follow dynamic strengths");
+                            Ok(())
+                        }
                     },
                 };
                 info!(handle, "done");
+                action_stats.finish(handle, &action_name);
+                if let Some(path) = &action_stats_path {
+                    config_store.try_write(&action_stats.get_all(), path, ACTION_STATS_FILE);
+                }
+                if let Some(guard) = &runtime_guard {
+                    guard.abort();
+                }
+                if let Some(caps) = &runtime_caps {
+                    record_runtime(&runtime_ledger, caps, &config_store, &actuator_ids, start_ms, now.elapsed());
+                }
                 match result {
                     Ok(()) => {
                         info!(
                             handle, elapsed=?now.elapsed(), "action done"
                         );
+                        let mut failing_since = failing_since.lock().unwrap();
+                        for actuator in &actuator_ids {
+                            failing_since.remove(actuator);
+                        }
                     }
                     Err(err) => {
                         error!(
                             handle, elapsed=?now.elapsed(), ?err, "action errored"
-                        )
+                        );
+                        let mut failing_since = failing_since.lock().unwrap();
+                        for err in err.errors() {
+                            let _ = event_sender.send(ClientEvent::CommandFailed(CommandFailure::new(
+                                err,
+                                &action_name,
+                                handle,
+                            )));
+                            failing_since.entry(err.actuator.identifier().to_owned()).or_insert(now);
+                        }
                     }
                 };
             }
             .instrument(sp)
             .await;
-        });
+        };
+
+        Some((handle, ret_actuators, task))
+    }
 
-        (handle, ret_actuators)
+    /// Creates a player under the same handle for each actuator that has a channel
+    /// in the bundle, empty if the bundle can't be read or no actuator matches.
+    /// None if the players exceed `settings.resource_limits`
+    fn create_bundle_players(
+        &mut self,
+        actuators: &[Arc<Actuator>],
+        bundle_name: &str,
+        pattern_source: &PatternSource,
+        handle: i32,
+        action_name: &str,
+    ) -> Option<Vec<(PatternPlayer, FScript)>> {
+        let Some(bundle) = read_bundle(&pattern_source.path, bundle_name) else {
+            return Some(vec![]);
+        };
+        let patterns = bundle.load(|pattern| pattern_source.read(pattern, true));
+        let channels = actuators
+            .iter()
+            .filter_map(|actuator| match patterns.get(&actuator.index_in_device) {
+                Some(fscript) => Some((actuator, fscript)),
+                None => {
+                    debug!(%actuator, bundle_name, "actuator has no bundle channel");
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if !channels.is_empty() && !self.has_capacity(handle, channels.len()) {
+            return None;
+        }
+        let mut handle = handle;
+        let mut players = vec![];
+        for (actuator, fscript) in channels {
+            let player = self.scheduler().create_action_player(vec![actuator.clone()], handle, action_name);
+            handle = player.handle;
+            players.push((player, copy_actions(fscript)));
+        }
+        Some(players)
     }
 }
 
-impl fmt::Debug for BpClient {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("BpClient")
-            .field("settings", &self.settings)
-            .finish()
+/// Reads the patterns of a dispatch
+struct PatternSource {
+    path: String,
+    library: Arc<Mutex<PatternLibrary>>,
+    /// fits all patterns to this duration, see `Transposition::fit_duration`
+    fit: Option<Duration>,
+}
+
+impl PatternSource {
+    fn read(&self, name: &str, vibration: bool) -> Option<FScript> {
+        let fscript = self.library.lock().unwrap().read(&self.path, name, vibration)?;
+        Some(match self.fit {
+            Some(duration) => fit_to_duration(&fscript, duration),
+            None => fscript,
+        })
+    }
+
+    /// All of 'names' that can be read and contain actions
+    fn read_all(&self, names: &[String], vibration: bool) -> Vec<FScript> {
+        names
+            .iter()
+            .filter_map(|x| self.read(x, vibration))
+            .filter(|x| !x.actions.is_empty())
+            .collect()
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use actuator::Actuators;
-    use buttplug::client::ButtplugClientDevice;
-    use buttplug::core::message::{ActuatorType, DeviceAdded};
-    use funscript::FScript;
-    use itertools::Itertools;
-    use pattern::read_pattern;
-    use std::time::Instant;
-    use std::{thread, time::Duration, vec};
+/// Picks a random pattern of 'fscripts' for each loop
+fn random_loop(fscripts: Vec<FScript>) -> impl FnMut() -> Option<FScript> {
+    move || fscripts.choose(&mut rand::thread_rng()).map(copy_actions)
+}
 
-    use super::*;
-    use bp_fakes::*;
+/// Value that a one-shot dispatch of 'strength' starts with
+fn one_shot_speed(strength: &Strength, scale: Speed) -> Speed {
+    match strength {
+        Strength::Constant(speed)
+        | Strength::Funscript(speed, _)
+        | Strength::RandomFunscript(speed, _)
+        | Strength::Metronome(speed, _)
+        | Strength::Bundle(speed, _) => Speed::new((*speed).into()) * scale,
+        Strength::Variable(arc) => Speed::new(arc.load(Ordering::Relaxed)),
+        Strength::Expression(expression) => Speed::new(expression.sample()),
+    }
+}
 
-    macro_rules! assert_timeout {
+impl Drop for BpClient {
+    fn drop(&mut self) {
+        if let Some(manager) = &mut self.settings_manager {
+            manager.set_client(self.settings.clone());
+            manager.set_devices(self.device_settings.clone());
+            manager.save();
+        }
+        // SAFETY: the runtime is taken once and the field is not accessed afterwards
+        let runtime = unsafe { ManuallyDrop::take(&mut self.runtime) };
+        let cleanup = self.stop_on_drop();
+        let shutdown = move || {
+            if let Some(cleanup) = cleanup {
+                let waited = runtime.block_on(timeout(Duration::from_millis(DROP_TIMEOUT_MS), cleanup));
+                if waited.is_err() {
+                    error!("devices did not stop within {}ms of the drop", DROP_TIMEOUT_MS);
+                }
+            }
+        };
+        if Handle::try_current().is_ok() {
+            // blocking and dropping a runtime panic within an async context,
+            // the cleanup and the shutdown run on a background thread instead
+            thread::spawn(shutdown);
+        } else {
+            shutdown();
+        }
+    }
+}
+
+impl BpClient {
+    /// Stops all tasks and returns the device cleanup of `settings.on_drop`, if any
+    fn stop_on_drop(&mut self) -> Option<impl Future<Output = ()>> {
+        if !self.buttplug.connected() {
+            return None;
+        }
+        let disconnect = match self.settings.on_drop {
+            DropBehaviour::StopAndDisconnect => true,
+            DropBehaviour::StopDevices => false,
+            DropBehaviour::Nothing => return None,
+        };
+        info!(disconnect, "stopping devices on drop");
+        self.scheduler().stop_all();
+        self.stop_tracking();
+        if disconnect {
+            self.disconnect_requested.store(true, Ordering::Relaxed);
+        }
+        let buttplug = self.buttplug.clone();
+        Some(async move {
+            if let Err(err) = buttplug.stop_all_devices().await {
+                error!("Failed to queue stop_all {:?}", err);
+            }
+            if disconnect {
+                if let Err(err) = buttplug.disconnect().await {
+                    error!("Failed to send disconnect {:?}", err);
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Debug for BpClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BpClient")
+            .field("settings", &self.settings)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actuator::Actuators;
+    use buttplug::core::message::{ActuatorType, DeviceAdded};
+    use buttplug::core::errors::ButtplugDeviceError;
+    use crate::player::worker::{combine_results, WorkerError, WorkerResult};
+    use funscript::FScript;
+    use itertools::Itertools;
+    use pattern::read_pattern;
+    use std::time::Instant;
+    use std::{thread, time::Duration, vec};
+
+    use super::*;
+    use bp_fakes::*;
+
+    macro_rules! assert_timeout {
         ($cond:expr, $arg:tt) => {
             // starting time
             let start: Instant = Instant::now();
@@ -469,6 +1467,7 @@ mod tests {
             }
         };
     }
+    pub(super) use assert_timeout;
 
     impl BpClient {
         pub fn await_connect(&mut self, devices: usize) {
@@ -477,7 +1476,7 @@ mod tests {
     }
 
     /// Vibrate
-    pub fn test_cmd(
+    pub(super) fn test_cmd(
         tk: &mut BpClient,
         strength: Strength,
         duration: Duration,
@@ -519,6 +1518,240 @@ mod tests {
         call_registry.get_device(1)[1].assert_strenth(0.0);
     }
 
+    #[test]
+    fn test_tasks_are_controlled_from_other_threads() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+        let handle = test_cmd(
+            &mut tk,
+            Strength::Constant(100),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        // act
+        let tk = Arc::new(tk);
+        let updater = {
+            let tk = tk.clone();
+            thread::spawn(move || tk.update(handle, Speed::new(50)))
+        };
+        assert!(updater.join().unwrap());
+        thread::sleep(Duration::from_secs(1));
+        thread::spawn(move || tk.stop(handle)).join().unwrap();
+        thread::sleep(Duration::from_secs(1));
+
+        // assert
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.get_device(1)[1].assert_strenth(0.5);
+        call_registry.get_device(1)[2].assert_strenth(0.0);
+    }
+
+    #[test]
+    fn shared_client_controls_tasks_from_other_threads() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+        let handle = test_cmd(
+            &mut tk,
+            Strength::Constant(100),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(500));
+
+        // act
+        let tk = Arc::new(tk);
+        let on_thread = |control: fn(&BpClient, i32) -> bool| {
+            let tk = tk.clone();
+            let result = thread::spawn(move || control(&tk, handle)).join().unwrap();
+            thread::sleep(Duration::from_millis(500));
+            result
+        };
+        let paused = on_thread(|tk, handle| tk.pause(handle));
+        let resumed = on_thread(|tk, handle| tk.resume(handle));
+        on_thread(|tk, _| {
+            tk.set_global_intensity(Speed::new(50));
+            true
+        });
+        let stopped = on_thread(|tk, _| tk.emergency_stop());
+
+        // assert
+        assert!(paused && resumed && stopped);
+        let calls = call_registry.get_device(1);
+        calls[0].assert_strenth(1.0);
+        calls[1].assert_strenth(0.0);
+        calls[2].assert_strenth(1.0);
+        calls[3].assert_strenth(0.5);
+        calls.last().unwrap().assert_strenth(0.0);
+    }
+
+    #[test]
+    fn zero_duration_sends_start_and_stop_once() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+
+        // act
+        test_cmd(
+            &mut tk,
+            Strength::Constant(50),
+            Duration::ZERO,
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        // assert
+        let calls = call_registry.get_device(1);
+        calls[0].assert_strenth(0.5);
+        calls[1].assert_strenth(0.0);
+        assert_eq!(calls.len(), 2);
+    }
+
+    #[test]
+    fn dispatches_beyond_resource_limits_are_refused() {
+        // arrange
+        let settings = ClientSettings {
+            resource_limits: Some(ResourceLimits { max_handles: 1, max_players: 10 }),
+            ..Default::default()
+        };
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings), None);
+
+        // act
+        let first = test_cmd(
+            &mut tk,
+            Strength::Constant(100),
+            Duration::from_secs(1),
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        let second = test_cmd(
+            &mut tk,
+            Strength::Constant(50),
+            Duration::from_secs(1),
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        assert!(first > 0);
+        assert_eq!(second, -1);
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        assert!(tk
+            .events
+            .try_iter()
+            .any(|x| matches!(x, ClientEvent::CapacityExceeded(CapacityError::TooManyHandles(1, 1)))));
+    }
+
+    #[test]
+    fn bundles_beyond_resource_limits_are_refused() {
+        // arrange
+        let tmp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            tmp_dir.path().join("Duo.bundle.json"),
+            r#"{ "channels": [ { "actuator_index": 0, "pattern": "Wave" }, { "actuator_index": 1, "pattern": "Wave" } ] }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            tmp_dir.path().join("Wave.vibrator.funscript"),
+            r#"{ "actions": [ { "at": 0, "pos": 100 }, { "at": 1000, "pos": 100 } ] }"#,
+        )
+        .unwrap();
+        let settings = ClientSettings {
+            resource_limits: Some(ResourceLimits { max_handles: 10, max_players: 1 }),
+            ..Default::default()
+        };
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalars(1, "vib1", ActuatorType::Vibrate, 2)], Some(settings), None);
+        // replaces the default test patterns of `wait_for_connection`
+        tk.settings.pattern_path = tmp_dir.path().to_str().unwrap().to_owned();
+
+        // act
+        let handle = test_cmd(
+            &mut tk,
+            Strength::Bundle(100, "Duo".into()),
+            Duration::from_secs(1),
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        assert_eq!(handle, -1);
+        call_registry.assert_unused(1);
+        assert!(tk.scheduler().check_capacity(-1, 1).is_ok());
+        assert!(tk
+            .events
+            .try_iter()
+            .any(|x| matches!(x, ClientEvent::CapacityExceeded(CapacityError::TooManyPlayers(0, 1)))));
+    }
+
+    #[test]
+    fn command_failures_are_classified() {
+        let (tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+        let actuator = tk.buttplug.devices().flatten_actuators()[0].clone();
+        let failure = |err: ButtplugDeviceError| {
+            let err = WorkerError {
+                bp_error: ButtplugClientError::ButtplugError(err.into()),
+                actuator: actuator.clone(),
+                related: vec![],
+            };
+            events::CommandFailure::new(&err, "vibrate", 3)
+        };
+
+        let not_connected = failure(ButtplugDeviceError::DeviceNotConnected("vib1".into()));
+        assert_eq!(not_connected.actuator, "vib1 (Vibrate)");
+        assert_eq!(not_connected.handle, 3);
+        assert_eq!(not_connected.kind, events::CommandErrorKind::NotConnected);
+        assert!(!not_connected.retriable);
+
+        let communication = failure(ButtplugDeviceError::DeviceCommunicationError("timeout".into()));
+        assert_eq!(communication.kind, events::CommandErrorKind::Communication);
+        assert!(communication.retriable);
+    }
+
+    #[test]
+    fn partial_failures_keep_errors_of_all_devices() {
+        let (tk, _) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+                scalar(3, "vib3", ActuatorType::Vibrate),
+            ],
+            None,
+            None,
+        );
+        let actuators = tk.buttplug.devices().flatten_actuators();
+        let failed = |actuator: &Arc<Actuator>| -> WorkerResult {
+            Err(WorkerError {
+                bp_error: ButtplugClientError::ButtplugError(
+                    ButtplugDeviceError::DeviceNotConnected(actuator.identifier().into()).into(),
+                ),
+                actuator: actuator.clone(),
+                related: vec![],
+            })
+        };
+
+        let result = combine_results(vec![failed(&actuators[0]), Ok(()), failed(&actuators[2])]);
+
+        let err = result.unwrap_err();
+        let failed_ids = err.errors().map(|x| x.actuator.identifier()).collect::<Vec<_>>();
+        assert_eq!(failed_ids, vec![actuators[0].identifier(), actuators[2].identifier()]);
+        assert!(combine_results(vec![Ok(()), Ok(())]).is_ok());
+    }
+
     #[test]
     fn test_vibrate_and_stop_all() {
         // arrange
@@ -543,6 +1776,85 @@ mod tests {
         call_registry.get_device(1)[1].assert_strenth(0.0);
     }
 
+    #[test]
+    fn drop_stops_devices() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+        test_cmd(
+            &mut tk,
+            Strength::Constant(100),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(500));
+
+        // act
+        drop(tk);
+
+        // assert
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+    }
+
+    #[tokio::test]
+    async fn drop_within_async_context_stops_devices() {
+        // arrange
+        let (tk, call_registry) = tokio::task::spawn_blocking(|| {
+            let (mut tk, call_registry) =
+                wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+            test_cmd(
+                &mut tk,
+                Strength::Constant(100),
+                Duration::MAX,
+                vec![],
+                None,
+                &[ScalarActuator::Vibrate],
+            );
+            thread::sleep(Duration::from_millis(500));
+            (tk, call_registry)
+        })
+        .await
+        .unwrap();
+
+        // act
+        drop(tk);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        // assert
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+    }
+
+    #[test]
+    fn drop_behaviour_nothing_keeps_devices_running() {
+        // arrange
+        let settings = ClientSettings {
+            on_drop: DropBehaviour::Nothing,
+            ..Default::default()
+        };
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings), None);
+        test_cmd(
+            &mut tk,
+            Strength::Constant(100),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(500));
+
+        // act
+        drop(tk);
+
+        // assert
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        assert_eq!(call_registry.get_device(1).len(), 1);
+    }
+
     #[test]
     fn vibrate_all_demo_vibrators() {
         // arrange
@@ -626,6 +1938,29 @@ mod tests {
         call_registry.assert_unused(2);
     }
 
+    #[test]
+    fn low_strength_and_speed_keep_fractions() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+        tk.actions = Actions(vec![Action::new(
+            "vibrate",
+            vec![Control::Scalar(Selector::All, vec![ScalarActuator::Vibrate])],
+        )]);
+
+        // act
+        tk.execute_actions(
+            vec![(Strength::Constant(10), "vibrate".to_owned())],
+            vec![],
+            Speed::new(4),
+            Duration::from_millis(1),
+        );
+        thread::sleep(Duration::from_millis(500));
+
+        // assert
+        call_registry.get_device(1)[0].assert_strenth(0.004);
+    }
+
     #[test]
     fn settings_only_move_selected_actuators() {
         // arrange
@@ -662,7 +1997,7 @@ mod tests {
     #[test]
     #[ignore = "Requires one (1) vibrator to be connected via BTLE (vibrates it)"]
     fn vibrate_pattern() {
-        let (mut tk, handle) = test_pattern("02_Cruel-Tease", Duration::from_secs(10), true);
+        let (tk, handle) = test_pattern("02_Cruel-Tease", Duration::from_secs(10), true);
         thread::sleep(Duration::from_secs(2)); // dont disconnect
         tk.stop(handle);
         thread::sleep(Duration::from_secs(10));
@@ -676,11 +2011,14 @@ mod tests {
         let pattern_path = "TODO/Define/Me";
         let mut tk = BpClient::connect_with(
             || async move {
-                in_process_connector(InProcessFeatures {
-                    bluetooth: true,
-                    serial: false,
-                    xinput: false,
-                })
+                in_process_connector(
+                    InProcessFeatures {
+                        bluetooth: true,
+                        serial: false,
+                        xinput: false,
+                    },
+                    &InProcessDevices::default(),
+                )
             },
             None,
             None,
@@ -838,6 +2176,195 @@ mod tests {
         call_registry.assert_unused(2);
     }
 
+    #[test]
+    fn namespaced_action_only_moves_actuators_of_namespace() {
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            None,
+            None,
+        );
+        tk.device_settings.set_namespace("vib1 (Vibrate)", Some("p1"));
+        tk.device_settings.set_namespace("vib2 (Vibrate)", Some("P2 "));
+
+        let action = Action::new(
+            "foobar",
+            vec![Control::Scalar(Selector::All, vec![ScalarActuator::Vibrate])],
+        );
+        tk.dispatch_refs(
+            vec![(Strength::Constant(100), action.in_namespace("p2"))],
+            vec![],
+            Speed::max(),
+            Duration::from_millis(1),
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        call_registry.get_device(2)[0].assert_strenth(1.0);
+        call_registry.get_device(2)[1].assert_strenth(0.0);
+        call_registry.assert_unused(1);
+    }
+
+    #[test]
+    fn role_selector_only_moves_actuators_with_role() {
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            None,
+            None,
+        );
+        tk.device_settings.set_role("vib1 (Vibrate)", Some("Left"));
+        tk.device_settings.set_role("vib2 (Vibrate)", Some("right"));
+
+        let action = Action::new(
+            "foobar",
+            vec![Control::Scalar(Selector::Role("left".into()), vec![ScalarActuator::Vibrate])],
+        );
+        tk.dispatch_refs(
+            vec![(Strength::Constant(100), action)],
+            vec![],
+            Speed::max(),
+            Duration::from_millis(1),
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.assert_unused(2);
+    }
+
+    #[test]
+    fn failover_selector_uses_first_matching_body_part() {
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            None,
+            None,
+        );
+        tk.device_settings.set_body_parts("vib1 (Vibrate)", &["vaginal"]);
+        tk.device_settings.set_body_parts("vib2 (Vibrate)", &["nipple"]);
+
+        let selector = Selector::Failover(vec![
+            Selector::BodyParts(vec!["penis".into()]),
+            Selector::BodyParts(vec!["vaginal".into()]),
+            Selector::All,
+        ]);
+        let action = Action::new("foobar", vec![Control::Scalar(selector, vec![ScalarActuator::Vibrate])]);
+        tk.dispatch_refs(
+            vec![(Strength::Constant(100), action)],
+            vec![],
+            Speed::max(),
+            Duration::from_millis(1),
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        call_registry.get_device(1)[0].assert_strenth(1.0);
+        call_registry.assert_unused(2);
+    }
+
+    #[test]
+    fn body_part_actions_skip_excluded_transports() {
+        // arrange
+        let gamepad = "XBox (XInput) Compatible Gamepad";
+        let settings = ClientSettings {
+            body_part_excluded_transports: vec![Transport::XInput],
+            ..Default::default()
+        };
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, gamepad, ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            Some(settings),
+            None,
+        );
+        tk.device_settings.set_body_parts(&format!("{} (Vibrate)", gamepad), &["nipple"]);
+        tk.device_settings.set_body_parts("vib2 (Vibrate)", &["nipple"]);
+
+        // act
+        let selector = Selector::BodyParts(vec!["nipple".into()]);
+        let action = Action::new("foobar", vec![Control::Scalar(selector, vec![ScalarActuator::Vibrate])]);
+        tk.dispatch_refs(
+            vec![(Strength::Constant(100), action)],
+            vec![],
+            Speed::max(),
+            Duration::from_millis(1),
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        // assert
+        call_registry.get_device(2)[0].assert_strenth(1.0);
+        call_registry.assert_unused(1);
+        let transport_of = |name: &str| tk.all_actuators().iter().find(|x| x.device.name() == name).map(|x| x.transport);
+        assert_eq!(transport_of(gamepad), Some(Transport::XInput));
+        assert_eq!(transport_of("vib2"), Some(Transport::Unknown));
+    }
+
+    #[test]
+    fn failover_selector_skips_capped_actuators() {
+        let settings = ClientSettings {
+            runtime_caps: Some(RuntimeCapSettings {
+                max_mins_per_hour: Some(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            Some(settings),
+            None,
+        );
+        tk.device_settings.set_body_parts("vib1 (Vibrate)", &["vaginal"]);
+        tk.device_settings.set_body_parts("vib2 (Vibrate)", &["nipple"]);
+        tk.runtime_ledger
+            .lock()
+            .unwrap()
+            .record("vib1 (Vibrate)", unix_ms() - 60 * 1000, 60 * 1000);
+
+        let selector = Selector::Failover(vec![
+            Selector::BodyParts(vec!["vaginal".into()]),
+            Selector::BodyParts(vec!["nipple".into()]),
+        ]);
+        let action = Action::new("foobar", vec![Control::Scalar(selector, vec![ScalarActuator::Vibrate])]);
+        tk.dispatch_refs(
+            vec![(Strength::Constant(100), action)],
+            vec![],
+            Speed::max(),
+            Duration::from_millis(1),
+        );
+        thread::sleep(Duration::from_secs(1));
+
+        call_registry.assert_unused(1);
+        call_registry.get_device(2)[0].assert_strenth(1.0);
+    }
+
+    #[test]
+    fn failover_selector_without_alternatives_selects_nothing() {
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+
+        let action = Action::new(
+            "foobar",
+            vec![Control::Scalar(Selector::Failover(vec![]), vec![ScalarActuator::Vibrate])],
+        );
+        tk.dispatch_refs(
+            vec![(Strength::Constant(100), action)],
+            vec![],
+            Speed::max(),
+            Duration::from_millis(1),
+        );
+        thread::sleep(Duration::from_millis(500));
+
+        call_registry.assert_unused(1);
+    }
+
     #[test]
     fn event_is_trimmed_and_ignores_casing() {
         let (mut tk, call_registry) =
@@ -859,7 +2386,7 @@ mod tests {
         call_registry.get_device(1)[1].assert_strenth(0.0);
     }
 
-    fn wait_for_connection(
+    pub(super) fn wait_for_connection(
         devices: Vec<DeviceAdded>,
         settings: Option<ClientSettings>,
         device_settings: Option<ActuatorSettings>,
@@ -0,0 +1,323 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crossbeam_channel::Sender;
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::{
+    actuator::Actuator,
+    config::{client::RuntimeCapSettings, logging::redact, store::SharedConfigStore},
+};
+
+use super::{events::ClientEvent, BpClient};
+
+pub const RUNTIME_LEDGER_FILE: &str = "runtime.json";
+
+const HOUR_MS: u64 = 60 * 60 * 1000;
+const DAY_MS: u64 = 24 * HOUR_MS;
+/// longest time between two updates of the ledger while a task runs
+const MAX_CHECK_INTERVAL_MS: u64 = 60 * 1000;
+
+/// Active time of each actuator as (start, duration) in milliseconds since unix epoch
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RuntimeLedger(pub HashMap<String, Vec<(u64, u64)>>);
+
+impl RuntimeLedger {
+    /// Adds an entry, or extends the entry with the same start while its task is still running
+    pub fn record(&mut self, actuator_id: &str, start_ms: u64, duration_ms: u64) {
+        if duration_ms == 0 {
+            return;
+        }
+        let entries = self.0.entry(actuator_id.to_owned()).or_default();
+        match entries.iter_mut().find(|(start, _)| *start == start_ms) {
+            Some((_, duration)) => *duration = duration_ms.max(*duration),
+            None => entries.push((start_ms, duration_ms)),
+        }
+    }
+
+    /// Time in which the actuator was active within the 'window_ms' before 'now_ms',
+    /// overlapping tasks only count once
+    pub fn used_ms(&self, actuator_id: &str, now_ms: u64, window_ms: u64) -> u64 {
+        let window_start = now_ms.saturating_sub(window_ms);
+        let Some(entries) = self.0.get(actuator_id) else {
+            return 0;
+        };
+        let mut intervals = entries
+            .iter()
+            .map(|(start, duration)| (*start.max(&window_start), (start + duration).min(now_ms)))
+            .filter(|(start, end)| start < end)
+            .collect::<Vec<_>>();
+        intervals.sort_unstable();
+        let mut used = 0;
+        let mut covered_until = 0;
+        for (start, end) in intervals {
+            let start = start.max(covered_until);
+            if end > start {
+                used += end - start;
+                covered_until = end;
+            }
+        }
+        used
+    }
+
+    /// Time left until the actuator reaches one of its caps
+    pub fn remaining_ms(&self, actuator_id: &str, caps: &RuntimeCapSettings, now_ms: u64) -> Option<u64> {
+        let remaining = |cap_mins: Option<u64>, window_ms: u64| {
+            cap_mins.map(|mins| (mins * 60 * 1000).saturating_sub(self.used_ms(actuator_id, now_ms, window_ms)))
+        };
+        [remaining(caps.max_mins_per_hour, HOUR_MS), remaining(caps.max_mins_per_day, DAY_MS)]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Forgets everything that happened before the largest window
+    pub fn prune(&mut self, now_ms: u64) {
+        let oldest = now_ms.saturating_sub(DAY_MS);
+        for entries in self.0.values_mut() {
+            entries.retain(|(start, duration)| start + duration > oldest);
+        }
+        self.0.retain(|_, entries| !entries.is_empty());
+    }
+
+    /// Name of the first window in which the actuator used up its runtime
+    pub fn exceeded_cap(&self, actuator_id: &str, caps: &RuntimeCapSettings, now_ms: u64) -> Option<&'static str> {
+        let exceeds = |cap_mins: Option<u64>, window_ms: u64| {
+            cap_mins.is_some_and(|mins| self.used_ms(actuator_id, now_ms, window_ms) >= mins * 60 * 1000)
+        };
+        if exceeds(caps.max_mins_per_hour, HOUR_MS) {
+            return Some("hourly");
+        }
+        if exceeds(caps.max_mins_per_day, DAY_MS) {
+            return Some("daily");
+        }
+        None
+    }
+}
+
+pub(super) fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Adds the runtime of a task to the ledger and persists it
+pub(super) fn record_runtime(
+    ledger: &Arc<Mutex<RuntimeLedger>>,
+    caps: &RuntimeCapSettings,
+    store: &SharedConfigStore,
+    actuator_ids: &[String],
+    start_ms: u64,
+    elapsed: Duration,
+) {
+    let mut ledger = ledger.lock().unwrap();
+    for actuator_id in actuator_ids {
+        ledger.record(actuator_id, start_ms, elapsed.as_millis() as u64);
+    }
+    ledger.prune(unix_ms());
+    if let Some(state_path) = &caps.state_path {
+        store.try_write(&*ledger, state_path, RUNTIME_LEDGER_FILE);
+    }
+}
+
+/// Records the runtime of a task while it runs, so that endless tasks count too,
+/// and stops it with 'stop' once one of its actuators reaches a cap
+pub(super) async fn enforce_runtime_cap(
+    ledger: Arc<Mutex<RuntimeLedger>>,
+    caps: RuntimeCapSettings,
+    store: SharedConfigStore,
+    actuator_ids: Vec<String>,
+    (start_ms, started): (u64, Instant),
+    event_sender: Sender<ClientEvent>,
+    stop: Vec<CancellationToken>,
+) {
+    loop {
+        record_runtime(&ledger, &caps, &store, &actuator_ids, start_ms, started.elapsed());
+        let now_ms = unix_ms();
+        let (remaining, capped) = {
+            let ledger = ledger.lock().unwrap();
+            let remaining = actuator_ids
+                .iter()
+                .filter_map(|x| ledger.remaining_ms(x, &caps, now_ms))
+                .min()
+                .unwrap_or(MAX_CHECK_INTERVAL_MS);
+            let capped = actuator_ids
+                .iter()
+                .find_map(|x| ledger.exceeded_cap(x, &caps, now_ms).map(|window| (x.clone(), window)));
+            (remaining, capped)
+        };
+        if let Some((actuator_id, window)) = capped {
+            info!(actuator = redact(&actuator_id), window, "runtime cap reached, stopping task");
+            let _ = event_sender.send(ClientEvent::RuntimeCapReached(redact(&actuator_id), window.to_owned()));
+            for token in &stop {
+                token.cancel();
+            }
+            return;
+        }
+        sleep(Duration::from_millis(remaining.clamp(1, MAX_CHECK_INTERVAL_MS))).await;
+    }
+}
+
+impl BpClient {
+    /// Whether the actuator used up its runtime in the current hour or day,
+    /// raises a `RuntimeCapReached` event if it did
+    pub fn refuse_capped_actuator(&self, actuator: &Actuator) -> bool {
+        let Some(caps) = &self.settings.runtime_caps else {
+            return false;
+        };
+        let exceeded = self.runtime_ledger.lock().unwrap().exceeded_cap(actuator.identifier(), caps, unix_ms());
+        match exceeded {
+            Some(window) => {
+                info!(%actuator, window, "runtime cap reached, refusing task");
+                let _ = self.event_sender.send(ClientEvent::RuntimeCapReached(
+                    redact(actuator.identifier()),
+                    window.to_owned(),
+                ));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use buttplug::core::message::ActuatorType;
+
+    use bp_fakes::*;
+
+    use crate::client::tests::{test_cmd, wait_for_connection};
+    use crate::client::{ScalarActuator, Strength};
+    use crate::config::client::ClientSettings;
+
+    use super::*;
+
+    #[test]
+    fn counts_runtime_within_window() {
+        let now = 10 * DAY_MS;
+        let mut ledger = RuntimeLedger::default();
+        ledger.record("vib1", now - 2 * HOUR_MS, 20 * 60 * 1000);
+        ledger.record("vib1", now - HOUR_MS - 5 * 60 * 1000, 10 * 60 * 1000);
+        ledger.record("vib1", now - 60 * 1000, 60 * 1000);
+        ledger.record("vib1", now - 2 * DAY_MS, 60 * 1000);
+
+        assert_eq!(ledger.used_ms("vib1", now, HOUR_MS), 6 * 60 * 1000);
+        assert_eq!(ledger.used_ms("vib1", now, DAY_MS), 31 * 60 * 1000);
+        assert_eq!(ledger.used_ms("vib2", now, DAY_MS), 0);
+
+        let caps = RuntimeCapSettings {
+            max_mins_per_hour: Some(10),
+            max_mins_per_day: Some(30),
+            state_path: None,
+        };
+        assert_eq!(ledger.exceeded_cap("vib1", &caps, now), Some("daily"));
+        assert_eq!(ledger.exceeded_cap("vib2", &caps, now), None);
+
+        ledger.prune(now);
+        assert_eq!(ledger.0["vib1"].len(), 3);
+    }
+
+    #[test]
+    fn overlapping_tasks_count_once() {
+        let now = 10 * DAY_MS;
+        let mut ledger = RuntimeLedger::default();
+        ledger.record("vib1", now - 10 * 60 * 1000, 5 * 60 * 1000);
+        ledger.record("vib1", now - 8 * 60 * 1000, 5 * 60 * 1000);
+        ledger.record("vib1", now - 9 * 60 * 1000, 60 * 1000);
+        assert_eq!(ledger.used_ms("vib1", now, HOUR_MS), 7 * 60 * 1000);
+
+        // a running task extends its entry
+        ledger.record("vib1", now - 60 * 1000, 30 * 1000);
+        ledger.record("vib1", now - 60 * 1000, 60 * 1000);
+        assert_eq!(ledger.0["vib1"].len(), 4);
+        assert_eq!(ledger.used_ms("vib1", now, HOUR_MS), 8 * 60 * 1000);
+
+        let caps = RuntimeCapSettings {
+            max_mins_per_hour: Some(10),
+            max_mins_per_day: Some(60),
+            state_path: None,
+        };
+        assert_eq!(ledger.remaining_ms("vib1", &caps, now), Some(2 * 60 * 1000));
+        assert_eq!(ledger.remaining_ms("vib1", &RuntimeCapSettings::default(), now), None);
+    }
+
+    #[test]
+    fn actuators_over_runtime_cap_are_refused() {
+        // arrange
+        let settings = ClientSettings {
+            runtime_caps: Some(RuntimeCapSettings {
+                max_mins_per_hour: Some(0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings), None);
+
+        // act
+        test_cmd(
+            &mut tk,
+            Strength::Constant(50),
+            Duration::from_millis(100),
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(300));
+
+        // assert
+        call_registry.assert_unused(1);
+        assert!(tk
+            .events
+            .try_iter()
+            .any(|event| matches!(event, ClientEvent::RuntimeCapReached(_, x) if x == "hourly")));
+    }
+
+    #[test]
+    fn running_tasks_are_stopped_when_reaching_runtime_cap() {
+        // arrange
+        let settings = ClientSettings {
+            runtime_caps: Some(RuntimeCapSettings {
+                max_mins_per_hour: Some(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings), None);
+        tk.runtime_ledger
+            .lock()
+            .unwrap()
+            .record("vib1 (Vibrate)", unix_ms() - 60 * 1000, 60 * 1000 - 200);
+
+        // act
+        test_cmd(
+            &mut tk,
+            Strength::Constant(50),
+            Duration::MAX,
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(500));
+
+        // assert
+        let calls = call_registry.get_device(1);
+        calls[0].assert_strenth(0.5);
+        calls[1].assert_strenth(0.0);
+        assert_eq!(calls.len(), 2);
+        assert!(tk
+            .events
+            .try_iter()
+            .any(|event| matches!(event, ClientEvent::RuntimeCapReached(_, x) if x == "hourly")));
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use buttplug::client::ButtplugClient;
+use tokio::time::sleep;
+use tracing::{debug, error, info};
+
+use super::BpClient;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanDecision {
+    /// execute the operation right away
+    Now,
+    /// the operation is queued and runs after the given time
+    Queued(Duration),
+    /// another operation is already queued, the request replaced it
+    Replaced,
+    /// the same operation ran within the minimum interval
+    Debounced,
+}
+
+/// Spaces out start and stop scanning operations by a minimum interval,
+/// a request within the interval is queued and later requests replace it
+#[derive(Debug)]
+pub struct ScanLimiter {
+    min_interval: Duration,
+    /// last executed operation (true for start scanning) and when it ran
+    last: Option<(bool, Instant)>,
+    /// operation that runs once the interval passed
+    requested: Option<bool>,
+}
+
+impl ScanLimiter {
+    pub fn new(min_interval: Duration) -> Self {
+        ScanLimiter {
+            min_interval,
+            last: None,
+            requested: None,
+        }
+    }
+
+    pub fn request(&mut self, scan: bool, now: Instant) -> ScanDecision {
+        if self.requested.is_some() {
+            self.requested = Some(scan);
+            return ScanDecision::Replaced;
+        }
+        if let Some((last_scan, last_time)) = self.last {
+            let elapsed = now.saturating_duration_since(last_time);
+            if elapsed < self.min_interval {
+                if last_scan == scan {
+                    return ScanDecision::Debounced;
+                }
+                self.requested = Some(scan);
+                return ScanDecision::Queued(self.min_interval - elapsed);
+            }
+        }
+        self.last = Some((scan, now));
+        ScanDecision::Now
+    }
+
+    /// Takes the queued operation once it is due, None if it would
+    /// repeat the last operation
+    pub fn take_requested(&mut self, now: Instant) -> Option<bool> {
+        let scan = self.requested.take()?;
+        if self.last.is_some_and(|(last_scan, _)| last_scan == scan) {
+            return None;
+        }
+        self.last = Some((scan, now));
+        Some(scan)
+    }
+}
+
+async fn run_scan_operation(buttplug: &ButtplugClient, scan: bool) -> bool {
+    let result = if scan {
+        buttplug.start_scanning().await
+    } else {
+        buttplug.stop_scanning().await
+    };
+    if let Err(err) = result {
+        error!(scan, "Failed to change scanning {:?}", err);
+        return false;
+    }
+    true
+}
+
+impl BpClient {
+    /// Starts scanning, see `settings.scan_limit` for how often
+    pub fn scan_for_devices(&self) -> bool {
+        info!("start scan");
+        self.limited_scan_operation(true)
+    }
+
+    pub fn stop_scan(&self) -> bool {
+        info!("stop scan");
+        self.limited_scan_operation(false)
+    }
+
+    fn limited_scan_operation(&self, scan: bool) -> bool {
+        if !self.is_connected() {
+            error!(scan, "scan while not connected");
+            return false;
+        }
+        let decision = self.scan_limiter.lock().unwrap().request(scan, Instant::now());
+        debug!(scan, ?decision, "scan operation");
+        match decision {
+            ScanDecision::Now => self.runtime.block_on(run_scan_operation(&self.buttplug, scan)),
+            ScanDecision::Queued(delay) => {
+                let buttplug = self.buttplug.clone();
+                let scan_limiter = self.scan_limiter.clone();
+                self.runtime.spawn(run_queued_scan_operation(buttplug, scan_limiter, delay));
+                true
+            }
+            ScanDecision::Replaced | ScanDecision::Debounced => true,
+        }
+    }
+}
+
+async fn run_queued_scan_operation(buttplug: Arc<ButtplugClient>, scan_limiter: Arc<Mutex<ScanLimiter>>, delay: Duration) {
+    sleep(delay).await;
+    let scan = scan_limiter.lock().unwrap().take_requested(Instant::now());
+    if let Some(scan) = scan {
+        info!(scan, "running queued scan operation");
+        run_scan_operation(&buttplug, scan).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_operations_are_spaced_out() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut limiter = ScanLimiter::new(Duration::from_millis(1000));
+
+        assert_eq!(limiter.request(true, at(0)), ScanDecision::Now);
+        assert_eq!(limiter.request(true, at(100)), ScanDecision::Debounced);
+        assert_eq!(limiter.request(false, at(200)), ScanDecision::Queued(Duration::from_millis(800)));
+        assert_eq!(limiter.request(true, at(300)), ScanDecision::Replaced);
+        assert_eq!(limiter.take_requested(at(1000)), None);
+
+        assert_eq!(limiter.request(false, at(1100)), ScanDecision::Now);
+        assert_eq!(limiter.request(true, at(1200)), ScanDecision::Queued(Duration::from_millis(900)));
+        assert_eq!(limiter.take_requested(at(2100)), Some(true));
+        assert_eq!(limiter.request(true, at(3100)), ScanDecision::Now);
+    }
+}
@@ -0,0 +1,135 @@
+use std::{sync::Arc, time::Duration};
+
+use buttplug::client::ButtplugClientError;
+use tokio::time::sleep;
+use tracing::{error, info};
+
+use crate::{
+    actuator::{Actuator, ActuatorCommand, Actuators},
+    player::worker::WorkerResult,
+    filter::Filter,
+    speed::Speed,
+    WorkerChannel,
+};
+
+use super::BpClient;
+
+/// Scalar speed used for pulsing actuators during a self test
+const SELF_TEST_SPEED: i64 = 10;
+/// Time the device is kept active before it is stopped again
+const SELF_TEST_PULSE_MS: u64 = 300;
+/// Distance of the linear test stroke, relative to the actuators position range
+const SELF_TEST_STROKE: f64 = 0.1;
+
+#[derive(Debug)]
+pub struct SelfTestResult {
+    pub actuator: Arc<Actuator>,
+    pub result: Result<(), ButtplugClientError>,
+}
+
+impl BpClient {
+    /// Briefly pulses every enabled actuator at a safe level (linear actuators
+    /// do a tiny stroke) one after another and reports whether each command succeeded.
+    /// Actuators that are driven by a task are not touched and reported as failed
+    pub fn self_test(&mut self) -> Vec<SelfTestResult> {
+        info!("self test");
+        let loaded = self.load_configs(self.buttplug.devices().flatten_actuators());
+        let actuators = Filter::from_actuators(self.settings_cache.clone(), loaded)
+            .connected()
+            .enabled()
+            .result();
+
+        let mut channel = self.scheduler().worker_channel();
+        self.runtime.block_on(async move {
+            let mut results = vec![];
+            for actuator in actuators {
+                let result = pulse(&mut channel, &actuator).await.map_err(|err| err.bp_error);
+                match &result {
+                    Ok(()) => info!(actuator=%actuator, "self test ok"),
+                    Err(err) => error!(actuator=%actuator, ?err, "self test failed"),
+                }
+                results.push(SelfTestResult { actuator, result });
+            }
+            results
+        })
+    }
+}
+
+async fn pulse(channel: &mut WorkerChannel, actuator: &Arc<Actuator>) -> WorkerResult {
+    let pulse_ms = SELF_TEST_PULSE_MS as u32;
+    match actuator.command {
+        ActuatorCommand::Linear => {
+            let range = actuator.get_config().limits.linear_or_max();
+            let start = range.apply_pos(range.min_pos);
+            let end = range.apply_pos(range.min_pos + SELF_TEST_STROKE);
+            channel.probe(actuator, end, pulse_ms).await?;
+            sleep(Duration::from_millis(SELF_TEST_PULSE_MS)).await;
+            channel.probe(actuator, start, pulse_ms).await?;
+        }
+        ActuatorCommand::Rotate | ActuatorCommand::Scalar => {
+            channel.probe(actuator, Speed::new(SELF_TEST_SPEED).as_float(), 0).await?;
+            sleep(Duration::from_millis(SELF_TEST_PULSE_MS)).await;
+            channel.probe(actuator, Speed::min().as_float(), 0).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use buttplug::core::message::ActuatorType;
+
+    use bp_fakes::*;
+
+    use crate::client::tests::wait_for_connection;
+    use crate::config::actuators::InitCommand;
+
+    #[test]
+    fn self_test_pulses_enabled_actuators() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                linear(2, "lin1"),
+                scalar(3, "vib3", ActuatorType::Vibrate),
+            ],
+            None,
+            None,
+        );
+        tk.device_settings.set_enabled("vib3 (Vibrate)", false);
+
+        // act
+        let results = tk.self_test();
+
+        // assert
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|x| x.result.is_ok()));
+        call_registry.get_device(1)[0].assert_strenth(0.1);
+        call_registry.get_device(1)[1].assert_strenth(0.0);
+        call_registry.get_device(2)[0].assert_pos(0.1);
+        call_registry.get_device(2)[1].assert_pos(0.0);
+        call_registry.assert_unused(3);
+    }
+
+    #[test]
+    fn self_test_and_init_use_the_message_type_of_the_actuator() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "rot1", ActuatorType::Rotate)], None, None);
+        let mut config = tk.device_settings.get_config("rot1 (Rotate)").unwrap();
+        config.init_sequence = vec![InitCommand::Speed(20)];
+        tk.update_actuator_config(config);
+
+        // act
+        tk.initialize_devices();
+        let results = tk.self_test();
+
+        // assert
+        assert!(results.iter().all(|x| x.result.is_ok()));
+        let calls = call_registry.get_device(1);
+        calls[0].assert_strenth(0.2);
+        calls[1].assert_strenth(0.1);
+        calls[2].assert_strenth(0.0);
+        assert_eq!(calls.len(), 3);
+    }
+}
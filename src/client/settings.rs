@@ -0,0 +1,282 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+    time::sleep,
+};
+use tracing::{debug, info};
+
+use crate::{
+    actuators::ActuatorConfig,
+    config::{client::SettingsPersistence, logging::redact},
+};
+
+use super::{events::ClientEvent, BpClient};
+
+/// Writes the latest settings to the config store once no change arrived for the debounce time
+pub(super) fn spawn_settings_writer<T>(client: &BpClient, persistence: SettingsPersistence) -> UnboundedSender<T>
+where
+    T: Serialize + Send + 'static,
+{
+    let (sender, mut receiver) = unbounded_channel::<T>();
+    let store = client.settings.config_store.clone();
+    client.runtime.spawn(async move {
+        let debounce = Duration::from_millis(persistence.debounce_ms);
+        while let Some(mut latest) = receiver.recv().await {
+            loop {
+                tokio::select! {
+                    settings = receiver.recv() => match settings {
+                        Some(settings) => latest = settings,
+                        None => break,
+                    },
+                    _ = sleep(debounce) => break,
+                }
+            }
+            debug!(file = persistence.settings_file, "persisting settings");
+            store.try_write(&latest, &persistence.settings_path, &persistence.settings_file);
+        }
+    });
+    sender
+}
+
+impl BpClient {
+    pub fn set_enabled(&mut self, actuator_id: &str, enabled: bool) {
+        self.device_settings.set_enabled(actuator_id, enabled);
+        self.settings_changed(actuator_id, "enabled");
+    }
+
+    pub fn set_body_parts(&mut self, actuator_id: &str, body_parts: &[&str]) {
+        self.device_settings.set_body_parts(actuator_id, body_parts);
+        self.settings_changed(actuator_id, "body_parts");
+    }
+
+    pub fn set_namespace(&mut self, actuator_id: &str, namespace: Option<&str>) {
+        self.device_settings.set_namespace(actuator_id, namespace);
+        self.settings_changed(actuator_id, "namespace");
+    }
+
+    pub fn set_role(&mut self, actuator_id: &str, role: Option<&str>) {
+        self.device_settings.set_role(actuator_id, role);
+        self.settings_changed(actuator_id, "role");
+    }
+
+    /// Replaces the whole config of an actuator, e.g. after editing its limits
+    pub fn update_actuator_config(&mut self, config: ActuatorConfig) {
+        let actuator_id = config.actuator_config_id.clone();
+        self.device_settings.update_device(config);
+        self.settings_changed(&actuator_id, "config");
+    }
+
+    /// Raises `SettingsChanged` and the event of the changed field, applies the
+    /// settings to running tasks and persists them debounced if `settings.settings_persistence`
+    /// or a `SettingsManager` is configured
+    pub(super) fn settings_changed(&mut self, actuator_id: &str, field: &str) {
+        info!(actuator = redact(actuator_id), field, "settings changed");
+        self.scheduler().update_actuator_configs(&self.device_settings.0);
+        let _ = self
+            .event_sender
+            .send(ClientEvent::SettingsChanged(redact(actuator_id), field.to_owned()));
+        let field_event = match field {
+            "enabled" => Some(ClientEvent::EnabledChanged(
+                redact(actuator_id),
+                self.device_settings.get_enabled(actuator_id),
+            )),
+            "body_parts" => Some(ClientEvent::BodyPartsChanged(
+                redact(actuator_id),
+                self.device_settings.get_events(actuator_id),
+            )),
+            _ => None,
+        };
+        if let Some(event) = field_event {
+            let _ = self.event_sender.send(event);
+        }
+        if let Some(writer) = &self.settings_writer {
+            let _ = writer.send(self.device_settings.clone());
+        }
+        if let Some(manager) = &mut self.settings_manager {
+            manager.set_devices(self.device_settings.clone());
+            if let (true, Some(writer)) = (manager.is_dirty(), &self.managed_writer) {
+                let _ = writer.send(manager.document().clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use buttplug::core::message::ActuatorType;
+
+    use bp_fakes::*;
+
+    use crate::client::tests::{test_cmd, wait_for_connection};
+    use crate::client::{ActuatorSettings, ScalarActuator, SettingsDocument, SettingsManager, Strength};
+    use crate::config::client::ClientSettings;
+    use crate::config::read::read_or_default;
+    use crate::config::store::{MemoryStore, SharedConfigStore};
+
+    use super::*;
+
+    #[test]
+    fn settings_changes_raise_events_and_are_persisted() {
+        // arrange
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let settings_path = tmp_dir.path().to_str().unwrap().to_owned();
+        let settings = ClientSettings {
+            settings_persistence: Some(SettingsPersistence {
+                settings_path: settings_path.clone(),
+                settings_file: "devices.json".into(),
+                debounce_ms: 100,
+            }),
+            ..Default::default()
+        };
+        let (mut tk, _) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], Some(settings), None);
+
+        // act
+        tk.set_body_parts("vib1 (Vibrate)", &["Nipple"]);
+        tk.set_enabled("vib1 (Vibrate)", false);
+        thread::sleep(Duration::from_millis(500));
+
+        // assert
+        let changes = tk
+            .events
+            .try_iter()
+            .filter_map(|x| match x {
+                ClientEvent::SettingsChanged(actuator, field) => Some((actuator, field)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                ("vib1 (Vibrate)".to_owned(), "body_parts".to_owned()),
+                ("vib1 (Vibrate)".to_owned(), "enabled".to_owned())
+            ]
+        );
+        let stored = read_or_default::<ActuatorSettings>(&settings_path, "devices.json");
+        assert!(!stored.get_enabled("vib1 (Vibrate)"));
+        assert_eq!(stored.get_events("vib1 (Vibrate)"), vec!["nipple"]);
+    }
+
+    #[test]
+    fn managed_settings_are_stored_debounced() {
+        // arrange
+        let (mut tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+        let store = SharedConfigStore::new(MemoryStore::default());
+        tk.settings.config_store = store.clone();
+        tk.managed_writer = Some(spawn_settings_writer(
+            &tk,
+            SettingsPersistence {
+                settings_path: "save".into(),
+                settings_file: "settings.json".into(),
+                debounce_ms: 100,
+            },
+        ));
+        tk.settings_manager = Some(SettingsManager::load(store.clone(), "save", "settings.json"));
+
+        // act
+        tk.set_body_parts("vib1 (Vibrate)", &["Nipple"]);
+        tk.set_enabled("vib1 (Vibrate)", false);
+        let stored_immediately = store.0.read("save", "settings.json").is_ok();
+        thread::sleep(Duration::from_millis(500));
+
+        // assert
+        assert!(!stored_immediately);
+        let stored = SettingsDocument::migrate(&store.0.read("save", "settings.json").unwrap()).unwrap();
+        assert!(!stored.devices.get_enabled("vib1 (Vibrate)"));
+        assert_eq!(stored.devices.get_events("vib1 (Vibrate)"), vec!["nipple"]);
+    }
+
+    #[test]
+    fn direct_settings_edits_apply_to_the_next_dispatch() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(
+            vec![
+                scalar(1, "vib1", ActuatorType::Vibrate),
+                scalar(2, "vib2", ActuatorType::Vibrate),
+            ],
+            None,
+            None,
+        );
+        test_cmd(
+            &mut tk,
+            Strength::Constant(100),
+            Duration::from_millis(1),
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(500));
+
+        // act
+        tk.device_settings.set_enabled("vib2 (Vibrate)", false);
+        test_cmd(
+            &mut tk,
+            Strength::Constant(100),
+            Duration::from_millis(1),
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(500));
+
+        // assert
+        assert_eq!(call_registry.get_device(1).len(), 4);
+        assert_eq!(call_registry.get_device(2).len(), 2);
+    }
+
+    #[test]
+    fn enable_and_body_part_changes_raise_events() {
+        // arrange
+        let (mut tk, _) = wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+
+        // act
+        tk.set_enabled("vib1 (Vibrate)", false);
+        tk.set_body_parts("vib1 (Vibrate)", &["Nipple", "Clit"]);
+        tk.set_role("vib1 (Vibrate)", Some("top"));
+
+        // assert
+        let events = tk
+            .events
+            .try_iter()
+            .filter(|x| !matches!(x, ClientEvent::SettingsChanged(_, _)))
+            .collect::<Vec<_>>();
+        assert!(matches!(&events[0], ClientEvent::EnabledChanged(id, false) if id == "vib1 (Vibrate)"));
+        assert!(
+            matches!(&events[1], ClientEvent::BodyPartsChanged(id, parts) if id == "vib1 (Vibrate)" && parts == &vec!["nipple", "clit"])
+        );
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn settings_cache_follows_loaded_and_changed_configs() {
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+        test_cmd(
+            &mut tk,
+            Strength::Constant(100),
+            Duration::from_millis(1),
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(200));
+        assert!(tk.settings_cache.is_enabled("vib1 (Vibrate)"));
+
+        tk.set_enabled("vib1 (Vibrate)", false);
+        assert!(!tk.settings_cache.is_enabled("vib1 (Vibrate)"));
+        test_cmd(
+            &mut tk,
+            Strength::Constant(100),
+            Duration::from_millis(1),
+            vec![],
+            None,
+            &[ScalarActuator::Vibrate],
+        );
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(call_registry.get_device(1).len(), 2);
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use buttplug::{
+    client::{ButtplugClient, ButtplugClientError, ButtplugClientEvent},
+    core::{
+        connector::{new_json_ws_client_connector, ButtplugConnector},
+        message::{ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage},
+    },
+};
+use crossbeam_channel::Sender;
+use futures::StreamExt;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+use crate::config::{client::ReconnectSettings, connection::ConnectionType};
+
+use super::{events::ClientEvent, in_process_connector, BpClient};
+
+/// Connection of the client to the buttplug server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    Disconnected,
+    Connecting,
+    Connected,
+    /// the last connection attempt failed with the given error
+    Failed(String),
+}
+
+impl ConnectionState {
+    pub(super) fn from_result(result: &Result<(), ButtplugClientError>) -> Self {
+        match result {
+            Ok(()) => ConnectionState::Connected,
+            Err(err) => ConnectionState::Failed(err.to_string()),
+        }
+    }
+}
+
+impl BpClient {
+    pub fn connection_state(&self) -> ConnectionState {
+        self.connection_state.lock().unwrap().clone()
+    }
+
+    /// Connects again if the client is not connected, does nothing otherwise.
+    /// Returns whether the client is connected afterwards
+    pub fn ensure_connected(&mut self) -> bool {
+        if self.is_connected() {
+            return true;
+        }
+        match self.settings.connection.clone() {
+            ConnectionType::WebSocket(endpoint) => {
+                self.reconnect_with(new_json_ws_client_connector(&format!("ws://{}", endpoint)))
+            }
+            ConnectionType::InProcess => {
+                let connector = in_process_connector(self.settings.in_process_features, &self.in_process_devices);
+                self.reconnect_with(connector)
+            }
+            ConnectionType::Test => {
+                warn!("test connections cannot reconnect");
+                false
+            }
+        }
+    }
+
+    /// Whether device commands can be sent, notices connections that were lost
+    pub(super) fn is_connected(&self) -> bool {
+        let state = self.connection_state();
+        if state == ConnectionState::Connected && !self.buttplug.connected() {
+            error!("connection lost");
+            self.set_connection_state(ConnectionState::Disconnected);
+            return false;
+        }
+        state == ConnectionState::Connected
+    }
+
+    pub(super) fn set_connection_state(&self, state: ConnectionState) {
+        change_state(&self.connection_state, state, &self.event_sender);
+    }
+
+    /// Connects again with `connector` whenever the server disconnects, see `settings.reconnect`
+    pub(super) fn spawn_reconnect<F, T>(&self, connector: F)
+    where
+        F: Fn() -> T + Send + 'static,
+        T: ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage> + 'static,
+    {
+        if let Some(settings) = self.settings.reconnect.clone() {
+            self.runtime.spawn(run_reconnect(
+                self.buttplug.clone(),
+                connector,
+                settings,
+                self.connection_state.clone(),
+                self.disconnect_requested.clone(),
+                self.event_sender.clone(),
+            ));
+        }
+    }
+
+    fn reconnect_with<T>(&mut self, connector: T) -> bool
+    where
+        T: ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage> + 'static,
+    {
+        self.disconnect_requested.store(false, Ordering::Relaxed);
+        self.set_connection_state(ConnectionState::Connecting);
+        let buttplug = self.buttplug.clone();
+        self.connection_result = self.runtime.block_on(async move { buttplug.connect(connector).await });
+        if let Err(err) = &self.connection_result {
+            error!("connection error: {:?}", err)
+        }
+        self.set_connection_state(ConnectionState::from_result(&self.connection_result));
+        self.connection_result.is_ok()
+    }
+}
+
+fn change_state(current: &Mutex<ConnectionState>, state: ConnectionState, event_sender: &Sender<ClientEvent>) {
+    let mut current = current.lock().unwrap();
+    if *current != state {
+        info!(?state, "connection state");
+        *current = state.clone();
+        let _ = event_sender.send(ClientEvent::ConnectionStateChanged(state));
+    }
+}
+
+/// Waits for the server to disconnect and connects again, unless the disconnect
+/// was requested. Failed attempts are retried with doubling delays until
+/// `settings.max_attempts` is reached
+async fn run_reconnect<F, T>(
+    buttplug: Arc<ButtplugClient>,
+    connector: F,
+    settings: ReconnectSettings,
+    state: Arc<Mutex<ConnectionState>>,
+    disconnect_requested: Arc<AtomicBool>,
+    event_sender: Sender<ClientEvent>,
+) where
+    F: Fn() -> T,
+    T: ButtplugConnector<ButtplugCurrentSpecClientMessage, ButtplugCurrentSpecServerMessage> + 'static,
+{
+    let mut events = buttplug.event_stream();
+    while let Some(event) = events.next().await {
+        if !matches!(event, ButtplugClientEvent::ServerDisconnect) || disconnect_requested.load(Ordering::Relaxed) {
+            continue;
+        }
+        change_state(&state, ConnectionState::Disconnected, &event_sender);
+        let mut delay = Duration::from_millis(settings.initial_delay_ms);
+        let mut attempts = 0;
+        loop {
+            sleep(delay).await;
+            if disconnect_requested.load(Ordering::Relaxed) || buttplug.connected() {
+                break;
+            }
+            attempts += 1;
+            info!(attempts, "reconnecting");
+            change_state(&state, ConnectionState::Connecting, &event_sender);
+            let result = buttplug.connect(connector()).await;
+            if let Err(err) = &result {
+                error!(attempts, "reconnect failed: {:?}", err);
+            }
+            change_state(&state, ConnectionState::from_result(&result), &event_sender);
+            if result.is_ok() || settings.max_attempts.is_some_and(|max| attempts >= max) {
+                break;
+            }
+            delay = (delay * 2).min(Duration::from_millis(settings.max_delay_ms));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::{Duration, Instant}};
+
+    use buttplug::core::message::ActuatorType;
+
+    use bp_fakes::*;
+
+    use crate::client::tests::{assert_timeout, wait_for_connection};
+    use crate::client::{Action, Control, ScalarActuator, Selector, Strength};
+    use crate::config::client::ClientSettings;
+    use crate::speed::Speed;
+
+    use super::*;
+
+    #[test]
+    fn device_commands_are_refused_after_disconnect() {
+        // arrange
+        let (mut tk, call_registry) =
+            wait_for_connection(vec![scalar(1, "vib1", ActuatorType::Vibrate)], None, None);
+        assert_eq!(tk.connection_state(), ConnectionState::Connected);
+
+        // act
+        tk.disconnect();
+        let result = tk.dispatch_refs(
+            vec![(Strength::Constant(100), Action::new("vibrate", vec![Control::Scalar(Selector::All, vec![ScalarActuator::Vibrate])]))],
+            vec![],
+            Speed::max(),
+            Duration::from_millis(100),
+        );
+
+        // assert
+        assert_eq!(tk.connection_state(), ConnectionState::Disconnected);
+        assert!(tk
+            .events
+            .try_iter()
+            .any(|x| matches!(x, ClientEvent::ConnectionStateChanged(ConnectionState::Disconnected))));
+        assert_eq!(result.handle, -1);
+        assert!(!tk.scan_for_devices());
+        call_registry.assert_unused(1);
+    }
+
+    #[test]
+    fn reconnects_after_the_server_disconnected() {
+        // arrange
+        let devices = vec![scalar(1, "vib1", ActuatorType::Vibrate)];
+        let settings = ClientSettings {
+            reconnect: Some(ReconnectSettings {
+                initial_delay_ms: 10,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (tk, _) = wait_for_connection(devices.clone(), Some(settings), None);
+        tk.spawn_reconnect(move || FakeDeviceConnector::new(devices.clone()).0);
+
+        // act
+        let buttplug = tk.buttplug.clone();
+        tk.runtime.block_on(async move { buttplug.disconnect().await }).unwrap();
+
+        // assert
+        assert_timeout!(tk.connection_state() == ConnectionState::Connected && tk.buttplug.connected(), "Reconnected");
+        assert!(tk
+            .events
+            .try_iter()
+            .any(|x| matches!(x, ClientEvent::ConnectionStateChanged(ConnectionState::Connecting))));
+    }
+
+    #[test]
+    fn requested_disconnects_are_not_reconnected() {
+        // arrange
+        let devices = vec![scalar(1, "vib1", ActuatorType::Vibrate)];
+        let settings = ClientSettings {
+            reconnect: Some(ReconnectSettings {
+                initial_delay_ms: 10,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (tk, _) = wait_for_connection(devices.clone(), Some(settings), None);
+        tk.spawn_reconnect(move || FakeDeviceConnector::new(devices.clone()).0);
+
+        // act
+        tk.disconnect();
+        thread::sleep(Duration::from_millis(100));
+
+        // assert
+        assert_eq!(tk.connection_state(), ConnectionState::Disconnected);
+        assert!(!tk.buttplug.connected());
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use tokio::sync::mpsc::unbounded_channel;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::{
+    actuator::Actuator,
+    dynamic_tracking::{DynamicSettings, DynamicTracking, DynamicTrackingHandle, TrackingSignal},
+};
+
+use super::BpClient;
+
+impl BpClient {
+    /// Starts mirroring tracking signals on 'actuators', the signals are passed
+    /// with `DynamicTrackingHandle::signal`. Ends on `TrackingSignal::Stop`,
+    /// `DynamicTrackingHandle::stop` or `stop_all`
+    pub fn start_tracking(&self, actuators: Vec<Arc<Actuator>>, settings: DynamicSettings) -> DynamicTrackingHandle {
+        self.tracking.lock().unwrap().retain(|x| x.is_running());
+        let (sender, receiver) = unbounded_channel::<TrackingSignal>();
+        let cancel = CancellationToken::new();
+        let handle = DynamicTrackingHandle {
+            cancel: Some(cancel.clone()),
+            signals: Some(sender),
+            ..Default::default()
+        };
+        let mut tracking = DynamicTracking {
+            settings,
+            signals: receiver,
+            actuators,
+            status: handle.clone(),
+        };
+        info!(actuators=?tracking.actuators, "start tracking");
+        self.runtime.spawn(async move {
+            tokio::select! {
+                _ = cancel.cancelled() => info!("tracking cancelled"),
+                _ = tracking.track_mirror() => info!("tracking done"),
+            }
+            cancel.cancel();
+        });
+        self.tracking.lock().unwrap().push(handle.clone());
+        handle
+    }
+
+    /// Ends all tracking tasks started with `start_tracking`
+    pub fn stop_tracking(&self) {
+        for tracking in self.tracking.lock().unwrap().drain(..) {
+            tracking.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use bp_fakes::*;
+
+    use crate::actuator::Actuators;
+    use crate::client::tests::wait_for_connection;
+    use crate::client::{Action, ClientEvent, Control, Selector, Strength, StrokeRange};
+    use crate::speed::Speed;
+
+    use super::*;
+
+    #[test]
+    fn dynamic_strokes_are_refused() {
+        // arrange
+        let (mut tk, call_registry) = wait_for_connection(vec![linear(1, "lin1")], None, None);
+        let range = StrokeRange { min_ms: 100, max_ms: 1000, min_pos: 0.0, max_pos: 1.0 };
+        let action = Action::new("stroke", vec![Control::Stroke(Selector::All, range)]);
+        let variable = Arc::new(std::sync::atomic::AtomicI64::new(50));
+
+        // act
+        let result = tk.dispatch_refs(
+            vec![(Strength::Variable(variable), action)],
+            vec![],
+            Speed::max(),
+            Duration::from_secs(1),
+        );
+        thread::sleep(Duration::from_millis(200));
+
+        // assert
+        assert_eq!(result.handle, -1);
+        assert!(tk
+            .events
+            .try_iter()
+            .any(|x| matches!(x, ClientEvent::UnsupportedStrength(action, _) if action == "stroke")));
+        call_registry.assert_unused(1);
+    }
+
+    #[test]
+    fn stop_all_ends_tracking() {
+        // arrange
+        let (tk, _) = wait_for_connection(vec![linear(1, "lin1")], None, None);
+        let actuators = tk.buttplug.devices().flatten_actuators();
+        let tracking = tk.start_tracking(actuators, DynamicSettings {
+            move_at_start: false,
+            ..Default::default()
+        });
+        assert!(tracking.is_running());
+
+        // act
+        tk.stop_all();
+        thread::sleep(Duration::from_millis(100));
+
+        // assert
+        assert!(!tracking.is_running());
+        assert!(!tracking.signal(TrackingSignal::Stop));
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use buttplug::client::ButtplugClient;
+use crossbeam_channel::Sender;
+use tokio::time::{sleep, timeout};
+use tracing::{debug, error, info};
+
+use crate::config::client::WatchdogSettings;
+
+use super::events::ClientEvent;
+
+/// Periodically pings the server to detect a connector that hangs without
+/// disconnecting, which would otherwise make all commands silently vanish
+pub async fn run_watchdog(
+    buttplug: Arc<ButtplugClient>,
+    settings: WatchdogSettings,
+    event_sender: Sender<ClientEvent>,
+) {
+    let mut degraded = false;
+    loop {
+        sleep(Duration::from_millis(settings.interval_ms)).await;
+        if !buttplug.connected() {
+            continue;
+        }
+        let reason = match timeout(Duration::from_millis(settings.timeout_ms), buttplug.ping()).await {
+            Ok(Ok(())) => None,
+            Ok(Err(err)) => Some(format!("ping failed: {}", err)),
+            Err(_) => Some(format!("ping timed out after {}ms", settings.timeout_ms)),
+        };
+        match reason {
+            Some(reason) if !degraded => {
+                error!(reason, "connection degraded");
+                degraded = true;
+                let _ = event_sender.send(ClientEvent::ConnectionDegraded(reason));
+            }
+            None if degraded => {
+                info!("connection restored");
+                degraded = false;
+                let _ = event_sender.send(ClientEvent::ConnectionRestored);
+            }
+            _ => debug!(degraded, "watchdog"),
+        }
+    }
+}
+
+/// Measures the round trip time to the server for the jitter buffer and lookahead,
+/// failed pings keep the last measurement
+pub async fn run_rtt_probe(buttplug: Arc<ButtplugClient>, interval: Duration, rtt_ms: Arc<AtomicU64>) {
+    loop {
+        sleep(interval).await;
+        if !buttplug.connected() {
+            continue;
+        }
+        let start = Instant::now();
+        match timeout(interval, buttplug.ping()).await {
+            Ok(Ok(())) => {
+                let rtt = start.elapsed().as_millis() as u64;
+                debug!(rtt, "measured round trip time");
+                rtt_ms.store(rtt, Ordering::Relaxed);
+            }
+            Ok(Err(err)) => debug!(?err, "rtt probe failed"),
+            Err(_) => debug!("rtt probe timed out"),
+        }
+    }
+}
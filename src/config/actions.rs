@@ -5,9 +5,18 @@ use serde::{Deserialize, Serialize};
 
 use crate::speed::Speed;
 
+use super::expression::BoundExpression;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Actions(pub Vec<Action>);
 
+impl Actions {
+    /// Finds a loaded action by name (case insensitive)
+    pub fn get(&self, name: &str) -> Option<&Action> {
+        self.0.iter().find(|x| x.name.eq_ignore_ascii_case(name.trim()))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ActionRef {
     pub action: String,
@@ -37,7 +46,9 @@ pub enum Stren {
     Constant(i32),
     Variable(Variable),
     Funscript(i32, String),
-    RandomFunscript(i32, Vec<String>)
+    RandomFunscript(i32, Vec<String>),
+    /// expression over registered variables, e.g. `0.5 * speed + 0.3 * arousal`
+    Expression(String),
 }
 
 #[derive(Debug, Clone)]
@@ -45,17 +56,29 @@ pub enum Strength {
     Constant(i32),
     Variable(Arc<AtomicI64>),
     Funscript(i32, String),
-    RandomFunscript(i32, Vec<String>)
+    RandomFunscript(i32, Vec<String>),
+    Expression(Arc<BoundExpression>),
+    /// pulses (or strokes) on each beat of the tempo in beats per minute,
+    /// see `BpClient::update_tempo`
+    Metronome(i32, f64),
+    /// plays a pattern per actuator index of multi-actuator devices, see `PatternBundle`
+    Bundle(i32, String),
 }
 
 impl Strength {
+    /// Scales the strength to a whole percentage, low values can collapse to 0
+    /// (e.g. 10% * 4% = 0.4% => 0%). Dispatching keeps the speed separate and
+    /// only multiplies the resulting `Speed` to avoid this.
     pub fn multiply(self, speed: &Speed) -> Strength {
-        let mult = |x: i32| Speed::new(x.into()).multiply(speed).value.into();
+        let mult = |x: i32| Speed::new(x.into()).multiply(speed).rounded().into();
         match self {
             Strength::Constant(x) => Strength::Constant(mult(x)),
             Strength::Funscript(x, fs) => Strength::Funscript(mult(x), fs),
             Strength::RandomFunscript(x, fss) => Strength::RandomFunscript(mult(x), fss),
             Strength::Variable(arc) => Strength::Variable(arc),
+            Strength::Expression(expression) => Strength::Expression(expression),
+            Strength::Metronome(x, bpm) => Strength::Metronome(mult(x), bpm),
+            Strength::Bundle(x, bundle) => Strength::Bundle(mult(x), bundle),
         }
     }
 }
@@ -67,6 +90,9 @@ impl Display for Strength {
             Strength::Funscript(speed, funscript) => write!(f, "Funscript({}, {}%)", funscript, speed),
             Strength::RandomFunscript(speed, vec) => write!(f, "Random({}%, {})", speed, vec.join(",")),
             Strength::Variable(_) => write!(f, "Dynamic"),
+            Strength::Expression(expression) => write!(f, "Expression({})", expression.source),
+            Strength::Metronome(speed, bpm) => write!(f, "Metronome({}bpm, {}%)", bpm, speed),
+            Strength::Bundle(speed, bundle) => write!(f, "Bundle({}, {}%)", bundle, speed),
         }
     }
 }
@@ -84,6 +110,14 @@ impl Action {
             control
         }
     }
+
+    /// Restricts all controls of the action to actuators of the given namespace (avatar)
+    pub fn in_namespace(&self, namespace: &str) -> Action {
+        Action {
+            name: self.name.clone(),
+            control: self.control.iter().map(|x| x.in_namespace(namespace)).collect()
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -93,16 +127,31 @@ pub enum Control {
 }
 
 impl Control {
+    /// Whether the control can be played with 'strength', variables and
+    /// expressions only drive scalar actuators
+    pub fn supports(&self, strength: &Strength) -> bool {
+        !matches!(
+            (self, strength),
+            (Control::Stroke(_, _), Strength::Variable(_) | Strength::Expression(_))
+        )
+    }
+
     pub fn get_selector(&self) -> Selector {
         match self {
             Control::Scalar(selector, _) => selector.clone(),
             Control::Stroke(selector, _) => selector.clone(),
         }
     }
+    pub fn in_namespace(&self, namespace: &str) -> Control {
+        match self {
+            Control::Scalar(selector, actuators) => Control::Scalar(selector.in_namespace(namespace), actuators.clone()),
+            Control::Stroke(selector, range) => Control::Stroke(selector.in_namespace(namespace), range.clone()),
+        }
+    }
     pub fn get_actuators(&self) -> Vec<ActuatorType> {
         match self {
             Control::Scalar(_, y) => y.iter().map(|x| x.clone().into()).collect(),
-            Control::Stroke(_, _) => vec![ActuatorType::Position],
+            Control::Stroke(_, _) => vec![ActuatorType::Position, ActuatorType::Rotate],
         }
     }
 }
@@ -111,6 +160,15 @@ impl Control {
 pub enum Selector {
     All,
     BodyParts(Vec<String>),
+    /// Restricts the inner selector to actuators of a namespace (avatar)
+    Namespaced(String, Box<Selector>),
+    /// Actuators with a semantic role like "left" or "primary"
+    Role(String),
+    /// Actuators matching both selectors
+    And(Box<Selector>, Box<Selector>),
+    /// Actuators of the first selector that matches any, e.g. "penis", else "vaginal",
+    /// else `All` to work across different setups
+    Failover(Vec<Selector>),
 }
 
 impl Selector {
@@ -121,11 +179,21 @@ impl Selector {
         }
         result
     }
+    pub fn in_namespace(&self, namespace: &str) -> Selector {
+        match self {
+            Selector::Namespaced(_, inner) => Selector::Namespaced(namespace.into(), inner.clone()),
+            selector => Selector::Namespaced(namespace.into(), Box::new(selector.clone())),
+        }
+    }
     pub fn and(&self, selector: Selector) -> Selector {
         match self {
             Selector::All => match selector {
                 Selector::All => Selector::All,
                 Selector::BodyParts(vec) => Selector::BodyParts(vec),
+                Selector::Namespaced(ns, inner) => Selector::Namespaced(ns, inner),
+                Selector::Role(role) => Selector::Role(role),
+                Selector::And(a, b) => Selector::And(a, b),
+                Selector::Failover(selectors) => Selector::Failover(selectors),
             },
             Selector::BodyParts(vec) => match selector {
                 Selector::All => Selector::BodyParts(vec.clone()),
@@ -134,13 +202,72 @@ impl Selector {
                     a.extend(vec2);
                     Selector::BodyParts(a)
                 },
+                Selector::Namespaced(ns, inner) => Selector::Namespaced(ns, Box::new(self.and(*inner))),
+                selector => Selector::And(Box::new(self.clone()), Box::new(selector)),
+            },
+            Selector::Namespaced(ns, inner) => Selector::Namespaced(ns.clone(), Box::new(inner.and(selector))),
+            Selector::Failover(selectors) => match selector {
+                Selector::All => self.clone(),
+                selector => Selector::Failover(selectors.iter().map(|x| x.and(selector.clone())).collect()),
+            },
+            Selector::Role(_) | Selector::And(_, _) => match selector {
+                Selector::All => self.clone(),
+                Selector::Namespaced(ns, inner) => Selector::Namespaced(ns, Box::new(self.and(*inner))),
+                selector => Selector::And(Box::new(self.clone()), Box::new(selector)),
             },
         }
     }
+    /// Body parts of the selector, the ones of the first alternative of `Failover`
     pub fn as_vec(&self) -> Vec<String> {
         match self {
             Selector::All => vec![],
             Selector::BodyParts(vec) => vec.clone(),
+            Selector::Namespaced(_, inner) => inner.as_vec(),
+            Selector::Role(_) => vec![],
+            Selector::And(a, b) => {
+                let mut vec = a.as_vec();
+                vec.extend(b.as_vec());
+                vec
+            }
+            Selector::Failover(selectors) => selectors.first().map(|x| x.as_vec()).unwrap_or_default(),
+        }
+    }
+    pub fn namespace(&self) -> Option<String> {
+        match self {
+            Selector::Namespaced(ns, _) => Some(ns.clone()),
+            Selector::And(a, b) => a.namespace().or(b.namespace()),
+            Selector::Failover(selectors) => selectors.first().and_then(|x| x.namespace()),
+            _ => None,
+        }
+    }
+    pub fn role(&self) -> Option<String> {
+        match self {
+            Selector::Role(role) => Some(role.clone()),
+            Selector::Namespaced(_, inner) => inner.role(),
+            Selector::And(a, b) => a.role().or(b.role()),
+            Selector::Failover(selectors) => selectors.first().and_then(|x| x.role()),
+            _ => None,
+        }
+    }
+    /// Selectors without `Failover` in the order they should be tried
+    pub fn alternatives(&self) -> Vec<Selector> {
+        match self {
+            Selector::Failover(selectors) => selectors.iter().flat_map(|x| x.alternatives()).collect(),
+            Selector::Namespaced(ns, inner) => inner
+                .alternatives()
+                .into_iter()
+                .map(|x| Selector::Namespaced(ns.clone(), Box::new(x)))
+                .collect(),
+            Selector::And(a, b) => a
+                .alternatives()
+                .into_iter()
+                .flat_map(|a| {
+                    b.alternatives()
+                        .into_iter()
+                        .map(move |b| Selector::And(Box::new(a.clone()), Box::new(b)))
+                })
+                .collect(),
+            selector => vec![selector.clone()],
         }
     }
 }
@@ -206,6 +333,52 @@ mod tests {
         println!("{}", serde_json::to_string_pretty(&actions).unwrap());
     }
 
+    #[test]
+    pub fn namespaced_selector_keeps_namespace_when_combined() {
+        let selector = Selector::BodyParts(vec!["anal".into()]).in_namespace("p1");
+        let combined = selector.and(Selector::BodyParts(vec!["penis".into()]));
+        assert_eq!(combined.namespace(), Some("p1".into()));
+        assert_eq!(combined.as_vec(), vec!["anal", "penis"]);
+
+        let combined = Selector::All.and(selector);
+        assert_eq!(combined.namespace(), Some("p1".into()));
+        assert_eq!(combined.as_vec(), vec!["anal"]);
+
+        let action = Action::new("a", vec![Control::Scalar(Selector::All, vec![])]).in_namespace("p2");
+        assert_eq!(action.control[0].get_selector().namespace(), Some("p2".into()));
+    }
+
+    #[test]
+    pub fn role_selector_keeps_role_when_combined() {
+        let selector = Selector::Role("left".into());
+        let combined = selector.and(Selector::BodyParts(vec!["nipple".into()]));
+        assert_eq!(combined.role(), Some("left".into()));
+        assert_eq!(combined.as_vec(), vec!["nipple"]);
+
+        let combined = Selector::BodyParts(vec!["anal".into()]).and(combined).in_namespace("p1");
+        assert_eq!(combined.role(), Some("left".into()));
+        assert_eq!(combined.namespace(), Some("p1".into()));
+        assert_eq!(combined.as_vec(), vec!["anal", "nipple"]);
+    }
+
+    #[test]
+    pub fn failover_selector_lists_alternatives_in_order() {
+        let selector = Selector::Failover(vec![
+            Selector::BodyParts(vec!["penis".into()]),
+            Selector::BodyParts(vec!["vaginal".into()]),
+            Selector::All,
+        ])
+        .in_namespace("p1")
+        .and(Selector::All);
+        let alternatives = selector.alternatives();
+        assert_eq!(alternatives.len(), 3);
+        assert_eq!(alternatives[0].as_vec(), vec!["penis"]);
+        assert_eq!(alternatives[1].as_vec(), vec!["vaginal"]);
+        assert!(alternatives[2].as_vec().is_empty());
+        assert!(alternatives.iter().all(|x| x.namespace() == Some("p1".into())));
+        assert_eq!(selector.as_vec(), vec!["penis"]);
+    }
+
     #[test]
     pub fn serialize_and_deserialize_actions() {
         let a1 = Actions(vec![
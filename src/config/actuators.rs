@@ -1,3 +1,9 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error, instrument};
@@ -7,14 +13,65 @@ use buttplug::core::message::ActuatorType;
 use crate::{actuator::Actuator, util::trim_lower_str_list};
 
 use super::{
-    linear::{LinearRange, LinearSpeedScaling}, 
+    read::StoredActuatorConfig,
+    linear::{LinearRange, LinearSharing, LinearSpeedScaling},
     scalar::ScalarRange, ActuatorLimits
 };
 
-/// actuator sepcific settings
+/// actuator sepcific settings, configs in the legacy format are
+/// migrated when they are read, see `read::BpDeviceSettings`
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(from = "Vec<StoredActuatorConfig>")]
 pub struct ActuatorSettings(pub Vec<ActuatorConfig>);
 
+/// Body parts of configs that are created for new actuators
+const DEFAULT_BODY_PARTS: &[&str] = &["anal", "clitoral", "nipple", "oral", "penis", "vaginal"];
+
+/// Index of `ActuatorSettings` by actuator id for lookups that must not create configs.
+/// Clones share the index, it is only copied when a shared index is changed
+#[derive(Debug, Clone, Default)]
+pub struct SettingsCache(Arc<HashMap<String, Arc<ActuatorConfig>>>);
+
+impl SettingsCache {
+    pub fn get(&self, actuator_config_id: &str) -> Option<&Arc<ActuatorConfig>> {
+        self.0.get(actuator_config_id)
+    }
+
+    pub fn contains(&self, actuator_config_id: &str) -> bool {
+        self.0.contains_key(actuator_config_id)
+    }
+
+    /// False for unknown actuators
+    pub fn is_enabled(&self, actuator_config_id: &str) -> bool {
+        self.get(actuator_config_id).is_some_and(|x| x.enabled)
+    }
+
+    /// Adds or replaces the config
+    pub fn insert(&mut self, config: ActuatorConfig) {
+        Arc::make_mut(&mut self.0).insert(config.actuator_config_id.clone(), Arc::new(config));
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<&ActuatorSettings> for SettingsCache {
+    fn from(settings: &ActuatorSettings) -> Self {
+        SettingsCache(Arc::new(
+            settings
+                .0
+                .iter()
+                .map(|x| (x.actuator_config_id.clone(), Arc::new(x.clone())))
+                .collect(),
+        ))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ActuatorConfig {
     pub actuator_config_id: String,
@@ -22,6 +79,80 @@ pub struct ActuatorConfig {
     pub body_parts: Vec<String>,
     #[serde(default = "ActuatorLimits::default")]
     pub limits: ActuatorLimits,
+    /// avatar/tenant the actuator belongs to, e.g. "p1" in multiplayer sessions
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// semantic role within the device, e.g. "left"/"right" for dual-motor devices
+    #[serde(default)]
+    pub role: Option<String>,
+    /// why the actuator was disabled automatically, cleared when it is enabled again
+    #[serde(default)]
+    pub disabled_reason: Option<String>,
+    /// previous ids of the actuator, e.g. after the device was renamed
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// last change in milliseconds since unix epoch, used when merging documents
+    #[serde(default)]
+    pub updated_ms: u64,
+    /// commands that wake up the device before it is used for the first time
+    #[serde(default)]
+    pub init_sequence: Vec<InitCommand>,
+    /// body parts the device is probably meant for, guessed from its name when the
+    /// config was created, see `ActuatorSettings::accept_suggested_body_parts`
+    #[serde(default)]
+    pub suggested_body_parts: Vec<String>,
+    /// maximum scalar commands per second, faster updates are coalesced like with
+    /// `ClientSettings::command_budgets`
+    #[serde(default)]
+    pub max_commands_per_sec: Option<u32>,
+    /// how tasks share the actuator if it is linear, see `DeviceAccess::acquire_linear`
+    #[serde(default)]
+    pub linear_sharing: LinearSharing,
+}
+
+/// Body parts that devices whose name contains the keyword are usually meant for
+const BODY_PART_HINTS: &[(&str, &[&str])] = &[
+    ("keon", &["penis"]),
+    ("launch", &["penis"]),
+    ("onyx", &["penis"]),
+    ("handy", &["penis"]),
+    ("solace", &["penis"]),
+    ("osr", &["penis"]),
+    ("sr6", &["penis"]),
+    ("max", &["penis"]),
+    ("calor", &["penis"]),
+    ("gush", &["penis"]),
+    ("wand", &["clitoral"]),
+    ("domi", &["clitoral"]),
+    ("lush", &["vaginal"]),
+    ("nora", &["vaginal", "clitoral"]),
+    ("hush", &["anal"]),
+    ("edge", &["anal"]),
+    ("plug", &["anal"]),
+    ("nipple", &["nipple"]),
+];
+
+/// Likely body parts of a device by its name, empty if the device is not known
+pub fn suggest_body_parts(device_name: &str) -> Vec<String> {
+    let device_name = device_name.to_lowercase();
+    BODY_PART_HINTS
+        .iter()
+        .filter(|(keyword, _)| device_name.contains(keyword))
+        .flat_map(|(_, body_parts)| body_parts.iter())
+        .unique()
+        .map(|x| (*x).to_owned())
+        .collect()
+}
+
+/// Single step of an actuators init sequence
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum InitCommand {
+    /// scalar or rotation speed in percent
+    Speed(i64),
+    /// moves a linear actuator to the position (0.0-1.0) within the milliseconds
+    Move(f64, u32),
+    /// milliseconds to wait before the next step
+    Wait(u64),
 }
 
 impl ActuatorSettings {
@@ -30,25 +161,21 @@ impl ActuatorSettings {
     }
 
     pub fn get_or_create(&mut self, actuator_config_id: &str) -> ActuatorConfig {
-        let device = self.get_config(actuator_config_id);
-        match device {
-            Some(setting) => setting,
+        match self.find(actuator_config_id) {
+            Some(setting) => setting.clone(),
             None => {
-                let mut device = ActuatorConfig::from_identifier(actuator_config_id);
-                device.body_parts = vec![ // TODO: Needs to be defined somewhere else
-                    "anal".to_owned(),
-                    "clitoral".to_owned(),
-                    "nipple".to_owned(),
-                    "oral".to_owned(),
-                    "penis".to_owned(),
-                    "vaginal".to_owned()
-                ];
+                let device = ActuatorConfig::created(actuator_config_id);
                 self.update_device(device.clone());
                 device
             },
         }
     }
 
+    /// Config of the actuator without copying it
+    pub fn find(&self, actuator_config_id: &str) -> Option<&ActuatorConfig> {
+        self.0.iter().find(|d| d.actuator_config_id == actuator_config_id)
+    }
+
     // unused
     pub fn try_get_limits(&mut self, actuator_config_id: &str) -> ActuatorLimits {
         if let Some(setting) = self.get_config(actuator_config_id) {
@@ -110,8 +237,12 @@ impl ActuatorSettings {
         result
     }
     
-    pub fn update_device(&mut self, setting: ActuatorConfig)
+    pub fn update_device(&mut self, mut setting: ActuatorConfig)
     {
+        setting.updated_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_millis() as u64)
+            .unwrap_or_default();
         let insert_pos = self.0.iter().find_position(|x| x.actuator_config_id == setting.actuator_config_id);
         if let Some((pos, _)) = insert_pos {
             self.0[ pos ] = setting;
@@ -121,10 +252,7 @@ impl ActuatorSettings {
     }
 
     pub fn get_config(&self, actuator_config_id: &str) -> Option<ActuatorConfig> {
-         self.0
-                .iter()
-                .find(|d| d.actuator_config_id == actuator_config_id)
-                .cloned()
+        self.find(actuator_config_id).cloned()
     }
 
     #[instrument]
@@ -132,9 +260,21 @@ impl ActuatorSettings {
         debug!("set_enabled");
         let mut device =  self.get_or_create(actuator_config_id);
         device.enabled = enabled;
+        if enabled {
+            device.disabled_reason = None;
+        }
         self.update_device(device)
     }
 
+    #[instrument]
+    pub fn disable_with_reason(&mut self, actuator_config_id: &str, reason: &str) {
+        debug!("disable_with_reason");
+        let mut device = self.get_or_create(actuator_config_id);
+        device.enabled = false;
+        device.disabled_reason = Some(reason.to_owned());
+        self.update_device(device);
+    }
+
     #[instrument]
     pub fn set_body_parts(&mut self, actuator_config_id: &str, events: &[&str]) {
         debug!("set_body_parts");
@@ -143,23 +283,74 @@ impl ActuatorSettings {
         self.update_device(device);
     }
 
-    pub fn get_events(&mut self, actuator_config_id: &str) -> Vec<String> {
-        self.get_or_create(actuator_config_id).body_parts
+    #[instrument]
+    pub fn set_namespace(&mut self, actuator_config_id: &str, namespace: Option<&str>) {
+        debug!("set_namespace");
+        let mut device = self.get_or_create(actuator_config_id);
+        device.namespace = namespace.map(|x| x.to_lowercase().trim().to_owned());
+        self.update_device(device);
     }
 
-    pub fn get_enabled(&mut self, actuator_config_id: &str) -> bool {
-        self.get_or_create(actuator_config_id).enabled
+    #[instrument]
+    pub fn set_role(&mut self, actuator_config_id: &str, role: Option<&str>) {
+        debug!("set_role");
+        let mut device = self.get_or_create(actuator_config_id);
+        device.role = role.map(|x| x.to_lowercase().trim().to_owned());
+        self.update_device(device);
+    }
+
+    /// Replaces the body parts with the suggested ones, returns false if there are no suggestions
+    #[instrument]
+    pub fn accept_suggested_body_parts(&mut self, actuator_config_id: &str) -> bool {
+        debug!("accept_suggested_body_parts");
+        let mut device = self.get_or_create(actuator_config_id);
+        if device.suggested_body_parts.is_empty() {
+            return false;
+        }
+        device.body_parts = std::mem::take(&mut device.suggested_body_parts);
+        self.update_device(device);
+        true
+    }
+
+    /// Body parts of the actuator, the ones of a new config if it does not exist
+    pub fn get_events(&self, actuator_config_id: &str) -> Vec<String> {
+        match self.find(actuator_config_id) {
+            Some(device) => device.body_parts.clone(),
+            None => DEFAULT_BODY_PARTS.iter().map(|x| (*x).to_owned()).collect(),
+        }
+    }
+
+    /// False if the actuator does not exist
+    pub fn get_enabled(&self, actuator_config_id: &str) -> bool {
+        self.find(actuator_config_id).is_some_and(|x| x.enabled)
     }
 }
 
 
 impl ActuatorConfig {
+    /// Config of an actuator that was not configured before
+    pub fn created(actuator_id: &str) -> ActuatorConfig {
+        ActuatorConfig {
+            body_parts: DEFAULT_BODY_PARTS.iter().map(|x| (*x).to_owned()).collect(),
+            ..ActuatorConfig::from_identifier(actuator_id)
+        }
+    }
+
     pub fn from_identifier(actuator_id: &str) -> ActuatorConfig {
         ActuatorConfig {
             actuator_config_id: actuator_id.into(),
             enabled: false,
             body_parts: vec![],
             limits: ActuatorLimits::None,
+            namespace: None,
+            role: None,
+            disabled_reason: None,
+            aliases: vec![],
+            updated_ms: 0,
+            init_sequence: vec![],
+            suggested_body_parts: vec![],
+            max_commands_per_sec: None,
+            linear_sharing: LinearSharing::Preempt,
         }
     }
     pub fn from_actuator(actuator: &Actuator) -> ActuatorConfig {
@@ -176,6 +367,56 @@ impl ActuatorConfig {
                 ActuatorType::Position => ActuatorLimits::Linear(LinearRange::default()),
                 _ => ActuatorLimits::None,
             },
+            namespace: None,
+            role: None,
+            disabled_reason: None,
+            aliases: vec![],
+            updated_ms: 0,
+            init_sequence: vec![],
+            suggested_body_parts: vec![],
+            max_commands_per_sec: None,
+            linear_sharing: LinearSharing::Preempt,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_parts_are_suggested_by_device_name() {
+        assert_eq!(suggest_body_parts("Kiiroo Keon"), vec!["penis"]);
+        assert_eq!(suggest_body_parts("Lovense Nora"), vec!["vaginal", "clitoral"]);
+        assert!(suggest_body_parts("Unknown Device").is_empty());
+
+        let mut settings = ActuatorSettings::default();
+        let mut config = settings.get_or_create("Lovense Hush (Vibrate)");
+        assert!(!settings.accept_suggested_body_parts("Lovense Hush (Vibrate)"));
+        config.suggested_body_parts = suggest_body_parts("Lovense Hush");
+        settings.update_device(config);
+
+        assert!(settings.accept_suggested_body_parts("Lovense Hush (Vibrate)"));
+        let config = settings.get_config("Lovense Hush (Vibrate)").unwrap();
+        assert_eq!(config.body_parts, vec!["anal"]);
+        assert!(config.suggested_body_parts.is_empty());
+    }
+
+    #[test]
+    fn queries_do_not_create_configs() {
+        let mut settings = ActuatorSettings::default();
+        settings.set_enabled("vib1", true);
+
+        assert!(!settings.get_enabled("vib2"));
+        assert_eq!(settings.get_events("vib2").len(), DEFAULT_BODY_PARTS.len());
+        assert_eq!(settings.0.len(), 1);
+
+        let cache = SettingsCache::from(&settings);
+        let mut copy = cache.clone();
+        copy.insert(ActuatorConfig::created("vib2"));
+        assert!(cache.is_enabled("vib1"));
+        assert!(!cache.contains("vib2"));
+        assert!(copy.contains("vib2"));
+        assert!(Arc::ptr_eq(cache.get("vib1").unwrap(), copy.get("vib1").unwrap()));
+    }
+}
@@ -1,8 +1,15 @@
-use std::fmt::{self, Display};
+use std::{collections::HashMap, fmt::{self, Display}, time::Duration};
 use buttplug::core::message::LogLevel;
 use serde::{Deserialize, Serialize};
 
-use super::connection::ConnectionType;
+use crate::speed::{Speed, SpeedLadder};
+
+use super::{
+    connection::{ConnectionType, Transport},
+    linear::ScalingProfile,
+    logging::{set_redaction, Redaction},
+    store::SharedConfigStore,
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct InProcessFeatures {
@@ -14,20 +21,460 @@ pub struct InProcessFeatures {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LoggingSettings {
     pub log_level: LogLevel,
+    /// how device identifiers appear in logs and events
+    #[serde(default)]
+    pub redaction: Redaction,
+}
+
+impl LoggingSettings {
+    /// Applies the redaction mode to all identifiers logged from now on
+    pub fn apply_redaction(&self) {
+        set_redaction(self.redaction);
+    }
 }
 
 impl Default for LoggingSettings {
     fn default() -> Self {
-        Self { log_level: LogLevel::Debug }
+        Self {
+            log_level: LogLevel::Debug,
+            redaction: Redaction::None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WatchdogSettings {
+    pub interval_ms: u64,
+    pub timeout_ms: u64,
+}
+
+impl Default for WatchdogSettings {
+    fn default() -> Self {
+        Self {
+            interval_ms: 5_000,
+            timeout_ms: 2_000,
+        }
+    }
+}
+
+/// Connects again after the server disconnected without `BpClient::disconnect`,
+/// doubling the delay after each failed attempt
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReconnectSettings {
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// gives up after this many failed attempts, None retries forever
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectSettings {
+    fn default() -> Self {
+        Self {
+            initial_delay_ms: 1_000,
+            max_delay_ms: 30_000,
+            max_attempts: None,
+        }
+    }
+}
+
+/// Polls the battery of devices and limits their output while it is low
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct BatterySettings {
+    pub interval_ms: u64,
+    /// devices are limited below this battery level (percent)
+    pub low_pct: u32,
+    /// limits are lifted again at or above this battery level (percent)
+    pub restore_pct: u32,
+    /// cap of the scalar speed (percent) while the battery is low
+    pub max_speed: u32,
+    /// minimum time between two scalar commands to the device while the battery is low
+    pub min_command_interval_ms: u64,
+}
+
+impl Default for BatterySettings {
+    fn default() -> Self {
+        Self {
+            interval_ms: 60_000,
+            low_pct: 20,
+            restore_pct: 25,
+            max_speed: 50,
+            min_command_interval_ms: 250,
+        }
+    }
+}
+
+/// Limits of actuators that were never seen before, they stay disabled until the
+/// user enables them and keep the limits until the user changes them
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SafeModeSettings {
+    /// cap of the scalar speed and the linear stroke speed (percent)
+    pub max_speed: u32,
+}
+
+impl Default for SafeModeSettings {
+    fn default() -> Self {
+        Self { max_speed: 50 }
+    }
+}
+
+/// Kind of connection to a device, used to limit the command rate of flaky hardware
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceClass {
+    Bluetooth,
+    Serial,
+    XInput,
+}
+
+impl DeviceClass {
+    /// Guesses the class from the device name, buttplug does not expose the transport
+    pub fn detect(device_name: &str) -> DeviceClass {
+        let name = device_name.to_lowercase();
+        if name.contains("xinput") {
+            DeviceClass::XInput
+        } else if name.contains("serial") || name.contains("tcode") {
+            DeviceClass::Serial
+        } else {
+            DeviceClass::Bluetooth
+        }
+    }
+}
+
+/// Evens out the command cadence of websocket connections with variable latency
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JitterBufferSettings {
+    /// latency that is added to every command, reduced by half of the measured round trip
+    pub added_latency_ms: u64,
+    /// how often the round trip time to the server is measured
+    pub probe_interval_ms: u64,
+}
+
+impl Default for JitterBufferSettings {
+    fn default() -> Self {
+        Self {
+            added_latency_ms: 150,
+            probe_interval_ms: 2_000,
+        }
+    }
+}
+
+/// Sends pattern commands ahead of time by half of the measured round trip time
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LookaheadSettings {
+    /// upper limit for how early a command is sent
+    pub max_lead_ms: u64,
+    /// how often the round trip time to the server is measured
+    pub probe_interval_ms: u64,
+}
+
+impl Default for LookaheadSettings {
+    fn default() -> Self {
+        Self {
+            max_lead_ms: 250,
+            probe_interval_ms: 2_000,
+        }
+    }
+}
+
+/// Bounds the memory of parsed patterns that are kept for later dispatches
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PatternCacheSettings {
+    pub max_entries: usize,
+    pub max_kb: usize,
+    /// reads patterns again after their files changed, see `PatternWatcher`. Needs
+    /// the `pattern-watch` feature, ignored without it
+    #[serde(default)]
+    pub watch: bool,
+}
+
+impl Default for PatternCacheSettings {
+    fn default() -> Self {
+        Self {
+            max_entries: 256,
+            max_kb: 16 * 1024,
+            watch: false,
+        }
+    }
+}
+
+/// Protects the bluetooth adapter from hosts that start and stop scanning too often
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScanLimitSettings {
+    /// minimum time between two scan operations, requests in between are
+    /// queued and only the latest one is executed
+    pub min_interval_ms: u64,
+}
+
+impl Default for ScanLimitSettings {
+    fn default() -> Self {
+        Self { min_interval_ms: 1_000 }
+    }
+}
+
+/// Caps the players that run at the same time, dispatches beyond them are refused
+/// so that a host that dispatches in a loop can't exhaust tasks and channels
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// handles with at least one running player
+    pub max_handles: usize,
+    /// running players of all handles
+    pub max_players: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            max_handles: 64,
+            max_players: 256,
+        }
+    }
+}
+
+/// Limits that apply to all tasks while quiet mode is on
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuietModeSettings {
+    /// ceiling for all scalar outputs in percent
+    pub max_speed: i64,
+    /// lower limit for the duration of a stroke
+    pub min_stroke_ms: i64,
+}
+
+impl Default for QuietModeSettings {
+    fn default() -> Self {
+        Self {
+            max_speed: 30,
+            min_stroke_ms: 1_000,
+        }
     }
 }
 
+/// Lowers all outputs in stages when `BpClient::stop_all` is called instead of stopping abruptly
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StagedStopSettings {
+    /// applied in order before everything is stopped
+    pub stages: Vec<StopStage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StopStage {
+    /// ceiling for all scalar outputs in percent
+    pub speed: u32,
+    pub duration_ms: u64,
+}
+
+impl StagedStopSettings {
+    pub fn stages(&self) -> Vec<(Speed, Duration)> {
+        self.stages
+            .iter()
+            .map(|x| (Speed::new(x.speed.into()), Duration::from_millis(x.duration_ms)))
+            .collect()
+    }
+}
+
+impl Default for StagedStopSettings {
+    fn default() -> Self {
+        Self {
+            stages: vec![StopStage {
+                speed: 30,
+                duration_ms: 1_000,
+            }],
+        }
+    }
+}
+
+/// Ignores small changes of variables and expressions, so that noisy
+/// values don't cause a constant stream of device updates
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct DeadbandSettings {
+    /// changes smaller than this (percent) are ignored
+    pub min_change: u32,
+    /// change (percent) that is required in addition when the direction reverses
+    pub hysteresis: u32,
+}
+
+impl DeadbandSettings {
+    /// Whether 'value' differs enough from 'last', the last value that was sent.
+    /// 'rising' is the direction of the last sent change, dropping to 0 is always sent
+    pub fn accepts(&self, last: i64, value: i64, rising: Option<bool>) -> bool {
+        let change = value - last;
+        if change == 0 {
+            return false;
+        }
+        if value == 0 {
+            return true;
+        }
+        let reverses = rising.is_some_and(|rising| rising != (change > 0));
+        let threshold = self.min_change + if reverses { self.hysteresis } else { 0 };
+        change.unsigned_abs() >= threshold.into()
+    }
+}
+
+/// What the client does with the devices when it is dropped
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DropBehaviour {
+    #[default]
+    StopAndDisconnect,
+    StopDevices,
+    /// the host manages shutdown itself
+    Nothing,
+}
+
+/// How dispatches with a duration of zero are played
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZeroDurationBehaviour {
+    /// sends the start value and the stop exactly once (a single stroke for linear actuators)
+    #[default]
+    OneShot,
+    /// starts a regular task that is cancelled right away
+    Timed,
+}
+
+/// How `Strength::RandomFunscript` picks its pattern
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RandomPatternMode {
+    /// one random pattern for the whole duration
+    #[default]
+    PerAction,
+    /// a new random pattern each time the current one completes
+    PerLoop,
+}
+
+/// Where `device_settings` are stored automatically after changes made through the client
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SettingsPersistence {
+    pub settings_path: String,
+    pub settings_file: String,
+    /// changes within this time are written at once
+    pub debounce_ms: u64,
+}
+
+/// Maximum active time of each actuator within the last hour and day
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RuntimeCapSettings {
+    #[serde(default)]
+    pub max_mins_per_hour: Option<u64>,
+    #[serde(default)]
+    pub max_mins_per_day: Option<u64>,
+    /// directory where the used runtime is stored, so that caps survive restarts
+    #[serde(default)]
+    pub state_path: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ClientSettings {
     pub connection: ConnectionType,
     pub in_process_features: InProcessFeatures,
+    /// transports that are never selected for actions with body parts, e.g. `XInput`
+    /// to keep gamepad rumble out of them
+    #[serde(default)]
+    pub body_part_excluded_transports: Vec<Transport>,
     #[serde(skip)]
-    pub pattern_path: String
+    pub pattern_path: String,
+    /// where device settings, statistics and runtime state are persisted
+    #[serde(skip)]
+    pub config_store: SharedConfigStore,
+    /// ping the server periodically to detect a hung connection
+    #[serde(default)]
+    pub watchdog: Option<WatchdogSettings>,
+    /// connect again when the server disconnects unexpectedly, None stays disconnected
+    #[serde(default)]
+    pub reconnect: Option<ReconnectSettings>,
+    #[serde(default)]
+    pub on_drop: DropBehaviour,
+    /// disables enabled actuators that are disconnected or failing for longer than this
+    #[serde(default)]
+    pub auto_disable_after_mins: Option<u64>,
+    #[serde(default)]
+    pub quiet_mode: QuietModeSettings,
+    /// action that is executed instead of action names that are not loaded
+    #[serde(default)]
+    pub fallback_action: Option<String>,
+    /// only used for websocket connections
+    #[serde(default)]
+    pub jitter_buffer: Option<JitterBufferSettings>,
+    /// only used for websocket connections
+    #[serde(default)]
+    pub lookahead: Option<LookaheadSettings>,
+    /// maximum scalar commands per second and device, faster updates are coalesced
+    #[serde(default)]
+    pub command_budgets: HashMap<DeviceClass, u32>,
+    #[serde(default)]
+    pub zero_duration: ZeroDurationBehaviour,
+    /// refuses new tasks for actuators that ran too long recently
+    #[serde(default)]
+    pub runtime_caps: Option<RuntimeCapSettings>,
+    #[serde(default)]
+    pub settings_persistence: Option<SettingsPersistence>,
+    /// directory where the usage statistics of actions are stored
+    #[serde(default)]
+    pub action_stats_path: Option<String>,
+    /// blends across the seam of repeating patterns instead of jumping back to the start
+    #[serde(default)]
+    pub loop_crossfade_ms: Option<u64>,
+    /// pause after each loop of a pattern
+    #[serde(default)]
+    pub loop_gap_ms: Option<u64>,
+    /// speeds that `BpClient::step_up` and `step_down` move tasks through
+    #[serde(default)]
+    pub speed_ladder: SpeedLadder,
+    /// blends between two tasks when one takes over an actuator from the other
+    #[serde(default)]
+    pub takeover_crossfade_ms: Option<u64>,
+    /// fades scalar tasks in when they start, see `PlayerSettings`
+    #[serde(default)]
+    pub ramp_in_ms: u32,
+    /// fades scalar tasks out when they end or are stopped
+    #[serde(default)]
+    pub ramp_out_ms: u32,
+    #[serde(default)]
+    pub random_patterns: RandomPatternMode,
+    #[serde(default)]
+    pub scan_limit: ScanLimitSettings,
+    #[serde(default)]
+    pub pattern_cache: PatternCacheSettings,
+    /// limits devices with a low battery, None does not poll batteries
+    #[serde(default)]
+    pub battery: Option<BatterySettings>,
+    /// applies to tasks driven by variables or expressions
+    #[serde(default)]
+    pub variable_deadband: Option<DeadbandSettings>,
+    /// winds down outputs on `stop_all`, None stops right away
+    #[serde(default)]
+    pub staged_stop: Option<StagedStopSettings>,
+    /// pre-fill the configs of new actuators by device name, checked before the builtin profiles
+    #[serde(default)]
+    pub scaling_profiles: Vec<ScalingProfile>,
+    /// directory of community profile files, checked after `scaling_profiles`
+    /// and before the builtin profiles
+    #[serde(default)]
+    pub device_profile_path: Option<String>,
+    /// length of each pulse of `Strength::Metronome`
+    #[serde(default = "default_metronome_pulse_ms")]
+    pub metronome_pulse_ms: u64,
+    /// creates the configs of new actuators disabled and limited, None creates them
+    /// disabled with the default limits
+    #[serde(default = "default_safe_mode")]
+    pub safe_mode: Option<SafeModeSettings>,
+    /// refuses dispatches while too many tasks are running, None does not limit them
+    #[serde(default)]
+    pub resource_limits: Option<ResourceLimits>,
+    /// applied when connecting, see `BpClient::set_logging`
+    #[serde(default)]
+    pub logging: LoggingSettings,
+    /// raises the scalar resolution to the slowest actuator measured by `BpClient::measure_latency`
+    #[serde(default)]
+    pub latency_sets_scalar_resolution: bool,
+}
+
+fn default_metronome_pulse_ms() -> u64 {
+    100
+}
+
+fn default_safe_mode() -> Option<SafeModeSettings> {
+    Some(SafeModeSettings::default())
 }
 
 impl Default for ClientSettings {
@@ -35,6 +482,40 @@ impl Default for ClientSettings {
         Self {
             connection: ConnectionType::InProcess,
             pattern_path: "".into(),
+            config_store: SharedConfigStore::default(),
+            watchdog: None,
+            reconnect: None,
+            on_drop: DropBehaviour::default(),
+            auto_disable_after_mins: None,
+            quiet_mode: QuietModeSettings::default(),
+            fallback_action: None,
+            jitter_buffer: None,
+            lookahead: None,
+            command_budgets: HashMap::new(),
+            zero_duration: ZeroDurationBehaviour::default(),
+            runtime_caps: None,
+            settings_persistence: None,
+            action_stats_path: None,
+            loop_crossfade_ms: None,
+            loop_gap_ms: None,
+            takeover_crossfade_ms: None,
+            speed_ladder: SpeedLadder::default(),
+            ramp_in_ms: 0,
+            ramp_out_ms: 0,
+            body_part_excluded_transports: vec![],
+            random_patterns: RandomPatternMode::default(),
+            scan_limit: ScanLimitSettings::default(),
+            pattern_cache: PatternCacheSettings::default(),
+            battery: None,
+            variable_deadband: None,
+            staged_stop: None,
+            scaling_profiles: vec![],
+            device_profile_path: None,
+            metronome_pulse_ms: default_metronome_pulse_ms(),
+            safe_mode: default_safe_mode(),
+            resource_limits: None,
+            logging: LoggingSettings::default(),
+            latency_sets_scalar_resolution: false,
             in_process_features: InProcessFeatures {
                 bluetooth: true,
                 serial: true,
@@ -101,6 +582,20 @@ pub(crate) mod settings_tests {
         assert_eq!(settings.0.len(), settings.0.len());
     }
 
+    #[test]
+    fn detects_device_class_from_name() {
+        assert_eq!(DeviceClass::detect("XBox Compatible Gamepad (XInput)"), DeviceClass::XInput);
+        assert_eq!(DeviceClass::detect("TCode v0.3 (Single Linear Axis)"), DeviceClass::Serial);
+        assert_eq!(DeviceClass::detect("Lovense Hush"), DeviceClass::Bluetooth);
+
+        let settings: ClientSettings = serde_json::from_str(
+            r#"{ "connection": "InProcess", "in_process_features": { "bluetooth": true, "serial": true, "xinput": true },
+                 "command_budgets": { "Bluetooth": 10, "Serial": 50 } }"#,
+        )
+        .unwrap();
+        assert_eq!(settings.command_budgets.get(&DeviceClass::Serial), Some(&50));
+    }
+
     #[test]
     fn adds_every_device_only_once() {
         let mut settings = ActuatorSettings::default();
@@ -155,6 +650,17 @@ pub(crate) mod settings_tests {
         assert!(settings.get_enabled("a"));
     }
 
+    #[test]
+    fn enabling_clears_disabled_reason() {
+        let mut settings = ActuatorSettings::default();
+        settings.set_enabled("a", true);
+        settings.disable_with_reason("a", "disconnected");
+        assert_eq!(settings.get_config("a").unwrap().disabled_reason, Some("disconnected".into()));
+
+        settings.set_enabled("a", true);
+        assert_eq!(settings.get_config("a").unwrap().disabled_reason, None);
+    }
+
     #[test]
     fn set_valid_websocket_endpoint() {
         let mut settings = ClientSettings::default();
@@ -179,6 +685,31 @@ pub(crate) mod settings_tests {
         }
     }
 
+    #[test]
+    fn deadband_ignores_small_changes_and_reversals() {
+        let deadband = DeadbandSettings {
+            min_change: 5,
+            hysteresis: 5,
+        };
+        assert!(!deadband.accepts(50, 54, None));
+        assert!(deadband.accepts(50, 55, None));
+        assert!(!deadband.accepts(55, 48, Some(true)));
+        assert!(deadband.accepts(55, 45, Some(true)));
+        assert!(deadband.accepts(45, 40, Some(false)));
+        assert!(deadband.accepts(2, 0, Some(true)));
+        assert!(!deadband.accepts(0, 0, None));
+    }
+
+    #[test]
+    fn client_settings_without_watchdog_are_parsed() {
+        let settings: ClientSettings = serde_json::from_str(
+            r#"{ "connection": "InProcess", "in_process_features": { "bluetooth": true, "serial": false, "xinput": false } }"#,
+        )
+        .unwrap();
+        assert!(settings.watchdog.is_none());
+        assert_eq!(settings.on_drop, DropBehaviour::StopAndDisconnect);
+    }
+
     pub fn create_temp_file(name: &str, content: &str) -> (String, String, TempDir) {
         let tmp_path = tempdir().unwrap();
         assert_ok!(fs::create_dir_all(tmp_path.path().to_str().unwrap()));
@@ -1,8 +1,92 @@
 use serde::{Deserialize, Serialize};
 
+use super::client::InProcessFeatures;
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum ConnectionType {
     InProcess,
     WebSocket(String),
     Test,
 }
+
+/// Communication transport a device is connected with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Transport {
+    Bluetooth,
+    Serial,
+    XInput,
+    /// the device is connected to a remote server, which does not report its transport
+    WebSocket,
+    /// more than one transport of the in-process server could have found the device
+    #[default]
+    Unknown,
+}
+
+impl Transport {
+    /// Transport of a device named 'device_name' that was found through 'connection'.
+    /// XInput gamepads are recognized by their name on all connections
+    pub fn detect(device_name: &str, connection: &ConnectionType, features: &InProcessFeatures) -> Transport {
+        if Transport::is_xinput_name(device_name) {
+            return Transport::XInput;
+        }
+        match connection {
+            ConnectionType::WebSocket(_) => Transport::WebSocket,
+            ConnectionType::InProcess => match (features.bluetooth, features.serial) {
+                (true, false) => Transport::Bluetooth,
+                (false, true) => Transport::Serial,
+                _ => Transport::Unknown,
+            },
+            ConnectionType::Test => Transport::Unknown,
+        }
+    }
+
+    /// Transport of a device of the in-process server with the hardware 'address', btleplug
+    /// addresses are printed peripheral ids and XInput addresses are the gamepad index.
+    /// Serial is the only other comm manager of the in-process server
+    pub fn from_address(address: &str) -> Transport {
+        if address.starts_with("PeripheralId(") {
+            Transport::Bluetooth
+        } else if address.parse::<u32>().is_ok() {
+            Transport::XInput
+        } else {
+            Transport::Serial
+        }
+    }
+
+    /// Buttplug names all XInput devices "XBox (XInput) Compatible Gamepad"
+    pub fn is_xinput_name(device_name: &str) -> bool {
+        device_name.contains("(XInput)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transport_is_detected_from_connection_and_name() {
+        let features = |bluetooth, serial| InProcessFeatures {
+            bluetooth,
+            serial,
+            xinput: true,
+        };
+        let in_process = ConnectionType::InProcess;
+        let remote = ConnectionType::WebSocket("127.0.0.1:12345".into());
+        let gamepad = "XBox (XInput) Compatible Gamepad";
+
+        assert_eq!(Transport::detect("Lovense Hush", &in_process, &features(true, false)), Transport::Bluetooth);
+        assert_eq!(Transport::detect("Lovense Hush", &in_process, &features(false, true)), Transport::Serial);
+        assert_eq!(Transport::detect("Lovense Hush", &in_process, &features(true, true)), Transport::Unknown);
+        assert_eq!(Transport::detect("Lovense Hush", &remote, &features(true, false)), Transport::WebSocket);
+        assert_eq!(Transport::detect(gamepad, &remote, &features(true, false)), Transport::XInput);
+        assert_eq!(Transport::detect(gamepad, &in_process, &features(true, true)), Transport::XInput);
+    }
+
+    #[test]
+    fn transport_is_detected_from_address() {
+        assert_eq!(Transport::from_address("PeripheralId(E4:C2:3A:12:9B:01)"), Transport::Bluetooth);
+        assert_eq!(Transport::from_address("0"), Transport::XInput);
+        assert_eq!(Transport::from_address("COM3"), Transport::Serial);
+        assert_eq!(Transport::from_address("/dev/ttyUSB0"), Transport::Serial);
+    }
+}
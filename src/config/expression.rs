@@ -0,0 +1,313 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+};
+
+/// Parsed form of a `Stren::Expression` like `0.5 * speed + 0.3 * arousal`
+///
+/// Supports numbers, registered variables, `+ - * /`, parentheses
+/// and the functions `min(a, b)` and `max(a, b)`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Expression>),
+    Add(Box<Expression>, Box<Expression>),
+    Sub(Box<Expression>, Box<Expression>),
+    Mul(Box<Expression>, Box<Expression>),
+    Div(Box<Expression>, Box<Expression>),
+    Min(Box<Expression>, Box<Expression>),
+    Max(Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionError {
+    /// position in the source and description of what was expected
+    Syntax(usize, String),
+    UnknownVariable(String),
+}
+
+impl Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionError::Syntax(pos, msg) => write!(f, "syntax error at {}: {}", pos, msg),
+            ExpressionError::UnknownVariable(name) => write!(f, "unknown variable '{}'", name),
+        }
+    }
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Expression, ExpressionError> {
+        let mut parser = Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+        let expression = parser.sum()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("end of expression"));
+        }
+        Ok(expression)
+    }
+
+    /// Names of all variables the expression reads
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = vec![];
+        self.collect_variables(&mut names);
+        names
+    }
+
+    fn collect_variables<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Expression::Number(_) => {}
+            Expression::Variable(name) => {
+                if !names.contains(&name.as_str()) {
+                    names.push(name)
+                }
+            }
+            Expression::Negate(x) => x.collect_variables(names),
+            Expression::Add(a, b)
+            | Expression::Sub(a, b)
+            | Expression::Mul(a, b)
+            | Expression::Div(a, b)
+            | Expression::Min(a, b)
+            | Expression::Max(a, b) => {
+                a.collect_variables(names);
+                b.collect_variables(names);
+            }
+        }
+    }
+
+    /// Evaluates the expression, division by zero yields 0
+    pub fn eval(&self, variable: &dyn Fn(&str) -> f64) -> f64 {
+        match self {
+            Expression::Number(x) => *x,
+            Expression::Variable(name) => variable(name),
+            Expression::Negate(x) => -x.eval(variable),
+            Expression::Add(a, b) => a.eval(variable) + b.eval(variable),
+            Expression::Sub(a, b) => a.eval(variable) - b.eval(variable),
+            Expression::Mul(a, b) => a.eval(variable) * b.eval(variable),
+            Expression::Div(a, b) => {
+                let divisor = b.eval(variable);
+                if divisor == 0.0 {
+                    return 0.0;
+                }
+                a.eval(variable) / divisor
+            }
+            Expression::Min(a, b) => a.eval(variable).min(b.eval(variable)),
+            Expression::Max(a, b) => a.eval(variable).max(b.eval(variable)),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, expected: &str) -> ExpressionError {
+        ExpressionError::Syntax(self.pos, format!("expected {}", expected))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|x| x.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ExpressionError> {
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("'{}'", c)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn sum(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.product()?;
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.pos += 1;
+                    left = Expression::Add(Box::new(left), Box::new(self.product()?));
+                }
+                Some('-') => {
+                    self.pos += 1;
+                    left = Expression::Sub(Box::new(left), Box::new(self.product()?));
+                }
+                _ => return Ok(left),
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<Expression, ExpressionError> {
+        let mut left = self.unary()?;
+        loop {
+            match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    left = Expression::Mul(Box::new(left), Box::new(self.unary()?));
+                }
+                Some('/') => {
+                    self.pos += 1;
+                    left = Expression::Div(Box::new(left), Box::new(self.unary()?));
+                }
+                _ => return Ok(left),
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        if self.peek() == Some('-') {
+            self.pos += 1;
+            return Ok(Expression::Negate(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expression, ExpressionError> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.sum()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.pos;
+                while self.chars.get(self.pos).is_some_and(|x| x.is_ascii_digit() || *x == '.') {
+                    self.pos += 1;
+                }
+                let literal: String = self.chars[start..self.pos].iter().collect();
+                literal
+                    .parse()
+                    .map(Expression::Number)
+                    .map_err(|_| ExpressionError::Syntax(start, format!("invalid number '{}'", literal)))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let start = self.pos;
+                while self.chars.get(self.pos).is_some_and(|x| x.is_alphanumeric() || *x == '_' || *x == '.') {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                if self.peek() != Some('(') {
+                    return Ok(Expression::Variable(name.to_lowercase()));
+                }
+                self.pos += 1;
+                let a = Box::new(self.sum()?);
+                self.expect(',')?;
+                let b = Box::new(self.sum()?);
+                self.expect(')')?;
+                match name.to_lowercase().as_str() {
+                    "min" => Ok(Expression::Min(a, b)),
+                    "max" => Ok(Expression::Max(a, b)),
+                    _ => Err(ExpressionError::Syntax(start, format!("unknown function '{}'", name))),
+                }
+            }
+            _ => Err(self.error("number, variable or '('")),
+        }
+    }
+}
+
+/// Variables that expressions can refer to by name (case insensitive),
+/// the host keeps writing the current values into the registered atomics
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    values: HashMap<String, Arc<AtomicI64>>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Variables::default()
+    }
+
+    pub fn register(&mut self, name: &str, value: Arc<AtomicI64>) {
+        self.values.insert(name.trim().to_lowercase(), value);
+    }
+
+    /// Parses 'source' and binds it to the registered variables
+    pub fn bind(&self, source: &str) -> Result<BoundExpression, ExpressionError> {
+        let expression = Expression::parse(source)?;
+        let mut values = HashMap::new();
+        for name in expression.variables() {
+            let value = self
+                .values
+                .get(name)
+                .ok_or_else(|| ExpressionError::UnknownVariable(name.to_owned()))?;
+            values.insert(name.to_owned(), value.clone());
+        }
+        Ok(BoundExpression {
+            source: source.to_owned(),
+            expression,
+            values,
+        })
+    }
+}
+
+/// An expression that is ready to be sampled by the player
+#[derive(Debug)]
+pub struct BoundExpression {
+    pub source: String,
+    expression: Expression,
+    values: HashMap<String, Arc<AtomicI64>>,
+}
+
+impl BoundExpression {
+    /// Current value of the expression as a speed in 0-100
+    pub fn sample(&self) -> i64 {
+        let value = self.expression.eval(&|name| {
+            self.values
+                .get(name)
+                .map(|x| x.load(Ordering::Relaxed) as f64)
+                .unwrap_or_default()
+        });
+        if value.is_nan() {
+            return 0;
+        }
+        value.round().clamp(0.0, 100.0) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_with_precedence() {
+        let expression = Expression::parse("0.5 * speed + 0.3 * (arousal - -10) / 2").unwrap();
+        assert_eq!(expression.variables(), vec!["speed", "arousal"]);
+        let value = expression.eval(&|name| if name == "speed" { 100.0 } else { 50.0 });
+        assert_eq!(value, 59.0);
+
+        assert_eq!(Expression::parse("max(min(a, 10), 2)").unwrap().eval(&|_| 50.0), 10.0);
+        assert!(matches!(Expression::parse("1 +"), Err(ExpressionError::Syntax(3, _))));
+        assert!(matches!(Expression::parse("foo(1, 2)"), Err(ExpressionError::Syntax(0, _))));
+    }
+
+    #[test]
+    fn bound_expression_samples_current_values() {
+        let speed = Arc::new(AtomicI64::new(80));
+        let mut variables = Variables::new();
+        variables.register("Speed", speed.clone());
+
+        let expression = variables.bind("0.5 * speed + 60").unwrap();
+        assert_eq!(expression.sample(), 100);
+        speed.store(20, Ordering::Relaxed);
+        assert_eq!(expression.sample(), 70);
+
+        assert_eq!(
+            variables.bind("arousal").unwrap_err(),
+            ExpressionError::UnknownVariable("arousal".into())
+        );
+    }
+}
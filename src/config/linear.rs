@@ -1,10 +1,15 @@
+use std::f64::consts::PI;
+
 use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use buttplug::core::message::ActuatorType;
 
 use crate::speed::Speed;
 
-use super::ActuatorLimits;
+use super::{actuators::ActuatorConfig, read::read_config_dir, scalar::ScalarRange, ActuatorLimits};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum LinearSpeedScaling {
     Linear,         // f(x) = x
     Parabolic(i32), // f(x) = 1 - (1 - x)^n
@@ -23,6 +28,153 @@ impl LinearSpeedScaling {
     }
 }
 
+/// Defaults for the actuators of devices whose name contains 'device_name', applied
+/// when their config is created. Profile files are json lists of them
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScalingProfile {
+    pub device_name: String,
+    /// speed scaling of position actuators, replaces the one of `limits`
+    #[serde(default)]
+    pub scaling: Option<LinearSpeedScaling>,
+    /// only applied to actuators of the same kind, scalar limits to scalar actuators
+    /// and linear limits to position actuators
+    #[serde(default)]
+    pub limits: ActuatorLimits,
+    /// maximum scalar commands per second, see `ActuatorConfig::max_commands_per_sec`
+    #[serde(default)]
+    pub max_commands_per_sec: Option<u32>,
+}
+
+impl ScalingProfile {
+    pub fn new(device_name: &str, scaling: LinearSpeedScaling) -> Self {
+        ScalingProfile {
+            device_name: device_name.to_owned(),
+            scaling: Some(scaling),
+            limits: ActuatorLimits::None,
+            max_commands_per_sec: None,
+        }
+    }
+
+    fn matches(&self, device_name: &str) -> bool {
+        device_name.to_lowercase().contains(&self.device_name.to_lowercase())
+    }
+
+    /// Pre-fills the new 'config' of an actuator of type 'actuator'
+    pub fn apply(&self, actuator: ActuatorType, config: &mut ActuatorConfig) {
+        debug!(profile = self.device_name, actuator_id = config.actuator_config_id, "applying profile");
+        match (&self.limits, actuator) {
+            (ActuatorLimits::Linear(range), ActuatorType::Position) => config.limits = ActuatorLimits::Linear(range.clone()),
+            (ActuatorLimits::Scalar(range), actuator) if actuator != ActuatorType::Position => {
+                config.limits = ActuatorLimits::Scalar(range.clone())
+            }
+            _ => {}
+        }
+        if let (Some(scaling), ActuatorType::Position) = (&self.scaling, actuator) {
+            let range = match &config.limits {
+                ActuatorLimits::Linear(range) => range.clone(),
+                _ => LinearRange::max(),
+            };
+            config.limits = ActuatorLimits::Linear(LinearRange {
+                scaling: scaling.clone(),
+                ..range
+            });
+        }
+        if self.max_commands_per_sec.is_some() {
+            config.max_commands_per_sec = self.max_commands_per_sec;
+        }
+    }
+}
+
+/// Reads all profile files in 'profile_dir'
+pub fn read_scaling_profiles(profile_dir: &str) -> Vec<ScalingProfile> {
+    read_config_dir(profile_dir.to_owned())
+}
+
+/// Known strokers, slow ones get a parabolic curve so that low speeds stay noticeable
+pub fn builtin_scaling_profiles() -> Vec<ScalingProfile> {
+    vec![
+        ScalingProfile::new("keon", LinearSpeedScaling::Parabolic(2)),
+        ScalingProfile::new("onyx", LinearSpeedScaling::Parabolic(2)),
+        ScalingProfile::new("solace", LinearSpeedScaling::Parabolic(2)),
+        ScalingProfile::new("handy", LinearSpeedScaling::Linear),
+        ScalingProfile::new("osr", LinearSpeedScaling::Linear),
+        ScalingProfile::new("sr6", LinearSpeedScaling::Linear),
+    ]
+}
+
+/// Scaling of the first profile in 'profiles' or the builtin ones that matches 'device_name'
+pub fn scaling_for_device(profiles: &[ScalingProfile], device_name: &str) -> Option<LinearSpeedScaling> {
+    profiles
+        .iter()
+        .cloned()
+        .chain(builtin_scaling_profiles())
+        .filter(|x| x.matches(device_name))
+        .find_map(|x| x.scaling)
+}
+
+/// Applies the profiles in 'profiles' and the builtin ones that match 'device_name' to the
+/// new 'config', earlier profiles take precedence
+pub fn apply_profiles(profiles: &[ScalingProfile], device_name: &str, actuator: ActuatorType, config: &mut ActuatorConfig) {
+    let matching = profiles
+        .iter()
+        .cloned()
+        .chain(builtin_scaling_profiles())
+        .filter(|x| x.matches(device_name))
+        .collect::<Vec<_>>();
+    for profile in matching.iter().rev() {
+        profile.apply(actuator, config);
+    }
+}
+
+/// Speed curve within a single stroke
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum StrokeProfile {
+    /// one move with constant speed
+    #[default]
+    Constant,
+    /// accelerates and decelerates within the stroke, approximated by 3 or 4 moves
+    EaseInOut(u8),
+}
+
+impl StrokeProfile {
+    /// Splits a stroke from 'from' to 'to' into moves of (position, duration_ms)
+    pub fn segments(&self, from: f64, to: f64, duration_ms: u32) -> Vec<(f64, u32)> {
+        match self {
+            StrokeProfile::Constant => vec![(to, duration_ms)],
+            StrokeProfile::EaseInOut(moves) => {
+                let n = (*moves).clamp(3, 4) as u32;
+                (1..=n)
+                    .map(|k| {
+                        let eased = (1.0 - (PI * k as f64 / n as f64).cos()) / 2.0;
+                        let ms = duration_ms * k / n - duration_ms * (k - 1) / n;
+                        let pos = from + (to - from) * eased;
+                        ((pos * 10_000.0).round() / 10_000.0, ms)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// How tasks that move the same linear actuator at the same time share it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum LinearSharing {
+    /// the task that started last controls the actuator until it ends
+    #[default]
+    Preempt,
+    /// the tasks take turns in the order they started
+    TimeSlice(TimeSlice),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TimeSlice {
+    /// time a task controls the actuator before the next one takes over
+    pub slice_ms: u32,
+    /// minimum duration of the first move after a handover, so the
+    /// actuator glides into the rhythm of the next task
+    pub handover_ms: u32,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LinearRange {
     pub min_ms: i64,
@@ -31,6 +183,8 @@ pub struct LinearRange {
     pub max_pos: f64,
     pub invert: bool,
     pub scaling: LinearSpeedScaling,
+    #[serde(default)]
+    pub profile: StrokeProfile,
 }
 
 impl LinearRange {
@@ -42,6 +196,7 @@ impl LinearRange {
             max_pos: 1.0,
             invert: false,
             scaling: LinearSpeedScaling::Linear,
+            profile: StrokeProfile::Constant,
         }
     }
 }
@@ -55,6 +210,7 @@ impl Default for LinearRange {
             max_pos: 1.0,
             invert: false,
             scaling: LinearSpeedScaling::Linear,
+            profile: StrokeProfile::Constant,
         }
     }
 }
@@ -66,4 +222,95 @@ impl ActuatorLimits {
         }
         LinearRange::max()
     }
+
+    /// Limits that cap the speed at 'max_speed' percent, missing limits are
+    /// replaced with the ones that apply to the actuator without config
+    pub fn capped(&self, actuator: ActuatorType, max_speed: i64) -> ActuatorLimits {
+        let limits = match (self, actuator) {
+            (ActuatorLimits::None, ActuatorType::Position) => ActuatorLimits::Linear(LinearRange::max()),
+            (ActuatorLimits::None, ActuatorType::Unknown) => ActuatorLimits::None,
+            (ActuatorLimits::None, _) => ActuatorLimits::Scalar(ScalarRange::default()),
+            (limits, _) => limits.clone(),
+        };
+        let max_speed = max_speed.clamp(0, 100);
+        match limits {
+            ActuatorLimits::Scalar(range) => ActuatorLimits::Scalar(ScalarRange {
+                min_speed: range.min_speed.min(max_speed),
+                max_speed: range.max_speed.min(max_speed),
+                ..range
+            }),
+            ActuatorLimits::Linear(range) => {
+                // the fastest stroke at 'max_speed' of the range
+                let min_ms = range.max_ms - (range.max_ms - range.min_ms) * max_speed / 100;
+                ActuatorLimits::Linear(LinearRange {
+                    min_ms: range.min_ms.max(min_ms),
+                    ..range
+                })
+            }
+            ActuatorLimits::None => ActuatorLimits::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ease_in_out_is_fastest_in_the_middle() {
+        assert_eq!(StrokeProfile::Constant.segments(0.0, 1.0, 300), vec![(1.0, 300)]);
+
+        let segments = StrokeProfile::EaseInOut(3).segments(1.0, 0.0, 300);
+        assert_eq!(segments.iter().map(|x| x.1).collect::<Vec<_>>(), vec![100, 100, 100]);
+        assert_eq!(segments.iter().map(|x| x.0).collect::<Vec<_>>(), vec![0.75, 0.25, 0.0]);
+
+        let segments = StrokeProfile::EaseInOut(4).segments(0.0, 1.0, 250);
+        assert_eq!(segments.iter().map(|x| x.1).sum::<u32>(), 250);
+        assert!(segments[1].0 - segments[0].0 > segments[0].0);
+    }
+
+    #[test]
+    fn capped_limits_halve_the_speed_range() {
+        let scalar = ActuatorLimits::None.capped(ActuatorType::Vibrate, 50);
+        assert!(matches!(scalar, ActuatorLimits::Scalar(range) if range.max_speed == 50));
+
+        let linear = ActuatorLimits::Linear(LinearRange { min_ms: 100, max_ms: 1100, ..LinearRange::max() });
+        assert!(matches!(linear.capped(ActuatorType::Position, 50), ActuatorLimits::Linear(range) if range.min_ms == 600));
+        assert!(matches!(ActuatorLimits::None.capped(ActuatorType::Unknown, 50), ActuatorLimits::None));
+    }
+
+    #[test]
+    fn configured_profiles_take_precedence_over_builtin_ones() {
+        let profiles = vec![ScalingProfile::new("KEON", LinearSpeedScaling::Parabolic(3))];
+        assert_eq!(scaling_for_device(&profiles, "Kiiroo Keon"), Some(LinearSpeedScaling::Parabolic(3)));
+        assert_eq!(scaling_for_device(&[], "Kiiroo Keon"), Some(LinearSpeedScaling::Parabolic(2)));
+        assert_eq!(scaling_for_device(&[], "The Handy"), Some(LinearSpeedScaling::Linear));
+        assert_eq!(scaling_for_device(&[], "Unknown Stroker"), None);
+    }
+
+    #[test]
+    fn profiles_prefill_matching_actuators() {
+        let profiles: Vec<ScalingProfile> = serde_json::from_str(
+            r#"[
+                {"device_name": "Edge", "limits": {"Scalar": {"min_speed": 10, "max_speed": 80, "factor": 1.0, "scaling": "Linear"}}, "max_commands_per_sec": 10},
+                {"device_name": "Keon", "max_commands_per_sec": 5}
+            ]"#,
+        )
+        .unwrap();
+
+        let mut vibrator = ActuatorConfig::from_identifier("vib1");
+        apply_profiles(&profiles, "Lovense EDGE", ActuatorType::Vibrate, &mut vibrator);
+        assert!(matches!(vibrator.limits, ActuatorLimits::Scalar(ScalarRange { max_speed: 80, .. })));
+        assert_eq!(vibrator.max_commands_per_sec, Some(10));
+
+        let mut stroker = ActuatorConfig::from_identifier("keon");
+        apply_profiles(&profiles, "Kiiroo Keon", ActuatorType::Position, &mut stroker);
+        assert!(matches!(stroker.limits, ActuatorLimits::Linear(range) if range.scaling == LinearSpeedScaling::Parabolic(2)));
+        assert_eq!(stroker.max_commands_per_sec, Some(5));
+        assert_eq!(scaling_for_device(&profiles, "Kiiroo Keon"), Some(LinearSpeedScaling::Parabolic(2)));
+
+        let mut unknown = ActuatorConfig::from_identifier("max");
+        apply_profiles(&profiles, "Lovense Max", ActuatorType::Vibrate, &mut unknown);
+        assert!(matches!(unknown.limits, ActuatorLimits::None));
+    }
 }
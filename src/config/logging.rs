@@ -1,3 +1,9 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    sync::Mutex,
+};
+
 use serde::{Deserialize, Serialize};
 use tracing::Level;
 
@@ -21,3 +27,92 @@ impl From<LogLevel> for Level {
         }
     }
 }
+
+/// How device identifiers appear in tracing output and client events
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Redaction {
+    /// identifiers are logged as they are
+    #[default]
+    None,
+    /// identifiers are replaced by a salted hash like `dev-3f2a91c0`,
+    /// the salt changes with every session
+    Hash,
+    /// identifiers are replaced by `actuator-1`, `actuator-2`, ...
+    /// in the order they are first logged
+    Alias,
+}
+
+/// Replaces identifiers consistently for the lifetime of the redactor,
+/// so log lines of the same device can still be correlated
+#[derive(Debug, Default)]
+pub struct Redactor {
+    pub mode: Redaction,
+    salt: RandomState,
+    aliases: HashMap<String, String>,
+}
+
+impl Redactor {
+    pub fn new(mode: Redaction) -> Self {
+        Redactor {
+            mode,
+            salt: RandomState::new(),
+            aliases: HashMap::new(),
+        }
+    }
+
+    pub fn redact(&mut self, identifier: &str) -> String {
+        match self.mode {
+            Redaction::None => identifier.to_owned(),
+            Redaction::Hash => format!("dev-{:08x}", self.salt.hash_one(identifier) as u32),
+            Redaction::Alias => {
+                let next = self.aliases.len() + 1;
+                self.aliases
+                    .entry(identifier.to_owned())
+                    .or_insert_with(|| format!("actuator-{}", next))
+                    .clone()
+            }
+        }
+    }
+}
+
+static REDACTOR: Mutex<Option<Redactor>> = Mutex::new(None);
+
+/// Sets the redaction used for all identifiers logged by this process,
+/// changing the mode starts a new session with fresh hashes and aliases
+pub fn set_redaction(mode: Redaction) {
+    let mut redactor = REDACTOR.lock().unwrap_or_else(|x| x.into_inner());
+    if redactor.as_ref().map(|x| x.mode) != Some(mode) {
+        *redactor = Some(Redactor::new(mode));
+    }
+}
+
+/// Device identifier as it should appear in logs and events
+pub fn redact(identifier: &str) -> String {
+    let mut redactor = REDACTOR.lock().unwrap_or_else(|x| x.into_inner());
+    match redactor.as_mut() {
+        Some(redactor) => redactor.redact(identifier),
+        None => identifier.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted_identifiers_stay_correlatable() {
+        let mut redactor = Redactor::new(Redaction::Alias);
+        assert_eq!(redactor.redact("vib1 (Vibrate)"), "actuator-1");
+        assert_eq!(redactor.redact("lin1 (Position)"), "actuator-2");
+        assert_eq!(redactor.redact("vib1 (Vibrate)"), "actuator-1");
+
+        let mut redactor = Redactor::new(Redaction::Hash);
+        let hashed = redactor.redact("vib1 (Vibrate)");
+        assert!(hashed.starts_with("dev-") && !hashed.contains("vib1"));
+        assert_eq!(redactor.redact("vib1 (Vibrate)"), hashed);
+        assert_ne!(redactor.redact("lin1 (Position)"), hashed);
+
+        let mut redactor = Redactor::new(Redaction::None);
+        assert_eq!(redactor.redact("vib1 (Vibrate)"), "vib1 (Vibrate)");
+    }
+}
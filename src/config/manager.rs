@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{error, info};
+
+use super::{
+    actuators::ActuatorSettings,
+    client::{ClientSettings, SettingsPersistence},
+    read::StoredActuatorConfig,
+    store::SharedConfigStore,
+};
+
+/// Version of the documents that `SettingsManager` writes
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// Debounce time of stores after changes if the client settings don't configure one
+pub const SETTINGS_DEBOUNCE_MS: u64 = 1000;
+
+/// Client and actuator settings stored in a single file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SettingsDocument {
+    /// format of the document, 0 for the legacy formats
+    pub version: u32,
+    pub client: ClientSettings,
+    pub devices: ActuatorSettings,
+}
+
+impl Default for SettingsDocument {
+    fn default() -> Self {
+        SettingsDocument {
+            version: SETTINGS_VERSION,
+            client: ClientSettings::default(),
+            devices: ActuatorSettings::default(),
+        }
+    }
+}
+
+impl SettingsDocument {
+    /// Reads documents of all versions, the version is kept. Documents without version
+    /// are the legacy formats: either a bare list of actuator configs, or the client settings with
+    /// the actuator configs in `devices`. Actuator configs are migrated like `read::BpDeviceSettings`,
+    /// entries that cannot be read are skipped, other parts that cannot be read use their defaults
+    pub fn migrate(json: &str) -> Result<SettingsDocument, serde_json::Error> {
+        let value = serde_json::from_str::<Value>(json)?;
+        match value.get("version").and_then(|x| x.as_u64()) {
+            Some(_) => serde_json::from_value(value),
+            None => Ok(Self::from_legacy(value)),
+        }
+    }
+
+    fn from_legacy(value: Value) -> SettingsDocument {
+        info!("migrating legacy settings");
+        let (client, devices) = match value {
+            Value::Array(_) => (None, Some(value)),
+            Value::Object(mut fields) => {
+                let devices = fields.remove("devices");
+                (Some(Value::Object(fields)), devices)
+            }
+            _ => (None, None),
+        };
+        SettingsDocument {
+            version: 0,
+            client: client.map(parse_or_default).unwrap_or_default(),
+            devices: devices.map(parse_devices).unwrap_or_default(),
+        }
+    }
+}
+
+fn differs<T: Serialize>(current: &T, new: &T) -> bool {
+    serde_json::to_value(current).ok() != serde_json::to_value(new).ok()
+}
+
+fn parse_or_default<T>(value: Value) -> T
+where
+    T: for<'de> Deserialize<'de> + Default,
+{
+    serde_json::from_value(value).unwrap_or_else(|err| {
+        error!(?err, "legacy settings could not be migrated, using defaults");
+        T::default()
+    })
+}
+
+fn parse_devices(value: Value) -> ActuatorSettings {
+    let entries = match value {
+        Value::Array(entries) => entries,
+        _ => return parse_or_default(value),
+    };
+    let stored = entries
+        .into_iter()
+        .filter_map(|entry| {
+            serde_json::from_value::<StoredActuatorConfig>(entry)
+                .map_err(|err| error!(?err, "legacy actuator config could not be migrated, skipping it"))
+                .ok()
+        })
+        .collect::<Vec<_>>();
+    stored.into()
+}
+
+/// Loads the client and actuator settings at startup and stores them again after they
+/// changed, e.g. by `BpClient::connect_managed`. Changes that were not stored yet are
+/// stored when the manager is dropped
+#[derive(Debug)]
+pub struct SettingsManager {
+    store: SharedConfigStore,
+    dir: String,
+    file: String,
+    document: SettingsDocument,
+    dirty: bool,
+}
+
+impl SettingsManager {
+    /// Reads 'file' in 'dir', migrating older formats. Uses the defaults if it
+    /// does not exist or is invalid
+    pub fn load(store: SharedConfigStore, dir: &str, file: &str) -> Self {
+        let mut document = match store.0.read(dir, file) {
+            Ok(json) => SettingsDocument::migrate(&json).unwrap_or_else(|err| {
+                error!("File '{}/{}' could not be parsed. Error: {}. Using default configuration.", dir, file, err);
+                SettingsDocument::default()
+            }),
+            Err(err) => {
+                info!("File '{}/{}' could not be opened. Error: {}. Using default configuration.", dir, file, err);
+                SettingsDocument::default()
+            }
+        };
+        // migrated documents are stored in the current format
+        let dirty = document.version != SETTINGS_VERSION;
+        document.version = SETTINGS_VERSION;
+        document.client.config_store = store.clone();
+        SettingsManager {
+            store,
+            dir: dir.to_owned(),
+            file: file.to_owned(),
+            document,
+            dirty,
+        }
+    }
+
+    pub fn client(&self) -> &ClientSettings {
+        &self.document.client
+    }
+
+    pub fn devices(&self) -> &ActuatorSettings {
+        &self.document.devices
+    }
+
+    /// Replaces the client settings, they are dirty if they differ
+    pub fn set_client(&mut self, client: ClientSettings) {
+        self.dirty |= differs(&self.document.client, &client);
+        self.document.client = client;
+    }
+
+    /// Replaces the actuator settings, they are dirty if they differ
+    pub fn set_devices(&mut self, devices: ActuatorSettings) {
+        self.dirty |= differs(&self.document.devices, &devices);
+        self.document.devices = devices;
+    }
+
+    pub fn document(&self) -> &SettingsDocument {
+        &self.document
+    }
+
+    /// Where and how debounced the document is stored after changes
+    pub fn persistence(&self) -> SettingsPersistence {
+        SettingsPersistence {
+            settings_path: self.dir.clone(),
+            settings_file: self.file.clone(),
+            debounce_ms: self
+                .document
+                .client
+                .settings_persistence
+                .as_ref()
+                .map(|x| x.debounce_ms)
+                .unwrap_or(SETTINGS_DEBOUNCE_MS),
+        }
+    }
+
+    /// Whether there are changes that were not stored yet
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Stores the settings if they changed, returns false if storing failed
+    pub fn save(&mut self) -> bool {
+        if !self.dirty {
+            return true;
+        }
+        self.dirty = !self.store.try_write(&self.document, &self.dir, &self.file);
+        !self.dirty
+    }
+}
+
+impl Drop for SettingsManager {
+    fn drop(&mut self) {
+        self.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{actuators::ActuatorConfig, config::store::MemoryStore};
+
+    use super::*;
+
+    #[test]
+    fn legacy_documents_are_migrated() {
+        let devices = r#"[{"actuator_config_id": "vib1", "enabled": true, "body_parts": ["anal"]}]"#;
+        let document = SettingsDocument::migrate(devices).unwrap();
+        assert_eq!(document.version, 0);
+        assert!(document.devices.get_enabled("vib1"));
+
+        let combined = format!(r#"{{"metronome_pulse_ms": 250, "devices": {}}}"#, devices);
+        let document = SettingsDocument::migrate(&combined).unwrap();
+        assert!(document.devices.get_enabled("vib1"));
+
+        let legacy = r#"{"devices": [
+            {"actuator_id": "vib2 (Vibrate)", "enabled": true, "events": ["Nipple"]},
+            {"actuator_id": 5},
+            {"actuator_config_id": "vib3 (Vibrate)", "enabled": true, "body_parts": []}
+        ]}"#;
+        let document = SettingsDocument::migrate(legacy).unwrap();
+        assert_eq!(document.devices.0.len(), 2);
+        assert_eq!(document.devices.get_events("vib2 (Vibrate)"), vec!["nipple"]);
+        assert!(document.devices.get_enabled("vib3 (Vibrate)"));
+
+        let store = SharedConfigStore::new(MemoryStore::default());
+        store.0.write("save", "settings.json", devices).unwrap();
+        let manager = SettingsManager::load(store, "save", "settings.json");
+        assert!(manager.is_dirty());
+    }
+
+    #[test]
+    fn changes_are_saved_once() {
+        let store = SharedConfigStore::new(MemoryStore::default());
+        let mut manager = SettingsManager::load(store.clone(), "save", "settings.json");
+        assert!(!manager.is_dirty());
+
+        let mut devices = ActuatorSettings::default();
+        devices.update_device(ActuatorConfig::from_identifier("vib1"));
+        manager.set_devices(devices.clone());
+        assert!(manager.is_dirty());
+        assert!(manager.save());
+        manager.set_devices(devices);
+        assert!(!manager.is_dirty());
+        drop(manager);
+
+        let manager = SettingsManager::load(store, "save", "settings.json");
+        assert!(!manager.is_dirty());
+        assert_eq!(manager.devices().0[0].actuator_config_id, "vib1");
+    }
+}
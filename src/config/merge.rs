@@ -0,0 +1,148 @@
+use tracing::debug;
+
+use super::{actuators::{ActuatorConfig, ActuatorSettings}, ActuatorLimits};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// the config that was changed last replaces the other one entirely
+    NewestWins,
+    /// the newer config wins for every field both configs define, fields that
+    /// are only defined in one of them (limits, namespace, role, body parts) are kept
+    FieldLevel,
+}
+
+impl ActuatorConfig {
+    /// Whether both configs belong to the same actuator, by id or alias
+    fn is_same_actuator(&self, other: &ActuatorConfig) -> bool {
+        self.actuator_config_id == other.actuator_config_id
+            || self.aliases.contains(&other.actuator_config_id)
+            || other.aliases.contains(&self.actuator_config_id)
+    }
+
+    fn merge(self, other: ActuatorConfig, strategy: MergeStrategy) -> ActuatorConfig {
+        let (newer, older) = if other.updated_ms > self.updated_ms {
+            (other, self)
+        } else {
+            (self, other)
+        };
+        let mut aliases = newer.aliases.clone();
+        for id in older.aliases.iter().chain([&older.actuator_config_id]) {
+            if *id != newer.actuator_config_id && !aliases.contains(id) {
+                aliases.push(id.clone());
+            }
+        }
+        let merged = match strategy {
+            MergeStrategy::NewestWins => newer,
+            MergeStrategy::FieldLevel => ActuatorConfig {
+                body_parts: if newer.body_parts.is_empty() { older.body_parts } else { newer.body_parts },
+                limits: match newer.limits {
+                    ActuatorLimits::None => older.limits,
+                    limits => limits,
+                },
+                namespace: newer.namespace.or(older.namespace),
+                role: newer.role.or(older.role),
+                init_sequence: if newer.init_sequence.is_empty() { older.init_sequence } else { newer.init_sequence },
+                ..newer
+            },
+        };
+        ActuatorConfig { aliases, ..merged }
+    }
+}
+
+impl ActuatorSettings {
+    /// Merges the configs of 'other' into this document, configs of the same
+    /// actuator are matched by id or alias and combined according to 'strategy'
+    pub fn merge(&mut self, other: ActuatorSettings, strategy: MergeStrategy) {
+        for config in other.0 {
+            let existing = self.0.iter().position(|x| x.is_same_actuator(&config));
+            match existing {
+                Some(pos) => {
+                    let current = self.0.remove(pos);
+                    debug!(current.actuator_config_id, config.actuator_config_id, ?strategy, "merging");
+                    self.0.insert(pos, current.merge(config, strategy));
+                }
+                None => self.0.push(config),
+            }
+        }
+        // a merged alias can make two existing configs refer to the same actuator
+        let mut i = 0;
+        while i < self.0.len() {
+            if let Some(j) = (i + 1..self.0.len()).find(|j| self.0[i].is_same_actuator(&self.0[*j])) {
+                let duplicate = self.0.remove(j);
+                let current = self.0.remove(i);
+                self.0.insert(i, current.merge(duplicate, strategy));
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{linear::LinearRange, scalar::ScalarRange};
+
+    use super::*;
+
+    fn config(id: &str, updated_ms: u64) -> ActuatorConfig {
+        ActuatorConfig {
+            updated_ms,
+            ..ActuatorConfig::from_identifier(id)
+        }
+    }
+
+    #[test]
+    fn newest_config_wins_for_duplicates() {
+        let mut ours = ActuatorSettings(vec![ActuatorConfig { enabled: true, ..config("a", 10) }, config("b", 10)]);
+        let theirs = ActuatorSettings(vec![
+            ActuatorConfig { body_parts: vec!["anal".into()], ..config("a", 20) },
+            config("b", 5),
+            config("c", 1),
+        ]);
+
+        ours.merge(theirs, MergeStrategy::NewestWins);
+
+        assert_eq!(ours.0.len(), 3);
+        let a = ours.get_config("a").unwrap();
+        assert!(!a.enabled);
+        assert_eq!(a.body_parts, vec!["anal"]);
+        assert_eq!(ours.get_config("b").unwrap().updated_ms, 10);
+    }
+
+    #[test]
+    fn renamed_actuators_are_matched_by_alias() {
+        let mut ours = ActuatorSettings(vec![
+            ActuatorConfig { role: Some("left".into()), ..config("old (Vibrate)", 10) },
+            config("new (Vibrate)", 5),
+        ]);
+        let theirs = ActuatorSettings(vec![ActuatorConfig {
+            aliases: vec!["old (Vibrate)".into()],
+            ..config("new (Vibrate)", 20)
+        }]);
+
+        ours.merge(theirs, MergeStrategy::FieldLevel);
+
+        assert_eq!(ours.0.len(), 1);
+        let merged = &ours.0[0];
+        assert_eq!(merged.actuator_config_id, "new (Vibrate)");
+        assert_eq!(merged.aliases, vec!["old (Vibrate)"]);
+        assert_eq!(merged.role, Some("left".into()));
+    }
+
+    #[test]
+    fn field_level_merge_keeps_limits_and_prefers_newer_limit_type() {
+        let mut ours = ActuatorSettings(vec![
+            ActuatorConfig { limits: ActuatorLimits::Scalar(ScalarRange::default()), ..config("a", 10) },
+            ActuatorConfig { limits: ActuatorLimits::Scalar(ScalarRange::default()), ..config("b", 10) },
+        ]);
+        let theirs = ActuatorSettings(vec![
+            config("a", 20),
+            ActuatorConfig { limits: ActuatorLimits::Linear(LinearRange::default()), ..config("b", 20) },
+        ]);
+
+        ours.merge(theirs, MergeStrategy::FieldLevel);
+
+        assert!(matches!(ours.get_config("a").unwrap().limits, ActuatorLimits::Scalar(_)));
+        assert!(matches!(ours.get_config("b").unwrap().limits, ActuatorLimits::Linear(_)));
+    }
+}
@@ -5,11 +5,16 @@ use serde::{Deserialize, Serialize};
 pub mod actions;
 pub mod actuators;
 pub mod connection;
+pub mod expression;
 pub mod client;
 pub mod linear;
 pub mod logging;
+pub mod manager;
+pub mod merge;
 pub mod read;
+pub mod registry;
 pub mod scalar;
+pub mod store;
 pub mod write;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -1,8 +1,17 @@
-use std::{fs, path::PathBuf};
+use std::fs;
 
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
 use tracing::{error, info};
 
+use crate::util::trim_lower_str_list;
+
+use super::{
+    actuators::{ActuatorConfig, ActuatorSettings},
+    store::SharedConfigStore,
+    ActuatorLimits,
+};
+
 pub fn read_config_dir<T>(config_dir: String) -> Vec<T>
 where
     T: DeserializeOwned,
@@ -36,26 +45,89 @@ where
     results
 }
 
+/// Reads 'settings_file' from the file system, see `SharedConfigStore` for other storage
 pub fn read_or_default<T>(settings_dir: &str, settings_file: &str) -> T 
 where
     T: DeserializeOwned,
     T: Clone,
     T: Default
 {
-    let path: PathBuf = [settings_dir, settings_file].iter().collect::<PathBuf>();
-    match fs::read_to_string(path) {
-        Ok(settings_json) => match serde_json::from_str::<T>(&settings_json) {
-            Ok(settings) => {
-                settings
-            }
-            Err(err) => {
-                error!("File '{}/{}' could not be parsed. Error: {}. Using default configuration.", settings_dir, settings_file, err);
-                T::default()
-            }
-        },
-        Err(err) => {
-            info!("File '{}/{}' could not be opened. Error: {}. Using default configuration.", settings_dir, settings_file, err);
-            T::default()
+    SharedConfigStore::default().read_or_default(settings_dir, settings_file)
+}
+
+/// Actuator config of the former `settings` module
+#[derive(Deserialize, Debug, Clone)]
+pub struct BpDeviceSettings {
+    pub actuator_id: String,
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default)]
+    pub actuator_settings: Value,
+}
+
+impl From<BpDeviceSettings> for ActuatorConfig {
+    fn from(legacy: BpDeviceSettings) -> Self {
+        info!(actuator_id = legacy.actuator_id, "migrating legacy actuator config");
+        let limits = match legacy.actuator_settings {
+            Value::Null => ActuatorLimits::None,
+            settings => serde_json::from_value(settings).unwrap_or_else(|err| {
+                error!(?err, "legacy actuator limits could not be migrated, using defaults");
+                ActuatorLimits::None
+            }),
+        };
+        ActuatorConfig {
+            enabled: legacy.enabled,
+            body_parts: trim_lower_str_list(&legacy.events.iter().map(|x| x.as_str()).collect::<Vec<_>>()),
+            limits,
+            ..ActuatorConfig::from_identifier(&legacy.actuator_id)
         }
     }
-}
\ No newline at end of file
+}
+
+/// Entry of stored `ActuatorSettings`, either in the current or the legacy format
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum StoredActuatorConfig {
+    Current(ActuatorConfig),
+    Legacy(BpDeviceSettings),
+}
+
+impl From<Vec<StoredActuatorConfig>> for ActuatorSettings {
+    fn from(stored: Vec<StoredActuatorConfig>) -> Self {
+        ActuatorSettings(
+            stored
+                .into_iter()
+                .map(|x| match x {
+                    StoredActuatorConfig::Current(config) => config,
+                    StoredActuatorConfig::Legacy(legacy) => legacy.into(),
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_device_settings_are_migrated() {
+        let json = r#"[
+            {"actuator_id": "vib1 (Vibrate)", "enabled": true, "events": [" Nipple"],
+             "actuator_settings": {"Scalar": {"min_speed": 10, "max_speed": 80, "factor": 1.0, "scaling": "Linear"}}},
+            {"actuator_id": "lin1 (Position)", "events": []},
+            {"actuator_config_id": "vib2 (Vibrate)", "enabled": true, "body_parts": ["anal"]}
+        ]"#;
+        let settings = serde_json::from_str::<ActuatorSettings>(json).unwrap();
+
+        let vib1 = settings.find("vib1 (Vibrate)").unwrap();
+        assert!(vib1.enabled);
+        assert_eq!(vib1.body_parts, vec!["nipple"]);
+        assert!(matches!(&vib1.limits, ActuatorLimits::Scalar(range) if range.max_speed == 80));
+        assert!(!settings.get_enabled("lin1 (Position)"));
+        assert!(matches!(settings.find("lin1 (Position)").unwrap().limits, ActuatorLimits::None));
+        assert_eq!(settings.get_events("vib2 (Vibrate)"), vec!["anal"]);
+    }
+}
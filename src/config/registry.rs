@@ -0,0 +1,197 @@
+use std::fmt::{self, Display};
+
+use crate::pattern::get_pattern_names;
+
+use super::actions::{Action, Actions, Control, ScalarActuator, Selector, Strength, StrokeRange};
+
+/// Reference to a funscript pattern that is defined in code,
+/// checked against the pattern directory when the registry is validated
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PatternName {
+    name: String,
+    vibration: bool,
+}
+
+impl PatternName {
+    /// A `<name>.vibrator.funscript` pattern
+    pub fn vibration(name: &str) -> Self {
+        PatternName {
+            name: name.into(),
+            vibration: true,
+        }
+    }
+
+    /// A positional `<name>.funscript` pattern
+    pub fn linear(name: &str) -> Self {
+        PatternName {
+            name: name.into(),
+            vibration: false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_vibration(&self) -> bool {
+        self.vibration
+    }
+
+    pub fn strength(&self, speed: i32) -> Strength {
+        Strength::Funscript(speed, self.name.clone())
+    }
+}
+
+/// Defines an action in code instead of json
+pub struct ActionBuilder {
+    action: Action,
+}
+
+impl ActionBuilder {
+    pub fn new(name: &str) -> Self {
+        ActionBuilder {
+            action: Action::new(name, vec![]),
+        }
+    }
+
+    pub fn scalar(mut self, selector: Selector, actuators: &[ScalarActuator]) -> Self {
+        self.action
+            .control
+            .push(Control::Scalar(selector, actuators.to_vec()));
+        self
+    }
+
+    pub fn stroke(mut self, selector: Selector, range: StrokeRange) -> Self {
+        self.action.control.push(Control::Stroke(selector, range));
+        self
+    }
+
+    pub fn build(self) -> Action {
+        self.action
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegistryError {
+    DuplicateAction(String),
+    NoControls(String),
+    /// scalar control without any actuator type
+    NoActuators(String),
+    PatternNotFound(String),
+}
+
+impl Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::DuplicateAction(name) => write!(f, "action '{}' is defined twice", name),
+            RegistryError::NoControls(name) => write!(f, "action '{}' has no controls", name),
+            RegistryError::NoActuators(name) => write!(f, "action '{}' has a scalar control without actuators", name),
+            RegistryError::PatternNotFound(name) => write!(f, "pattern '{}' not found", name),
+        }
+    }
+}
+
+/// Collects actions and pattern references that are defined in code and
+/// validates them at startup before they are added to the loaded `Actions`
+#[derive(Default)]
+pub struct ActionRegistry {
+    actions: Vec<Action>,
+    patterns: Vec<PatternName>,
+}
+
+impl ActionRegistry {
+    pub fn new() -> Self {
+        ActionRegistry::default()
+    }
+
+    pub fn action(mut self, action: ActionBuilder) -> Self {
+        self.actions.push(action.build());
+        self
+    }
+
+    /// Adds a pattern that is used by the host so that it is validated
+    pub fn pattern(mut self, pattern: &PatternName) -> Self {
+        self.patterns.push(pattern.clone());
+        self
+    }
+
+    pub fn validate(&self, existing: &Actions, pattern_path: &str) -> Vec<RegistryError> {
+        let mut errors = vec![];
+        for (i, action) in self.actions.iter().enumerate() {
+            let defined_before = self.actions[..i].iter().any(|x| x.name.eq_ignore_ascii_case(&action.name));
+            if defined_before || existing.get(&action.name).is_some() {
+                errors.push(RegistryError::DuplicateAction(action.name.clone()));
+            }
+            if action.control.is_empty() {
+                errors.push(RegistryError::NoControls(action.name.clone()));
+            }
+            if action.control.iter().any(|x| matches!(x, Control::Scalar(_, actuators) if actuators.is_empty())) {
+                errors.push(RegistryError::NoActuators(action.name.clone()));
+            }
+        }
+        let vibration_patterns = get_pattern_names(pattern_path, true);
+        let linear_patterns = get_pattern_names(pattern_path, false);
+        for pattern in &self.patterns {
+            let known = if pattern.vibration { &vibration_patterns } else { &linear_patterns };
+            if !known.iter().any(|x| x.eq_ignore_ascii_case(&pattern.name)) {
+                errors.push(RegistryError::PatternNotFound(pattern.name.clone()));
+            }
+        }
+        errors
+    }
+
+    /// Validates all definitions and adds the actions to 'actions', nothing is added on errors
+    pub fn register(self, actions: &mut Actions, pattern_path: &str) -> Result<(), Vec<RegistryError>> {
+        let errors = self.validate(actions, pattern_path);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        actions.0.extend(self.actions);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::client::settings_tests::*;
+
+    use super::*;
+
+    #[test]
+    fn registers_valid_actions() {
+        let (_, pattern_path, _tmp) = create_temp_file("wave.vibrator.funscript", "{}");
+        let mut actions = Actions(vec![]);
+
+        let result = ActionRegistry::new()
+            .action(ActionBuilder::new("vibrate").scalar(Selector::All, &[ScalarActuator::Vibrate]))
+            .pattern(&PatternName::vibration("Wave"))
+            .register(&mut actions, &pattern_path);
+
+        assert!(result.is_ok());
+        assert!(actions.get("vibrate").is_some());
+    }
+
+    #[test]
+    fn rejects_invalid_definitions() {
+        let (_, pattern_path, _tmp) = create_temp_file("wave.vibrator.funscript", "{}");
+        let mut actions = Actions(vec![Action::new("vibrate", vec![])]);
+
+        let result = ActionRegistry::new()
+            .action(ActionBuilder::new("vibrate").scalar(Selector::All, &[ScalarActuator::Vibrate]))
+            .action(ActionBuilder::new("empty"))
+            .action(ActionBuilder::new("nothing").scalar(Selector::All, &[]))
+            .pattern(&PatternName::linear("wave"))
+            .register(&mut actions, &pattern_path);
+
+        assert_eq!(
+            result.unwrap_err(),
+            vec![
+                RegistryError::DuplicateAction("vibrate".into()),
+                RegistryError::NoControls("empty".into()),
+                RegistryError::NoActuators("nothing".into()),
+                RegistryError::PatternNotFound("wave".into()),
+            ]
+        );
+        assert_eq!(actions.0.len(), 1);
+    }
+}
@@ -8,12 +8,62 @@ pub enum ScalarScaling {
     QuadraticFraction, // f(x) = x^(1/2)
 }
 
+/// Smooths changes of the scalar output over time, attack is
+/// used for rising speeds, decay for falling speeds
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum ScalarEasing {
+    #[default]
+    None,
+    Soft,  // attack 250ms, decay 500ms
+    Slow,  // attack 1000ms, decay 1500ms
+    Custom { attack_ms: u32, decay_ms: u32 },
+}
+
+impl ScalarEasing {
+    /// Returns the (attack, decay) times in ms
+    pub fn get_times_ms(&self) -> (u32, u32) {
+        match self {
+            ScalarEasing::None => (0, 0),
+            ScalarEasing::Soft => (250, 500),
+            ScalarEasing::Slow => (1_000, 1_500),
+            ScalarEasing::Custom { attack_ms, decay_ms } => (*attack_ms, *decay_ms),
+        }
+    }
+}
+
+/// How a rotate actuator reacts to positional (stroke) patterns
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum RotatePlayback {
+    /// only plays scalar patterns, not selected for strokes
+    #[default]
+    Scalar,
+    /// translates movement speed into rotation speed, upstrokes rotate
+    /// clockwise and downstrokes counter-clockwise
+    Alternating,
+}
+
+/// How the value 0 of a pattern is played
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PatternZero {
+    /// the actuator stops
+    #[default]
+    Off,
+    /// the actuator keeps running at `min_speed` until the pattern ends
+    MinSpeed,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ScalarRange {
     pub min_speed: i64,
     pub max_speed: i64,
     pub factor: f64,
     pub scaling: ScalarScaling,
+    #[serde(default)]
+    pub easing: ScalarEasing,
+    #[serde(default)]
+    pub rotate_playback: RotatePlayback,
+    #[serde(default)]
+    pub pattern_zero: PatternZero,
 }
 
 impl Default for ScalarRange {
@@ -23,6 +73,9 @@ impl Default for ScalarRange {
             max_speed: 100,
             factor: 1.0,
             scaling: ScalarScaling::Linear,
+            easing: ScalarEasing::None,
+            rotate_playback: RotatePlayback::Scalar,
+            pattern_zero: PatternZero::Off,
         }
     }
 }
@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+    fs, io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{error, info};
+
+/// Persistence of settings and state files, implemented by hosts that keep
+/// their configuration in their own save system instead of the file system
+pub trait ConfigStore: Send + Sync {
+    /// Content of 'file' in 'dir'
+    fn read(&self, dir: &str, file: &str) -> io::Result<String>;
+
+    /// Replaces the content of 'file' in 'dir'
+    fn write(&self, dir: &str, file: &str, content: &str) -> io::Result<()>;
+}
+
+/// Stores each file as a json file in its directory
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileStore;
+
+impl ConfigStore for FileStore {
+    fn read(&self, dir: &str, file: &str) -> io::Result<String> {
+        fs::read_to_string([dir, file].iter().collect::<PathBuf>())
+    }
+
+    fn write(&self, dir: &str, file: &str, content: &str) -> io::Result<()> {
+        let _ = fs::create_dir_all(dir);
+        fs::write([dir, file].iter().collect::<PathBuf>(), content)
+    }
+}
+
+/// Keeps all files in memory, e.g. for hosts that serialize the content themselves
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    files: Arc<Mutex<HashMap<(String, String), String>>>,
+}
+
+impl ConfigStore for MemoryStore {
+    fn read(&self, dir: &str, file: &str) -> io::Result<String> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(&(dir.to_owned(), file.to_owned()))
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file not stored"))
+    }
+
+    fn write(&self, dir: &str, file: &str, content: &str) -> io::Result<()> {
+        self.files
+            .lock()
+            .unwrap()
+            .insert((dir.to_owned(), file.to_owned()), content.to_owned());
+        Ok(())
+    }
+}
+
+/// Shared `ConfigStore` that all settings logic reads from and writes to,
+/// defaults to the file system
+#[derive(Clone)]
+pub struct SharedConfigStore(pub Arc<dyn ConfigStore>);
+
+impl SharedConfigStore {
+    pub fn new<T: ConfigStore + 'static>(store: T) -> Self {
+        SharedConfigStore(Arc::new(store))
+    }
+
+    /// Reads the json 'file' in 'dir', uses the default if it does not exist or is invalid
+    pub fn read_or_default<T>(&self, dir: &str, file: &str) -> T
+    where
+        T: DeserializeOwned + Default,
+    {
+        match self.0.read(dir, file) {
+            Ok(json) => match serde_json::from_str::<T>(&json) {
+                Ok(settings) => settings,
+                Err(err) => {
+                    error!("File '{}/{}' could not be parsed. Error: {}. Using default configuration.", dir, file, err);
+                    T::default()
+                }
+            },
+            Err(err) => {
+                info!("File '{}/{}' could not be opened. Error: {}. Using default configuration.", dir, file, err);
+                T::default()
+            }
+        }
+    }
+
+    /// Stores 'content' as json 'file' in 'dir', returns whether it succeeded
+    pub fn try_write<T>(&self, content: &T, dir: &str, file: &str) -> bool
+    where
+        T: ?Sized + Serialize,
+    {
+        match serde_json::to_string_pretty(content) {
+            Ok(json) => {
+                info!(dir, file, "storing file");
+                if let Err(err) = self.0.write(dir, file, &json) {
+                    error!(?err, dir, file, "errorr writing to path");
+                    return false;
+                }
+                true
+            }
+            Err(err) => {
+                error!(?err, "error deserializing");
+                false
+            }
+        }
+    }
+}
+
+impl Default for SharedConfigStore {
+    fn default() -> Self {
+        SharedConfigStore::new(FileStore)
+    }
+}
+
+impl Debug for SharedConfigStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedConfigStore")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actuators::{ActuatorConfig, ActuatorSettings};
+
+    use super::*;
+
+    #[test]
+    fn settings_round_trip_through_custom_store() {
+        let store = SharedConfigStore::new(MemoryStore::default());
+        let settings = ActuatorSettings(vec![ActuatorConfig::from_identifier("vib1")]);
+
+        assert!(store.try_write(&settings, "save", "devices.json"));
+        let stored = store.read_or_default::<ActuatorSettings>("save", "devices.json");
+        let missing = store.read_or_default::<ActuatorSettings>("save", "other.json");
+
+        assert_eq!(stored.0[0].actuator_config_id, "vib1");
+        assert!(missing.0.is_empty());
+    }
+}
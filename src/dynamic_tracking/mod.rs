@@ -2,7 +2,7 @@ use std::sync::{atomic::{AtomicI64, Ordering}, Arc};
 
 use derive_new::new;
 use serde::{Deserialize, Serialize};
-use tokio::{sync::mpsc::UnboundedReceiver, time::Instant};
+use tokio::{sync::mpsc::{UnboundedReceiver, UnboundedSender}, time::Instant};
 use tokio_util::sync::CancellationToken;
 
 use crate::actuator::Actuator;
@@ -35,6 +35,23 @@ pub struct DynamicSettings {
     pub sampling_rate_ms: u64,
     pub initial_timeout_ms: u64,
     pub stroke_default_ms: u32,
+    /// how the stroke duration is estimated from the recent turns
+    #[serde(default)]
+    pub smoothing: Smoothing,
+    /// ignores stroke intervals that deviate more than this percentage from the running estimate
+    #[serde(default)]
+    pub outlier_rejection_pct: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Smoothing {
+    /// average over the measurement window
+    #[default]
+    Mean,
+    /// median over the measurement window, ignores single delayed signals
+    Median,
+    /// exponential moving average with the given weight (0.0-1.0) of the latest interval
+    Ema(f64),
 }
 
 impl Default for DynamicSettings {
@@ -48,6 +65,8 @@ impl Default for DynamicSettings {
             sampling_rate_ms: 50,
             stroke_default_ms: 400,
             initial_timeout_ms: 800,
+            smoothing: Smoothing::default(),
+            outlier_rejection_pct: None,
         }
     }
 }
@@ -66,7 +85,9 @@ pub struct DynamicTrackingHandle {
     pub cancel: Option<CancellationToken>,
     pub cur_avg_ms: Arc<AtomicI64>,
     pub cur_avg_depth: Arc<AtomicI64>,
-    pub cur_pos: Arc<AtomicI64>
+    pub cur_pos: Arc<AtomicI64>,
+    /// feeds the tracking task, set when it was started by the client
+    pub signals: Option<UnboundedSender<TrackingSignal>>,
 }
 
 impl DynamicTrackingHandle {
@@ -74,6 +95,22 @@ impl DynamicTrackingHandle {
         self.cur_avg_ms.store(0, Ordering::Relaxed);
         self.cur_avg_depth.store(0, Ordering::Relaxed);
     }
+
+    /// Passes a signal to the tracking task, false if it already ended
+    pub fn signal(&self, signal: TrackingSignal) -> bool {
+        self.signals.as_ref().is_some_and(|x| x.send(signal).is_ok())
+    }
+
+    /// Ends the tracking task immediately
+    pub fn stop(&self) {
+        if let Some(cancel) = &self.cancel {
+            cancel.cancel();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|x| !x.is_cancelled())
+    }
 }
 
 impl Default for DynamicTrackingHandle {
@@ -83,6 +120,7 @@ impl Default for DynamicTrackingHandle {
             cur_avg_ms: Arc::new(AtomicI64::new(0)), 
             cur_avg_depth: Arc::new(AtomicI64::new(0)),
             cur_pos: Arc::new(AtomicI64::new(0)),
+            signals: None,
         }
     }
 }
\ No newline at end of file
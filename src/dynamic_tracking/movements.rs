@@ -2,10 +2,17 @@ use std::time::Duration;
 
 use tokio::time::Instant;
 
+use super::{
+    util::{elapsed_between, within_last},
+    Smoothing,
+};
+
 pub struct Movements {
     pub points: Vec<Instant>,
     pub default_time_ms: u32,
     pub meas_window_ms: u32,
+    pub smoothing: Smoothing,
+    pub outlier_rejection_pct: Option<u32>,
 }
 
 impl Movements {
@@ -14,9 +21,17 @@ impl Movements {
             points: vec![],
             default_time_ms,
             meas_window_ms,
+            smoothing: Smoothing::Mean,
+            outlier_rejection_pct: None,
         }
     }
 
+    pub fn with_smoothing(mut self, smoothing: Smoothing, outlier_rejection_pct: Option<u32>) -> Self {
+        self.smoothing = smoothing;
+        self.outlier_rejection_pct = outlier_rejection_pct;
+        self
+    }
+
     pub fn measure_now(&mut self) {
         self.points.push(Instant::now());
     }
@@ -25,6 +40,8 @@ impl Movements {
         self.points.push(instant);
     }
 
+    /// Estimated stroke duration from the intervals within the measurement window,
+    /// the first interval always counts and seeds the outlier rejection
     pub fn get_avg_ms(&mut self) -> u32 {
         self.points = self
             .points
@@ -32,21 +49,48 @@ impl Movements {
             .filter(|t| self.in_timeframe(t))
             .cloned()
             .collect();
-        let len = self.points.len();
-        if len > 1 {
-            let sum_us = self
-                .points
-                .windows(2)
-                .map(|w| (w[1] - w[0]).as_micros())
-                .sum::<u128>();
-            (sum_us as f64 / (len - 1) as f64 / 1000.0) as u32
-        } else {
-            self.default_time_ms
+        let mut accepted: Vec<f64> = vec![];
+        let mut estimate: Option<f64> = None;
+        for interval in self
+            .points
+            .windows(2)
+            .map(|w| elapsed_between(w[0], w[1]).as_micros() as f64 / 1000.0)
+        {
+            if let (Some(current), Some(pct)) = (estimate, self.outlier_rejection_pct) {
+                if (interval - current).abs() > current * pct as f64 / 100.0 {
+                    continue;
+                }
+            }
+            accepted.push(interval);
+            estimate = Some(match (self.smoothing, estimate) {
+                (Smoothing::Ema(alpha), Some(current)) => {
+                    let alpha = alpha.clamp(0.0, 1.0);
+                    alpha * interval + (1.0 - alpha) * current
+                }
+                (Smoothing::Ema(_), None) => interval,
+                (Smoothing::Mean, _) => accepted.iter().sum::<f64>() / accepted.len() as f64,
+                (Smoothing::Median, _) => median(&accepted),
+            });
+        }
+        match estimate {
+            Some(ms) => ms as u32,
+            None => self.default_time_ms,
         }
     }
 
     fn in_timeframe(&self, instant: &Instant) -> bool {
-        instant > &(Instant::now() - Duration::from_millis(self.meas_window_ms.into()))
+        within_last(*instant, Duration::from_millis(self.meas_window_ms.into()))
+    }
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    if mid * 2 == sorted.len() {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
     }
 }
 
@@ -55,7 +99,8 @@ impl Movements {
 mod tests {
     use std::time::Duration;
     use more_asserts::{assert_ge, assert_le};
-    use crate::dynamic_tracking::movements::Movements;
+    use tokio::time::Instant;
+    use crate::dynamic_tracking::{movements::Movements, Smoothing};
         
     #[tokio::test]
     pub async fn measurement_returns_defaul_no_meas() {
@@ -82,6 +127,42 @@ mod tests {
         assert_le!(avg, ms + 15);
     }
 
+    #[tokio::test]
+    pub async fn measurement_window_longer_than_uptime_does_not_panic() {
+        let mut meas = Movements::new(50, u32::MAX);
+        meas.measure_now();
+        meas.measure_now();
+        assert_le!(meas.get_avg_ms(), 15);
+    }
+
+    fn measure_intervals(meas: &mut Movements, intervals_ms: &[u64]) -> u32 {
+        let mut instant = Instant::now();
+        meas.measure(instant);
+        for ms in intervals_ms {
+            instant += Duration::from_millis(*ms);
+            meas.measure(instant);
+        }
+        meas.get_avg_ms()
+    }
+
+    #[tokio::test]
+    pub async fn median_ignores_single_delayed_signal() {
+        let mut meas = Movements::new(50, 999_999).with_smoothing(Smoothing::Median, None);
+        assert_eq!(measure_intervals(&mut meas, &[200, 200, 900, 200, 210]), 200);
+    }
+
+    #[tokio::test]
+    pub async fn ema_weights_latest_interval() {
+        let mut meas = Movements::new(50, 999_999).with_smoothing(Smoothing::Ema(0.5), None);
+        assert_eq!(measure_intervals(&mut meas, &[200, 400, 400]), 350);
+    }
+
+    #[tokio::test]
+    pub async fn outliers_are_rejected() {
+        let mut meas = Movements::new(50, 999_999).with_smoothing(Smoothing::Mean, Some(50));
+        assert_eq!(measure_intervals(&mut meas, &[200, 220, 900, 20, 180]), 200);
+    }
+
     #[tokio::test]
     pub async fn measure_avg_2() {
         measurement_test_avg(100, 2).await;
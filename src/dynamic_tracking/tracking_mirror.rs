@@ -10,9 +10,7 @@ impl DynamicTracking {
     /// mirrors the movement range of the last range for an estimated duration
     pub async fn track_mirror(&mut self) {
         let penetrating = |pen_time: &Option<Instant>| match pen_time {
-            Some(time) => {
-                (Instant::now() - *time) < Duration::from_millis(self.settings.stroke_max_ms.into())
-            }
+            Some(time) => within_last(*time, Duration::from_millis(self.settings.stroke_max_ms.into())),
             None => false,
         };
 
@@ -28,12 +26,10 @@ impl DynamicTracking {
         }
 
         let mut last_pen = None;
-        let mut meas = Movements::new(self.settings.stroke_default_ms, self.settings.stroke_max_ms);
+        let mut meas = Movements::new(self.settings.stroke_default_ms, self.settings.stroke_max_ms)
+            .with_smoothing(self.settings.smoothing, self.settings.outlier_rejection_pct);
 
-        // this might crash somewhere because there is no guaranetee that Instant
-        // can go 20 seconds into the past but I'm just gonna gamble that 
-        // the cpu has at least 20s worth of cycles whenever this is called
-        let mut last_turn = Instant::now() - Duration::from_secs(20);
+        let mut last_turn: Option<Instant> = None;
         let mut last_pos = 0.0;
         let mut moving_inward = true;
 
@@ -47,7 +43,7 @@ impl DynamicTracking {
                             error!("not moving outward");
                         } else if !self.below_min_resolution(last_turn, instant) {
                             debug!("moving inward");
-                            last_turn = instant;
+                            last_turn = Some(instant);
                             moving_inward = true;
                             if penetrating(&last_pen) {
                                 meas.measure(instant);
@@ -73,7 +69,7 @@ impl DynamicTracking {
                             error!("not moving inward");
                         } else if !self.below_min_resolution(last_turn, instant) {
                             debug!("moving outward");
-                            last_turn = instant;
+                            last_turn = Some(instant);
                             moving_inward = false;
                             if penetrating(&last_pen) {
                                 meas.measure(instant);
@@ -135,7 +131,7 @@ impl DynamicTracking {
         for actuator in &self.actuators {
             info!(
                 "moving {} to {} over {}ms...",
-                actuator.identifier(),
+                actuator,
                 last_pos,
                 estimated_dur
             );
@@ -148,8 +144,11 @@ impl DynamicTracking {
         }
     }
 
-    fn below_min_resolution(&self, last_instant: Instant, instant: Instant) -> bool {
-        let elapsed = (instant - last_instant).as_millis() as f64;
+    fn below_min_resolution(&self, last_instant: Option<Instant>, instant: Instant) -> bool {
+        let Some(last_instant) = last_instant else {
+            return false;
+        };
+        let elapsed = elapsed_between(last_instant, instant).as_millis() as f64;
         if elapsed < self.settings.min_resolution_ms as f64 {
             debug!(
                 "skipping {}ms below min resolution {}",
@@ -188,7 +187,8 @@ mod tests {
                 starting_position: 0.0,
                 stroke_max_ms: 3_000,
                 sampling_rate_ms: 50,
-                initial_timeout_ms: 1200
+                initial_timeout_ms: 1200,
+                ..Default::default()
             },
             signals: receiver,
             actuators,
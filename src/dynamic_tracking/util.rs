@@ -1,3 +1,7 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
 
 pub fn limit_speed(from_pos: f64, to_pos: f64, duration_ms: u32, min_duration_full_range: u32) -> f64 {
     if duration_ms < min_duration_full_range {
@@ -15,8 +19,23 @@ pub fn limit_speed(from_pos: f64, to_pos: f64, duration_ms: u32, min_duration_fu
     }
 }
 
+/// Time from 'from' to 'to', zero if 'to' is earlier (e.g. signals that arrive out of order)
+pub fn elapsed_between(from: Instant, to: Instant) -> Duration {
+    to.checked_duration_since(from).unwrap_or_default()
+}
+
+/// Whether 'instant' lies within the last 'window', without subtracting from
+/// Instant::now() which panics when the window reaches before the clock's origin
+pub fn within_last(instant: Instant, window: Duration) -> bool {
+    elapsed_between(instant, Instant::now()) < window
+}
+
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
     use crate::dynamic_tracking::util::*;
 
     #[tokio::test]
@@ -27,4 +46,20 @@ mod tests {
         assert_eq!(limit_speed(0.0, 1.0, 50, 200), 0.25, "moves 25% of the range if the speed is 4x too fast ");
         assert_eq!(limit_speed(0.75, 0.0, 100, 200), 0.25, "moves 75% of the range of the speed 25% to ");
     }
+
+    #[tokio::test]
+    pub async fn elapsed_between_saturates_for_out_of_order_instants() {
+        let earlier = Instant::now();
+        let later = earlier + Duration::from_millis(100);
+        assert_eq!(elapsed_between(earlier, later), Duration::from_millis(100));
+        assert_eq!(elapsed_between(later, earlier), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    pub async fn within_last_accepts_windows_longer_than_uptime() {
+        let now = Instant::now();
+        assert!(within_last(now, Duration::from_secs(u32::MAX.into())));
+        assert!(within_last(now + Duration::from_secs(1), Duration::from_millis(1)));
+        assert!(!within_last(now, Duration::ZERO));
+    }
 }
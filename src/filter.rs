@@ -3,17 +3,18 @@ use std::sync::Arc;
 use buttplug::{client::ButtplugClientDevice, core::message::ActuatorType};
 use tracing::{debug, error};
 
-use crate::{actuator::{Actuator, ActuatorConfigLoader, Actuators}, actuators::ActuatorConfig};
+use crate::{actuator::{Actuator, ActuatorConfigLoader, Actuators}, actuators::ActuatorConfig, connection::Transport};
 
-use super::actuators::ActuatorSettings;
+use super::{actions::Selector, actuators::{ActuatorSettings, SettingsCache}, linear::ScalingProfile};
 
+#[derive(Clone)]
 pub struct Filter {
-    settings: ActuatorSettings,
+    settings: SettingsCache,
     actuators: Vec<Arc<Actuator>>
 }
 
 impl Filter {
-    pub fn new(settings: ActuatorSettings, devices: &[Arc<ButtplugClientDevice>]) -> Self {
+    pub fn new(settings: SettingsCache, devices: &[Arc<ButtplugClientDevice>]) -> Self {
         let actuators = devices
             .iter()
             .filter(|x| x.connected())
@@ -28,7 +29,7 @@ impl Filter {
         }
     }
 
-    pub fn from_actuators(settings: ActuatorSettings, actuators: Vec<Arc<Actuator>>) -> Self {
+    pub fn from_actuators(settings: SettingsCache, actuators: Vec<Arc<Actuator>>) -> Self {
         Filter {
             settings,
             actuators
@@ -40,13 +41,27 @@ impl Filter {
         self
     }
 
-    pub fn load_config(mut self, settings: &mut ActuatorSettings) -> Self {
-        self.actuators = self.actuators.load_config(settings);
+    pub fn load_config(self, settings: &mut ActuatorSettings) -> Self {
+        self.load_config_with_profiles(settings, &[])
+    }
+
+    /// Loads the configs of all actuators, configs that are created are
+    /// added to 'settings'
+    pub fn load_config_with_profiles(mut self, settings: &mut ActuatorSettings, profiles: &[ScalingProfile]) -> Self {
+        self.actuators = self.actuators.load_config_with_profiles(settings, profiles);
+        for actuator in &self.actuators {
+            if let Some(config) = &actuator.config {
+                if !self.settings.contains(&config.actuator_config_id) {
+                    self.settings.insert(config.clone());
+                }
+            }
+        }
         self
     }
 
+    /// Only keeps enabled actuators, actuators without config count as disabled
     pub fn enabled(mut self) -> Self {
-        self.actuators.retain(|x| x.get_settings(&mut self.settings).enabled);
+        self.actuators.retain(|x| self.settings.is_enabled(x.identifier()));
         self
     }
 
@@ -68,9 +83,79 @@ impl Filter {
         self
     }
 
-    pub fn result(self) -> (ActuatorSettings, Vec<Arc<Actuator>>) {
+    /// Only keeps actuators assigned to 'namespace', does nothing if no namespace is requested
+    pub fn with_namespace(mut self, namespace: Option<&str>) -> Self {
+        if let Some(namespace) = namespace {
+            let namespace = namespace.to_lowercase();
+            self.actuators.retain(|x| {
+                if let Some(c) = &x.config {
+                    return c.namespace.as_deref() == Some(namespace.trim())
+                }
+                error!("settings not initialised");
+                false
+            });
+        }
+        self
+    }
+
+    /// Only keeps actuators with 'role', does nothing if no role is requested
+    pub fn with_role(mut self, role: Option<&str>) -> Self {
+        if let Some(role) = role {
+            let role = role.to_lowercase();
+            self.actuators.retain(|x| {
+                if let Some(c) = &x.config {
+                    return c.role.as_deref() == Some(role.trim())
+                }
+                error!("settings not initialised");
+                false
+            });
+        }
+        self
+    }
+
+    /// Only keeps actuators that can play positional patterns, does nothing if not 'required'
+    pub fn with_position_playback(mut self, required: bool) -> Self {
+        if required {
+            self.actuators.retain(|x| x.plays_positions());
+        }
+        self
+    }
+
+    /// Removes actuators connected with one of 'transports'
+    pub fn without_transports(mut self, transports: &[Transport]) -> Self {
+        self.actuators.retain(|x| !transports.contains(&x.transport));
+        self
+    }
+
+    /// Only keeps actuators for which 'keep' returns true
+    pub fn retain<F>(mut self, keep: F) -> Self
+    where
+        F: FnMut(&Arc<Actuator>) -> bool,
+    {
+        self.actuators.retain(keep);
+        self
+    }
+
+    /// Applies 'filter' with each of 'selectors' in order and keeps the first result
+    /// with any actuator, empty if there is no selector, see `Selector::alternatives`
+    pub fn with_first_match<F>(mut self, selectors: &[Selector], filter: F) -> Self
+    where
+        F: Fn(Filter, &Selector) -> Filter,
+    {
+        for selector in selectors {
+            let filtered = filter(self.clone(), selector);
+            if !filtered.actuators.is_empty() {
+                debug!(?selector, "selected");
+                return filtered;
+            }
+        }
+        self.actuators.clear();
+        self
+    }
+
+    pub fn result(self) -> Vec<Arc<Actuator>> {
         debug!(?self.actuators, "result");
-        (self.settings, self.actuators)
+        self.actuators
     }
 }
 
@@ -1,108 +1,692 @@
-use std::{sync::Arc, time::Duration, collections::HashMap};
+use buttplug::core::connector::ButtplugConnectorError;
+use std::{sync::{Arc, RwLock}, time::Duration, collections::HashMap, fmt::{self, Display}, future::Future};
 
 use tokio::{
-    sync::mpsc::{unbounded_channel, UnboundedSender},
-    time::sleep,
+    sync::mpsc::{unbounded_channel, Receiver, UnboundedReceiver, UnboundedSender},
+    time::{sleep, Instant},
 };
 use tracing::{debug, error};
 
 use tokio_util::sync::CancellationToken;
 
 pub mod actuator;
+#[cfg(feature = "client")]
 pub mod client;
 pub mod config; 
 pub mod dynamic_tracking;
 pub mod player;
 pub mod pattern;
+#[cfg(feature = "remote")]
+pub mod remote;
 pub mod speed;
 pub mod filter;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 mod util;
 
 use config::*;
-use speed::Speed;
+use config::client::{DeadbandSettings, DeviceClass, QuietModeSettings, ResourceLimits};
+use config::actuators::ActuatorConfig;
+use speed::{Speed, SpeedLadder};
 use actuator::Actuator;
 
-use player::worker::{ButtplugWorker, WorkerResult, WorkerTask};
-use player::PatternPlayer;
+use player::session_log::SessionLog;
+use player::access::Degradation;
+use player::stats::ActionStatsStore;
+use player::status::{ActuatorStatus, CommandedValues, HandleDescription, TaskState};
+use player::worker::{ButtplugWorker, RequestId, WorkerError, WorkerResponse, WorkerResult, WorkerTask};
+use player::{PatternPlayer, SpeedUpdate};
+use player::pause::PauseSwitch;
+use player::lifecycle::{HandleLifecycle, SchedulerEvent, SchedulerEvents};
+use player::strokes::{StrokeCounter, StrokeMilestones};
+use player::jitter::JitterBuffer;
+use player::lookahead::Lookahead;
+use player::trigger::SamplingTrigger;
 
 #[derive(Debug)]
 pub struct ButtplugScheduler {
     worker_task_sender: UnboundedSender<WorkerTask>,
+    event_sender: SchedulerEvents,
     settings: PlayerSettings,
     control_handles: HashMap<i32, Vec<ControlHandle>>,
     last_handle: i32,
+    /// shared with the worker, records every processed command while enabled
+    pub session_log: SessionLog,
+    /// shared with the worker, usage statistics per action name
+    pub action_stats: ActionStatsStore,
+    /// shared with the worker, last values commanded by each handle
+    pub commanded: CommandedValues,
+    /// shared with all players, limits strokes while quiet mode is on
+    quiet_mode: Arc<RwLock<Option<QuietModeSettings>>>,
+    /// shared with all players, see `set_global_intensity`
+    global_intensity: Arc<RwLock<Option<Speed>>>,
+    /// shared with all players, latest actuator configs by identifier
+    live_configs: Arc<RwLock<HashMap<String, ActuatorConfig>>>,
+    /// shared with all players, sends pattern commands ahead of time
+    lookahead: Arc<RwLock<Option<Lookahead>>>,
+    /// passed to new players, see `set_sampling_trigger`
+    sampling_trigger: Option<SamplingTrigger>,
+    /// passed to new players, see `set_loop_crossfade`
+    loop_crossfade: Option<Duration>,
+    /// passed to new players, see `set_loop_gap`
+    loop_gap: Option<Duration>,
+    /// see `step_up`
+    speed_ladder: SpeedLadder,
+    /// passed to new players, see `set_variable_deadband`
+    variable_deadband: Option<DeadbandSettings>,
+    /// see `check_capacity`
+    resource_limits: Option<ResourceLimits>,
+    /// cancelled when the running wind down ends, see `wind_down`
+    winding_down: Option<CancellationToken>,
+}
+
+/// Why new players were refused, see `ButtplugScheduler::check_capacity`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CapacityError {
+    /// running handles and the limit
+    TooManyHandles(usize, usize),
+    /// running players and the limit
+    TooManyPlayers(usize, usize),
+}
+
+impl Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CapacityError::TooManyHandles(running, max) => {
+                write!(f, "{} handles are running, no more than {} are allowed", running, max)
+            }
+            CapacityError::TooManyPlayers(running, max) => {
+                write!(f, "{} players are running, no more than {} are allowed", running, max)
+            }
+        }
+    }
+}
+
+/// Limits single devices to save power from outside the scheduler,
+/// e.g. from a task that polls battery levels
+#[derive(Debug, Clone)]
+pub struct DeviceLimiter {
+    worker_task_sender: UnboundedSender<WorkerTask>,
+}
+
+impl DeviceLimiter {
+    /// Caps the device with 'device_index' and slows down its commands, None lifts the limits
+    pub fn set_degradation(&self, device_index: u32, degradation: Option<Degradation>) {
+        debug!(device_index, ?degradation, "set device degradation");
+        self.worker_task_sender
+            .send(WorkerTask::SetDegradation(device_index, degradation))
+            .unwrap_or_else(|_| error!("queue err"));
+    }
+}
+
+/// Sanctioned access to the worker queue for hosts that send their own `WorkerTask`s,
+/// e.g. custom move sequences, while the worker still arbitrates device access
+/// between them and the scheduled tasks.
+///
+/// The actuator variants of `WorkerTask` (`Start`, `Update`, `End`, `Move`, `EndLinear`
+/// and `Rotate`) and the methods of this type only change with a major version.
+/// Tasks should use `handle()`, which is never given to a scheduled task
+#[derive(Debug)]
+pub struct WorkerChannel {
+    handle: i32,
+    worker_task_sender: UnboundedSender<WorkerTask>,
+    last_request_id: RequestId,
+    result_sender: UnboundedSender<WorkerResponse>,
+    result_receiver: UnboundedReceiver<WorkerResponse>,
+}
+
+impl WorkerChannel {
+    pub fn handle(&self) -> i32 {
+        self.handle
+    }
+
+    /// Queues 'task', returns false if the worker stopped
+    pub fn send(&self, task: WorkerTask) -> bool {
+        self.worker_task_sender.send(task).is_ok()
+    }
+
+    /// Clone of the raw sender, e.g. to queue tasks from other threads
+    pub fn sender(&self) -> UnboundedSender<WorkerTask> {
+        self.worker_task_sender.clone()
+    }
+
+    /// Id and sender for a task that reports its result (`End`, `Move`, `Rotate`),
+    /// the response is returned by `next_result`
+    pub fn request(&mut self) -> (RequestId, UnboundedSender<WorkerResponse>) {
+        self.last_request_id += 1;
+        (self.last_request_id, self.result_sender.clone())
+    }
+
+    /// Next response to a task created with `request`
+    pub async fn next_result(&mut self) -> Option<WorkerResponse> {
+        self.result_receiver.recv().await
+    }
+
+    /// Sends a `WorkerTask::Probe` and waits until the server acknowledged it,
+    /// fails if the worker stopped
+    pub async fn probe(&mut self, actuator: &Arc<Actuator>, value: f64, duration_ms: u32) -> WorkerResult {
+        let (id, result_sender) = self.request();
+        self.send(WorkerTask::Probe(actuator.clone(), value, duration_ms, self.handle, id, result_sender));
+        loop {
+            match self.next_result().await {
+                Some(response) if response.id == id => return response.result,
+                Some(_) => continue,
+                None => {
+                    error!(%actuator, "worker stopped before answering the probe");
+                    return Err(WorkerError {
+                        bp_error: ButtplugConnectorError::ConnectorNotConnected.into(),
+                        actuator: actuator.clone(),
+                        related: vec![],
+                    });
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 struct ControlHandle {
     cancellation_token: CancellationToken,
-    update_sender: UnboundedSender<Speed>,
+    update_sender: UnboundedSender<SpeedUpdate>,
+    /// name of the dispatching action, if any
+    action: Option<String>,
+    actuators: Vec<Arc<Actuator>>,
+    started: Instant,
+    /// set by the player once it knows its duration
+    deadline: Arc<RwLock<Option<Instant>>>,
+    pause: PauseSwitch,
+    /// set by the players once they know the speed of the task, shared by all players of the handle
+    task_speed: Arc<RwLock<Option<Speed>>>,
+    /// completed strokes, shared by all players of the handle
+    strokes: Arc<StrokeCounter>,
+    /// shared by all players of the handle
+    lifecycle: Arc<HandleLifecycle>,
+    /// skips the ramp out of the players
+    halt: CancellationToken,
 }
 
 #[derive(Debug)]
 pub struct PlayerSettings {
     pub scalar_resolution_ms: i32,
+    /// scalar tasks rise from zero to their speed within this time
+    pub ramp_in_ms: u32,
+    /// scalar tasks fall to zero within this time when they end or are stopped,
+    /// `stop_all` and `wind_down` stop immediately
+    pub ramp_out_ms: u32,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self {
+            scalar_resolution_ms: 100,
+            ramp_in_ms: 0,
+            ramp_out_ms: 0,
+        }
+    }
 }
 
 impl ButtplugScheduler {
+    /// Creates the scheduler and the worker that executes the device commands of all players
     pub fn create(settings: PlayerSettings) -> (ButtplugScheduler, ButtplugWorker) {
+        ButtplugScheduler::create_with_sender(settings, SchedulerEvents::default())
+    }
+
+    /// Like `create`, also returns a receiver for the `SchedulerEvent`s of each handle. It holds
+    /// up to `SCHEDULER_EVENT_CAPACITY` events, newer ones are dropped while it is full
+    pub fn create_with_events(settings: PlayerSettings) -> (ButtplugScheduler, ButtplugWorker, Receiver<SchedulerEvent>) {
+        let (event_sender, event_receiver) = SchedulerEvents::channel();
+        let (scheduler, worker) = ButtplugScheduler::create_with_sender(settings, event_sender);
+        (scheduler, worker, event_receiver)
+    }
+
+    fn create_with_sender(settings: PlayerSettings, event_sender: SchedulerEvents) -> (ButtplugScheduler, ButtplugWorker) {
         let (worker_task_sender, task_receiver) = unbounded_channel::<WorkerTask>();
+        let session_log = SessionLog::default();
+        let action_stats = ActionStatsStore::default();
+        let mut worker = ButtplugWorker::new(task_receiver, session_log.clone());
+        worker.action_stats = action_stats.clone();
+        let commanded = CommandedValues::default();
+        worker.commanded = commanded.clone();
         (
             ButtplugScheduler {
                 worker_task_sender,
+                event_sender,
                 settings,
                 control_handles: HashMap::new(),
                 last_handle: 0,
+                session_log,
+                action_stats,
+                commanded,
+                quiet_mode: Arc::new(RwLock::new(None)),
+                global_intensity: Arc::new(RwLock::new(None)),
+                live_configs: Arc::new(RwLock::new(HashMap::new())),
+                lookahead: Arc::new(RwLock::new(None)),
+                sampling_trigger: None,
+                loop_crossfade: None,
+                loop_gap: None,
+                speed_ladder: SpeedLadder::default(),
+                variable_deadband: None,
+                resource_limits: None,
+                winding_down: None,
             },
-            ButtplugWorker { task_receiver },
+            worker,
         )
     }
 
+    /// Creates a player for a new handle, or a child player of 'existing_handle' if it is > 0.
+    ///
+    /// All players of a handle form a group: updates are sent to each of them, stopping
+    /// the handle stops all of them and the handle runs until its last player finished,
+    /// see `is_running` and `finished`. Unknown handles get a new handle instead
     pub fn create_player(&mut self, actuators: Vec<Arc<Actuator>>, existing_handle: i32) -> PatternPlayer {
-        let (update_sender, update_receiver) = unbounded_channel::<Speed>();
-        let cancellation_token = CancellationToken::new();
-        let mut handle = existing_handle;
-
-        if existing_handle > 0 {
-            if let Some(ref mut control_handles) = self.control_handles.get_mut(&existing_handle) {
-                control_handles.push(ControlHandle {
-                    cancellation_token: cancellation_token.clone(),
-                    update_sender,
-                })
+        let (update_sender, update_receiver) = unbounded_channel::<SpeedUpdate>();
+        // players created while winding down stop together with the others
+        let cancellation_token = match self.winding_down.as_ref().filter(|x| !x.is_cancelled()) {
+            Some(end) => end.child_token(),
+            None => CancellationToken::new(),
+        };
+        let halt = CancellationToken::new();
+        let deadline = Arc::new(RwLock::new(None));
+        let pause = PauseSwitch::default();
+        let control_handle = |(lifecycle, task_speed, strokes)| ControlHandle {
+            cancellation_token: cancellation_token.clone(),
+            update_sender: update_sender.clone(),
+            action: None,
+            actuators: actuators.clone(),
+            started: Instant::now(),
+            deadline: deadline.clone(),
+            pause: pause.clone(),
+            task_speed,
+            strokes,
+            lifecycle,
+            halt: halt.clone(),
+        };
+        // state that all players of a handle share, only the first player counts strokes
+        let (handle, shared, counts_strokes) = match self.control_handles.get_mut(&existing_handle) {
+            Some(control_handles) if existing_handle > 0 && !control_handles.is_empty() => {
+                let first = &control_handles[0];
+                let shared = (first.lifecycle.clone(), first.task_speed.clone(), first.strokes.clone());
+                control_handles.push(control_handle(shared.clone()));
+                (existing_handle, shared, false)
             }
-        } else {
-            handle = self.get_next_handle();
-            self.control_handles.insert(
-                handle,
-                vec![ControlHandle {
-                    cancellation_token: cancellation_token.clone(),
-                    update_sender,
-                }],
-            );
-        }
+            _ => {
+                if existing_handle > 0 {
+                    error!(existing_handle, "Unknown handle, creating a new one");
+                }
+                let handle = self.get_next_handle();
+                let shared = (
+                    HandleLifecycle::start(handle, self.event_sender.clone()),
+                    Arc::new(RwLock::new(None)),
+                    StrokeCounter::new(handle, self.event_sender.clone()),
+                );
+                self.control_handles.insert(handle, vec![control_handle(shared.clone())]);
+                (handle, shared, true)
+            }
+        };
+        let (lifecycle, task_speed, strokes) = shared;
+        strokes.join(cancellation_token.clone());
         let (result_sender, result_receiver) =
-            unbounded_channel::<WorkerResult>();
-        PatternPlayer::new(
+            unbounded_channel::<WorkerResponse>();
+        let player = PatternPlayer::new(
             handle,
             actuators,
             result_sender,
             result_receiver,
             update_receiver,
-            cancellation_token,
+            cancellation_token.clone(),
             self.worker_task_sender.clone(),
             self.settings.scalar_resolution_ms,
         )
+        .with_quiet_mode(self.quiet_mode.clone())
+        .with_global_intensity(self.global_intensity.clone())
+        .with_live_configs(self.live_configs.clone())
+        .with_lookahead(self.lookahead.clone())
+        .with_sampling_trigger(self.sampling_trigger.clone())
+        .with_loop_crossfade(self.loop_crossfade)
+        .with_loop_gap(self.loop_gap)
+        .with_deadband(self.variable_deadband)
+        .with_deadline(deadline)
+        .with_pause(pause)
+        .with_task_speed(task_speed);
+        let player = match counts_strokes {
+            true => player.with_stroke_counter(strokes),
+            false => player,
+        };
+        player
+            .with_ramps(
+                Duration::from_millis(self.settings.ramp_in_ms.into()),
+                Duration::from_millis(self.settings.ramp_out_ms.into()),
+                halt,
+            )
+            .with_lifecycle(lifecycle.join(cancellation_token))
+    }
+
+    /// Like `create_player`, but refuses to create the player if it exceeds the
+    /// resource limits, see `check_capacity`
+    pub fn try_create_player(
+        &mut self,
+        actuators: Vec<Arc<Actuator>>,
+        existing_handle: i32,
+    ) -> Result<PatternPlayer, CapacityError> {
+        self.check_capacity(existing_handle, 1)?;
+        Ok(self.create_player(actuators, existing_handle))
+    }
+
+    /// Whether 'players' more players fit within the resource limits, they start a new
+    /// handle unless 'existing_handle' is running. Always succeeds without limits
+    pub fn check_capacity(&self, existing_handle: i32, players: usize) -> Result<(), CapacityError> {
+        let Some(limits) = self.resource_limits else {
+            return Ok(());
+        };
+        let running = |x: &ControlHandle| !x.cancellation_token.is_cancelled();
+        let handles = self
+            .control_handles
+            .values()
+            .filter(|x| x.iter().any(running))
+            .count();
+        if players > 0 && !self.is_running(existing_handle) && handles >= limits.max_handles {
+            error!(handles, limits.max_handles, "too many handles");
+            return Err(CapacityError::TooManyHandles(handles, limits.max_handles));
+        }
+        let running_players = self.control_handles.values().flatten().filter(|x| running(x)).count();
+        if running_players + players > limits.max_players {
+            error!(running_players, limits.max_players, "too many players");
+            return Err(CapacityError::TooManyPlayers(running_players, limits.max_players));
+        }
+        Ok(())
+    }
+
+    /// Limits the players that `check_capacity` accepts, None does not limit them
+    pub fn set_resource_limits(&mut self, limits: Option<ResourceLimits>) {
+        debug!(?limits, "set resource limits");
+        self.resource_limits = limits;
+    }
+
+    /// Like `create_player` but remembers the name of the action that is played,
+    /// so that the handle can be found with `handles_for_action`
+    pub fn create_action_player(
+        &mut self,
+        actuators: Vec<Arc<Actuator>>,
+        existing_handle: i32,
+        action_name: &str,
+    ) -> PatternPlayer {
+        let player = self.create_player(actuators, existing_handle);
+        if let Some(control_handle) = self
+            .control_handles
+            .get_mut(&player.handle)
+            .and_then(|x| x.last_mut())
+        {
+            control_handle.action = Some(action_name.to_owned());
+        }
+        player
+    }
+
+    /// Handles of all running tasks that were started by the action 'name' (case insensitive)
+    pub fn handles_for_action(&self, name: &str) -> Vec<i32> {
+        let mut handles = self
+            .control_handles
+            .iter()
+            .filter(|(_, handles)| {
+                handles.iter().any(|x| {
+                    !x.cancellation_token.is_cancelled()
+                        && x.action.as_deref().is_some_and(|x| x.eq_ignore_ascii_case(name.trim()))
+                })
+            })
+            .map(|(handle, _)| *handle)
+            .collect::<Vec<_>>();
+        handles.sort();
+        handles
+    }
+
+    /// Whether 'handle' is running, paused or finished, the actuators that are driven by it with
+    /// their last commanded value (speed or position) and how long the task has been and will be
+    /// running. None if the handle was never issued
+    pub fn describe_handle(&self, handle: i32) -> Option<HandleDescription> {
+        let running = self
+            .control_handles
+            .get(&handle)
+            .map(|handles| {
+                handles
+                    .iter()
+                    .filter(|x| !x.cancellation_token.is_cancelled())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        if running.is_empty() {
+            return (handle > 0 && handle <= self.last_handle).then(|| HandleDescription {
+                handle,
+                state: TaskState::Finished,
+                elapsed: Duration::ZERO,
+                playing: Duration::ZERO,
+                remaining: Some(Duration::ZERO),
+                actuators: vec![],
+            });
+        }
+        let now = Instant::now();
+        let values = self.commanded.get(handle);
+        let state = match running.iter().all(|x| x.pause.is_paused()) {
+            true => TaskState::Paused,
+            false => TaskState::Running,
+        };
+        let started = running.iter().map(|x| x.started).min()?;
+        let playing = running
+            .iter()
+            .map(|x| now.saturating_duration_since(x.started).saturating_sub(x.pause.paused_for()))
+            .max()
+            .unwrap_or_default();
+        let remaining = running
+            .iter()
+            .map(|x| *x.deadline.read().unwrap())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|x| x.into_iter().max())
+            .map(|deadline| deadline.saturating_duration_since(now));
+        let actuators = running
+            .iter()
+            .flat_map(|control_handle| {
+                control_handle.actuators.iter().map(|actuator| ActuatorStatus {
+                    actuator: actuator.clone(),
+                    action: control_handle.action.clone(),
+                    value: values.get(actuator.identifier()).copied(),
+                })
+            })
+            .collect();
+        Some(HandleDescription {
+            handle,
+            state,
+            elapsed: now.saturating_duration_since(started),
+            playing,
+            remaining,
+            actuators,
+        })
+    }
+
+    /// Stops everything that was started by the action 'name' and returns the stopped handles
+    pub fn stop_action(&mut self, name: &str) -> Vec<i32> {
+        let handles = self.handles_for_action(name);
+        self.stop_tasks(&handles);
+        handles
     }
 
     pub fn update_task(&mut self, handle: i32, speed: Speed) -> bool {
+        self.send_update(handle, SpeedUpdate::All(speed))
+    }
+
+    /// Changes the speed of a running task to the next step of the speed ladder, tasks
+    /// with an unknown speed count as 100%. Returns the new speed, None if the handle is unknown
+    pub fn step_up(&mut self, handle: i32) -> Option<Speed> {
+        self.step_task(handle, true)
+    }
+
+    /// Changes the speed of a running task to the previous step of the speed ladder, see `step_up`
+    pub fn step_down(&mut self, handle: i32) -> Option<Speed> {
+        self.step_task(handle, false)
+    }
+
+    fn step_task(&mut self, handle: i32, up: bool) -> Option<Speed> {
+        let task_speed = self.control_handles.get(&handle)?.first()?.task_speed.clone();
+        let current = task_speed.read().unwrap().unwrap_or(Speed::max());
+        let speed = match up {
+            true => self.speed_ladder.step_up(current),
+            false => self.speed_ladder.step_down(current),
+        };
+        debug!(handle, ?current, ?speed, "step task");
+        // steps that follow before the players received the update build on it
+        *task_speed.write().unwrap() = Some(speed);
+        self.update_task(handle, speed).then_some(speed)
+    }
+
+    /// Reports the progress of a running stroke task with `SchedulerEvent::StrokeMilestone`
+    /// and `SchedulerEvent::StrokeTarget`, strokes that were already completed count.
+    /// Returns false if the handle is unknown
+    pub fn set_stroke_milestones(&mut self, handle: i32, milestones: StrokeMilestones) -> bool {
+        debug!(handle, ?milestones, "set stroke milestones");
+        match self.control_handles.get(&handle).and_then(|x| x.first()) {
+            Some(control_handle) => {
+                control_handle.strokes.set_milestones(milestones);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Strokes that the players of 'handle' completed, a stroke of a positional pattern
+    /// ends when it turns from moving down to moving up. None if the handle is unknown
+    pub fn stroke_count(&self, handle: i32) -> Option<u64> {
+        Some(self.control_handles.get(&handle)?.first()?.strokes.count())
+    }
+
+    /// Replaces the speeds that `step_up` and `step_down` move tasks through
+    pub fn set_speed_ladder(&mut self, ladder: SpeedLadder) {
+        debug!(?ladder, "set speed ladder");
+        self.speed_ladder = ladder;
+    }
+
+    /// Updates the speed of several tasks at once, so that they change in the same tick.
+    /// Returns whether each handle was found
+    pub fn update_tasks(&mut self, updates: &[(i32, Speed)]) -> Vec<bool> {
+        updates
+            .iter()
+            .map(|(handle, speed)| self.send_update(*handle, SpeedUpdate::All(*speed)))
+            .collect()
+    }
+
+    /// Sets individual speeds for some actuators (by identifier) of a running task,
+    /// the remaining actuators keep following the task speed
+    pub fn update_task_lanes(&mut self, handle: i32, lanes: HashMap<String, Speed>) -> bool {
+        self.send_update(handle, SpeedUpdate::Lanes(lanes))
+    }
+
+    /// Raises the speed of a running task to at least 'speed' for 'duration',
+    /// afterwards the task continues with its previous speed
+    pub fn boost_task(&mut self, handle: i32, speed: Speed, duration: Duration) -> bool {
+        self.send_update(handle, SpeedUpdate::Boost(speed, duration))
+    }
+
+    /// Plays a running task on 'actuators' instead of its current actuators. Removed actuators
+    /// are stopped, added actuators join at the current pattern position. In handles with
+    /// several players each player keeps the actuators it already has, added ones go to the
+    /// first running player. Returns false if the handle has no running players
+    pub fn retarget_task(&mut self, handle: i32, actuators: Vec<Arc<Actuator>>) -> bool {
+        let contains = |list: &[Arc<Actuator>], actuator: &Arc<Actuator>| {
+            list.iter().any(|x| x.identifier() == actuator.identifier())
+        };
+        let mut players = match self.control_handles.get_mut(&handle) {
+            Some(players) => players
+                .iter_mut()
+                .filter(|x| !x.cancellation_token.is_cancelled())
+                .collect::<Vec<_>>(),
+            None => vec![],
+        };
+        if players.is_empty() {
+            error!(handle, "unknown handle");
+            return false;
+        }
+        let mut added = actuators
+            .iter()
+            .filter(|x| !players.iter().any(|player| contains(&player.actuators, x)))
+            .cloned()
+            .collect::<Vec<_>>();
+        debug!(handle, ?actuators, "retarget task");
+        for (i, player) in players.iter_mut().enumerate() {
+            let mut targets = player
+                .actuators
+                .iter()
+                .filter(|x| contains(&actuators, x))
+                .cloned()
+                .collect::<Vec<_>>();
+            if i == 0 {
+                targets.append(&mut added);
+            }
+            if targets.len() != player.actuators.len() || targets.iter().any(|x| !contains(&player.actuators, x)) {
+                player.actuators = targets.clone();
+                let _ = player.update_sender.send(SpeedUpdate::Actuators(targets));
+            }
+        }
+        true
+    }
+
+    /// Changes the tempo of a running metronome task to 'bpm' beats per minute
+    pub fn update_tempo(&mut self, handle: i32, bpm: f64) -> bool {
+        self.send_update(handle, SpeedUpdate::Tempo(bpm))
+    }
+
+    /// Changes the pause after each loop of a running pattern task, zero removes it.
+    /// Applies from the next loop on
+    pub fn update_loop_gap(&mut self, handle: i32, gap: Duration) -> bool {
+        self.send_update(handle, SpeedUpdate::LoopGap(gap))
+    }
+
+    /// Marks a running task as tracking-driven: it pauses like `pause_task` when it receives
+    /// no speed updates for 'timeout' and resumes with the next one, None unmarks it
+    pub fn set_auto_pause(&mut self, handle: i32, timeout: Option<Duration>) -> bool {
+        match self.control_handles.get(&handle) {
+            Some(players) => {
+                debug!(handle, ?timeout, "set auto pause");
+                for player in players {
+                    player.pause.set_auto_pause(timeout);
+                }
+                true
+            }
+            None => {
+                error!(handle, "unkown handle");
+                false
+            }
+        }
+    }
+
+    /// Stores the actuator configs that are used by future tasks, running
+    /// tasks pick them up with their next command
+    pub fn sync_actuator_configs(&mut self, configs: &[ActuatorConfig]) {
+        *self.live_configs.write().unwrap() = configs
+            .iter()
+            .map(|x| (x.actuator_config_id.clone(), x.clone()))
+            .collect();
+    }
+
+    /// Applies changed limits to all running tasks, tasks with a constant
+    /// speed re-send it immediately
+    pub fn update_actuator_configs(&mut self, configs: &[ActuatorConfig]) {
+        debug!("update actuator configs");
+        self.sync_actuator_configs(configs);
+        let handles: Vec<i32> = self.control_handles.keys().copied().collect();
+        for handle in handles {
+            self.send_update(handle, SpeedUpdate::Settings);
+        }
+    }
+
+    fn send_update(&mut self, handle: i32, update: SpeedUpdate) -> bool {
         if self.control_handles.contains_key(&handle) {
-            debug!(handle, "updating handle");
+            debug!(handle, ?update, "updating handle");
             let handles = self
                 .control_handles
                 .get(&handle)
                 .unwrap();
             for handle in handles {
-                let _ = handle.update_sender.send(speed);
+                let _ = handle.update_sender.send(update.clone());
+                // speed changes resume auto-paused tasks once they are queued
+                if let SpeedUpdate::All(_) | SpeedUpdate::Lanes(_) | SpeedUpdate::Boost(..) | SpeedUpdate::Tempo(_) = update {
+                    handle.pause.touch();
+                }
             }
             true
         } else {
@@ -111,6 +695,164 @@ impl ButtplugScheduler {
         }
     }
 
+    /// Scales the output of all running and future tasks like a master volume: scalar and
+    /// rotate speeds and the speed of strokes. Positions of linear patterns are not changed
+    pub fn set_global_intensity(&mut self, intensity: Speed) {
+        debug!(?intensity, "set global intensity");
+        *self.global_intensity.write().unwrap() = Some(intensity).filter(|x| *x < Speed::max());
+        self.worker_task_sender
+            .send(WorkerTask::SetIntensity(intensity))
+            .unwrap_or_else(|_| error!("queue err"));
+    }
+
+    pub fn global_intensity(&self) -> Speed {
+        self.global_intensity.read().unwrap().unwrap_or(Speed::max())
+    }
+
+    /// Caps scalar outputs and slows down strokes of all running and
+    /// future tasks, None turns quiet mode off
+    pub fn set_quiet_mode(&mut self, quiet_mode: Option<QuietModeSettings>) {
+        debug!(?quiet_mode, "set quiet mode");
+        let ceiling = quiet_mode.as_ref().map(|x| Speed::new(x.max_speed));
+        *self.quiet_mode.write().unwrap() = quiet_mode;
+        self.worker_task_sender
+            .send(WorkerTask::SetCeiling(ceiling))
+            .unwrap_or_else(|_| error!("queue err"));
+    }
+
+    /// Streams the values commanded to the actuators to 'settings.target', None stops it
+    #[cfg(feature = "telemetry")]
+    pub fn set_telemetry(
+        &mut self,
+        settings: Option<player::telemetry::TelemetrySettings>,
+    ) -> std::io::Result<()> {
+        let telemetry = match settings {
+            Some(settings) => Some(Arc::new(player::telemetry::Telemetry::connect(&settings)?)),
+            None => None,
+        };
+        self.worker_task_sender
+            .send(WorkerTask::SetTelemetry(telemetry))
+            .unwrap_or_else(|_| error!("queue err"));
+        Ok(())
+    }
+
+    /// Limits the scalar commands per second that are sent to devices of each class,
+    /// updates in between are coalesced into the next allowed command
+    pub fn set_command_budgets(&mut self, budgets: HashMap<DeviceClass, u32>) {
+        debug!(?budgets, "set command budgets");
+        self.worker_task_sender
+            .send(WorkerTask::SetCommandBudgets(budgets))
+            .unwrap_or_else(|_| error!("queue err"));
+    }
+
+    /// Blends from the old to the new speed within 'window' when a task starts on an
+    /// actuator that is already in use, or when it ends and another task takes over
+    pub fn set_takeover_crossfade(&mut self, window: Option<Duration>) {
+        debug!(?window, "set takeover crossfade");
+        self.worker_task_sender
+            .send(WorkerTask::SetTakeoverCrossfade(window))
+            .unwrap_or_else(|_| error!("queue err"));
+    }
+
+    /// Limits single devices while running, see `DeviceLimiter`
+    pub fn device_limiter(&self) -> DeviceLimiter {
+        DeviceLimiter {
+            worker_task_sender: self.worker_task_sender.clone(),
+        }
+    }
+
+    /// Access to the worker queue with a handle of its own, see `WorkerChannel`
+    pub fn worker_channel(&mut self) -> WorkerChannel {
+        let (result_sender, result_receiver) = unbounded_channel();
+        WorkerChannel {
+            handle: self.get_next_handle(),
+            worker_task_sender: self.worker_task_sender.clone(),
+            last_request_id: 0,
+            result_sender,
+            result_receiver,
+        }
+    }
+
+    /// Scalar patterns of tasks that start afterwards skip points that are closer than
+    /// 'resolution_ms' to the previous one
+    pub fn set_scalar_resolution(&mut self, resolution_ms: i32) {
+        debug!(resolution_ms, "set scalar resolution");
+        self.settings.scalar_resolution_ms = resolution_ms;
+    }
+
+    /// Holds back all commands by the jitter buffer delay, None sends them immediately again
+    pub fn set_jitter_buffer(&mut self, jitter_buffer: Option<JitterBuffer>) {
+        debug!(?jitter_buffer, "set jitter buffer");
+        self.worker_task_sender
+            .send(WorkerTask::SetJitterBuffer(jitter_buffer))
+            .unwrap_or_else(|_| error!("queue err"));
+    }
+
+    /// Sends the commands of running and future patterns ahead of time, None sends them on time
+    pub fn set_lookahead(&mut self, lookahead: Option<Lookahead>) {
+        debug!(?lookahead, "set lookahead");
+        *self.lookahead.write().unwrap() = lookahead;
+    }
+
+    /// Variable-driven tasks that start afterwards sample on each signal of 'trigger'
+    /// instead of every 200ms, None restores the fixed rate
+    pub fn set_sampling_trigger(&mut self, trigger: Option<SamplingTrigger>) {
+        debug!(?trigger, "set sampling trigger");
+        self.sampling_trigger = trigger;
+    }
+
+    /// Patterns that start afterwards blend from their last point back to their
+    /// first within 'window' when they repeat, None jumps immediately
+    pub fn set_loop_crossfade(&mut self, window: Option<Duration>) {
+        debug!(?window, "set loop crossfade");
+        self.loop_crossfade = window;
+    }
+
+    /// Patterns that start afterwards pause for 'gap' after each loop, None
+    /// repeats them immediately. See `update_loop_gap` for running tasks
+    pub fn set_loop_gap(&mut self, gap: Option<Duration>) {
+        debug!(?gap, "set loop gap");
+        self.loop_gap = gap;
+    }
+
+    /// Variable-driven tasks that start afterwards ignore changes within the
+    /// deadband, None sends every change
+    pub fn set_variable_deadband(&mut self, deadband: Option<DeadbandSettings>) {
+        debug!(?deadband, "set variable deadband");
+        self.variable_deadband = deadband;
+    }
+
+    /// Freezes the playback of all players of 'handle': scalar outputs drop to zero, linear
+    /// actuators hold their position and patterns continue from the same point once resumed.
+    /// Time spent paused does not count towards the duration of the task
+    pub fn pause_task(&mut self, handle: i32) -> bool {
+        self.set_paused(handle, true)
+    }
+
+    /// Continues the playback of a task paused with `pause_task`
+    pub fn resume_task(&mut self, handle: i32) -> bool {
+        self.set_paused(handle, false)
+    }
+
+    fn set_paused(&mut self, handle: i32, paused: bool) -> bool {
+        match self.control_handles.get(&handle) {
+            Some(control_handles) => {
+                debug!(handle, paused, "set paused");
+                for control_handle in control_handles {
+                    match paused {
+                        true => control_handle.pause.pause(),
+                        false => control_handle.pause.resume(),
+                    };
+                }
+                true
+            }
+            None => {
+                error!(handle, "Unknown handle");
+                false
+            }
+        }
+    }
+
     pub fn stop_task(&mut self, handle: i32) {
         if self.control_handles.contains_key(&handle) {
             let handles = self.control_handles
@@ -119,6 +861,7 @@ impl ButtplugScheduler {
             debug!(handle, ?handles, "stop handle");
 
             for handle in handles {
+                handle.lifecycle.cancel();
                 handle.cancellation_token.cancel();
             }
         } else {
@@ -126,6 +869,29 @@ impl ButtplugScheduler {
         } 
     }
 
+    /// Stops several tasks at once, so that they end in the same tick
+    pub fn stop_tasks(&mut self, handles: &[i32]) {
+        let tokens = handles
+            .iter()
+            .filter_map(|handle| match self.control_handles.remove(handle) {
+                Some(control_handles) => Some(control_handles),
+                None => {
+                    error!(handle, "Unknown handle");
+                    None
+                }
+            })
+            .flatten()
+            .map(|x| {
+                x.lifecycle.cancel();
+                x.cancellation_token
+            })
+            .collect::<Vec<_>>();
+        debug!(?handles, "stop handles");
+        for token in tokens {
+            token.cancel();
+        }
+    }
+
     pub fn stop_all(&mut self) {
         let queue_full_err = "Event sender full";
         self.worker_task_sender
@@ -134,17 +900,88 @@ impl ButtplugScheduler {
         for entry in self.control_handles.drain() {
             debug!("stop-all - stopping handle {:?}", entry.0);
             for handle in entry.1 {
+                handle.lifecycle.cancel();
+                handle.halt.cancel();
                 handle.cancellation_token.cancel()
             }
         }
         self.control_handles.clear();
     }
 
+    /// Caps all scalar outputs to the speed of each stage for its duration before
+    /// stopping all tasks, the returned future must be polled for the tasks to stop.
+    /// Tasks that start during the wind down are capped and stopped as well. Linear
+    /// actuators are not capped, they keep their amplitude until all tasks stop.
+    /// Quiet mode is restored afterwards
+    pub fn wind_down(&mut self, stages: Vec<(Speed, Duration)>) -> impl Future<Output = ()> + Send + 'static {
+        debug!(?stages, "wind down");
+        let end = CancellationToken::new();
+        self.winding_down = Some(end.clone());
+        let worker_task_sender = self.worker_task_sender.clone();
+        let quiet_mode = self.quiet_mode.clone();
+        // running handles stay controllable until the end, `clean_finished_tasks` forgets them
+        let handles = self
+            .control_handles
+            .values()
+            .flatten()
+            .map(|x| (x.lifecycle.clone(), x.halt.clone(), x.cancellation_token.clone()))
+            .collect::<Vec<_>>();
+        async move {
+            for (speed, duration) in stages {
+                let ceiling = match quiet_mode.read().unwrap().as_ref() {
+                    Some(quiet_mode) => std::cmp::min(speed, Speed::new(quiet_mode.max_speed)),
+                    None => speed,
+                };
+                worker_task_sender
+                    .send(WorkerTask::SetCeiling(Some(ceiling)))
+                    .unwrap_or_else(|_| error!("queue err"));
+                sleep(duration).await;
+            }
+            worker_task_sender
+                .send(WorkerTask::StopAll)
+                .unwrap_or_else(|_| error!("queue err"));
+            for (lifecycle, halt, token) in handles {
+                lifecycle.cancel();
+                halt.cancel();
+                token.cancel();
+            }
+            end.cancel();
+            let ceiling = quiet_mode.read().unwrap().as_ref().map(|x| Speed::new(x.max_speed));
+            worker_task_sender
+                .send(WorkerTask::SetCeiling(ceiling))
+                .unwrap_or_else(|_| error!("queue err"));
+        }
+    }
+
+    /// Forgets finished players, handles are removed once all of their players finished
     pub fn clean_finished_tasks(&mut self) {
+        self.control_handles.retain(|_, handles| {
+            handles.retain(|x| !x.cancellation_token.is_cancelled());
+            !handles.is_empty()
+        });
+        self.commanded.retain(|handle| self.control_handles.contains_key(&handle));
+    }
+
+    /// Whether any player of 'handle' is still running
+    pub fn is_running(&self, handle: i32) -> bool {
         self.control_handles
-            .retain(|_, handles| {
-                ! handles.first().and_then(|x| Some(x.cancellation_token.is_cancelled()) ).unwrap_or(false)
-            }  )
+            .get(&handle)
+            .is_some_and(|handles| handles.iter().any(|x| !x.cancellation_token.is_cancelled()))
+    }
+
+    /// Completes once all current players of 'handle' finished or were stopped,
+    /// players that are added to the handle afterwards are not awaited
+    pub fn finished(&self, handle: i32) -> impl Future<Output = ()> + Send + 'static {
+        let tokens = self
+            .control_handles
+            .get(&handle)
+            .map(|handles| handles.iter().map(|x| x.cancellation_token.clone()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        async move {
+            for token in tokens {
+                token.cancelled().await;
+            }
+        }
     }
 
     fn get_next_handle(&mut self) -> i32 {
@@ -168,6 +1005,7 @@ async fn cancellable_wait(duration: Duration, cancel: &CancellationToken) -> boo
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::sync::Arc;
     use std::thread;
     use std::time::{Duration, Instant};
@@ -177,9 +1015,13 @@ mod tests {
     use futures::future::join_all;
 
     use buttplug::client::ButtplugClientDevice;
-    use buttplug::core::message::ActuatorType;
+    use buttplug::core::message::{
+        ActuatorType, ButtplugCurrentSpecClientMessage, ClientDeviceMessageAttributesBuilder,
+        ClientGenericDeviceMessageAttributes, DeviceAdded,
+    };
 
     use tokio::runtime::Handle;
+    use tokio::sync::mpsc::Receiver;
     use tokio::task::JoinHandle;
     use tokio::time::timeout;
 
@@ -187,42 +1029,49 @@ mod tests {
     use crate::player::PatternPlayer;
     use crate::config::*;
     use crate::config::linear::*;
+    use crate::config::scalar::*;
+    use crate::config::client::ResourceLimits;
     use crate::speed::Speed;
-    
+
     use bp_fakes::*;
 
-    use super::{Actuator, ButtplugScheduler, PlayerSettings};
+    use super::{
+        Actuator, ButtplugScheduler, CapacityError, PlayerSettings, SchedulerEvent,
+    };
 
-    struct PlayerTest {
+    pub(crate) struct PlayerTest {
         pub scheduler: ButtplugScheduler,
         pub handles: Vec<JoinHandle<()>>,
         pub actuators: Vec<Arc<Actuator>>,
+        pub events: Receiver<SchedulerEvent>,
     }
 
     impl PlayerTest {
-        fn setup_no_settings(devices: &Vec<Arc<ButtplugClientDevice>>) -> Self {
+        pub(crate) fn setup_no_settings(devices: &Vec<Arc<ButtplugClientDevice>>) -> Self {
             PlayerTest::setup_with_settings(
                 devices.flatten_actuators().clone(),
                 PlayerSettings {
                     scalar_resolution_ms: 1,
+                    ..Default::default()
                 },
             )
         }
 
-        fn setup(actuators: Vec<Arc<Actuator>>) -> Self {
+        pub(crate) fn setup(actuators: Vec<Arc<Actuator>>) -> Self {
             PlayerTest::setup_with_settings(
                 actuators,
                 PlayerSettings {
                     scalar_resolution_ms: 1,
+                    ..Default::default()
                 },
             )
         }
 
-        fn setup_with_settings(
+        pub(crate) fn setup_with_settings(
             actuators: Vec<Arc<Actuator>>,
             settings: PlayerSettings,
         ) -> Self {
-            let (scheduler, mut worker) = ButtplugScheduler::create(settings);
+            let (scheduler, mut worker, events) = ButtplugScheduler::create_with_events(settings);
             Handle::current().spawn(async move {
                 worker.run_worker_thread().await;
             });
@@ -230,10 +1079,11 @@ mod tests {
                 scheduler,
                 handles: vec![],
                 actuators,
+                events,
             }
         }
 
-        async fn play_scalar_pattern(
+        pub(crate) async fn play_scalar_pattern(
             &mut self,
             duration: Duration,
             fscript: FScript,
@@ -246,7 +1096,7 @@ mod tests {
                 .unwrap();
         }
 
-        fn play_scalar(
+        pub(crate) fn play_scalar(
             &mut self,
             duration: Duration,
             speed: Speed
@@ -257,16 +1107,16 @@ mod tests {
             }));
         }
 
-        fn get_player(&mut self) -> PatternPlayer {
+        pub(crate) fn get_player(&mut self) -> PatternPlayer {
             self.scheduler
                 .create_player(self.actuators.clone(), -1 )
         }
 
-        fn get_player_with_settings(&mut self, handle: i32) -> PatternPlayer {
+        pub(crate) fn get_player_with_settings(&mut self, handle: i32) -> PatternPlayer {
             self.scheduler.create_player(self.actuators.clone(), handle)
         }
 
-        async fn play_linear(&mut self, funscript: FScript, duration: Duration) {
+        pub(crate) async fn play_linear(&mut self, funscript: FScript, duration: Duration) {
             let player = self
                 .scheduler
                 .create_player(self.actuators.clone(), -1);
@@ -276,11 +1126,11 @@ mod tests {
                 .unwrap();
         }
 
-        async fn await_last(&mut self) {
+        pub(crate) async fn await_last(&mut self) {
             let _ = self.handles.pop().unwrap().await;
         }
 
-        async fn await_all(self) {
+        pub(crate) async fn await_all(self) {
             join_all(self.handles).await;
         }
     }
@@ -319,7 +1169,7 @@ mod tests {
     async fn test_stroke_linear_1() {
         let (client, _) = test_stroke(
             Speed::new(100),
-            LinearRange{ min_pos: 0.0, max_pos: 1.0, min_ms: 50, max_ms: 400, invert: false, scaling: crate::config::linear::LinearSpeedScaling::Linear },
+            LinearRange{ min_pos: 0.0, max_pos: 1.0, min_ms: 50, max_ms: 400, invert: false, scaling: crate::config::linear::LinearSpeedScaling::Linear, profile: StrokeProfile::Constant },
         )
         .await;
 
@@ -333,7 +1183,7 @@ mod tests {
     async fn test_stroke_linear_2() {
         let (client, _) = test_stroke(
             Speed::new(0),
-            LinearRange{ min_pos: 1.0, max_pos: 0.0, min_ms: 10, max_ms: 100, invert: false, scaling: crate::config::linear::LinearSpeedScaling::Linear }
+            LinearRange{ min_pos: 1.0, max_pos: 0.0, min_ms: 10, max_ms: 100, invert: false, scaling: crate::config::linear::LinearSpeedScaling::Linear, profile: StrokeProfile::Constant }
         )
         .await;
 
@@ -347,7 +1197,7 @@ mod tests {
     async fn test_stroke_linear_3() {
         let (client, _) = test_stroke(
             Speed::new(75),
-            LinearRange{ min_pos: 0.2, max_pos: 0.7, min_ms: 100, max_ms: 200, invert: false, scaling: crate::config::linear::LinearSpeedScaling::Linear }
+            LinearRange{ min_pos: 0.2, max_pos: 0.7, min_ms: 100, max_ms: 200, invert: false, scaling: crate::config::linear::LinearSpeedScaling::Linear, profile: StrokeProfile::Constant }
         )
         .await;
 
@@ -361,7 +1211,7 @@ mod tests {
     async fn test_stroke_linear_invert() {
         let (client, _) = test_stroke(
             Speed::new(100),
-            LinearRange{ min_pos: 0.2, max_pos: 0.7, min_ms: 50, max_ms: 50, invert: true, scaling: crate::config::linear::LinearSpeedScaling::Linear }
+            LinearRange{ min_pos: 0.2, max_pos: 0.7, min_ms: 50, max_ms: 50, invert: true, scaling: crate::config::linear::LinearSpeedScaling::Linear, profile: StrokeProfile::Constant }
         )
         .await;
 
@@ -371,6 +1221,21 @@ mod tests {
         calls[2].assert_pos(0.3);
     }
 
+    #[tokio::test]
+    async fn test_stroke_linear_ease_in_out() {
+        let (client, start) = test_stroke(
+            Speed::new(100),
+            LinearRange { min_ms: 300, max_ms: 300, profile: StrokeProfile::EaseInOut(3), ..LinearRange::max() },
+        )
+        .await;
+
+        let calls = client.get_device_calls(1);
+        calls[0].assert_duration(100).assert_pos(0.25).assert_time(0, start);
+        calls[1].assert_duration(100).assert_pos(0.75).assert_time(100, start);
+        calls[2].assert_duration(100).assert_pos(1.0).assert_time(200, start);
+        calls[3].assert_duration(100).assert_pos(0.75).assert_time(300, start);
+    }
+
     #[tokio::test]
     async fn test_stroke_update() {
         let client: ButtplugTestClient = get_test_client(vec![linear(1, "lin1")]).await;
@@ -390,7 +1255,8 @@ mod tests {
                         min_ms: 10, 
                         max_ms: 100, 
                         invert: true, 
-                        scaling: crate::config::linear::LinearSpeedScaling::Linear
+                        scaling: crate::config::linear::LinearSpeedScaling::Linear,
+                        profile: StrokeProfile::Constant,
                     })
                 .await;
         });
@@ -409,7 +1275,7 @@ mod tests {
         let client = get_test_client(vec![linear(1, "lin1")]).await;
 
         let mut config = ActuatorSettings::default();
-        config.update_device(ActuatorConfig { actuator_config_id: "lin1 (Position)".into(), enabled: true, body_parts: vec![], limits: ActuatorLimits::Linear(range.clone()) } );
+        config.update_device(ActuatorConfig { actuator_config_id: "lin1 (Position)".into(), enabled: true, body_parts: vec![], limits: ActuatorLimits::Linear(range.clone()), ..Default::default() } );
 
         let actuators = client.created_devices.flatten_actuators().load_config(&mut config).clone();
         let mut test = PlayerTest::setup(actuators);
@@ -426,6 +1292,24 @@ mod tests {
         (client, start)
     }
 
+    #[tokio::test]
+    async fn test_new_linear_configs_use_scaling_profiles() {
+        let client = get_test_client(vec![linear(1, "Kiiroo Keon"), linear(2, "lin2"), linear(3, "lin3")]).await;
+        let mut config = ActuatorSettings::default();
+        let profiles = vec![ScalingProfile::new("lin2", LinearSpeedScaling::Parabolic(3))];
+
+        client.created_devices.flatten_actuators().load_config_with_profiles(&mut config, &profiles);
+
+        let scaling = |id: &str| match config.get_config(id).unwrap().limits {
+            ActuatorLimits::Linear(range) => Some(range.scaling),
+            _ => None,
+        };
+        assert_eq!(scaling("Kiiroo Keon (Position)"), Some(LinearSpeedScaling::Parabolic(2)));
+        assert_eq!(scaling("lin2 (Position)"), Some(LinearSpeedScaling::Parabolic(3)));
+        assert_eq!(scaling("lin3 (Position)"), None);
+        assert_eq!(config.get_config("Kiiroo Keon (Position)").unwrap().suggested_body_parts, vec!["penis"]);
+    }
+
     #[tokio::test]
     async fn test_linear_empty_pattern_finishes_and_does_not_panic() {
         let client = get_test_client(vec![linear(1, "lin1")]).await;
@@ -472,6 +1356,30 @@ mod tests {
             .assert_time(200, start);
     }
 
+    #[tokio::test]
+    async fn test_linear_multiple_actuators_await_all_results() {
+        // arrange
+        let client = get_test_client(vec![linear(1, "lin1"), linear(2, "lin2")]).await;
+        let mut player = PlayerTest::setup(client.created_devices.flatten_actuators().clone());
+
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 0, at: 200 });
+        fscript.actions.push(FSPoint { pos: 100, at: 400 });
+
+        // act
+        let start = Instant::now();
+        let duration = get_duration_ms(&fscript);
+        player.play_linear(fscript, duration).await;
+
+        // assert
+        client.print_device_calls(start);
+        for device in [1, 2] {
+            let calls = client.get_device_calls(device);
+            calls[0].assert_pos(0.0).assert_time(0, start);
+            calls[1].assert_pos(1.0).assert_time(200, start);
+        }
+    }
+
     #[tokio::test]
     async fn test_linear_timing_remains_synced_with_clock() {
         // arrange
@@ -664,6 +1572,7 @@ mod tests {
             client.created_devices.flatten_actuators().clone(),
             PlayerSettings {
                 scalar_resolution_ms: 100,
+                ..Default::default()
             },
         );
 
@@ -741,6 +1650,575 @@ mod tests {
             .assert_time(300, start);
     }
 
+    #[tokio::test]
+    async fn test_scalar_speed_lanes_per_actuator() {
+        // arrange
+        let client = get_test_client(vec![
+            scalar(1, "vib1", ActuatorType::Vibrate),
+            scalar(2, "vib2", ActuatorType::Vibrate),
+        ])
+        .await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(200), Speed::new(100));
+        wait_ms(100).await;
+        player.scheduler.update_task_lanes(1, HashMap::from([("vib2 (Vibrate)".into(), Speed::new(20))]));
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        client.get_device_calls(1)[0].assert_strenth(1.0);
+        client.get_device_calls(1)[1].assert_strenth(1.0);
+        client.get_device_calls(2)[0].assert_strenth(1.0);
+        client.get_device_calls(2)[1].assert_strenth(0.2).assert_time(100, start);
+    }
+
+    #[tokio::test]
+    async fn test_step_up_and_down_follow_speed_ladder() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(300), Speed::new(30));
+        wait_ms(100).await;
+        let handle = player.scheduler.control_handles.keys().copied().max().unwrap();
+        assert_eq!(player.scheduler.step_up(handle), Some(Speed::new(50)));
+        assert_eq!(player.scheduler.step_up(handle), Some(Speed::new(75)));
+        wait_ms(100).await;
+        assert_eq!(player.scheduler.step_down(handle), Some(Speed::new(50)));
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.3).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(100, start);
+        calls[2].assert_strenth(0.75).assert_time(100, start);
+        calls[3].assert_strenth(0.5).assert_time(200, start);
+        calls[4].assert_strenth(0.0).assert_time(300, start);
+    }
+
+    #[tokio::test]
+    async fn test_changed_limits_apply_to_running_scalar() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(200), Speed::max());
+        wait_ms(50).await;
+        let mut config = ActuatorSettings::default();
+        config.update_device(ActuatorConfig {
+            actuator_config_id: "vib1 (Vibrate)".into(),
+            enabled: true,
+            limits: ActuatorLimits::Scalar(ScalarRange { max_speed: 40, ..Default::default() }),
+            ..Default::default()
+        });
+        player.scheduler.update_actuator_configs(&config.0);
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        client.get_device_calls(1)[0].assert_strenth(1.0);
+        client.get_device_calls(1)[1].assert_strenth(0.4).assert_time(50, start);
+        client.get_device_calls(1)[2].assert_strenth(0.0);
+    }
+
+    #[tokio::test]
+    async fn test_pattern_zero_plays_min_speed() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let mut config = ActuatorSettings::default();
+        config.update_device(ActuatorConfig {
+            actuator_config_id: "vib1 (Vibrate)".into(),
+            enabled: true,
+            limits: ActuatorLimits::Scalar(ScalarRange {
+                min_speed: 20,
+                pattern_zero: PatternZero::MinSpeed,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        player.scheduler.sync_actuator_configs(&config.0);
+
+        // act
+        let mut fs = FScript::default();
+        fs.actions.push(FSPoint { pos: 100, at: 0 });
+        fs.actions.push(FSPoint { pos: 0, at: 100 });
+        fs.actions.push(FSPoint { pos: 50, at: 200 });
+        let start = Instant::now();
+        player
+            .play_scalar_pattern(Duration::from_millis(250), fs, Speed::max())
+            .await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.2).assert_time(100, start);
+        calls[2].assert_strenth(0.5).assert_time(200, start);
+        calls[3].assert_strenth(0.0).assert_time(250, start);
+    }
+
+    #[tokio::test]
+    async fn test_wind_down_lowers_outputs_in_stages() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_secs(10), Speed::max());
+        wait_ms(50).await;
+        let stages = vec![
+            (Speed::new(30), Duration::from_millis(100)),
+            (Speed::new(10), Duration::from_millis(100)),
+        ];
+        player.scheduler.wind_down(stages).await;
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(1.0);
+        calls[1].assert_strenth(0.3).assert_time(50, start);
+        calls[2].assert_strenth(0.1).assert_time(150, start);
+        calls[3].assert_strenth(0.0).assert_time(250, start);
+        assert_eq!(calls.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_wind_down_caps_and_stops_tasks_started_during_it() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let start = Instant::now();
+        let wind_down = player.scheduler.wind_down(vec![(Speed::new(30), Duration::from_millis(200))]);
+        let wind_down = Handle::current().spawn(wind_down);
+        wait_ms(50).await;
+        player.play_scalar(Duration::from_secs(10), Speed::max());
+        let _ = wind_down.await;
+        let finished = timeout(Duration::from_secs(1), player.await_all()).await;
+
+        // assert
+        client.print_device_calls(start);
+        assert!(finished.is_ok());
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.3).assert_time(50, start);
+        calls[1].assert_strenth(0.0).assert_time(200, start);
+        assert_eq!(calls.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_wind_down_keeps_linear_amplitude_until_the_end() {
+        // arrange
+        let client = get_test_client(vec![linear(1, "lin1")]).await;
+        let mut player = PlayerTest::setup(client.created_devices.flatten_actuators().clone());
+        let range = LinearRange { min_ms: 100, max_ms: 100, ..LinearRange::max() };
+
+        // act
+        let start = Instant::now();
+        let stroke = player.get_player();
+        let stroke = Handle::current().spawn(async move {
+            stroke.play_linear_stroke(Duration::from_secs(10), Speed::max(), range).await
+        });
+        wait_ms(50).await;
+        player.scheduler.wind_down(vec![(Speed::new(10), Duration::from_millis(200))]).await;
+        let finished = timeout(Duration::from_secs(1), stroke).await;
+
+        // assert
+        client.print_device_calls(start);
+        assert!(finished.is_ok());
+        let calls = client.get_device_calls(1);
+        calls[1].assert_pos(1.0).assert_duration(100).assert_time(100, start);
+        calls[2].assert_pos(0.0).assert_duration(100).assert_time(200, start);
+        assert_eq!(calls.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_resource_limits_refuse_new_players() {
+        let (mut scheduler, _) = ButtplugScheduler::create(PlayerSettings::default());
+        assert!(scheduler.check_capacity(-1, 1000).is_ok());
+        scheduler.set_resource_limits(Some(ResourceLimits { max_handles: 1, max_players: 2 }));
+
+        let player = scheduler.try_create_player(vec![], -1).unwrap();
+        assert_eq!(scheduler.check_capacity(-1, 1), Err(CapacityError::TooManyHandles(1, 1)));
+        let _child = scheduler.try_create_player(vec![], player.handle).unwrap();
+        assert!(matches!(
+            scheduler.try_create_player(vec![], player.handle),
+            Err(CapacityError::TooManyPlayers(2, 2))
+        ));
+
+        scheduler.stop_task(player.handle);
+        assert!(scheduler.check_capacity(-1, 2).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_play_rotate_sends_rotate_commands_in_direction() {
+        // arrange
+        let client = get_test_client(vec![rotate(1, "rot1")]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let start = Instant::now();
+        let rotate_player = player.scheduler.create_player(player.actuators.clone(), -1);
+        rotate_player
+            .play_rotate(Duration::from_millis(200), Speed::new(80), false)
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        assert_rotation(&calls[0], 0.8, false);
+        calls[0].assert_time(0, start);
+        assert_rotation(&calls[1], 0.0, false);
+        calls[1].assert_time(200, start);
+    }
+
+    #[tokio::test]
+    async fn test_rotate_pattern_changes_direction_of_rotate_devices() {
+        // arrange
+        let client = get_test_client(vec![rotate(1, "rot1")]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 100, at: 0 });
+        fscript.actions.push(FSPoint { pos: 0, at: 100 });
+        fscript.actions.push(FSPoint { pos: 0, at: 300 });
+
+        // act
+        let start = Instant::now();
+        let pattern_player = player.scheduler.create_player(player.actuators.clone(), -1);
+        pattern_player
+            .play_rotate_pattern(Duration::from_millis(200), fscript, Speed::max())
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        assert_rotation(&calls[0], 1.0, true);
+        calls[0].assert_time(0, start);
+        assert_rotation(&calls[1], 1.0, false);
+        calls[1].assert_time(100, start);
+        assert_rotation(&calls[2], 0.0, false);
+        calls[2].assert_time(200, start);
+    }
+
+    #[tokio::test]
+    async fn test_rotate_pattern_centers_on_half_position() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "rot1", ActuatorType::Rotate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 100, at: 0 });
+        fscript.actions.push(FSPoint { pos: 50, at: 100 });
+        fscript.actions.push(FSPoint { pos: 25, at: 200 });
+        fscript.actions.push(FSPoint { pos: 25, at: 400 });
+
+        // act
+        let start = Instant::now();
+        let pattern_player = player.scheduler.create_player(player.actuators.clone(), -1);
+        pattern_player
+            .play_rotate_pattern(Duration::from_millis(300), fscript, Speed::max())
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.0).assert_time(100, start);
+        calls[2].assert_strenth(0.5).assert_time(200, start);
+        calls[3].assert_strenth(0.0).assert_time(300, start);
+    }
+
+    #[tokio::test]
+    async fn test_min_speed_zero_does_not_apply_to_rests() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate), scalar(2, "rot1", ActuatorType::Rotate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let mut config = ActuatorSettings::default();
+        for actuator_config_id in ["vib1 (Vibrate)", "rot1 (Rotate)"] {
+            config.update_device(ActuatorConfig {
+                actuator_config_id: actuator_config_id.into(),
+                enabled: true,
+                limits: ActuatorLimits::Scalar(ScalarRange {
+                    min_speed: 20,
+                    pattern_zero: PatternZero::MinSpeed,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            });
+        }
+        player.scheduler.sync_actuator_configs(&config.0);
+        player.scheduler.set_loop_gap(Some(Duration::from_millis(100)));
+        let vib = vec![player.actuators[0].clone()];
+        let rot = vec![player.actuators[1].clone()];
+
+        // act
+        let mut fs = FScript::default();
+        fs.actions.push(FSPoint { pos: 100, at: 0 });
+        fs.actions.push(FSPoint { pos: 50, at: 100 });
+        let mut centered = FScript::default();
+        centered.actions.push(FSPoint { pos: 100, at: 0 });
+        centered.actions.push(FSPoint { pos: 50, at: 100 });
+        centered.actions.push(FSPoint { pos: 50, at: 1000 });
+        let start = Instant::now();
+        let vib_player = player.scheduler.create_player(vib, -1);
+        let rot_player = player.scheduler.create_player(rot, -1);
+        let _ = futures::future::join(
+            vib_player.play_scalar_pattern(Duration::from_millis(250), fs, Speed::max()),
+            rot_player.play_rotate_pattern(Duration::from_millis(250), centered, Speed::max()),
+        )
+        .await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(100, start);
+        calls[2].assert_strenth(0.0).assert_time(100, start);
+        calls[3].assert_strenth(1.0).assert_time(200, start);
+        let calls = client.get_device_calls(2);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.0).assert_time(100, start);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_pattern_starts_at_offset() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 100, at: 0 });
+        fscript.actions.push(FSPoint { pos: 50, at: 100 });
+        fscript.actions.push(FSPoint { pos: 20, at: 200 });
+        fscript.actions.push(FSPoint { pos: 80, at: 300 });
+
+        // act
+        let start = Instant::now();
+        let pattern_player = player.scheduler.create_player(player.actuators.clone(), -1);
+        pattern_player
+            .play_scalar_pattern_from(
+                Duration::from_millis(150),
+                fscript,
+                Speed::max(),
+                Duration::from_millis(450),
+            )
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.5).assert_time(0, start);
+        calls[1].assert_strenth(0.2).assert_time(50, start);
+        calls[2].assert_strenth(0.0).assert_time(150, start);
+    }
+
+    #[tokio::test]
+    async fn test_linear_pattern_starts_at_offset() {
+        // arrange
+        let client = get_test_client(vec![linear(1, "lin1")]).await;
+        let mut player = PlayerTest::setup(client.created_devices.flatten_actuators().clone());
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 0, at: 0 });
+        fscript.actions.push(FSPoint { pos: 100, at: 100 });
+        fscript.actions.push(FSPoint { pos: 0, at: 200 });
+        fscript.actions.push(FSPoint { pos: 100, at: 300 });
+
+        // act
+        let start = Instant::now();
+        player
+            .get_player()
+            .play_linear_from(Duration::from_millis(250), fscript, Duration::from_millis(450))
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_pos(0.0).assert_duration(50).assert_time(0, start);
+        calls[1].assert_pos(1.0).assert_duration(100).assert_time(50, start);
+        calls[2].assert_pos(1.0).assert_duration(100).assert_time(150, start);
+    }
+
+    #[tokio::test]
+    async fn test_retarget_moves_task_to_other_actuators() {
+        // arrange
+        let client = get_test_client(vec![
+            scalar(1, "vib1", ActuatorType::Vibrate),
+            scalar(2, "vib2", ActuatorType::Vibrate),
+        ])
+        .await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let pattern_player = player.scheduler.create_player(vec![player.actuators[0].clone()], -1);
+        let handle = pattern_player.handle;
+
+        // act
+        let start = Instant::now();
+        let task = Handle::current().spawn(pattern_player.play_scalar(Duration::from_millis(200), Speed::new(60)));
+        wait_ms(100).await;
+        let retargeted = player.scheduler.retarget_task(handle, vec![player.actuators[1].clone()]);
+        let description = player.scheduler.describe_handle(handle).unwrap();
+        let result = task.await.unwrap();
+
+        // assert
+        assert!(retargeted);
+        assert!(result.is_ok());
+        assert_eq!(
+            description.actuators.iter().map(|x| x.actuator.identifier()).collect::<Vec<_>>(),
+            vec!["vib2 (Vibrate)"]
+        );
+        assert!(!player.scheduler.retarget_task(42, vec![]));
+        client.print_device_calls(start);
+        let removed = client.get_device_calls(1);
+        removed[0].assert_strenth(0.6).assert_time(0, start);
+        removed[1].assert_strenth(0.0).assert_time(100, start);
+        let added = client.get_device_calls(2);
+        added[0].assert_strenth(0.6).assert_time(100, start);
+        added[1].assert_strenth(0.0).assert_time(200, start);
+    }
+
+    #[tokio::test]
+    async fn test_child_players_share_their_handle() {
+        // arrange
+        let client = get_test_client(vec![
+            scalar(1, "vib1", ActuatorType::Vibrate),
+            scalar(2, "vib2", ActuatorType::Vibrate),
+        ])
+        .await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let parent = player.scheduler.create_player(vec![player.actuators[0].clone()], -1);
+        let handle = parent.handle;
+        let child = player.scheduler.create_player(vec![player.actuators[1].clone()], handle);
+        assert_eq!(child.handle, handle);
+        let finished = player.scheduler.finished(handle);
+
+        // act
+        let start = Instant::now();
+        let parent_task = Handle::current().spawn(parent.play_scalar(Duration::from_millis(100), Speed::new(50)));
+        let child_task = Handle::current().spawn(child.play_scalar(Duration::from_secs(10), Speed::new(50)));
+        wait_ms(150).await;
+        player.scheduler.clean_finished_tasks();
+        let running_after_parent = player.scheduler.is_running(handle);
+        let updated = player.scheduler.update_task(handle, Speed::new(80));
+        wait_ms(50).await;
+        player.scheduler.stop_task(handle);
+        finished.await;
+        let _ = join_all(vec![parent_task, child_task]).await;
+
+        // assert
+        assert!(running_after_parent);
+        assert!(updated);
+        assert!(!player.scheduler.is_running(handle));
+        client.print_device_calls(start);
+        client.get_device_calls(1)[1].assert_strenth(0.0).assert_time(100, start);
+        client.get_device_calls(2)[1].assert_strenth(0.8).assert_time(150, start);
+        client.get_device_calls(2)[2].assert_strenth(0.0).assert_time(200, start);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_parent_handle_creates_new_handle() {
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        let orphan = player.scheduler.create_player(player.actuators.clone(), 42);
+
+        assert_ne!(orphan.handle, 42);
+        assert!(player.scheduler.is_running(orphan.handle));
+        assert!(!player.scheduler.is_running(42));
+    }
+
+    #[tokio::test]
+    async fn test_update_and_stop_many_handles() {
+        // arrange
+        let client = get_test_client(vec![
+            scalar(1, "vib1", ActuatorType::Vibrate),
+            scalar(2, "vib2", ActuatorType::Vibrate),
+        ])
+        .await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        for actuator in player.actuators.clone() {
+            let task = player.scheduler.create_player(vec![actuator], -1);
+            player.handles.push(Handle::current().spawn(async move {
+                let _ = task.play_scalar(Duration::from_secs(10), Speed::new(50)).await;
+            }));
+        }
+
+        // act
+        let start = Instant::now();
+        wait_ms(50).await;
+        let found = player.scheduler.update_tasks(&[(1, Speed::new(80)), (2, Speed::new(20)), (3, Speed::max())]);
+        wait_ms(50).await;
+        player.scheduler.stop_tasks(&[1, 2]);
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        assert_eq!(found, vec![true, true, false]);
+        client.get_device_calls(1)[1].assert_strenth(0.8).assert_time(50, start);
+        client.get_device_calls(2)[1].assert_strenth(0.2).assert_time(50, start);
+        client.get_device_calls(1)[2].assert_strenth(0.0).assert_time(100, start);
+        client.get_device_calls(2)[2].assert_strenth(0.0).assert_time(100, start);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_boost_reverts_after_duration() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(300), Speed::new(50));
+        wait_ms(50).await;
+        player.scheduler.boost_task(1, Speed::max(), Duration::from_millis(100));
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        client.get_device_calls(1)[0].assert_strenth(0.5);
+        client.get_device_calls(1)[1].assert_strenth(1.0).assert_time(50, start);
+        client.get_device_calls(1)[2].assert_strenth(0.5).assert_time(150, start);
+        client.get_device_calls(1)[3].assert_strenth(0.0).assert_time(300, start);
+    }
+
+    #[tokio::test]
+    async fn test_stop_action_stops_all_its_handles() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let mut play = |action: &str| {
+            let player = player.scheduler.create_action_player(vec![], -1, action);
+            Handle::current().spawn(async move {
+                let _ = player.play_scalar(Duration::from_secs(10), Speed::max()).await;
+            })
+        };
+        let first = play("vibrate");
+        let _other = play("inflate");
+        let second = play("Vibrate");
+
+        // act
+        let stopped = player.scheduler.stop_action("vibrate ");
+
+        // assert
+        assert_eq!(stopped, vec![1, 3]);
+        first.await.unwrap();
+        second.await.unwrap();
+        assert_eq!(player.scheduler.handles_for_action("vibrate"), Vec::<i32>::new());
+        assert_eq!(player.scheduler.handles_for_action("inflate"), vec![2]);
+    }
+
     #[tokio::test]
     async fn test_clean_finished_tasks() {
         // arrange
@@ -923,7 +2401,7 @@ mod tests {
         client.get_device_calls(2)[1].assert_strenth(0.0);
     }
 
-    async fn wait_ms(ms: u64) {
+    pub(crate) async fn wait_ms(ms: u64) {
         tokio::time::sleep(Duration::from_millis(ms)).await;
     }
 
@@ -931,6 +2409,23 @@ mod tests {
         Duration::from_millis(fs.actions.last().unwrap().at as u64)
     }
 
+    /// Device that is only controlled with `RotateCmd`
+    pub(crate) fn rotate(id: u32, name: &str) -> DeviceAdded {
+        let mut attributes = ClientDeviceMessageAttributesBuilder::default();
+        attributes.rotate_cmd(&[ClientGenericDeviceMessageAttributes::new(name, 20, ActuatorType::Rotate)]);
+        DeviceAdded::new(id, name, &None, &None, &attributes.finish())
+    }
+
+    pub(crate) fn assert_rotation(call: &FakeMessage, speed: f64, clockwise: bool) {
+        match &call.message {
+            ButtplugCurrentSpecClientMessage::RotateCmd(cmd) => {
+                let rotation = &cmd.rotations()[0];
+                assert_eq!((rotation.speed(), rotation.clockwise()), (speed, clockwise));
+            }
+            message => panic!("expected a rotate command, got {:?}", message),
+        }
+    }
+
     fn check_timing(device_calls: Vec<FakeMessage>, n: usize, start: Instant) {
         for i in 0..n - 1 {
             device_calls[i].assert_time((i * 100) as i32, start);
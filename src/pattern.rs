@@ -1,8 +1,59 @@
-use std::{path::PathBuf, time::Instant, fs};
+use std::{collections::{HashMap, HashSet}, fmt::{self, Display}, fs, mem, path::PathBuf, time::{Duration, Instant}};
+#[cfg(feature = "pattern-watch")]
+use std::{path::Path, sync::{Arc, Mutex}};
 use anyhow::anyhow;
-use tracing::{error, debug};
+use serde::{Deserialize, Serialize};
+use tracing::{error, debug, warn};
+#[cfg(feature = "pattern-watch")]
+use tracing::info;
 
-use funscript::FScript;
+use funscript::{FSPoint, FScript};
+
+use crate::player::options::Interpolation;
+
+/// Optional information from the funscript 'metadata' block
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PatternMetadata {
+    pub title: String,
+    pub creator: String,
+    pub description: String,
+    pub performers: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PatternInfo {
+    pub name: String,
+    pub is_vibration: bool,
+    /// time of the last action in the pattern
+    pub duration: Duration,
+    /// number of actions in the pattern
+    #[serde(default)]
+    pub points: usize,
+    pub metadata: PatternMetadata,
+    /// axes of a multi-axis pattern that have their own '<name>.<axis>.funscript'
+    #[serde(default)]
+    pub axes: Vec<String>,
+}
+
+impl PatternInfo {
+    pub fn duration_within(&self, min: Duration, max: Duration) -> bool {
+        self.duration >= min && self.duration <= max
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct FunscriptHeader {
+    metadata: PatternMetadata,
+    actions: Vec<FunscriptActionTime>,
+}
+
+#[derive(Deserialize)]
+struct FunscriptActionTime {
+    at: i64,
+}
 
 pub fn get_pattern_names(pattern_path: &str, vibration_patterns: bool) -> Vec<String> {
     match get_pattern_paths(pattern_path) {
@@ -18,6 +69,392 @@ pub fn get_pattern_names(pattern_path: &str, vibration_patterns: bool) -> Vec<St
     }
 }
 
+/// Lists all patterns with their metadata, patterns that
+/// cannot be parsed are skipped
+pub fn get_pattern_infos(pattern_path: &str, vibration_patterns: bool) -> Vec<PatternInfo> {
+    match get_pattern_paths(pattern_path) {
+        Ok(patterns) => group_axes(
+            patterns
+                .iter()
+                .filter(|p| p.is_vibration == vibration_patterns)
+                .filter_map(|p| match read_pattern_info(p) {
+                    Ok(info) => Some(info),
+                    Err(err) => {
+                        error!("Failed reading pattern info {} {}", p.name, err);
+                        None
+                    }
+                })
+                .collect(),
+        ),
+        Err(err) => {
+            error!("Failed reading patterns {}", err);
+            vec![]
+        }
+    }
+}
+
+/// Lists the axis files of multi-axis patterns in `PatternInfo::axes` of their main
+/// script instead of as patterns of their own
+fn group_axes(infos: Vec<PatternInfo>) -> Vec<PatternInfo> {
+    let axis_of = |info: &PatternInfo| {
+        let (base, axis) = info.name.rsplit_once('.')?;
+        let axis = AXES.iter().find(|x| x.eq_ignore_ascii_case(axis))?;
+        infos
+            .iter()
+            .any(|x| !x.is_vibration && x.name.eq_ignore_ascii_case(base))
+            .then(|| (base.to_lowercase(), axis.to_string()))
+    };
+    let axes = infos
+        .iter()
+        .filter(|x| !x.is_vibration)
+        .filter_map(axis_of)
+        .collect::<Vec<_>>();
+    infos
+        .iter()
+        .filter(|x| x.is_vibration || axis_of(x).is_none())
+        .map(|info| {
+            let mut info = info.clone();
+            if !info.is_vibration {
+                info.axes = axes
+                    .iter()
+                    .filter(|(base, _)| info.name.eq_ignore_ascii_case(base))
+                    .map(|(_, axis)| axis.clone())
+                    .collect();
+                info.axes.sort();
+            }
+            info
+        })
+        .collect()
+}
+
+/// Lists all patterns with a duration between 'min' and 'max'
+pub fn get_pattern_infos_by_duration(
+    pattern_path: &str,
+    vibration_patterns: bool,
+    min: Duration,
+    max: Duration,
+) -> Vec<PatternInfo> {
+    get_pattern_infos(pattern_path, vibration_patterns)
+        .into_iter()
+        .filter(|p| p.duration_within(min, max))
+        .collect()
+}
+
+fn read_pattern_info(pattern: &PatternIntern) -> Result<PatternInfo, anyhow::Error> {
+    let header: FunscriptHeader = serde_json::from_str(&fs::read_to_string(&pattern.path)?)?;
+    let last_at = header.actions.iter().map(|x| x.at).max().unwrap_or(0);
+    Ok(PatternInfo {
+        name: pattern.name.clone(),
+        is_vibration: pattern.is_vibration,
+        duration: Duration::from_millis(last_at.max(0) as u64),
+        points: header.actions.len(),
+        metadata: header.metadata,
+        axes: vec![],
+    })
+}
+
+/// Copy of the actions of 'fscript', the funscript types are not `Clone`
+pub fn copy_actions(fscript: &FScript) -> FScript {
+    FScript {
+        actions: fscript.actions.iter().map(|x| FSPoint { pos: x.pos, at: x.at }).collect(),
+        ..Default::default()
+    }
+}
+
+/// Copy of 'fscript' with its timestamps scaled so that the last action is at 'duration'
+pub fn fit_to_duration(fscript: &FScript, duration: Duration) -> FScript {
+    let last_at = fscript.actions.iter().map(|x| x.at).max().unwrap_or(0);
+    if last_at <= 0 {
+        return copy_actions(fscript);
+    }
+    let factor = duration.as_millis() as f64 / last_at as f64;
+    FScript {
+        actions: fscript
+            .actions
+            .iter()
+            .map(|x| FSPoint { pos: x.pos, at: (x.at as f64 * factor).round() as i32 })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Copy of 'fscript' with intermediate actions every 'resolution_ms' between two actions
+/// that are further apart and have different positions, placed along 'interpolation'
+pub fn upsample(fscript: &FScript, resolution_ms: i32, interpolation: Interpolation) -> FScript {
+    let resolution_ms = resolution_ms.max(1);
+    let mut actions = vec![];
+    for (i, point) in fscript.actions.iter().enumerate() {
+        actions.push(FSPoint { pos: point.pos, at: point.at });
+        let Some(next) = fscript.actions.get(i + 1).filter(|x| x.pos != point.pos) else {
+            continue;
+        };
+        let span = next.at - point.at;
+        for at in (point.at + resolution_ms..next.at).step_by(resolution_ms as usize) {
+            let t = interpolation.apply((at - point.at) as f64 / span as f64);
+            let pos = point.pos as f64 + (next.pos - point.pos) as f64 * t;
+            actions.push(FSPoint { pos: pos.round() as i32, at });
+        }
+    }
+    FScript { actions, ..Default::default() }
+}
+
+/// Most actions within one second of a pattern, denser patterns are faster than devices can follow
+pub const MAX_POINTS_PER_SEC: usize = 50;
+
+/// Problem of a pattern that makes its playback misbehave, actions by index
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum PatternIssue {
+    /// action and its timestamp that is earlier than the one of the action before
+    NonMonotonic(usize, i32),
+    /// action and its timestamp before the start of the pattern
+    NegativeTime(usize, i32),
+    /// action and its position, outside of 0 to 100
+    OutOfRange(usize, i32),
+    /// action with the same timestamp as the action before
+    ZeroDuration(usize),
+    /// timestamp where the densest second of the pattern starts and its number of actions
+    TooDense(i32, usize),
+}
+
+impl Display for PatternIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternIssue::NonMonotonic(index, at) => write!(f, "action {} at {}ms goes back in time", index, at),
+            PatternIssue::NegativeTime(index, at) => write!(f, "action {} at {}ms starts before the pattern", index, at),
+            PatternIssue::OutOfRange(index, pos) => write!(f, "action {} has position {} outside of 0-100", index, pos),
+            PatternIssue::ZeroDuration(index) => write!(f, "action {} has the same time as the action before", index),
+            PatternIssue::TooDense(at, points) => {
+                write!(f, "{} actions within one second at {}ms, at most {} can be played", points, at, MAX_POINTS_PER_SEC)
+            }
+        }
+    }
+}
+
+impl PatternIssue {
+    /// Playback can not follow the timeline of patterns with fatal issues, they are refused
+    /// when they are read. Positions are clamped and dense actions are skipped instead
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, PatternIssue::NonMonotonic(_, _) | PatternIssue::NegativeTime(_, _))
+    }
+}
+
+/// Checks the actions of 'fscript' for anything that playback cannot handle
+pub fn validate(fscript: &FScript) -> Vec<PatternIssue> {
+    let actions = &fscript.actions;
+    let mut issues = vec![];
+    for (index, point) in actions.iter().enumerate() {
+        if !(0..=100).contains(&point.pos) {
+            issues.push(PatternIssue::OutOfRange(index, point.pos));
+        }
+        if point.at < 0 {
+            issues.push(PatternIssue::NegativeTime(index, point.at));
+        }
+        match index.checked_sub(1).map(|x| actions[x].at) {
+            Some(previous) if point.at < previous => issues.push(PatternIssue::NonMonotonic(index, point.at)),
+            Some(previous) if point.at == previous => issues.push(PatternIssue::ZeroDuration(index)),
+            _ => {}
+        }
+    }
+    let mut times = actions.iter().map(|x| x.at).collect::<Vec<_>>();
+    times.sort();
+    let mut first = 0;
+    let mut densest = (0, 0);
+    for (last, at) in times.iter().enumerate() {
+        while at - times[first] >= 1000 {
+            first += 1;
+        }
+        if last + 1 - first > densest.1 {
+            densest = (times[first], last + 1 - first);
+        }
+    }
+    if densest.1 > MAX_POINTS_PER_SEC {
+        issues.push(PatternIssue::TooDense(densest.0, densest.1));
+    }
+    issues
+}
+
+/// Funscripts for the individual scalar actuators of multi-actuator devices,
+/// read from '<name>.bundle.json' in the pattern directory
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PatternBundle {
+    pub channels: Vec<BundleChannel>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BundleChannel {
+    /// index of the actuator within its device
+    pub actuator_index: u32,
+    /// name of the vibration pattern
+    pub pattern: String,
+    #[serde(default)]
+    pub transform: ChannelTransform,
+}
+
+/// Changes the values of a pattern for a single channel
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct ChannelTransform {
+    pub scale: f64,
+    /// added after scaling
+    pub offset: i32,
+    pub invert: bool,
+}
+
+impl Default for ChannelTransform {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            offset: 0,
+            invert: false,
+        }
+    }
+}
+
+impl ChannelTransform {
+    /// Copy of 'fscript' with all values transformed and clamped to 0-100
+    pub fn apply(&self, fscript: &FScript) -> FScript {
+        FScript {
+            actions: fscript
+                .actions
+                .iter()
+                .map(|x| {
+                    let pos = if self.invert { 100 - x.pos } else { x.pos };
+                    let pos = (pos as f64 * self.scale).round() as i32 + self.offset;
+                    FSPoint { pos: pos.clamp(0, 100), at: x.at }
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl PatternBundle {
+    /// Transformed pattern of each channel by actuator index, channels whose
+    /// pattern can't be read with 'read' are left out
+    pub fn load<F>(&self, read: F) -> HashMap<u32, FScript>
+    where
+        F: Fn(&str) -> Option<FScript>,
+    {
+        self.channels
+            .iter()
+            .filter_map(|channel| match read(&channel.pattern) {
+                Some(fscript) => Some((channel.actuator_index, channel.transform.apply(&fscript))),
+                None => {
+                    error!(channel.pattern, "error reading bundle pattern");
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn read_bundle(pattern_path: &str, bundle_name: &str) -> Option<PatternBundle> {
+    let path = [pattern_path, &format!("{}.bundle.json", bundle_name)].iter().collect::<PathBuf>();
+    let result = fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| serde_json::from_str::<PatternBundle>(&json).map_err(anyhow::Error::from));
+    match result {
+        Ok(bundle) => Some(bundle),
+        Err(err) => {
+            error!("Error loading pattern bundle={} err={}", bundle_name, err);
+            None
+        }
+    }
+}
+
+/// Axis of the main script of a multi-axis pattern
+pub const STROKE_AXIS: &str = "stroke";
+
+/// Axes of multi-axis patterns besides the stroke, each is read from '<name>.<axis>.funscript'
+pub const AXES: &[&str] = &["surge", "sway", "twist", "roll", "pitch"];
+
+/// Actuators that play an axis of a multi-axis pattern
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum AxisTarget {
+    /// actuators with the body part
+    BodyPart(String),
+    /// actuators with the index within their device
+    Index(u32),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AxisMapping {
+    pub axis: String,
+    pub target: AxisTarget,
+}
+
+impl AxisMapping {
+    pub fn new(axis: &str, target: AxisTarget) -> Self {
+        AxisMapping {
+            axis: axis.to_owned(),
+            target,
+        }
+    }
+}
+
+/// Maps the axes to the actuator indices of OSR2/SR6 devices in TCode order (L0, L1, L2, R0, R1, R2)
+pub fn default_axis_mapping() -> Vec<AxisMapping> {
+    [STROKE_AXIS]
+        .iter()
+        .chain(AXES)
+        .enumerate()
+        .map(|(i, axis)| AxisMapping::new(axis, AxisTarget::Index(i as u32)))
+        .collect()
+}
+
+/// Script of an axis together with the actuators that play it
+#[derive(Debug)]
+pub struct AxisChannel {
+    pub axis: String,
+    pub target: AxisTarget,
+    pub fscript: FScript,
+}
+
+/// Scripts of all axes of a pattern by axis name
+#[derive(Debug, Default)]
+pub struct MultiAxisPattern {
+    pub axes: HashMap<String, FScript>,
+}
+
+impl MultiAxisPattern {
+    /// Reads the stroke script '<name>.funscript' and the scripts of all other axes that
+    /// exist with 'read', None if there is no stroke script
+    pub fn load<F>(pattern_name: &str, read: F) -> Option<Self>
+    where
+        F: Fn(&str) -> Option<FScript>,
+    {
+        let mut axes = HashMap::new();
+        axes.insert(STROKE_AXIS.to_owned(), read(pattern_name)?);
+        for axis in AXES {
+            if let Some(fscript) = read(&format!("{}.{}", pattern_name, axis)) {
+                axes.insert((*axis).to_owned(), fscript);
+            }
+        }
+        debug!(pattern_name, axes = axes.len(), "read multi-axis pattern");
+        Some(MultiAxisPattern { axes })
+    }
+
+    /// Channels of all axes that are part of 'mapping' and of the pattern, in the order of 'mapping'
+    pub fn channels(&self, mapping: &[AxisMapping]) -> Vec<AxisChannel> {
+        mapping
+            .iter()
+            .filter_map(|x| {
+                self.axes.get(&x.axis.to_lowercase()).map(|fscript| AxisChannel {
+                    axis: x.axis.to_lowercase(),
+                    target: x.target.clone(),
+                    fscript: copy_actions(fscript),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Reads the multi-axis pattern 'pattern_name' from 'pattern_path', see `MultiAxisPattern::load`
+pub fn read_multi_axis(pattern_path: &str, pattern_name: &str) -> Option<MultiAxisPattern> {
+    MultiAxisPattern::load(pattern_name, |name| read_pattern_name(pattern_path, name, false).ok())
+}
+
 pub fn read_pattern(
     pattern_path: &str,
     pattern_name: &str,
@@ -35,10 +472,157 @@ pub fn read_pattern(
     }
 }
 
+/// Hit and miss counts of the `PatternLibrary`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatternCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    /// estimated memory of the cached actions
+    pub bytes: usize,
+}
+
+struct CachedPattern {
+    fscript: FScript,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// Keeps parsed patterns in memory, the least recently used ones are
+/// evicted once 'max_entries' or 'max_bytes' is exceeded
+pub struct PatternLibrary {
+    max_entries: usize,
+    max_bytes: usize,
+    entries: HashMap<(String, String, bool), CachedPattern>,
+    /// infos of all patterns by pattern path, see `list_patterns`
+    listings: HashMap<String, Vec<PatternInfo>>,
+    /// pattern paths whose infos are kept in `listings`, see `cache_listing`
+    cached_listings: HashSet<String>,
+    tick: u64,
+    stats: PatternCacheStats,
+}
+
+impl PatternLibrary {
+    pub fn new(max_entries: usize, max_bytes: usize) -> Self {
+        PatternLibrary {
+            max_entries,
+            max_bytes,
+            entries: HashMap::new(),
+            listings: HashMap::new(),
+            cached_listings: HashSet::new(),
+            tick: 0,
+            stats: PatternCacheStats::default(),
+        }
+    }
+
+    /// Like `read_pattern`, only reads the file if the pattern is not cached
+    pub fn read(&mut self, pattern_path: &str, pattern_name: &str, vibration_pattern: bool) -> Option<FScript> {
+        self.tick += 1;
+        let key = (pattern_path.to_owned(), pattern_name.to_lowercase(), vibration_pattern);
+        if let Some(cached) = self.entries.get_mut(&key) {
+            cached.last_used = self.tick;
+            self.stats.hits += 1;
+            return Some(copy_actions(&cached.fscript));
+        }
+        self.stats.misses += 1;
+        let fscript = read_pattern(pattern_path, pattern_name, vibration_pattern)?;
+        let bytes = key.0.len() + key.1.len() + fscript.actions.len() * mem::size_of::<FSPoint>();
+        let result = copy_actions(&fscript);
+        self.entries.insert(key, CachedPattern { fscript, bytes, last_used: self.tick });
+        self.stats.bytes += bytes;
+        self.evict();
+        Some(result)
+    }
+
+    pub fn stats(&self) -> PatternCacheStats {
+        PatternCacheStats {
+            entries: self.entries.len(),
+            ..self.stats.clone()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.listings.clear();
+        self.stats.bytes = 0;
+    }
+
+    /// Infos of the vibration and linear patterns in 'pattern_path'. The directory is read
+    /// on each call, unless `cache_listing` was called for it
+    pub fn list_patterns(&mut self, pattern_path: &str) -> Vec<PatternInfo> {
+        let read = || {
+            let mut infos = get_pattern_infos(pattern_path, true);
+            infos.extend(get_pattern_infos(pattern_path, false));
+            infos
+        };
+        if !self.cached_listings.contains(pattern_path) {
+            return read();
+        }
+        self.listings.entry(pattern_path.to_owned()).or_insert_with(read).clone()
+    }
+
+    /// Keeps the pattern infos of 'pattern_path' until `invalidate` or `clear`,
+    /// only safe while something invalidates them, see `PatternWatcher`
+    pub fn cache_listing(&mut self, pattern_path: &str) {
+        self.cached_listings.insert(pattern_path.to_owned());
+    }
+
+    /// Forgets the pattern that is read from 'file_name' in 'pattern_path'
+    /// and the pattern infos of the directory
+    pub fn invalidate(&mut self, pattern_path: &str, file_name: &str) {
+        self.listings.remove(pattern_path);
+        let Some((name, is_vibration)) = parse_pattern_file_name(file_name) else {
+            return;
+        };
+        if let Some(evicted) = self.entries.remove(&(pattern_path.to_owned(), name.to_lowercase(), is_vibration)) {
+            debug!(pattern = name, "invalidating pattern");
+            self.stats.bytes -= evicted.bytes;
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.max_entries || self.stats.bytes > self.max_bytes {
+            let Some(key) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, x)| x.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&key) {
+                debug!(pattern = key.1, "evicting pattern");
+                self.stats.bytes -= evicted.bytes;
+                self.stats.evictions += 1;
+            }
+        }
+    }
+}
+
+/// Reads the pattern like `load_pattern_name` and refuses it if it has fatal issues,
+/// see `PatternIssue::is_fatal`
 pub fn read_pattern_name(
     pattern_path: &str,
     pattern_name: &str,
     vibration_pattern: bool,
+) -> Result<FScript, anyhow::Error> {
+    let fs = load_pattern_name(pattern_path, pattern_name, vibration_pattern)?;
+    let issues = validate(&fs);
+    for issue in &issues {
+        warn!(pattern = pattern_name, %issue, "invalid pattern");
+    }
+    if let Some(issue) = issues.iter().find(|x| x.is_fatal()) {
+        return Err(anyhow!("Pattern '{}' can't be played: {}", pattern_name, issue));
+    }
+    Ok(fs)
+}
+
+/// Reads the pattern file without validating it
+pub fn load_pattern_name(
+    pattern_path: &str,
+    pattern_name: &str,
+    vibration_pattern: bool,
 ) -> Result<FScript, anyhow::Error> {
     let now = Instant::now();
     let patterns = get_pattern_paths(pattern_path)?;
@@ -68,28 +652,296 @@ fn get_pattern_paths(pattern_path: &str) -> Result<Vec<PatternIntern>, anyhow::E
             .ok_or_else(|| anyhow!("No file name"))?
             .to_str()
             .ok_or_else(|| anyhow!("Invalid unicode"))?;
-        if !file_name.to_lowercase().ends_with(".funscript") {
+        let Some((name, is_vibration)) = parse_pattern_file_name(file_name) else {
             continue;
-        }
-
-        let is_vibration = file_name.to_lowercase().ends_with(".vibrator.funscript");
-        let removal: usize = if is_vibration {
-            file_name.len() - ".vibrator.funscript".len()
-        } else {
-            file_name.len() - ".funscript".len()
         };
 
         patterns.push(PatternIntern {
             path: path_clone,
             is_vibration,
-            name: String::from(&file_name[0..removal]),
+            name,
         })
     }
     Ok(patterns)
 }
 
+/// Pattern name of a funscript file and whether it is a vibration pattern
+fn parse_pattern_file_name(file_name: &str) -> Option<(String, bool)> {
+    if !file_name.to_lowercase().ends_with(".funscript") {
+        return None;
+    }
+    let is_vibration = file_name.to_lowercase().ends_with(".vibrator.funscript");
+    let removal: usize = if is_vibration {
+        file_name.len() - ".vibrator.funscript".len()
+    } else {
+        file_name.len() - ".funscript".len()
+    };
+    Some((String::from(&file_name[0..removal]), is_vibration))
+}
+
+/// Invalidates the patterns of a `PatternLibrary` when their files change, until it is dropped
+#[cfg(feature = "pattern-watch")]
+#[derive(Debug)]
+pub struct PatternWatcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+#[cfg(feature = "pattern-watch")]
+impl PatternWatcher {
+    /// Watches the files in 'pattern_path', changed, created and removed patterns are
+    /// invalidated in 'library' and read again on their next use
+    pub fn watch(library: Arc<Mutex<PatternLibrary>>, pattern_path: &str) -> notify::Result<Self> {
+        use notify::{RecursiveMode, Watcher};
+
+        let path = pattern_path.to_owned();
+        let listed = library.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if !event.kind.is_access() => {
+                let mut library = library.lock().unwrap();
+                for file_name in event.paths.iter().filter_map(|x| x.file_name()?.to_str()) {
+                    library.invalidate(&path, file_name);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => error!(?err, "pattern watch error"),
+        })?;
+        watcher.watch(Path::new(pattern_path), RecursiveMode::NonRecursive)?;
+        listed.lock().unwrap().cache_listing(pattern_path);
+        info!(pattern_path, "watching patterns");
+        Ok(PatternWatcher { _watcher: watcher })
+    }
+}
+
 struct PatternIntern {
     path: PathBuf,
     is_vibration: bool,
     name: String,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::config::client::settings_tests::*;
+
+    use super::*;
+
+    #[test]
+    fn fitting_scales_timestamps_to_duration() {
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 10, at: 0 });
+        fscript.actions.push(FSPoint { pos: 90, at: 300 });
+        fscript.actions.push(FSPoint { pos: 50, at: 1200 });
+
+        let fitted = fit_to_duration(&fscript, Duration::from_secs(6));
+
+        let actions = fitted.actions.iter().map(|x| (x.at, x.pos)).collect::<Vec<_>>();
+        assert_eq!(actions, vec![(0, 10), (1500, 90), (6000, 50)]);
+    }
+
+    #[test]
+    fn validation_reports_broken_actions() {
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 0, at: 0 });
+        fscript.actions.push(FSPoint { pos: 120, at: 100 });
+        fscript.actions.push(FSPoint { pos: 50, at: 100 });
+        fscript.actions.push(FSPoint { pos: 50, at: 50 });
+        fscript.actions.push(FSPoint { pos: 50, at: -10 });
+        assert_eq!(
+            validate(&fscript),
+            vec![
+                PatternIssue::OutOfRange(1, 120),
+                PatternIssue::ZeroDuration(2),
+                PatternIssue::NonMonotonic(3, 50),
+                PatternIssue::NegativeTime(4, -10),
+                PatternIssue::NonMonotonic(4, -10)
+            ]
+        );
+
+        let dense = FScript {
+            actions: (0..120).map(|i| FSPoint { pos: (i % 2) * 100, at: 1000 + i * 10 }).collect(),
+            ..Default::default()
+        };
+        assert_eq!(validate(&dense), vec![PatternIssue::TooDense(1000, 100)]);
+        fscript.actions.truncate(1);
+        fscript.actions.push(FSPoint { pos: 100, at: 200 });
+        assert!(validate(&fscript).is_empty());
+    }
+
+    #[test]
+    fn upsampling_fills_gaps_between_sparse_actions() {
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 0, at: 0 });
+        fscript.actions.push(FSPoint { pos: 100, at: 400 });
+        fscript.actions.push(FSPoint { pos: 100, at: 1000 });
+        fscript.actions.push(FSPoint { pos: 50, at: 1050 });
+
+        let linear = upsample(&fscript, 100, Interpolation::Linear);
+        let actions = linear.actions.iter().map(|x| (x.at, x.pos)).collect::<Vec<_>>();
+        assert_eq!(actions, vec![(0, 0), (100, 25), (200, 50), (300, 75), (400, 100), (1000, 100), (1050, 50)]);
+
+        let cosine = upsample(&fscript, 100, Interpolation::Cosine);
+        let actions = cosine.actions.iter().map(|x| (x.at, x.pos)).take(5).collect::<Vec<_>>();
+        assert_eq!(actions, vec![(0, 0), (100, 15), (200, 50), (300, 85), (400, 100)]);
+    }
+
+    #[test]
+    fn library_evicts_least_recently_used_patterns() {
+        let (_, tmp_dir, tmp_handle) = create_temp_file("A.funscript", r#"{ "actions": [ { "at": 100, "pos": 0 } ] }"#);
+        add_temp_file("B.funscript", r#"{ "actions": [ { "at": 200, "pos": 0 } ] }"#, &tmp_handle);
+        add_temp_file("C.funscript", r#"{ "actions": [ { "at": 300, "pos": 0 } ] }"#, &tmp_handle);
+        let mut library = PatternLibrary::new(2, 1024 * 1024);
+
+        library.read(&tmp_dir, "A", false).unwrap();
+        library.read(&tmp_dir, "a", false).unwrap();
+        library.read(&tmp_dir, "B", false).unwrap();
+        library.read(&tmp_dir, "C", false).unwrap();
+        let a = library.read(&tmp_dir, "A", false).unwrap();
+        assert!(library.read(&tmp_dir, "missing", false).is_none());
+
+        assert_eq!(a.actions[0].at, 100);
+        let stats = library.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions, stats.entries), (1, 5, 2, 2));
+
+        let mut small = PatternLibrary::new(10, 1);
+        small.read(&tmp_dir, "A", false).unwrap();
+        assert_eq!(small.stats().entries, 0);
+        assert_eq!(small.stats().bytes, 0);
+    }
+
+    #[test]
+    fn library_lists_patterns_until_invalidated() {
+        let (_, tmp_dir, tmp_handle) = create_temp_file("A.funscript", r#"{ "actions": [ { "at": 100, "pos": 0 } ] }"#);
+        add_temp_file("B.vibrator.funscript", r#"{ "actions": [ { "at": 0, "pos": 0 }, { "at": 200, "pos": 50 } ] }"#, &tmp_handle);
+        let mut library = PatternLibrary::new(10, 1024 * 1024);
+        library.cache_listing(&tmp_dir);
+
+        let infos = library.list_patterns(&tmp_dir);
+        assert_eq!(infos.len(), 2);
+        assert!(infos.iter().any(|x| x.name == "B" && x.is_vibration && x.points == 2));
+        library.read(&tmp_dir, "A", false).unwrap();
+
+        add_temp_file("A.funscript", r#"{ "actions": [ { "at": 300, "pos": 0 } ] }"#, &tmp_handle);
+        add_temp_file("C.funscript", r#"{ "actions": [ { "at": 400, "pos": 0 } ] }"#, &tmp_handle);
+        assert_eq!(library.list_patterns(&tmp_dir).len(), 2);
+        assert_eq!(library.read(&tmp_dir, "A", false).unwrap().actions[0].at, 100);
+
+        library.invalidate(&tmp_dir, "A.funscript");
+        assert_eq!(library.list_patterns(&tmp_dir).len(), 3);
+        assert_eq!(library.read(&tmp_dir, "A", false).unwrap().actions[0].at, 300);
+        assert_eq!(library.stats().entries, 1);
+    }
+
+    #[test]
+    fn pattern_info_contains_metadata_and_duration() {
+        let (_, tmp_dir, tmp_handle) = create_temp_file(
+            "Tease.vibrator.funscript",
+            r#"{ "actions": [ { "at": 0, "pos": 10 }, { "at": 1500, "pos": 90 } ],
+                 "metadata": { "title": "Tease", "performers": [ "a", "b" ], "duration": 2 } }"#,
+        );
+        add_temp_file("Stroke.funscript", r#"{ "actions": [ { "at": 400, "pos": 0 } ] }"#, &tmp_handle);
+
+        let infos = get_pattern_infos(&tmp_dir, true);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].name, "Tease");
+        assert_eq!(infos[0].metadata.title, "Tease");
+        assert_eq!(infos[0].metadata.performers, vec!["a", "b"]);
+        assert_eq!(infos[0].duration, Duration::from_millis(1500));
+
+        let infos = get_pattern_infos(&tmp_dir, false);
+        assert_eq!(infos[0].metadata.title, "");
+        assert_eq!(infos[0].duration, Duration::from_millis(400));
+    }
+
+    #[test]
+    fn pattern_infos_filtered_by_duration() {
+        let (_, tmp_dir, tmp_handle) = create_temp_file("Short.funscript", r#"{ "actions": [ { "at": 100, "pos": 0 } ] }"#);
+        add_temp_file("Long.funscript", r#"{ "actions": [ { "at": 5000, "pos": 0 } ] }"#, &tmp_handle);
+
+        let infos = get_pattern_infos_by_duration(&tmp_dir, false, Duration::from_secs(1), Duration::from_secs(10));
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].name, "Long");
+    }
+
+    #[test]
+    fn patterns_with_fatal_issues_are_refused() {
+        let (_, tmp_dir, tmp_handle) = create_temp_file(
+            "Backwards.funscript",
+            r#"{ "actions": [ { "at": 0, "pos": 0 }, { "at": 200, "pos": 100 }, { "at": 100, "pos": 0 } ] }"#,
+        );
+        add_temp_file("Loud.funscript", r#"{ "actions": [ { "at": 0, "pos": 0 }, { "at": 100, "pos": 120 } ] }"#, &tmp_handle);
+
+        assert!(read_pattern_name(&tmp_dir, "Backwards", false).is_err());
+        assert!(load_pattern_name(&tmp_dir, "Backwards", false).is_ok());
+        assert!(read_pattern_name(&tmp_dir, "Loud", false).is_ok());
+    }
+
+    #[test]
+    fn bundle_channels_are_transformed() {
+        let (_, tmp_dir, tmp_handle) = create_temp_file(
+            "Duo.bundle.json",
+            r#"{ "channels": [
+                { "actuator_index": 0, "pattern": "Wave" },
+                { "actuator_index": 1, "pattern": "Wave", "transform": { "scale": 0.5, "offset": 10, "invert": true } },
+                { "actuator_index": 2, "pattern": "Missing" } ] }"#,
+        );
+        add_temp_file("Wave.vibrator.funscript", r#"{ "actions": [ { "at": 0, "pos": 0 }, { "at": 100, "pos": 80 } ] }"#, &tmp_handle);
+
+        let bundle = read_bundle(&tmp_dir, "Duo").unwrap();
+        let patterns = bundle.load(|name| read_pattern(&tmp_dir, name, true));
+
+        let values = |index: u32| patterns[&index].actions.iter().map(|x| (x.at, x.pos)).collect::<Vec<_>>();
+        assert_eq!(patterns.len(), 2);
+        assert_eq!(values(0), vec![(0, 0), (100, 80)]);
+        assert_eq!(values(1), vec![(0, 60), (100, 20)]);
+        assert!(read_bundle(&tmp_dir, "Missing").is_none());
+    }
+
+    #[test]
+    fn library_lists_uncached_patterns_on_each_call() {
+        let (_, tmp_dir, tmp_handle) = create_temp_file("A.funscript", r#"{ "actions": [ { "at": 100, "pos": 0 } ] }"#);
+        let mut library = PatternLibrary::new(10, 1024 * 1024);
+
+        assert_eq!(library.list_patterns(&tmp_dir).len(), 1);
+        add_temp_file("B.funscript", r#"{ "actions": [ { "at": 200, "pos": 0 } ] }"#, &tmp_handle);
+        assert_eq!(library.list_patterns(&tmp_dir).len(), 2);
+    }
+
+    #[test]
+    fn pattern_infos_group_axes_under_their_pattern() {
+        let (_, tmp_dir, tmp_handle) = create_temp_file("Ride.funscript", r#"{ "actions": [ { "at": 100, "pos": 0 } ] }"#);
+        add_temp_file("Ride.pitch.funscript", r#"{ "actions": [ { "at": 200, "pos": 50 } ] }"#, &tmp_handle);
+        add_temp_file("Ride.twist.funscript", r#"{ "actions": [ { "at": 200, "pos": 50 } ] }"#, &tmp_handle);
+        add_temp_file("Solo.roll.funscript", r#"{ "actions": [ { "at": 200, "pos": 50 } ] }"#, &tmp_handle);
+
+        let mut infos = get_pattern_infos(&tmp_dir, false);
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].name, "Ride");
+        assert_eq!(infos[0].axes, vec!["pitch", "twist"]);
+        assert_eq!(infos[1].name, "Solo.roll");
+        assert!(infos[1].axes.is_empty());
+    }
+
+    #[test]
+    fn multi_axis_pattern_reads_existing_axes() {
+        let (_, tmp_dir, tmp_handle) = create_temp_file("Ride.funscript", r#"{ "actions": [ { "at": 100, "pos": 0 } ] }"#);
+        add_temp_file("Ride.pitch.funscript", r#"{ "actions": [ { "at": 200, "pos": 50 } ] }"#, &tmp_handle);
+        add_temp_file("Ride.twist.funscript", r#"{ "actions": [ { "at": 300, "pos": 90 } ] }"#, &tmp_handle);
+
+        let pattern = read_multi_axis(&tmp_dir, "Ride").unwrap();
+        assert_eq!(pattern.axes.len(), 3);
+        let channels = pattern.channels(&[
+            AxisMapping::new("Pitch", AxisTarget::BodyPart("anal".into())),
+            AxisMapping::new("roll", AxisTarget::Index(4)),
+            AxisMapping::new("stroke", AxisTarget::Index(0)),
+        ]);
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0].axis, "pitch");
+        assert_eq!(channels[0].fscript.actions[0].at, 200);
+        assert_eq!(channels[1].target, AxisTarget::Index(0));
+        assert!(read_multi_axis(&tmp_dir, "Missing").is_none());
+        assert_eq!(default_axis_mapping()[5], AxisMapping::new("pitch", AxisTarget::Index(5)));
+    }
+}
@@ -1,10 +1,17 @@
-use buttplug::client::{ButtplugClientError, ScalarCommand};
+use buttplug::client::ButtplugClientError;
+use futures::future::BoxFuture;
 use std::collections::HashMap;
 
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::{runtime::Handle, task::JoinHandle, time::{sleep, sleep_until, Instant}};
 use tracing::{error, trace, instrument};
 
-use crate::{actuator::Actuator, speed::Speed};
+use crate::{actuator::{Actuator, ActuatorCommand}, config::{client::DeviceClass, linear::LinearSharing}, speed::Speed, ActuatorLimits};
+
+/// Interval between two commands of an eased speed change
+const EASING_STEP_MS: u32 = 50;
 
 /// Stores information about concurrent accesses to a buttplug actuator
 /// to calculate the actual vibration speed or linear movement
@@ -16,15 +23,107 @@ pub struct DeviceEntry {
     pub linear_tasks: Vec<(i32, Speed)>,
 }
 
-#[derive(Default, Debug, PartialEq, Eq, Hash)]
+/// Limits of a single device that has to save power, e.g. because its battery is low
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Degradation {
+    /// cap of all scalar outputs of the device
+    pub max_speed: Speed,
+    /// minimum time between two scalar commands to the device
+    pub min_interval: Duration,
+}
+
+/// Actuators of different message types can share an index within their device
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct ActuatorIndex {
     device_index: u32,
-    actuator_index: u32
+    actuator_index: u32,
+    command: ActuatorCommand,
+}
+
+/// Last scalar output of an actuator (as f64 bits), updated by running easing ramps
+#[derive(Default)]
+struct ScalarOutput {
+    value: Arc<AtomicU64>,
+    /// direction of rotate actuators, clockwise by default
+    counter_clockwise: Arc<AtomicBool>,
+    ramp: Option<JoinHandle<()>>,
+    /// command that waits for the next free slot of the device command budget
+    deferred: Option<JoinHandle<()>>,
+    /// last speed requested by the tasks, before the ceiling was applied
+    requested: Option<(Arc<Actuator>, Speed)>,
+    /// end of the crossfade after another task took over the actuator
+    crossfade_until: Option<Instant>,
+    /// first error of an easing ramp or deferred command, reported by the next stop
+    failure: Arc<Mutex<Option<ButtplugClientError>>>,
+}
+
+impl ScalarOutput {
+    fn get(&self) -> f64 {
+        f64::from_bits(self.value.load(Ordering::Relaxed))
+    }
+    fn clockwise(&self) -> bool {
+        !self.counter_clockwise.load(Ordering::Relaxed)
+    }
+    fn abort_ramp(&mut self) {
+        if let Some(ramp) = self.ramp.take() {
+            ramp.abort();
+        }
+    }
+    fn is_deferred(&self) -> bool {
+        self.deferred.as_ref().is_some_and(|x| !x.is_finished())
+    }
+    fn take_failure(&self) -> Result<(), ButtplugClientError> {
+        match self.failure.lock().unwrap().take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Keeps the first error of a command that was sent in the background
+fn record_failure(failure: &Mutex<Option<ButtplugClientError>>, result: Result<(), ButtplugClientError>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(err) => {
+            failure.lock().unwrap().get_or_insert(err);
+            false
+        }
+    }
+}
+
+/// Task that controls a time-sliced linear actuator
+struct LinearTurn {
+    handle: i32,
+    since: Instant,
+    /// the first move of the turn was not sent yet
+    handover: bool,
 }
 
 #[derive(Default)]
 pub struct DeviceAccess {
     device_actions: HashMap<ActuatorIndex, DeviceEntry>,
+    scalar_outputs: HashMap<ActuatorIndex, ScalarOutput>,
+    /// caps all scalar outputs, e.g. in quiet mode
+    ceiling: Option<Speed>,
+    /// scales all scalar outputs and rotations, None is 100%
+    intensity: Option<Speed>,
+    /// unscaled speed and direction of rotating actuators, re-applied by `set_intensity`
+    rotations: HashMap<ActuatorIndex, (Arc<Actuator>, Speed, bool)>,
+    /// tasks that move a linear actuator, only the last one that started (or the one
+    /// whose turn it is) controls the device, the others resume their rhythm once it ends
+    linear_owners: HashMap<ActuatorIndex, Vec<i32>>,
+    /// current turn of linear actuators that are shared by time slicing
+    linear_turns: HashMap<ActuatorIndex, LinearTurn>,
+    /// maximum scalar commands per second for each device class
+    pub command_budgets: HashMap<DeviceClass, u32>,
+    /// next time a device (by index) may receive a command within its budget
+    next_slots: HashMap<u32, Instant>,
+    /// devices (by index) that are limited to save power
+    degradations: HashMap<u32, Degradation>,
+    /// time to blend between the speeds of two tasks when one takes over an actuator
+    pub takeover_crossfade: Option<Duration>,
+    #[cfg(feature = "telemetry")]
+    pub telemetry: Option<Arc<super::telemetry::Telemetry>>,
 }
 
 impl DeviceAccess {
@@ -36,6 +135,12 @@ impl DeviceAccess {
         handle: i32,
     ) {
         trace!( handle, ?speed, "start scalar");
+        if self.device_actions.get(&actuator.clone().into()).is_some_and(|x| x.task_count > 0) {
+            self.begin_crossfade(&actuator);
+        } else if let Some(output) = self.scalar_outputs.get(&actuator.clone().into()) {
+            // errors of earlier tasks are not reported to this one
+            let _ = output.take_failure();
+        }
         self.device_actions
             .entry(actuator.clone().into())
             .and_modify(|entry| {
@@ -73,8 +178,10 @@ impl DeviceAccess {
             self.device_actions.insert(actuator.clone().into(), entry);
             if count == 0 {
                 // nothing else is controlling the device, stop it
-                return self.set_scalar(actuator, Speed::min()).await;
+                let result = self.set_scalar(actuator.clone(), Speed::min()).await;
+                return result.and(self.scalar_outputs[&actuator.into()].take_failure());
             } else if let Some(last_speed) = self.calculate_speed(actuator.clone()) {
+                self.begin_crossfade(&actuator);
                 let _ = self.set_scalar(actuator, last_speed).await;
             }
         }
@@ -101,28 +208,266 @@ impl DeviceAccess {
 
     #[instrument(skip(self))]
     async fn set_scalar(
-        &self,
+        &mut self,
         actuator: Arc<Actuator>,
         speed: Speed,
     ) -> Result<(), ButtplugClientError> {
-        let cmd = ScalarCommand::ScalarMap(HashMap::from([(
-            actuator.index_in_device,
-            (speed.as_float(), actuator.actuator),
-        )]));
+        let target = self.scalar_output(&actuator, speed).as_float();
+        let degradation = self.degradations.get(&actuator.device.index()).copied();
+        let output = self.scalar_outputs.entry(actuator.clone().into()).or_default();
+        output.abort_ramp();
+        output.requested = Some((actuator.clone(), speed));
 
-        if let Err(err) = actuator.device.scalar(&cmd).await {
-            error!("failed to set scalar speed {:?}", err);
-            return Err(err);
+        let current = output.get();
+        #[cfg(feature = "telemetry")]
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.emit(actuator.identifier(), "scalar", target);
+        }
+        let (attack_ms, decay_ms) = match actuator.get_config().limits {
+            ActuatorLimits::Scalar(range) => range.easing.get_times_ms(),
+            _ => (0, 0),
+        };
+        let crossfade_ms = output
+            .crossfade_until
+            .map(|until| until.saturating_duration_since(Instant::now()).as_millis() as u32)
+            .unwrap_or(0);
+        let ramp_ms = if target > current { attack_ms } else { decay_ms }.max(crossfade_ms);
+        if ramp_ms < EASING_STEP_MS || target == current {
+            output.value.store(target.to_bits(), Ordering::Relaxed);
+            let interval = command_interval(&self.command_budgets, &actuator)
+                .into_iter()
+                .chain(degradation.map(|x| x.min_interval))
+                .max();
+            let Some(interval) = interval else {
+                return send_scalar(&actuator, target, output.clockwise()).await;
+            };
+            if output.is_deferred() {
+                trace!(target, "coalescing with deferred command");
+                return Ok(());
+            }
+            let now = Instant::now();
+            let slot = self.next_slots.entry(actuator.device.index()).or_insert(now);
+            if *slot <= now {
+                *slot = now + interval;
+                return send_scalar(&actuator, target, output.clockwise()).await;
+            }
+            let at = *slot;
+            *slot += interval;
+            let value = output.value.clone();
+            let counter_clockwise = output.counter_clockwise.clone();
+            let failure = output.failure.clone();
+            output.deferred = Some(Handle::current().spawn(async move {
+                sleep_until(at).await;
+                let value = f64::from_bits(value.load(Ordering::Relaxed));
+                record_failure(&failure, send_scalar(&actuator, value, !counter_clockwise.load(Ordering::Relaxed)).await);
+            }));
+            return Ok(());
         }
+
+        trace!(current, target, ramp_ms, "easing");
+        let value = output.value.clone();
+        let counter_clockwise = output.counter_clockwise.clone();
+        let failure = output.failure.clone();
+        output.ramp = Some(Handle::current().spawn(async move {
+            let steps = ramp_ms / EASING_STEP_MS;
+            for step in 1..=steps {
+                let x = current + (target - current) * step as f64 / steps as f64;
+                value.store(x.to_bits(), Ordering::Relaxed);
+                let sent = send_scalar(&actuator, x, !counter_clockwise.load(Ordering::Relaxed)).await;
+                if !record_failure(&failure, sent) {
+                    return;
+                }
+                if step < steps {
+                    sleep(Duration::from_millis(EASING_STEP_MS as u64)).await;
+                }
+            }
+        }));
         Ok(())
     }
 
+    /// Whether a task currently drives 'actuator'
+    pub fn is_busy(&self, actuator: &Arc<Actuator>) -> bool {
+        let index: ActuatorIndex = actuator.clone().into();
+        self.device_actions.get(&index).is_some_and(|x| x.task_count > 0)
+            || self.linear_owners.get(&index).is_some_and(|x| !x.is_empty())
+            || self.rotations.contains_key(&index)
+    }
+
+    /// Sends a single command outside of any task to an idle actuator, scalar speeds are
+    /// scaled, capped and budgeted like those of tasks and rotations use the global intensity
+    pub async fn probe(&mut self, actuator: Arc<Actuator>, value: f64, duration_ms: u32) -> Result<(), ButtplugClientError> {
+        trace!(value, duration_ms, "probe");
+        match actuator.command {
+            ActuatorCommand::Linear => actuator.linear(duration_ms, value).await,
+            ActuatorCommand::Rotate => actuator.rotate(self.apply_intensity(Speed::from_float(value)).as_float(), true).await,
+            ActuatorCommand::Scalar => {
+                let result = self.set_scalar(actuator.clone(), Speed::from_float(value)).await;
+                result.and(self.scalar_outputs[&actuator.into()].take_failure())
+            }
+        }
+    }
+
+    /// Blends the following speed changes of 'actuator' within the takeover crossfade
+    fn begin_crossfade(&mut self, actuator: &Arc<Actuator>) {
+        if let Some(window) = self.takeover_crossfade {
+            trace!(?window, "takeover crossfade");
+            let output = self.scalar_outputs.entry(actuator.clone().into()).or_default();
+            output.crossfade_until = Some(Instant::now() + window);
+        }
+    }
+
+    /// Sets the direction of a rotate actuator, applies from its next speed change on
+    pub fn set_rotate_direction(&mut self, actuator: Arc<Actuator>, clockwise: bool) {
+        trace!(clockwise, "set rotate direction");
+        self.scalar_outputs
+            .entry(actuator.into())
+            .or_default()
+            .counter_clockwise
+            .store(!clockwise, Ordering::Relaxed);
+    }
+
+    /// Caps all scalar outputs at 'ceiling' and re-applies the speeds
+    /// of actuators that are currently running
+    pub async fn set_ceiling(&mut self, ceiling: Option<Speed>) {
+        trace!(?ceiling, "set ceiling");
+        self.ceiling = ceiling;
+        let running = self
+            .scalar_outputs
+            .values()
+            .filter_map(|x| x.requested.clone())
+            .filter(|(_, speed)| *speed > Speed::min())
+            .collect::<Vec<_>>();
+        for (actuator, speed) in running {
+            let _ = self.set_scalar(actuator, speed).await;
+        }
+    }
+
+    /// Scales all scalar outputs and rotations by 'intensity' and re-applies
+    /// the speeds of actuators that are currently running
+    pub async fn set_intensity(&mut self, intensity: Speed) {
+        trace!(?intensity, "set intensity");
+        self.intensity = Some(intensity).filter(|x| *x < Speed::max());
+        let running = self
+            .scalar_outputs
+            .values()
+            .filter_map(|x| x.requested.clone())
+            .filter(|(_, speed)| *speed > Speed::min())
+            .collect::<Vec<_>>();
+        for (actuator, speed) in running {
+            let _ = self.set_scalar(actuator, speed).await;
+        }
+        let rotating = self.rotations.values().cloned().collect::<Vec<_>>();
+        for (actuator, speed, clockwise) in rotating {
+            let _ = self.rotate(actuator, speed, clockwise).await;
+        }
+    }
+
+    /// Rotates 'actuator' with 'speed' scaled by the global intensity
+    pub fn rotate(
+        &mut self,
+        actuator: Arc<Actuator>,
+        speed: Speed,
+        clockwise: bool,
+    ) -> BoxFuture<'static, Result<(), ButtplugClientError>> {
+        let index: ActuatorIndex = actuator.clone().into();
+        if speed > Speed::min() {
+            self.rotations.insert(index, (actuator.clone(), speed, clockwise));
+        } else {
+            self.rotations.remove(&index);
+        }
+        actuator.rotate(self.apply_intensity(speed).as_float(), clockwise)
+    }
+
+    /// 'speed' scaled by the global intensity
+    pub fn apply_intensity(&self, speed: Speed) -> Speed {
+        match self.intensity {
+            Some(intensity) => speed.multiply(&intensity),
+            None => speed,
+        }
+    }
+
+    /// Speed sent to a scalar actuator for 'speed', scaled by the global intensity
+    /// and capped by the ceiling and the degradation of its device
+    pub fn scalar_output(&self, actuator: &Actuator, speed: Speed) -> Speed {
+        let scaled = self.apply_intensity(speed);
+        let degradation = self.degradations.get(&actuator.device.index());
+        match self.ceiling.into_iter().chain(degradation.map(|x| x.max_speed)).min() {
+            Some(ceiling) if scaled > ceiling => ceiling,
+            _ => scaled,
+        }
+    }
+
+    /// Limits the device with 'device_index' to save power, None lifts the limits.
+    /// Re-applies the speeds of its actuators that are currently running
+    pub async fn set_degradation(&mut self, device_index: u32, degradation: Option<Degradation>) {
+        trace!(device_index, ?degradation, "set degradation");
+        match degradation {
+            Some(degradation) => self.degradations.insert(device_index, degradation),
+            None => self.degradations.remove(&device_index),
+        };
+        let running = self
+            .scalar_outputs
+            .values()
+            .filter_map(|x| x.requested.clone())
+            .filter(|(actuator, speed)| actuator.device.index() == device_index && *speed > Speed::min())
+            .collect::<Vec<_>>();
+        for (actuator, speed) in running {
+            let _ = self.set_scalar(actuator, speed).await;
+        }
+    }
+
+    /// Registers 'handle' as controller of a linear actuator and returns the duration of its
+    /// move of 'duration_ms', or None if it should not be sent to the device. By default a new
+    /// task preempts all existing ones, actuators configured with `LinearSharing::TimeSlice`
+    /// alternate between the tasks and stretch the first move after each handover
+    pub fn acquire_linear(&mut self, actuator: Arc<Actuator>, handle: i32, duration_ms: u32) -> Option<u32> {
+        let sharing = actuator.config.as_ref().map(|x| x.linear_sharing).unwrap_or_default();
+        let index: ActuatorIndex = actuator.into();
+        let owners = self.linear_owners.entry(index.clone()).or_default();
+        if !owners.contains(&handle) {
+            owners.push(handle);
+        }
+        let LinearSharing::TimeSlice(slice) = sharing else {
+            return (owners.last() == Some(&handle)).then_some(duration_ms);
+        };
+        let now = Instant::now();
+        let turn = self.linear_turns.entry(index).or_insert(LinearTurn { handle, since: now, handover: false });
+        let next = match owners.iter().position(|x| *x == turn.handle) {
+            None => Some(handle),
+            Some(_) if owners.len() < 2 => None,
+            Some(_) if now.duration_since(turn.since) < Duration::from_millis(slice.slice_ms as u64) => None,
+            Some(i) => Some(owners[(i + 1) % owners.len()]),
+        };
+        if let Some(next) = next {
+            trace!(from = turn.handle, to = next, "linear handover");
+            *turn = LinearTurn { handle: next, since: now, handover: true };
+        }
+        if turn.handle != handle {
+            return None;
+        }
+        if std::mem::take(&mut turn.handover) {
+            return Some(duration_ms.max(slice.handover_ms));
+        }
+        Some(duration_ms)
+    }
+
+    pub fn release_linear(&mut self, actuator: Arc<Actuator>, handle: i32) {
+        trace!(handle, "release linear");
+        let index: ActuatorIndex = actuator.into();
+        if let Some(owners) = self.linear_owners.get_mut(&index) {
+            owners.retain(|x| *x != handle);
+            if owners.is_empty() {
+                self.linear_turns.remove(&index);
+            }
+        }
+    }
+
     fn calculate_speed(&self, actuator: Arc<Actuator>) -> Option<Speed> {
         // concurrency-strategy: always use the highest existing value
         if let Some(entry) = self.device_actions.get(&actuator.into()) {
             // let mut sorted: Vec<(i32, Speed)> = entry.linear_tasks.clone();
-            if let Some(percentage) = entry.linear_tasks.iter().map(|x| x.1.value).max() {
-                return Some(Speed::new(percentage.into()));
+            if let Some(speed) = entry.linear_tasks.iter().map(|x| x.1).max() {
+                return Some(speed);
             }
         }
         None
@@ -130,14 +475,365 @@ impl DeviceAccess {
 
     pub fn clear_all(&mut self) {
         self.device_actions.clear();
+        self.linear_owners.clear();
+        self.linear_turns.clear();
+        for output in self.scalar_outputs.values_mut() {
+            output.abort_ramp();
+            if let Some(deferred) = output.deferred.take() {
+                deferred.abort();
+            }
+        }
+        self.scalar_outputs.clear();
+        self.rotations.clear();
     }
 }
 
+/// Minimum time between two commands to the device of 'actuator', if its class
+/// or its config has a budget
+fn command_interval(budgets: &HashMap<DeviceClass, u32>, actuator: &Actuator) -> Option<Duration> {
+    budgets
+        .get(&DeviceClass::detect(actuator.device.name()))
+        .copied()
+        .into_iter()
+        .chain(actuator.config.as_ref().and_then(|x| x.max_commands_per_sec))
+        .filter(|x| *x > 0)
+        .min()
+        .map(|x| Duration::from_secs(1) / x)
+}
+
+/// Rotate actuators are sent rotate commands in the direction 'clockwise'
+async fn send_scalar(actuator: &Actuator, value: f64, clockwise: bool) -> Result<(), ButtplugClientError> {
+    let result = match actuator.command {
+        ActuatorCommand::Rotate => actuator.rotate(value, clockwise).await,
+        _ => actuator.scalar(value).await,
+    };
+    if let Err(err) = result {
+        error!("failed to set scalar speed {:?}", err);
+        return Err(err);
+    }
+    Ok(())
+}
+
 impl From<Arc<Actuator>> for ActuatorIndex {
     fn from(value: Arc<Actuator>) -> Self {
         ActuatorIndex {
             device_index: value.device.index(),
             actuator_index: value.index_in_device,
+            command: value.command,
         } 
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    use funscript::{FSPoint, FScript};
+
+    use buttplug::core::message::ActuatorType;
+
+    use tokio::runtime::Handle;
+
+    use crate::actuator::{ActuatorConfigLoader, Actuators};
+    use crate::config::ActuatorLimits;
+    use crate::config::actuators::{ActuatorConfig, ActuatorSettings};
+    use crate::config::client::{DeviceClass, QuietModeSettings};
+    use crate::config::linear::{LinearRange, LinearSharing, TimeSlice};
+    use crate::config::scalar::{ScalarEasing, ScalarRange};
+    use crate::speed::Speed;
+    use crate::tests::{PlayerTest, assert_rotation, rotate, wait_ms};
+
+    use bp_fakes::*;
+
+    use super::Degradation;
+
+    #[tokio::test]
+    async fn test_linear_preempted_stroke_resumes_after_short_task() {
+        // stroke |1111111111111111111-->|
+        // short       |2222->|
+        // result |1111122222211111111-->|
+
+        // arrange
+        let client = get_test_client(vec![linear(1, "lin1")]).await;
+        let mut player = PlayerTest::setup(client.created_devices.flatten_actuators().clone());
+        let range = LinearRange { min_ms: 100, max_ms: 100, ..LinearRange::max() };
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 50, at: 0 });
+        fscript.actions.push(FSPoint { pos: 50, at: 200 });
+
+        // act
+        let start = Instant::now();
+        let stroke = player.get_player();
+        let stroke = Handle::current().spawn(async move {
+            stroke.play_linear_stroke(Duration::from_millis(800), Speed::max(), range).await
+        });
+        wait_ms(250).await;
+        player.play_linear(fscript, Duration::from_millis(200)).await;
+        let _ = stroke.await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[2].assert_time(200, start);
+        calls[3].assert_pos(0.5).assert_time(250, start);
+        calls[4].assert_pos(0.5).assert_time(250, start);
+        calls[5].assert_time(500, start);
+    }
+
+    #[tokio::test]
+    async fn test_linear_preempted_stroke_resumes_after_aborted_task() {
+        // stroke |1111111111111111111-->|
+        // abort       |2222x
+        // result |11111222211111111111-->|
+
+        // arrange
+        let client = get_test_client(vec![linear(1, "lin1")]).await;
+        let mut player = PlayerTest::setup(client.created_devices.flatten_actuators().clone());
+        let range = LinearRange { min_ms: 100, max_ms: 100, ..LinearRange::max() };
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 50, at: 0 });
+        fscript.actions.push(FSPoint { pos: 50, at: 1000 });
+
+        // act
+        let start = Instant::now();
+        let stroke = player.get_player();
+        let stroke = Handle::current().spawn(async move {
+            stroke.play_linear_stroke(Duration::from_millis(800), Speed::max(), range).await
+        });
+        wait_ms(250).await;
+        let short = player.get_player();
+        let short = Handle::current().spawn(async move { short.play_linear(Duration::from_millis(1000), fscript).await });
+        wait_ms(200).await;
+        short.abort();
+        let _ = stroke.await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[3].assert_pos(0.5).assert_time(250, start);
+        calls[4].assert_pos(0.5).assert_time(250, start);
+        calls[5].assert_time(500, start);
+    }
+
+    #[tokio::test]
+    async fn test_linear_time_sliced_stroke_hands_over_smoothly() {
+        // stroke |111111111111-->|  slice 200ms
+        // short       |2222->|
+        // result |11111222222111-->|, first move after each handover takes 150ms
+
+        // arrange
+        let client = get_test_client(vec![linear(1, "lin1")]).await;
+        let mut config = ActuatorSettings::default();
+        config.update_device(ActuatorConfig {
+            actuator_config_id: "lin1 (Position)".into(),
+            enabled: true,
+            linear_sharing: LinearSharing::TimeSlice(TimeSlice { slice_ms: 200, handover_ms: 150 }),
+            ..Default::default()
+        });
+        let actuators = client.created_devices.flatten_actuators().load_config(&mut config).clone();
+        let mut player = PlayerTest::setup(actuators);
+        let range = LinearRange { min_ms: 100, max_ms: 100, ..LinearRange::max() };
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 50, at: 0 });
+        fscript.actions.push(FSPoint { pos: 50, at: 200 });
+
+        // act
+        let start = Instant::now();
+        let stroke = player.get_player();
+        let stroke = Handle::current().spawn(async move {
+            stroke.play_linear_stroke(Duration::from_millis(800), Speed::max(), range).await
+        });
+        wait_ms(250).await;
+        player.play_linear(fscript, Duration::from_millis(200)).await;
+        let _ = stroke.await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[2].assert_duration(100).assert_time(200, start);
+        calls[3].assert_pos(0.5).assert_duration(150).assert_time(250, start);
+        calls[4].assert_pos(0.5).assert_duration(200);
+        calls[5].assert_duration(150).assert_time(500, start);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_easing_ramps_up_speed() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut config = ActuatorSettings::default();
+        let easing = ScalarEasing::Custom { attack_ms: 100, decay_ms: 0 };
+        config.update_device(ActuatorConfig {
+            actuator_config_id: "vib1 (Vibrate)".into(),
+            enabled: true,
+            limits: ActuatorLimits::Scalar(ScalarRange { easing, ..Default::default() }),
+            ..Default::default()
+        });
+        let actuators = client.created_devices.flatten_actuators().load_config(&mut config).clone();
+        let mut player = PlayerTest::setup(actuators);
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(200), Speed::max());
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.5).assert_time(0, start);
+        calls[1].assert_strenth(1.0).assert_time(50, start);
+        calls[2].assert_strenth(0.0).assert_time(200, start);
+    }
+
+    #[tokio::test]
+    async fn test_takeover_crossfades_between_tasks() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        player.scheduler.set_takeover_crossfade(Some(Duration::from_millis(100)));
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(500), Speed::new(20));
+        wait_ms(100).await;
+        player.play_scalar(Duration::from_millis(200), Speed::max());
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.2).assert_time(0, start);
+        calls[1].assert_strenth(0.6).assert_time(100, start);
+        calls[2].assert_strenth(1.0).assert_time(150, start);
+        calls[3].assert_strenth(0.6).assert_time(300, start);
+        calls[4].assert_strenth(0.2).assert_time(350, start);
+        calls[5].assert_strenth(0.0).assert_time(500, start);
+    }
+
+    #[tokio::test]
+    async fn test_quiet_mode_caps_running_scalar() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(200), Speed::max());
+        wait_ms(50).await;
+        player.scheduler.set_quiet_mode(Some(QuietModeSettings { max_speed: 30, min_stroke_ms: 1_000 }));
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        client.get_device_calls(1)[0].assert_strenth(1.0);
+        client.get_device_calls(1)[1].assert_strenth(0.3).assert_time(50, start);
+        client.get_device_calls(1)[2].assert_strenth(0.0);
+    }
+
+    #[tokio::test]
+    async fn test_global_intensity_scales_running_and_new_tasks() {
+        // arrange
+        let client = get_test_client(vec![
+            scalar(1, "vib1", ActuatorType::Vibrate),
+            scalar(2, "vib2", ActuatorType::Vibrate),
+        ])
+        .await;
+        let mut player = PlayerTest::setup(vec![client.get_device(1)].flatten_actuators());
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(200), Speed::new(80));
+        wait_ms(50).await;
+        player.scheduler.set_global_intensity(Speed::new(50));
+        let other = player.scheduler.create_player(vec![client.get_device(2)].flatten_actuators(), -1);
+        let _ = other.play_scalar(Duration::from_millis(100), Speed::max()).await;
+        let intensity = player.scheduler.global_intensity();
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        let running = client.get_device_calls(1);
+        running[0].assert_strenth(0.8).assert_time(0, start);
+        running[1].assert_strenth(0.4).assert_time(50, start);
+        let new = client.get_device_calls(2);
+        new[0].assert_strenth(0.5).assert_time(50, start);
+        assert_eq!(intensity, Speed::new(50));
+    }
+
+    #[tokio::test]
+    async fn test_global_intensity_scales_running_rotations() {
+        // arrange
+        let client = get_test_client(vec![rotate(1, "rot1")]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let start = Instant::now();
+        let rotate_player = player.scheduler.create_player(player.actuators.clone(), -1);
+        let task = Handle::current().spawn(rotate_player.play_rotate(Duration::from_millis(200), Speed::new(80), true));
+        wait_ms(50).await;
+        player.scheduler.set_global_intensity(Speed::new(50));
+        let _ = task.await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        assert_rotation(&calls[0], 0.8, true);
+        calls[0].assert_time(0, start);
+        assert_rotation(&calls[1], 0.4, true);
+        calls[1].assert_time(50, start);
+        assert_rotation(&calls[2], 0.0, true);
+        calls[2].assert_time(200, start);
+    }
+
+    #[tokio::test]
+    async fn test_device_degradation_caps_running_scalar() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let limiter = player.scheduler.device_limiter();
+        let degradation = Degradation {
+            max_speed: Speed::new(40),
+            min_interval: Duration::from_millis(100),
+        };
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(300), Speed::max());
+        wait_ms(50).await;
+        limiter.set_degradation(client.created_devices[0].index(), Some(degradation));
+        wait_ms(50).await;
+        limiter.set_degradation(client.created_devices[0].index(), None);
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        client.get_device_calls(1)[0].assert_strenth(1.0);
+        client.get_device_calls(1)[1].assert_strenth(0.4).assert_time(50, start);
+        client.get_device_calls(1)[2].assert_strenth(1.0).assert_time(100, start);
+        client.get_device_calls(1)[3].assert_strenth(0.0);
+    }
+
+    #[tokio::test]
+    async fn test_command_budget_coalesces_updates() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        player.scheduler.set_command_budgets(HashMap::from([(DeviceClass::Bluetooth, 10)]));
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(300), Speed::new(50));
+        wait_ms(10).await;
+        for speed in [60, 70, 80] {
+            player.scheduler.update_task(1, Speed::new(speed));
+        }
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        client.get_device_calls(1)[0].assert_strenth(0.5);
+        client.get_device_calls(1)[1].assert_strenth(0.8).assert_time(100, start);
+        client.get_device_calls(1)[2].assert_strenth(0.0).assert_time(300, start);
+        assert_eq!(client.get_device_calls(1).len(), 3);
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::time::{sleep_until, Instant};
+
+use super::worker::{ButtplugWorker, WorkerTask};
+
+/// Delays all worker tasks so that they reach the server a constant time after
+/// they were issued. The delay shrinks by half of the measured round trip
+/// time, so latency spikes on the connection don't disturb the pattern cadence
+#[derive(Debug, Clone)]
+pub struct JitterBuffer {
+    pub added_latency: Duration,
+    /// last measured round trip time to the server in milliseconds
+    pub rtt_ms: Arc<AtomicU64>,
+}
+
+impl JitterBuffer {
+    pub fn new(added_latency: Duration, rtt_ms: Arc<AtomicU64>) -> Self {
+        JitterBuffer {
+            added_latency,
+            rtt_ms,
+        }
+    }
+
+    pub fn delay(&self) -> Duration {
+        let one_way = Duration::from_millis(self.rtt_ms.load(Ordering::Relaxed) / 2);
+        self.added_latency.saturating_sub(one_way)
+    }
+}
+
+impl ButtplugWorker {
+    /// Receives the next task, holding it back while the jitter buffer is active
+    pub(super) async fn next_task(&mut self) -> Option<WorkerTask> {
+        let Some(delay) = self.jitter_buffer.as_ref().map(|x| x.delay()) else {
+            if let Some((_, task)) = self.delayed.pop_front() {
+                return Some(task);
+            }
+            return self.task_receiver.recv().await;
+        };
+        loop {
+            let due = self.delayed.front().map(|(received, _)| *received + delay);
+            if due.is_some_and(|x| x <= Instant::now()) {
+                return self.delayed.pop_front().map(|(_, task)| task);
+            }
+            tokio::select! {
+                task = self.task_receiver.recv() => match task {
+                    Some(task) => self.delayed.push_back((Instant::now(), task)),
+                    None => return self.delayed.pop_front().map(|(_, task)| task),
+                },
+                _ = sleep_until(due.unwrap_or_else(Instant::now)), if due.is_some() => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use crate::{player::session_log::SessionLog, test_util::run_paused};
+
+    use super::*;
+
+    #[test]
+    fn delay_compensates_round_trip_time() {
+        let rtt_ms = Arc::new(AtomicU64::new(0));
+        let buffer = JitterBuffer::new(Duration::from_millis(150), rtt_ms.clone());
+        assert_eq!(buffer.delay(), Duration::from_millis(150));
+        rtt_ms.store(100, Ordering::Relaxed);
+        assert_eq!(buffer.delay(), Duration::from_millis(100));
+        rtt_ms.store(500, Ordering::Relaxed);
+        assert_eq!(buffer.delay(), Duration::ZERO);
+    }
+
+    #[test]
+    fn worker_holds_back_tasks_in_order() {
+        run_paused(async {
+            let (sender, task_receiver) = unbounded_channel();
+            let mut worker = ButtplugWorker::new(task_receiver, SessionLog::default());
+            worker.jitter_buffer = Some(JitterBuffer::new(
+                Duration::from_millis(150),
+                Arc::new(AtomicU64::new(100)),
+            ));
+            let start = Instant::now();
+            sender.send(WorkerTask::SetCeiling(None)).unwrap();
+            sender.send(WorkerTask::StopAll).unwrap();
+
+            assert!(matches!(worker.next_task().await, Some(WorkerTask::SetCeiling(None))));
+            assert_eq!(start.elapsed(), Duration::from_millis(100));
+            assert!(matches!(worker.next_task().await, Some(WorkerTask::StopAll)));
+            assert_eq!(start.elapsed(), Duration::from_millis(100));
+        });
+    }
+}
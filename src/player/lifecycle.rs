@@ -0,0 +1,223 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use tokio::sync::mpsc::{channel, error::TrySendError, Receiver, Sender};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use super::worker::WorkerError;
+
+/// Events that are kept for the receiver of `ButtplugScheduler::create_with_events`,
+/// further events are dropped until it catches up
+pub const SCHEDULER_EVENT_CAPACITY: usize = 256;
+
+/// Playback lifecycle of a handle, see `ButtplugScheduler::create_with_events`
+#[derive(Debug, Clone)]
+pub enum SchedulerEvent {
+    /// A new handle was created, its players start playing
+    Started(i32),
+    /// All players of the handle played until their end
+    Finished(i32),
+    /// The handle was stopped before all of its players ended
+    Cancelled(i32),
+    /// A device command of the handle failed, sent instead of
+    /// `Finished` or `Cancelled` once all players ended
+    Errored(i32, WorkerError),
+    /// The handle completed a multiple of `StrokeMilestones::every` strokes
+    StrokeMilestone(i32, u64),
+    /// The handle completed `StrokeMilestones::target` strokes
+    StrokeTarget(i32, u64),
+}
+
+/// Sends the events of all handles, they are dropped if nobody subscribed
+/// or the receiver is full
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerEvents(Option<Sender<SchedulerEvent>>);
+
+impl SchedulerEvents {
+    pub fn channel() -> (Self, Receiver<SchedulerEvent>) {
+        let (sender, receiver) = channel(SCHEDULER_EVENT_CAPACITY);
+        (SchedulerEvents(Some(sender)), receiver)
+    }
+
+    pub fn send(&self, event: SchedulerEvent) {
+        let Some(sender) = &self.0 else {
+            return;
+        };
+        if let Err(TrySendError::Full(event)) = sender.try_send(event) {
+            warn!(?event, "scheduler events are not received, dropped");
+        }
+    }
+}
+
+/// Outcome of all players of a handle, the last player that ends reports it
+#[derive(Debug)]
+pub struct HandleLifecycle {
+    handle: i32,
+    players: AtomicUsize,
+    cancelled: AtomicBool,
+    error: Mutex<Option<WorkerError>>,
+    event_sender: SchedulerEvents,
+}
+
+impl HandleLifecycle {
+    /// Reports that 'handle' started
+    pub fn start(handle: i32, event_sender: SchedulerEvents) -> Arc<Self> {
+        event_sender.send(SchedulerEvent::Started(handle));
+        Arc::new(HandleLifecycle {
+            handle,
+            players: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false),
+            error: Mutex::new(None),
+            event_sender,
+        })
+    }
+
+    /// Counts a player of the handle until the guard is dropped
+    pub fn join(self: &Arc<Self>, cancellation_token: CancellationToken) -> LifecycleGuard {
+        self.players.fetch_add(1, Ordering::SeqCst);
+        LifecycleGuard {
+            lifecycle: self.clone(),
+            cancellation_token,
+        }
+    }
+
+    /// Marks the handle as stopped by the host
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Keeps the first error of the handle
+    pub fn record_error(&self, err: &WorkerError) {
+        self.error.lock().unwrap().get_or_insert_with(|| err.clone());
+    }
+
+    fn end_player(&self) {
+        if self.players.fetch_sub(1, Ordering::SeqCst) != 1 {
+            return;
+        }
+        let event = match self.error.lock().unwrap().take() {
+            Some(err) => SchedulerEvent::Errored(self.handle, err),
+            None if self.cancelled.load(Ordering::SeqCst) => SchedulerEvent::Cancelled(self.handle),
+            None => SchedulerEvent::Finished(self.handle),
+        };
+        debug!(?event, "handle ended");
+        self.event_sender.send(event);
+    }
+}
+
+/// Held by a player, ends the player when it is dropped. Players that are
+/// dropped before their task ended count as cancelled
+#[derive(Debug)]
+pub struct LifecycleGuard {
+    lifecycle: Arc<HandleLifecycle>,
+    cancellation_token: CancellationToken,
+}
+
+impl LifecycleGuard {
+    pub fn record_error(&self, err: &WorkerError) {
+        self.lifecycle.record_error(err);
+    }
+}
+
+impl Drop for LifecycleGuard {
+    fn drop(&mut self) {
+        if !self.cancellation_token.is_cancelled() {
+            self.lifecycle.cancel();
+        }
+        self.lifecycle.end_player();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use futures::future::join_all;
+
+    use buttplug::core::message::ActuatorType;
+
+    use tokio::runtime::Handle;
+
+    use crate::speed::Speed;
+    use crate::tests::{PlayerTest, wait_ms};
+
+    use bp_fakes::*;
+
+    #[test]
+    fn last_player_reports_the_outcome() {
+        let (sender, mut events) = SchedulerEvents::channel();
+        let lifecycle = HandleLifecycle::start(1, sender);
+        let token = CancellationToken::new();
+        let first = lifecycle.join(token.clone());
+        let second = lifecycle.join(token.clone());
+        token.cancel();
+
+        drop(first);
+        assert!(matches!(events.try_recv(), Ok(SchedulerEvent::Started(1))));
+        assert!(events.try_recv().is_err());
+        drop(second);
+        assert!(matches!(events.try_recv(), Ok(SchedulerEvent::Finished(1))));
+    }
+
+    #[test]
+    fn dropped_players_are_cancelled() {
+        let (sender, mut events) = SchedulerEvents::channel();
+        let lifecycle = HandleLifecycle::start(2, sender);
+        drop(lifecycle.join(CancellationToken::new()));
+
+        assert!(matches!(events.try_recv(), Ok(SchedulerEvent::Started(2))));
+        assert!(matches!(events.try_recv(), Ok(SchedulerEvent::Cancelled(2))));
+    }
+
+    #[test]
+    fn events_beyond_capacity_are_dropped() {
+        let (sender, mut events) = SchedulerEvents::channel();
+        for handle in 0..SCHEDULER_EVENT_CAPACITY + 10 {
+            sender.send(SchedulerEvent::Started(handle as i32));
+        }
+        SchedulerEvents::default().send(SchedulerEvent::Started(-1));
+
+        let mut received = 0;
+        while events.try_recv().is_ok() {
+            received += 1;
+        }
+        assert_eq!(received, SCHEDULER_EVENT_CAPACITY);
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_events_report_lifecycle() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let finishing = player.scheduler.create_player(player.actuators.clone(), -1);
+        let stopped = player.scheduler.create_player(player.actuators.clone(), -1);
+        let (finishing_handle, stopped_handle) = (finishing.handle, stopped.handle);
+
+        // act
+        let first = Handle::current().spawn(finishing.play_scalar(Duration::from_millis(50), Speed::max()));
+        let second = Handle::current().spawn(stopped.play_scalar(Duration::from_secs(10), Speed::max()));
+        wait_ms(100).await;
+        player.scheduler.stop_task(stopped_handle);
+        let _ = join_all(vec![first, second]).await;
+
+        // assert
+        let mut events = vec![];
+        while let Ok(event) = player.events.try_recv() {
+            events.push(format!("{:?}", event));
+        }
+        assert_eq!(
+            events,
+            vec![
+                format!("Started({})", finishing_handle),
+                format!("Started({})", stopped_handle),
+                format!("Finished({})", finishing_handle),
+                format!("Cancelled({})", stopped_handle),
+            ]
+        );
+    }
+}
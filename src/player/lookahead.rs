@@ -0,0 +1,86 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Sends pattern commands earlier by the estimated one-way latency to the
+/// server, so that they take effect at the time the pattern defines
+#[derive(Debug, Clone)]
+pub struct Lookahead {
+    /// upper limit for how early a command is sent
+    pub max_lead: Duration,
+    /// last measured round trip time to the server in milliseconds
+    pub rtt_ms: Arc<AtomicU64>,
+}
+
+impl Lookahead {
+    pub fn new(max_lead: Duration, rtt_ms: Arc<AtomicU64>) -> Self {
+        Lookahead { max_lead, rtt_ms }
+    }
+
+    pub fn lead(&self) -> Duration {
+        let one_way = Duration::from_millis(self.rtt_ms.load(Ordering::Relaxed) / 2);
+        one_way.min(self.max_lead)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    use funscript::{FSPoint, FScript};
+
+    use buttplug::core::message::ActuatorType;
+
+    use crate::speed::Speed;
+    use crate::tests::PlayerTest;
+
+    use bp_fakes::*;
+
+    #[test]
+    fn lead_follows_latency_up_to_max() {
+        let rtt_ms = Arc::new(AtomicU64::new(0));
+        let lookahead = Lookahead::new(Duration::from_millis(200), rtt_ms.clone());
+        assert_eq!(lookahead.lead(), Duration::ZERO);
+        rtt_ms.store(120, Ordering::Relaxed);
+        assert_eq!(lookahead.lead(), Duration::from_millis(60));
+        rtt_ms.store(1_000, Ordering::Relaxed);
+        assert_eq!(lookahead.lead(), Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_scalar_pattern_lookahead_sends_commands_early() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        player.scheduler.set_lookahead(Some(Lookahead::new(
+            Duration::from_millis(200),
+            Arc::new(AtomicU64::new(100)),
+        )));
+
+        // act
+        let mut fs = FScript::default();
+        fs.actions.push(FSPoint { pos: 100, at: 0 });
+        fs.actions.push(FSPoint { pos: 50, at: 100 });
+        fs.actions.push(FSPoint { pos: 70, at: 200 });
+
+        let start = Instant::now();
+        player
+            .play_scalar_pattern(Duration::from_millis(250), fs, Speed::max())
+            .await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(50, start);
+        calls[2].assert_strenth(0.7).assert_time(150, start);
+        calls[3].assert_strenth(1.0).assert_time(150, start);
+        calls[4].assert_strenth(0.0).assert_time(250, start);
+    }
+}
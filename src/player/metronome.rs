@@ -0,0 +1,261 @@
+use std::time::Duration;
+
+use tokio::time::{sleep_until, Instant};
+use tracing::{debug, info};
+
+use crate::{config::linear::LinearRange, speed::Speed};
+
+use super::{worker::WorkerResult, PatternPlayer, SpeedUpdate};
+
+pub const MIN_BPM: f64 = 1.0;
+pub const MAX_BPM: f64 = 600.0;
+
+/// Beat grid of a tempo in beats per minute, changing the tempo keeps
+/// the last beat and only moves the following ones
+#[derive(Debug, Clone)]
+pub struct Metronome {
+    bpm: f64,
+    last_beat: Instant,
+    next_beat: Instant,
+}
+
+impl Metronome {
+    /// Starts with a beat at 'first_beat'
+    pub fn new(bpm: f64, first_beat: Instant) -> Self {
+        Metronome {
+            bpm: clamp_bpm(bpm),
+            last_beat: first_beat,
+            next_beat: first_beat,
+        }
+    }
+
+    pub fn bpm(&self) -> f64 {
+        self.bpm
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f64(60.0 / self.bpm)
+    }
+
+    pub fn next_beat(&self) -> Instant {
+        self.next_beat
+    }
+
+    /// Length of a pulse on each beat, at most half the beat interval
+    /// so that consecutive pulses stay distinguishable
+    pub fn pulse_length(&self, pulse: Duration) -> Duration {
+        pulse.min(self.interval() / 2)
+    }
+
+    /// Called on each beat, schedules the following beat and skips beats
+    /// that were missed
+    pub fn advance(&mut self, now: Instant) {
+        self.last_beat = self.next_beat;
+        let interval = self.interval();
+        self.next_beat += interval;
+        while self.next_beat <= now {
+            self.next_beat += interval;
+        }
+    }
+
+    /// Changes the tempo, the next beat follows the last one by the new interval
+    /// or happens right away if that time already passed
+    pub fn set_bpm(&mut self, bpm: f64, now: Instant) {
+        self.bpm = clamp_bpm(bpm);
+        self.next_beat = (self.last_beat + self.interval()).max(now);
+    }
+}
+
+impl PatternPlayer {
+    /// Pulses all actuators with 'speed' for 'pulse' on each beat of 'bpm' for 'duration'
+    /// and consumes the player, `SpeedUpdate::Tempo` changes the tempo while running
+    pub async fn play_scalar_metronome(
+        mut self,
+        duration: Duration,
+        bpm: f64,
+        pulse: Duration,
+        mut speed: Speed,
+    ) -> WorkerResult {
+        info!(?duration, bpm, ?pulse, ?speed, "playing scalar metronome");
+        let waiter = self.stop_after(duration);
+        let mut metronome = Metronome::new(bpm, Instant::now());
+        let mut pulse_end: Option<Instant> = None;
+        let mut held = false;
+        let pause = self.pause.clone();
+        self.resting = true;
+        self.do_scalar(Speed::min(), speed, true);
+        loop {
+            tokio::select! {
+                _ = self.cancellation_token.cancelled() => {
+                    break;
+                }
+                _ = pause.paused(), if !held => {
+                    held = true;
+                    pulse_end = None;
+                    self.fade_to_rest(speed, true).await;
+                }
+                _ = pause.resumed(), if held => {
+                    held = false;
+                    self.ramp_in_again();
+                    metronome = Metronome::new(metronome.bpm(), Instant::now());
+                }
+                Some(update) = self.update_receiver.recv() => {
+                    if let SpeedUpdate::Tempo(bpm) = update {
+                        debug!(self.handle, bpm, "tempo changed");
+                        metronome.set_bpm(bpm, Instant::now());
+                        continue;
+                    }
+                    self.apply_update(update, &mut speed);
+                    if pulse_end.is_some() {
+                        self.do_update(Speed::max(), speed, true);
+                    }
+                }
+                _ = sleep_until(pulse_end.unwrap_or_else(Instant::now)), if pulse_end.is_some() => {
+                    pulse_end = None;
+                    self.do_rest(speed, true);
+                }
+                _ = sleep_until(metronome.next_beat()), if !held => {
+                    let now = Instant::now();
+                    metronome.advance(now);
+                    pulse_end = Some(now + metronome.pulse_length(pulse));
+                    self.do_update(Speed::max(), speed, true);
+                }
+            };
+        }
+        waiter.abort();
+        let result = self.do_stop(true).await;
+        info!("done");
+        result
+    }
+
+    /// Strokes between the ends of 'settings' once per beat of 'bpm' for 'duration'
+    /// and consumes the player, `SpeedUpdate::Tempo` applies from the next beat on
+    pub async fn play_linear_metronome(mut self, duration: Duration, bpm: f64, settings: LinearRange) -> WorkerResult {
+        info!(?duration, bpm, "playing linear metronome");
+        let waiter = self.stop_after(duration);
+        let mut metronome = Metronome::new(bpm, Instant::now());
+        let mut result = Ok(());
+        let mut move_up = true;
+        while !self.external_cancel() {
+            self.hold_while_paused().await;
+            let now = Instant::now();
+            metronome.advance(now);
+            while let Ok(update) = self.update_receiver.try_recv() {
+                match update {
+                    SpeedUpdate::Tempo(bpm) => {
+                        debug!(self.handle, bpm, "tempo changed");
+                        metronome.set_bpm(bpm, now);
+                    }
+                    SpeedUpdate::Actuators(actuators) => self.retarget(actuators),
+                    _ => {}
+                }
+            }
+            let stroke_ms = metronome.next_beat().saturating_duration_since(now).as_millis() as u32;
+            let token = &self.cancellation_token.clone();
+            tokio::select! {
+                _ = token.cancelled() => {}
+                stroke = self.do_linear(settings.get_pos(move_up), stroke_ms) => result = stroke,
+            }
+            move_up = !move_up;
+        }
+        waiter.abort();
+        if let Err(err) = self.finish_positional().await {
+            result = Err(err);
+        }
+        info!("done");
+        result
+    }
+}
+
+fn clamp_bpm(bpm: f64) -> f64 {
+    if bpm.is_nan() {
+        return MIN_BPM;
+    }
+    bpm.clamp(MIN_BPM, MAX_BPM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use buttplug::core::message::ActuatorType;
+
+    use tokio::runtime::Handle;
+
+    use crate::tests::{PlayerTest, wait_ms};
+
+    use bp_fakes::*;
+
+    fn ms(x: u64) -> Duration {
+        Duration::from_millis(x)
+    }
+
+    #[test]
+    fn beats_follow_tempo_changes() {
+        let start = Instant::now();
+        let mut metronome = Metronome::new(120.0, start);
+        assert_eq!(metronome.next_beat(), start);
+
+        metronome.advance(start);
+        assert_eq!(metronome.next_beat(), start + ms(500));
+
+        // faster tempo moves the next beat closer to the last one
+        metronome.set_bpm(240.0, start + ms(100));
+        assert_eq!(metronome.next_beat(), start + ms(250));
+
+        // beats that already passed happen right away
+        metronome.set_bpm(600.0, start + ms(200));
+        assert_eq!(metronome.next_beat(), start + ms(200));
+
+        metronome.advance(start + ms(200));
+        assert_eq!(metronome.next_beat(), start + ms(300));
+        assert_eq!(metronome.pulse_length(ms(100)), ms(50));
+    }
+
+    #[test]
+    fn missed_beats_are_skipped() {
+        let start = Instant::now();
+        let mut metronome = Metronome::new(60.0, start);
+        metronome.advance(start + ms(3500));
+        assert_eq!(metronome.next_beat(), start + ms(4000));
+    }
+
+    #[test]
+    fn invalid_tempos_are_clamped() {
+        let start = Instant::now();
+        assert_eq!(Metronome::new(f64::NAN, start).bpm(), MIN_BPM);
+        assert_eq!(Metronome::new(0.0, start).bpm(), MIN_BPM);
+        assert_eq!(Metronome::new(10_000.0, start).bpm(), MAX_BPM);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_metronome_follows_tempo_changes() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let metronome = player.get_player();
+
+        // act
+        let start = std::time::Instant::now();
+        let task = Handle::current().spawn(async move {
+            let _ = metronome
+                .play_scalar_metronome(Duration::from_millis(950), 120.0, Duration::from_millis(100), Speed::new(80))
+                .await;
+        });
+        wait_ms(600).await;
+        player.scheduler.update_tempo(1, 240.0);
+        task.await.unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.0);
+        calls[1].assert_strenth(0.8).assert_time(0, start);
+        calls[2].assert_strenth(0.0).assert_time(100, start);
+        calls[3].assert_strenth(0.8).assert_time(500, start);
+        calls[4].assert_strenth(0.0).assert_time(600, start);
+        calls[5].assert_strenth(0.8).assert_time(750, start);
+        calls[6].assert_strenth(0.0).assert_time(850, start);
+        calls[7].assert_strenth(0.0).assert_time(950, start);
+    }
+}
@@ -1,55 +1,364 @@
+use buttplug::core::message::ActuatorType;
 use derive_new::new;
-use funscript::FScript;
+use funscript::{FSPoint, FScript};
 use tokio::runtime::Handle;
 use tokio::task::JoinHandle;
-use worker::{WorkerResult, WorkerTask};
+use lifecycle::LifecycleGuard;
+use lookahead::Lookahead;
+use pause::PauseSwitch;
+use strokes::StrokeCounter;
+use trigger::SamplingTrigger;
+use options::{EmptyPatternPolicy, Transposition};
+use worker::{combine_results, RequestId, WorkerResponse, WorkerResult, WorkerTask};
 
 use std::{
+    collections::HashMap,
     fmt,
     sync::{
-        atomic::{AtomicI64, Ordering},
-        Arc,
+        atomic::AtomicI64,
+        Arc, RwLock,
     },
     time::Duration,
 };
 use tokio::{
     sync::mpsc::{UnboundedReceiver, UnboundedSender},
-    time::{sleep, Instant},
+    time::{sleep, sleep_until, Instant},
 };
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, trace};
 
 use crate::{
-    actuator::Actuator,
+    actuator::{Actuator, ActuatorCommand},
     cancellable_wait,
-    config::linear::{LinearRange, LinearSpeedScaling},
+    pattern::upsample,
+    config::{actuators::ActuatorConfig, client::{DeadbandSettings, QuietModeSettings}, scalar::PatternZero, linear::{LinearRange, LinearSpeedScaling, StrokeProfile}},
     speed::Speed,
     ActuatorLimits,
 };
 
 pub mod access;
+pub mod jitter;
+pub mod lifecycle;
+pub mod lookahead;
+pub mod metronome;
+pub mod options;
+pub mod pause;
+pub mod session_log;
+pub mod stats;
+pub mod status;
+pub mod strokes;
+pub mod timeline;
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
+pub mod trigger;
 pub mod worker;
 
+mod multi_axis;
+mod playlist;
+mod ramps;
+mod sampled;
+
+/// Duration of a full stroke that translates into max rotation speed
+const ROTATE_FULL_SPEED_MS: u32 = 200;
+
+/// Time to move to the position that is held instead of an empty pattern
+const HOLD_MOVE_MS: u32 = 500;
+
 #[derive(Debug)]
 pub enum Perc {
     Constant(Speed),
     Global(Arc<AtomicI64>),
 }
 
+/// What a repeating pattern plays after each loop, a `next_loop` that returns
+/// `Option<FScript>` repeats the current pattern with `None`
+#[allow(clippy::large_enum_variant)]
+pub enum NextLoop {
+    Repeat,
+    Play(FScript),
+    /// ends the task like reaching its duration
+    End,
+}
+
+impl From<Option<FScript>> for NextLoop {
+    fn from(value: Option<FScript>) -> Self {
+        value.map_or(NextLoop::Repeat, NextLoop::Play)
+    }
+}
+
+/// Linear actuators that a player moved. They are handed back to the tasks that were
+/// preempted by the player when it is dropped, also if its task never reached its end
+struct LinearRelease {
+    worker_task_sender: UnboundedSender<WorkerTask>,
+    handle: i32,
+    actuators: Vec<Arc<Actuator>>,
+}
+
+impl LinearRelease {
+    fn hold(&mut self, actuator: &Arc<Actuator>) {
+        if !self.actuators.iter().any(|x| x.identifier() == actuator.identifier()) {
+            self.actuators.push(actuator.clone());
+        }
+    }
+
+    fn release(&mut self, actuator: &Arc<Actuator>) {
+        if let Some(i) = self.actuators.iter().position(|x| x.identifier() == actuator.identifier()) {
+            let actuator = self.actuators.remove(i);
+            self.end_linear(actuator);
+        }
+    }
+
+    fn end_linear(&self, actuator: Arc<Actuator>) {
+        self.worker_task_sender
+            .send(WorkerTask::EndLinear(actuator, self.handle))
+            .unwrap_or_else(|err| error!("queue err {:?}", err));
+    }
+}
+
+impl Drop for LinearRelease {
+    fn drop(&mut self) {
+        for actuator in std::mem::take(&mut self.actuators) {
+            self.end_linear(actuator);
+        }
+    }
+}
+
+/// Speed update for a running task, either a single speed for all of its
+/// actuators, individual speed lanes per actuator identifier or a temporary boost
+#[derive(Debug, Clone)]
+pub enum SpeedUpdate {
+    All(Speed),
+    Lanes(HashMap<String, Speed>),
+    /// Raises the speed to at least the given value until the duration passed
+    Boost(Speed, Duration),
+    /// The actuator settings changed, re-sends the current speed with the new limits
+    Settings,
+    /// Changes the tempo of a metronome task in beats per minute
+    Tempo(f64),
+    /// Replaces the actuators of the task, see `ButtplugScheduler::retarget_task`
+    Actuators(Vec<Arc<Actuator>>),
+    /// Changes the silent gap between two loops of a pattern, zero removes it
+    LoopGap(Duration),
+}
+
 /// Pattern executor that can be passed from the schedulers main-thread to a sub-thread
 #[derive(new)]
 pub struct PatternPlayer {
     pub handle: i32,
     pub actuators: Vec<Arc<Actuator>>,
-    result_sender: UnboundedSender<WorkerResult>,
-    result_receiver: UnboundedReceiver<WorkerResult>,
-    update_receiver: UnboundedReceiver<Speed>,
+    result_sender: UnboundedSender<WorkerResponse>,
+    result_receiver: UnboundedReceiver<WorkerResponse>,
+    update_receiver: UnboundedReceiver<SpeedUpdate>,
     cancellation_token: CancellationToken,
     worker_task_sender: UnboundedSender<WorkerTask>,
     scalar_resolution_ms: i32,
+    #[new(default)]
+    last_request_id: RequestId,
+    /// results that arrived while awaiting a different request
+    #[new(default)]
+    pending_results: HashMap<RequestId, WorkerResult>,
+    /// stops of actuators that `retarget` removed, awaited when the task ends
+    #[new(default)]
+    retarget_requests: Vec<RequestId>,
+    /// per-actuator speeds that replace the task speed for that actuator
+    #[new(default)]
+    lanes: HashMap<String, Speed>,
+    /// last position of a positional pattern, used to derive rotation speeds
+    #[new(default)]
+    last_position: f64,
+    /// last point of a positional pattern, including points that were not sent in time
+    #[new(default)]
+    stroke_position: f64,
+    /// whether the last movement of a positional pattern went down
+    #[new(default)]
+    falling: bool,
+    /// shared by all players of the handle, see `ButtplugScheduler::set_stroke_milestones`
+    #[new(default)]
+    strokes: Option<Arc<StrokeCounter>>,
+    #[new(default)]
+    quiet_mode: Arc<RwLock<Option<QuietModeSettings>>>,
+    /// scales the speed of strokes, None is 100%
+    #[new(default)]
+    global_intensity: Arc<RwLock<Option<Speed>>>,
+    /// temporary minimum speed and the time it ends
+    #[new(default)]
+    boost: Option<(Speed, Instant)>,
+    #[new(default)]
+    transposition: Transposition,
+    /// latest actuator configs by identifier, take precedence over the
+    /// config the actuators were created with
+    #[new(default)]
+    live_configs: Arc<RwLock<HashMap<String, ActuatorConfig>>>,
+    #[new(default)]
+    lookahead: Arc<RwLock<Option<Lookahead>>>,
+    /// samples variables when the host signals instead of every 200ms
+    #[new(default)]
+    sampling_trigger: Option<SamplingTrigger>,
+    /// time to blend from the last point of a repeating pattern back to its first
+    #[new(default)]
+    loop_crossfade: Option<Duration>,
+    /// silence between two loops of a pattern
+    #[new(default)]
+    loop_gap: Option<Duration>,
+    /// time to fade scalar outputs in when the task starts
+    #[new(default)]
+    ramp_in: Duration,
+    /// time to fade scalar outputs out when the task ends
+    #[new(default)]
+    ramp_out: Duration,
+    /// start of the ramp in, scalar outputs are scaled by `ramp_factor` from then on
+    #[new(default)]
+    ramp_started: Option<Instant>,
+    /// cancelled by stops that skip the ramp out
+    #[new(default)]
+    halt: CancellationToken,
+    /// ignores small changes of sampled variables and expressions
+    #[new(default)]
+    deadband: Option<DeadbandSettings>,
+    /// shared with the scheduler, set to the time the task ends once it is known
+    #[new(default)]
+    deadline: Arc<RwLock<Option<Instant>>>,
+    /// shared with the scheduler, see `ButtplugScheduler::pause_task`
+    #[new(default)]
+    pause: PauseSwitch,
+    /// shared with the scheduler, set to the speed of the task once it is known
+    #[new(default)]
+    task_speed: Arc<RwLock<Option<Speed>>>,
+    /// last scalar value, task speed and whether it is a pattern, used to
+    /// start actuators that are added while the task is running
+    #[new(default)]
+    scalar_output: Option<(Speed, Speed, bool)>,
+    /// the scalar output is stopped while the task goes on (paused, between loops or
+    /// pulses, centered rotation), it stays at zero instead of `PatternZero::MinSpeed`
+    #[new(default)]
+    resting: bool,
+    /// reports the end of the player, see `ButtplugScheduler::create_with_events`
+    #[new(default)]
+    lifecycle: Option<LifecycleGuard>,
+    /// releases the linear actuators that were moved by the player
+    #[new(default)]
+    linear_release: Option<LinearRelease>,
+    /// pattern positions below 50 rotate counter-clockwise, see `play_rotate_pattern`
+    #[new(default)]
+    bidirectional: bool,
+    /// last direction that was sent to the rotate actuators
+    #[new(default)]
+    rotation: Option<bool>,
+    /// position in the pattern where the first loop starts
+    #[new(default)]
+    start_offset: Duration,
 }
 
 impl PatternPlayer {
+    /// Starts the player with individual speeds for some actuators (by identifier),
+    /// all other actuators use the speed passed to the play function
+    pub fn with_lanes(mut self, lanes: HashMap<String, Speed>) -> Self {
+        self.lanes = lanes;
+        self
+    }
+
+    /// Shares the quiet mode settings of the scheduler, strokes are slowed
+    /// down while they are set
+    pub fn with_quiet_mode(mut self, quiet_mode: Arc<RwLock<Option<QuietModeSettings>>>) -> Self {
+        self.quiet_mode = quiet_mode;
+        self
+    }
+
+    /// Shares the global intensity of the scheduler, see `ButtplugScheduler::set_global_intensity`
+    pub fn with_global_intensity(mut self, global_intensity: Arc<RwLock<Option<Speed>>>) -> Self {
+        self.global_intensity = global_intensity;
+        self
+    }
+
+    /// Shares the actuator configs of the scheduler, so that changed limits
+    /// apply to the running task
+    pub fn with_live_configs(mut self, live_configs: Arc<RwLock<HashMap<String, ActuatorConfig>>>) -> Self {
+        self.live_configs = live_configs;
+        self
+    }
+
+    /// Shares the lookahead of the scheduler, pattern commands are sent
+    /// earlier by its lead while it is set
+    pub fn with_lookahead(mut self, lookahead: Arc<RwLock<Option<Lookahead>>>) -> Self {
+        self.lookahead = lookahead;
+        self
+    }
+
+    /// Samples variables and expressions each time 'trigger' is signalled
+    pub fn with_sampling_trigger(mut self, trigger: Option<SamplingTrigger>) -> Self {
+        self.sampling_trigger = trigger;
+        self
+    }
+
+    /// Blends across the seam of repeating patterns within 'window' instead of
+    /// jumping from the last point straight back to the first
+    pub fn with_loop_crossfade(mut self, window: Option<Duration>) -> Self {
+        self.loop_crossfade = window;
+        self
+    }
+
+    /// Pauses for 'gap' each time a pattern completes a loop, scalar actuators
+    /// stop and linear actuators hold their position
+    pub fn with_loop_gap(mut self, gap: Option<Duration>) -> Self {
+        self.loop_gap = gap;
+        self
+    }
+
+    /// Fades scalar outputs in within 'ramp_in' and out within 'ramp_out', the
+    /// ramp out is skipped once 'halt' is cancelled
+    pub fn with_ramps(mut self, ramp_in: Duration, ramp_out: Duration, halt: CancellationToken) -> Self {
+        self.ramp_in = ramp_in;
+        self.ramp_out = ramp_out;
+        self.halt = halt;
+        self
+    }
+
+    /// Filters the sampled values of variables and expressions, None sends every change
+    pub fn with_deadband(mut self, deadband: Option<DeadbandSettings>) -> Self {
+        self.deadband = deadband;
+        self
+    }
+
+    /// Shares the end of the task with the scheduler, see `ButtplugScheduler::describe_handle`
+    pub fn with_deadline(mut self, deadline: Arc<RwLock<Option<Instant>>>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Shares the pause state with the scheduler
+    pub fn with_pause(mut self, pause: PauseSwitch) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Shares the speed of the task with the scheduler, see `ButtplugScheduler::step_up`
+    pub fn with_task_speed(mut self, task_speed: Arc<RwLock<Option<Speed>>>) -> Self {
+        self.task_speed = task_speed;
+        self
+    }
+
+    /// Counts the strokes of linear strokes and patterns
+    pub fn with_stroke_counter(mut self, strokes: Arc<StrokeCounter>) -> Self {
+        self.strokes = Some(strokes);
+        self
+    }
+
+    pub fn with_lifecycle(mut self, lifecycle: LifecycleGuard) -> Self {
+        self.lifecycle = Some(lifecycle);
+        self
+    }
+
+    /// Token that stops the player like `ButtplugScheduler::stop_task`
+    pub fn stop_token(&self) -> CancellationToken {
+        self.cancellation_token.clone()
+    }
+
+    /// Applies gain and offset to the values of played funscripts
+    pub fn with_transposition(mut self, transposition: Transposition) -> Self {
+        self.transposition = transposition;
+        self
+    }
+
     pub async fn play_linear_stroke(
         mut self,
         duration: Duration,
@@ -57,41 +366,76 @@ impl PatternPlayer {
         settings: LinearRange,
     ) -> WorkerResult {
         info!(?duration, "playing linear stroke");
+        self.report_speed(speed);
         let waiter = self.stop_after(duration);
         let mut result = Ok(());
         let mut current_speed = speed;
         while !self.external_cancel() {
+            self.hold_while_paused().await;
             self.try_update(&mut current_speed);
             result = self.do_stroke(true, current_speed, &settings).await;
             if self.external_cancel() {
                 break;
             }
+            self.hold_while_paused().await;
             self.try_update(&mut current_speed);
             result = self.do_stroke(false, current_speed, &settings).await;
+            self.count_stroke();
         }
         waiter.abort();
+        if let Err(err) = self.finish_positional().await {
+            result = Err(err);
+        }
         info!("done");
         result
     }
 
     /// Executes the linear 'fscript' for 'duration' and consumes the player
-    pub async fn play_linear(mut self, duration: Duration, fscript: FScript) -> WorkerResult {
+    pub async fn play_linear(self, duration: Duration, fscript: FScript) -> WorkerResult {
+        self.play_linear_patterns(duration, fscript, || None).await
+    }
+
+    /// Like `play_linear` but starts at 'start_offset' of the pattern, e.g. to follow
+    /// the timeline of a video. Offsets beyond the end of the pattern wrap around
+    pub async fn play_linear_from(mut self, duration: Duration, fscript: FScript, start_offset: Duration) -> WorkerResult {
+        self.start_offset = start_offset;
+        self.play_linear_patterns(duration, fscript, || None).await
+    }
+
+    /// Executes the linear 'fscript' for 'duration' and consumes the player, each time
+    /// a loop completes 'next_loop' decides what follows, see `NextLoop`
+    pub async fn play_linear_patterns<F, N>(mut self, duration: Duration, mut fscript: FScript, mut next_loop: F) -> WorkerResult
+    where
+        F: FnMut() -> N,
+        N: Into<NextLoop>,
+    {
         info!(?duration, "playing linear");
         let mut last_result = Ok(());
-        if fscript.actions.is_empty() || fscript.actions.iter().all(|x| x.at == 0) {
-            return last_result;
+        if !has_duration(&fscript) {
+            return self.play_empty_pattern(duration, Speed::max(), true).await;
         }
         let waiter = self.stop_after(duration);
+        // shifts the first loop instead of moving its start before `Instant::now`, which
+        // can underflow shortly after the clock started
+        let mut offset = pattern_offset(&fscript, std::mem::take(&mut self.start_offset));
+        let mut started = Instant::now();
         while !self.external_cancel() {
-            let started = Instant::now();
             for point in fscript.actions.iter() {
-                let point_as_float = Speed::from_fs(point).as_float();
+                self.retarget_pending();
+                let point_as_float = self.transposition.apply(point).as_float();
+                self.track_stroke(point_as_float);
                 if let Some(waiting_time) =
-                    Duration::from_millis(point.at as u64).checked_sub(started.elapsed())
+                    Duration::from_millis(point.at as u64).checked_sub(self.pattern_time(started) + offset)
                 {
                     let token = &self.cancellation_token.clone();
+                    let pause = &self.pause.clone();
                     if let Some(result) = tokio::select! {
                         _ = token.cancelled() => { None }
+                        _ = pause.paused() => {
+                            // holds the position and continues with the next point
+                            started += self.hold_while_paused().await;
+                            Some(Ok(()))
+                        }
                         result = async {
                             self.do_linear(point_as_float, waiting_time.as_millis() as u32).await
                         } => {
@@ -104,28 +448,92 @@ impl PatternPlayer {
                     }
                 }
             }
+            match next_loop().into() {
+                NextLoop::Play(next_fscript) if has_duration(&next_fscript) => fscript = next_fscript,
+                NextLoop::End => break,
+                _ => {}
+            }
+            self.retarget_pending();
+            if let Some(gap) = self.loop_gap.filter(|_| !self.external_cancel()) {
+                debug!(?gap, "loop gap");
+                if !cancellable_wait(gap, &self.cancellation_token).await {
+                    break;
+                }
+            }
+            if let Some(window) = self.loop_crossfade.filter(|_| !self.external_cancel()) {
+                let first = self.transposition.apply(&fscript.actions[0]).as_float();
+                let token = &self.cancellation_token.clone();
+                tokio::select! {
+                    _ = token.cancelled() => {}
+                    result = self.do_linear(first, window.as_millis() as u32) => last_result = result,
+                }
+            }
+            started = Instant::now() + self.lead();
+            offset = Duration::ZERO;
         }
         waiter.abort();
+        if let Err(err) = self.finish_positional().await {
+            last_result = Err(err);
+        }
         info!("done");
         last_result
     }
 
     /// Executes the scalar 'fscript' for 'duration' and consumes the player
-    pub async fn play_scalar_pattern(
+    pub async fn play_scalar_pattern(self, duration: Duration, fscript: FScript, speed: Speed) -> WorkerResult {
+        self.play_scalar_patterns(duration, fscript, speed, || None).await
+    }
+
+    /// Like `play_scalar_pattern` but starts at 'start_offset' of the pattern,
+    /// see `play_linear_from`
+    pub async fn play_scalar_pattern_from(
         mut self,
         duration: Duration,
         fscript: FScript,
         speed: Speed,
+        start_offset: Duration,
     ) -> WorkerResult {
-        if fscript.actions.is_empty() || fscript.actions.iter().all(|x| x.at == 0) {
-            return Ok(());
+        self.start_offset = start_offset;
+        self.play_scalar_patterns(duration, fscript, speed, || None).await
+    }
+
+    /// Executes the scalar 'fscript' for 'duration' and consumes the player, each time
+    /// a loop completes 'next_loop' decides what follows, see `NextLoop`
+    pub async fn play_scalar_patterns<F, N>(
+        mut self,
+        duration: Duration,
+        fscript: FScript,
+        speed: Speed,
+        mut next_loop: F,
+    ) -> WorkerResult
+    where
+        F: FnMut() -> N,
+        N: Into<NextLoop>,
+    {
+        if !has_duration(&fscript) {
+            return self.play_empty_pattern(duration, speed, false).await;
         }
         info!(?duration, ?speed, "playing scalar pattern");
+        let interpolation = self.transposition.interpolation;
+        let resolution_ms = self.scalar_resolution_ms;
+        let upsampled = move |fscript: FScript| match interpolation {
+            Some(interpolation) => upsample(&fscript, resolution_ms, interpolation),
+            None => fscript,
+        };
+        let mut fscript = upsampled(fscript);
+        self.report_speed(speed);
         let waiter = self.stop_after(duration);
-        let action_len = fscript.actions.len();
+        let mut action_len = fscript.actions.len();
         let mut started = false;
+        // shifts the first loop, see `play_linear_patterns`
+        let mut offset = pattern_offset(&fscript, std::mem::take(&mut self.start_offset));
         let mut loop_started = Instant::now();
-        let mut i: usize = 0;
+        // starts with the point that is playing at the offset
+        let mut i: usize = fscript
+            .actions
+            .iter()
+            .rposition(|x| Duration::from_millis(x.at.max(0) as u64) <= offset)
+            .unwrap_or(0);
         let mut current_speed = speed;
         loop {
             let mut j = 1;
@@ -136,29 +544,64 @@ impl PatternPlayer {
             }
             let current = &fscript.actions[i % action_len];
             let next = &fscript.actions[(i + j) % action_len];
-            if let Ok(update) = self.update_receiver.try_recv() {
-                current_speed = update;
-            }
+            self.try_update(&mut current_speed);
 
-            let speed = Speed::from_fs(current).multiply(&current_speed);
+            let speed = self.pattern_value(current);
             if !started {
-                self.do_scalar(speed, true);
+                self.ramp_started = Some(Instant::now());
+                self.do_scalar(speed, current_speed, true);
                 started = true;
             } else {
-                self.do_update(speed, true);
+                self.do_update(speed, current_speed, true);
             }
             if let Some(waiting_time) =
-                Duration::from_millis(next.at as u64).checked_sub(loop_started.elapsed())
+                Duration::from_millis(next.at as u64).checked_sub(self.pattern_time(loop_started) + offset)
             {
                 debug!(?speed, ?waiting_time, "vibrating");
-                if !(cancellable_wait(waiting_time, &self.cancellation_token).await) {
-                    debug!("scalar pattern cancelled");
-                    break;
+                match self.scalar_wait(waiting_time, speed, current_speed).await {
+                    Some(paused_for) => loop_started += paused_for,
+                    None => {
+                        debug!("scalar pattern cancelled");
+                        break;
+                    }
                 }
             }
             i += j;
             if (i % action_len) == 0 {
-                loop_started = Instant::now();
+                match next_loop().into() {
+                    NextLoop::Play(next_fscript) if has_duration(&next_fscript) => {
+                        fscript = upsampled(next_fscript);
+                        action_len = fscript.actions.len();
+                        i = 0;
+                    }
+                    NextLoop::End => {
+                        debug!("scalar patterns ended");
+                        break;
+                    }
+                    _ => {}
+                }
+                if self.external_cancel() {
+                    debug!("scalar pattern cancelled");
+                    break;
+                }
+                let mut last = speed;
+                if let Some(gap) = self.loop_gap {
+                    debug!(?gap, "loop gap");
+                    last = Speed::min();
+                    self.do_rest(current_speed, true);
+                    if self.scalar_wait(gap, last, current_speed).await.is_none() {
+                        debug!("scalar pattern cancelled");
+                        break;
+                    }
+                    self.try_update(&mut current_speed);
+                }
+                let first = self.pattern_value(&fscript.actions[0]);
+                if !self.crossfade(last, first, current_speed).await {
+                    debug!("scalar pattern cancelled");
+                    break;
+                }
+                loop_started = Instant::now() + self.lead();
+                offset = Duration::ZERO;
             }
         }
         waiter.abort();
@@ -167,52 +610,125 @@ impl PatternPlayer {
         result
     }
 
-    /// Executes a constant movement with 'speed' for 'duration' and consumes the player
-    pub async fn play_scalar(mut self, duration: Duration, speed: Speed) -> WorkerResult {
-        info!(?duration, ?speed, "playing scalar");
-        let waiter = self.stop_after(duration);
-        self.do_scalar(speed, false);
-        loop {
-            tokio::select! {
-                _ = self.cancellation_token.cancelled() => {
-                    break;
-                }
-                update = self.update_receiver.recv() => {
-                    if let Some(speed) = update {
-                        self.do_update(speed, false);
-                    }
+    /// Rotates in the given direction with 'speed' for 'duration' and consumes the player,
+    /// actuators that can not change their direction play 'speed' like `play_scalar`
+    pub async fn play_rotate(mut self, duration: Duration, speed: Speed, clockwise: bool) -> WorkerResult {
+        info!(?duration, ?speed, clockwise, "playing rotate");
+        self.set_rotation(clockwise);
+        self.play_scalar(duration, speed).await
+    }
+
+    /// Executes the rotate 'fscript' for 'duration' and consumes the player. Position 50
+    /// stops, higher positions rotate clockwise and lower ones counter-clockwise, the
+    /// further away from 50 the faster
+    pub async fn play_rotate_pattern(mut self, duration: Duration, fscript: FScript, speed: Speed) -> WorkerResult {
+        self.bidirectional = true;
+        self.play_scalar_pattern(duration, fscript, speed).await
+    }
+
+    /// Plays a funscript without actions according to `Transposition::empty_pattern`
+    async fn play_empty_pattern(mut self, duration: Duration, speed: Speed, positional: bool) -> WorkerResult {
+        let policy = self.transposition.empty_pattern;
+        info!(?duration, ?policy, "empty pattern");
+        match policy {
+            EmptyPatternPolicy::Return => {
+                self.cancellation_token.cancel();
+                Ok(())
+            }
+            EmptyPatternPolicy::Wait => {
+                let waiter = self.stop_after(duration);
+                self.cancellation_token.cancelled().await;
+                waiter.abort();
+                Ok(())
+            }
+            EmptyPatternPolicy::Constant(level) if positional => {
+                let waiter = self.stop_after(duration);
+                let token = self.cancellation_token.clone();
+                let mut result = tokio::select! {
+                    _ = token.cancelled() => Ok(()),
+                    result = self.do_linear(Speed::new(level.into()).as_float(), HOLD_MOVE_MS) => result,
+                };
+                token.cancelled().await;
+                waiter.abort();
+                if let Err(err) = self.finish_positional().await {
+                    result = Err(err);
                 }
-            };
+                result
+            }
+            EmptyPatternPolicy::Constant(level) => self.play_scalar(duration, Speed::new(level.into()) * speed).await,
         }
-        waiter.abort();
+    }
+
+    /// Sends 'speed' and the stop exactly once and consumes the player
+    pub async fn play_scalar_once(mut self, speed: Speed) -> WorkerResult {
+        info!(?speed, "playing scalar once");
+        self.do_scalar(Speed::max(), speed, false);
+        self.cancellation_token.cancel();
         let result = self.do_stop(false).await;
         info!("done");
         result
     }
 
-    /// Executes a constant movement with 'percentage' updating every 200ms
-    /// for 'duration' and consumes the player
-    pub async fn play_scalar_var(
-        self,
-        duration: Duration,
-        variable: Arc<AtomicI64>,
-    ) -> WorkerResult {
-        info!(?duration, "play scalar variable");
+    /// Executes a single stroke with 'speed' and consumes the player
+    pub async fn play_linear_once(mut self, speed: Speed, settings: LinearRange) -> WorkerResult {
+        info!(?speed, "playing linear once");
+        let mut result = self.do_stroke(true, speed, &settings).await;
+        self.cancellation_token.cancel();
+        if let Err(err) = self.finish_positional().await {
+            result = Err(err);
+        }
+        info!("done");
+        result
+    }
+
+    /// Executes a constant movement with 'speed' for 'duration' and consumes the player
+    pub async fn play_scalar(mut self, duration: Duration, mut speed: Speed) -> WorkerResult {
+        info!(?duration, ?speed, "playing scalar");
+        self.report_speed(speed);
         let waiter = self.stop_after(duration);
-        let mut last_var = variable.load(Ordering::Relaxed);
-        debug!(?last_var, self.handle, "var initialized");
-        self.do_scalar(Speed::new(last_var), false);
+        self.ramp_started = Some(Instant::now());
+        self.do_scalar(Speed::max(), speed, false);
+        let mut held = false;
+        let pause = self.pause.clone();
         loop {
+            let boost_end = self.boost.map(|(_, until)| until);
+            let ramp_step = self.next_ramp_step();
             tokio::select! {
                 _ = self.cancellation_token.cancelled() => {
                     break;
                 }
-                _ = sleep(Duration::from_millis(200)) => {
-                    let var = variable.load(Ordering::Relaxed);
-                    if var != last_var {
-                        debug!(?var, self.handle, "var updated");
-                        self.do_update(Speed::new(var), false);
-                        last_var = var;
+                update = self.update_receiver.recv() => {
+                    if let Some(update) = update {
+                        self.apply_update(update, &mut speed);
+                        if !held {
+                            self.do_update(Speed::max(), speed, false);
+                        }
+                    }
+                }
+                _ = pause.paused(), if !held => {
+                    debug!(self.handle, "paused");
+                    held = true;
+                    self.fade_to_rest(speed, false).await;
+                }
+                _ = pause.resumed(), if held => {
+                    debug!(self.handle, "resumed");
+                    held = false;
+                    // the update that resumed an auto-paused task may still be queued
+                    while let Ok(update) = self.update_receiver.try_recv() {
+                        self.apply_update(update, &mut speed);
+                    }
+                    self.ramp_in_again();
+                    self.do_update(Speed::max(), speed, false);
+                }
+                _ = sleep_until(boost_end.unwrap_or_else(Instant::now)), if boost_end.is_some() => {
+                    self.boost = None;
+                    if !held {
+                        self.do_update(Speed::max(), speed, false);
+                    }
+                }
+                _ = sleep_until(ramp_step.unwrap_or_else(Instant::now)), if ramp_step.is_some() => {
+                    if !held {
+                        self.do_update(Speed::max(), speed, false);
                     }
                 }
             };
@@ -223,13 +739,19 @@ impl PatternPlayer {
         result
     }
 
-    fn do_update(&self, speed: Speed, is_pattern: bool) {
+    /// Updates all actuators to 'value' scaled by the task 'speed'
+    /// or the speed lane of the respective actuator
+    fn do_update(&mut self, value: Speed, speed: Speed, is_pattern: bool) {
+        self.scalar_output = Some((value, speed, is_pattern));
+        self.resting &= value == Speed::min();
+        let ramp = self.ramp_factor();
         for actuator in &self.actuators {
-            trace!( actuator=actuator.identifier(), ?actuator.config, "do_update {} {:?}", speed, actuator);
+            let speed = value.multiply(&self.lane_speed(actuator, speed)) * ramp;
+            trace!(kind = "update", handle = self.handle, actuator_id = %actuator, value = speed.as_float(), limits = ?self.config(actuator).limits, "player command");
             self.worker_task_sender
                 .send(WorkerTask::Update(
                     actuator.clone(),
-                    apply_scalar_settings(speed, &actuator.get_config().limits),
+                    apply_scalar_settings(speed, &self.config(actuator).limits, is_pattern && !self.resting),
                     is_pattern,
                     self.handle,
                 ))
@@ -237,98 +759,466 @@ impl PatternPlayer {
         }
     }
 
-    fn do_scalar(&self, speed: Speed, is_pattern: bool) {
+    /// Drops the scalar output to zero without ending the task, see `resting`
+    fn do_rest(&mut self, speed: Speed, is_pattern: bool) {
+        self.resting = true;
+        self.do_update(Speed::min(), speed, is_pattern);
+    }
+
+    fn do_scalar(&mut self, value: Speed, speed: Speed, is_pattern: bool) {
+        self.scalar_output = Some((value, speed, is_pattern));
+        self.resting &= value == Speed::min();
         for actuator in &self.actuators {
-            trace!( actuator=actuator.identifier(), ?actuator.config, "do_scalar");
-            self.worker_task_sender
-                .send(WorkerTask::Start(
-                    actuator.clone(),
-                    apply_scalar_settings(speed, &actuator.get_config().limits),
-                    is_pattern,
-                    self.handle,
-                ))
-                .unwrap_or_else(|err| error!("queue err {:?}", err));
+            self.start_scalar(actuator, value, speed, is_pattern);
+        }
+    }
+
+    fn start_scalar(&self, actuator: &Arc<Actuator>, value: Speed, speed: Speed, is_pattern: bool) {
+        let speed = value.multiply(&self.lane_speed(actuator, speed)) * self.ramp_factor();
+        trace!(kind = "start", handle = self.handle, actuator_id = %actuator, value = speed.as_float(), limits = ?self.config(actuator).limits, "player command");
+        self.worker_task_sender
+            .send(WorkerTask::Start(
+                actuator.clone(),
+                apply_scalar_settings(speed, &self.config(actuator).limits, is_pattern && !self.resting),
+                is_pattern,
+                self.handle,
+            ))
+            .unwrap_or_else(|err| error!("queue err {:?}", err));
+    }
+
+    /// Stops the actuators that are no longer part of 'actuators' and starts the new
+    /// ones with the current scalar output, positional patterns move new actuators
+    /// with their next point
+    fn retarget(&mut self, actuators: Vec<Arc<Actuator>>) {
+        let contains = |list: &[Arc<Actuator>], actuator: &Arc<Actuator>| {
+            list.iter().any(|x| x.identifier() == actuator.identifier())
+        };
+        let removed = self.actuators.iter().filter(|x| !contains(&actuators, x)).cloned().collect::<Vec<_>>();
+        let added = actuators.iter().filter(|x| !contains(&self.actuators, x)).cloned().collect::<Vec<_>>();
+        debug!(handle = self.handle, ?removed, ?added, "retarget");
+        for actuator in removed {
+            match self.scalar_output {
+                Some((_, _, is_pattern)) => {
+                    let id = self.next_request_id();
+                    self.worker_task_sender
+                        .send(WorkerTask::End(actuator, is_pattern, self.handle, id, self.result_sender.clone()))
+                        .unwrap_or_else(|err| error!("queue err {:?}", err));
+                    self.retarget_requests.push(id);
+                }
+                None if actuator.actuator == ActuatorType::Position => {
+                    if let Some(linear_release) = &mut self.linear_release {
+                        linear_release.release(&actuator);
+                    }
+                }
+                None if actuator.actuator == ActuatorType::Rotate => {
+                    let id = self.next_request_id();
+                    self.do_rotate(&actuator, Speed::min(), true, id);
+                    self.retarget_requests.push(id);
+                }
+                None => {}
+            }
+        }
+        if let Some((value, speed, is_pattern)) = self.scalar_output {
+            for actuator in &added {
+                if let Some(clockwise) = self.rotation.filter(|_| actuator.command == ActuatorCommand::Rotate) {
+                    self.worker_task_sender
+                        .send(WorkerTask::RotateDirection(actuator.clone(), clockwise, self.handle))
+                        .unwrap_or_else(|err| error!("queue err {:?}", err));
+                }
+                self.start_scalar(actuator, value, speed, is_pattern);
+            }
         }
+        self.actuators = actuators;
     }
 
     async fn do_stop(mut self, is_pattern: bool) -> WorkerResult {
-        for actuator in self.actuators.iter() {
-            trace!( actuator=actuator.identifier(), ?actuator.config, "do_stop");
+        self.fade_out(is_pattern).await;
+        let mut ids = vec![];
+        for actuator in self.actuators.clone().iter() {
+            trace!(kind = "end", handle = self.handle, actuator_id = %actuator, value = 0.0, limits = ?self.config(actuator).limits, "player command");
+            let id = self.next_request_id();
             self.worker_task_sender
                 .send(WorkerTask::End(
                     actuator.clone(),
                     is_pattern,
                     self.handle,
+                    id,
                     self.result_sender.clone(),
                 ))
                 .unwrap_or_else(|err| error!("queue err {:?}", err));
+            ids.push(id);
         }
-        let mut last_result = Ok(());
-        for _ in self.actuators.iter() {
-            last_result = self.result_receiver.recv().await.unwrap();
-        }
-        last_result
+        ids.append(&mut self.retarget_requests);
+        combine_results(self.await_results(ids).await)
     }
 
     async fn do_linear(&mut self, mut pos: f64, duration_ms: u32) -> WorkerResult {
-        for actuator in &self.actuators {
-            let settings = &actuator.get_config().limits.linear_or_max();
+        let mut ids = vec![];
+        let (rotate_speed, clockwise) = rotation_for_move(self.last_position, pos, duration_ms);
+        self.last_position = pos;
+        for actuator in self.actuators.clone().iter() {
+            let id = self.next_request_id();
+            if actuator.actuator == ActuatorType::Rotate {
+                self.do_rotate(actuator, rotate_speed, clockwise, id);
+                ids.push(id);
+                continue;
+            }
+            let settings = &self.config(actuator).limits.linear_or_max();
             pos = settings.apply_pos(pos);
-            trace!(?duration_ms, ?pos, ?settings, "linear");
-            self.worker_task_sender
-                .send(WorkerTask::Move(
-                    actuator.clone(),
-                    pos,
-                    duration_ms,
-                    true,
-                    self.result_sender.clone(),
-                ))
-                .unwrap_or_else(|err| error!("queue err {:?}", err));
+            trace!(kind = "move", handle = self.handle, actuator_id = %actuator, value = pos, duration_ms, ?settings, "player command");
+            self.send_move(actuator, pos, duration_ms, true, id);
+            ids.push(id);
         }
         sleep(Duration::from_millis(duration_ms as u64)).await;
-        self.result_receiver.recv().await.unwrap()
+        combine_results(self.await_results(ids).await)
     }
 
     async fn do_stroke(
         &mut self,
         start: bool,
-        mut speed: Speed,
+        speed: Speed,
         settings: &LinearRange,
     ) -> WorkerResult {
         let mut wait_ms = 0;
-        for actuator in &self.actuators {
-            let actual_settings = settings.merge(&actuator.get_config().limits.linear_or_max());
-            speed = actual_settings.scaling.apply(speed);
-            wait_ms = actual_settings.get_duration_ms(speed);
+        let mut ids = vec![];
+        let mut segmented = vec![];
+        for actuator in self.actuators.clone().iter() {
+            let mut actual_settings = settings.merge(&self.config(actuator).limits.linear_or_max());
+            if let Some(quiet_mode) = self.quiet_mode.read().unwrap().as_ref() {
+                actual_settings.min_ms = actual_settings.min_ms.max(quiet_mode.min_stroke_ms);
+                actual_settings.max_ms = actual_settings.max_ms.max(actual_settings.min_ms);
+            }
+            let speed = actual_settings.scaling.apply(self.lane_speed(actuator, speed));
+            // rotate speeds are scaled by the worker
+            wait_ms = match *self.global_intensity.read().unwrap() {
+                Some(intensity) => actual_settings.get_duration_ms(speed.multiply(&intensity)),
+                None => actual_settings.get_duration_ms(speed),
+            };
             let target_pos = actual_settings.get_pos(start);
-            debug!(?wait_ms, ?target_pos, ?actual_settings, "stroke");
-            self.worker_task_sender
-                .send(WorkerTask::Move(
-                    actuator.clone(),
-                    target_pos,
-                    wait_ms,
-                    true,
-                    self.result_sender.clone(),
-                ))
-                .unwrap_or_else(|err| error!("queue err {:?}", err));
+            debug!(kind = "stroke", handle = self.handle, actuator_id = %actuator, value = target_pos, duration_ms = wait_ms, ?actual_settings, "player command");
+            let id = self.next_request_id();
+            if actuator.actuator == ActuatorType::Rotate {
+                self.do_rotate(actuator, speed, start, id);
+                ids.push(id);
+                continue;
+            }
+            let mut moves = actual_settings
+                .profile
+                .segments(actual_settings.get_pos(!start), target_pos, wait_ms);
+            let (pos, ms) = moves.remove(0);
+            self.send_move(actuator, pos, ms, moves.is_empty(), id);
+            ids.push(id);
+            if !moves.is_empty() {
+                segmented.push((actuator.clone(), moves, id, ms));
+            }
         }
         // breaks with multiple devices that have different settings
-        sleep(Duration::from_millis(wait_ms as u64)).await;
-        self.result_receiver.recv().await.unwrap()
+        let mut current_ms = segmented.first().map(|x| x.3).unwrap_or(wait_ms);
+        let segment_count = segmented.iter().map(|x| x.1.len()).max().unwrap_or(0);
+        for i in 0..segment_count {
+            sleep(Duration::from_millis(current_ms as u64)).await;
+            for (actuator, moves, id, _) in &segmented {
+                if let Some((pos, ms)) = moves.get(i) {
+                    self.send_move(actuator, *pos, *ms, i == moves.len() - 1, *id);
+                }
+            }
+            current_ms = segmented.iter().filter_map(|x| x.1.get(i)).map(|x| x.1).max().unwrap_or(0);
+        }
+        sleep(Duration::from_millis(current_ms as u64)).await;
+        combine_results(self.await_results(ids).await)
+    }
+
+    fn send_move(&mut self, actuator: &Arc<Actuator>, pos: f64, duration_ms: u32, finish: bool, id: RequestId) {
+        self.linear_release
+            .get_or_insert_with(|| LinearRelease {
+                worker_task_sender: self.worker_task_sender.clone(),
+                handle: self.handle,
+                actuators: vec![],
+            })
+            .hold(actuator);
+        self.worker_task_sender
+            .send(WorkerTask::Move(
+                actuator.clone(),
+                pos,
+                duration_ms,
+                finish,
+                self.handle,
+                id,
+                self.result_sender.clone(),
+            ))
+            .unwrap_or_else(|err| error!("queue err {:?}", err));
+    }
+
+    fn do_rotate(&self, actuator: &Arc<Actuator>, speed: Speed, clockwise: bool, id: RequestId) {
+        let speed = apply_scalar_settings(speed, &self.config(actuator).limits, false);
+        trace!(kind = "rotate", handle = self.handle, actuator_id = %actuator, value = speed.as_float(), clockwise, "player command");
+        self.worker_task_sender
+            .send(WorkerTask::Rotate(
+                actuator.clone(),
+                speed.as_float(),
+                clockwise,
+                self.handle,
+                id,
+                self.result_sender.clone(),
+            ))
+            .unwrap_or_else(|err| error!("queue err {:?}", err));
+    }
+
+    /// Hands linear actuators back to tasks that were preempted by this one
+    /// and stops rotate actuators that followed a positional pattern
+    async fn finish_positional(&mut self) -> WorkerResult {
+        self.linear_release = None;
+        let mut ids = vec![];
+        for actuator in self.actuators.clone().iter() {
+            if actuator.actuator == ActuatorType::Rotate {
+                let id = self.next_request_id();
+                self.do_rotate(actuator, Speed::min(), true, id);
+                ids.push(id);
+            }
+        }
+        ids.append(&mut self.retarget_requests);
+        combine_results(self.await_results(ids).await)
+    }
+
+    fn next_request_id(&mut self) -> RequestId {
+        self.last_request_id += 1;
+        self.last_request_id
     }
 
+    /// Waits for the results of exactly the requests in 'ids', results
+    /// of other requests that arrive in between are kept for later
+    async fn await_results(&mut self, ids: Vec<RequestId>) -> Vec<WorkerResult> {
+        let mut results = vec![];
+        for id in ids {
+            loop {
+                if let Some(result) = self.pending_results.remove(&id) {
+                    results.push(result);
+                    break;
+                }
+                match self.result_receiver.recv().await {
+                    Some(response) => {
+                        if let (Err(err), Some(lifecycle)) = (&response.result, &self.lifecycle) {
+                            lifecycle.record_error(err);
+                        }
+                        self.pending_results.insert(response.id, response.result);
+                    }
+                    None => {
+                        error!(id, "result channel closed");
+                        break;
+                    }
+                }
+            }
+        }
+        results
+    }
+
+    /// Cancels the task after 'duration', time spent paused does not count
     fn stop_after(&self, duration: Duration) -> JoinHandle<()> {
         let cancellation_clone = self.cancellation_token.clone();
+        let deadline = self.deadline.clone();
+        let pause = self.pause.clone();
         Handle::current().spawn(async move {
-            sleep(duration).await;
-            cancellation_clone.cancel();
+            let mut remaining = duration;
+            loop {
+                pause.resumed().await;
+                let resumed = Instant::now();
+                // endless tasks (Duration::MAX) keep no deadline
+                *deadline.write().unwrap() = resumed.checked_add(remaining);
+                tokio::select! {
+                    _ = sleep(remaining) => {
+                        cancellation_clone.cancel();
+                        return;
+                    }
+                    _ = pause.paused() => {
+                        remaining = remaining.saturating_sub(resumed.elapsed());
+                        *deadline.write().unwrap() = None;
+                    }
+                }
+            }
         })
     }
 
+    /// Waits while the task is paused and returns how long it was paused,
+    /// linear actuators keep their position
+    async fn hold_while_paused(&self) -> Duration {
+        if !self.pause.is_paused() {
+            return Duration::ZERO;
+        }
+        debug!(self.handle, "paused");
+        let paused_at = Instant::now();
+        tokio::select! {
+            _ = self.cancellation_token.cancelled() => {}
+            _ = self.pause.resumed() => debug!(self.handle, "resumed"),
+        }
+        paused_at.elapsed()
+    }
+
+    /// Waits for 'duration' of playback of a positional pattern, no moves are sent while
+    /// the task is paused so linear actuators hold their position. Returns the time spent
+    /// paused, None if cancelled
+    async fn positional_wait(&self, duration: Duration) -> Option<Duration> {
+        let mut remaining = duration;
+        let mut paused_for = Duration::ZERO;
+        loop {
+            let started = Instant::now();
+            tokio::select! {
+                _ = self.cancellation_token.cancelled() => return None,
+                _ = sleep(remaining) => return Some(paused_for),
+                _ = self.pause.paused() => {
+                    remaining = remaining.saturating_sub(started.elapsed());
+                    paused_for += self.hold_while_paused().await;
+                    if self.external_cancel() {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Waits for 'duration' of playback while the pattern plays 'value', scalar outputs
+    /// drop to zero while the task is paused. Returns the time spent paused, None if cancelled
+    async fn scalar_wait(&mut self, duration: Duration, value: Speed, speed: Speed) -> Option<Duration> {
+        let mut remaining = duration;
+        let mut paused_for = Duration::ZERO;
+        loop {
+            let started = Instant::now();
+            let ramp_step = self.next_ramp_step();
+            tokio::select! {
+                _ = self.cancellation_token.cancelled() => return None,
+                _ = sleep(remaining) => return Some(paused_for),
+                _ = sleep_until(ramp_step.unwrap_or_else(Instant::now)), if ramp_step.is_some() => {
+                    remaining = remaining.saturating_sub(started.elapsed());
+                    self.do_update(value, speed, true);
+                }
+                _ = self.pause.paused() => {
+                    remaining = remaining.saturating_sub(started.elapsed());
+                    let resting = self.resting;
+                    let paused_at = Instant::now();
+                    self.fade_to_rest(speed, true).await;
+                    paused_for += paused_at.elapsed() + self.hold_while_paused().await;
+                    if self.external_cancel() {
+                        return None;
+                    }
+                    self.resting = resting;
+                    self.ramp_in_again();
+                    self.do_update(value, speed, true);
+                }
+            }
+        }
+    }
+
+    /// Applies actuator and loop gap changes of players that do not follow speed updates
+    fn retarget_pending(&mut self) {
+        while let Ok(update) = self.update_receiver.try_recv() {
+            match update {
+                SpeedUpdate::Actuators(actuators) => self.retarget(actuators),
+                SpeedUpdate::LoopGap(gap) => self.set_loop_gap(gap),
+                _ => {}
+            }
+        }
+    }
+
+    fn set_loop_gap(&mut self, gap: Duration) {
+        self.loop_gap = Some(gap).filter(|x| !x.is_zero());
+    }
+
+    /// Scalar value of a pattern point, rotate patterns also change the direction
+    fn pattern_value(&mut self, point: &FSPoint) -> Speed {
+        let value = self.transposition.apply(point);
+        if !self.bidirectional {
+            self.resting = false;
+            return value;
+        }
+        let (speed, clockwise) = rotation_for_value(value);
+        self.set_rotation(clockwise);
+        // the center stops the rotation
+        self.resting = speed == Speed::min();
+        speed
+    }
+
+    fn set_rotation(&mut self, clockwise: bool) {
+        if self.rotation == Some(clockwise) {
+            return;
+        }
+        self.rotation = Some(clockwise);
+        for actuator in self.actuators.iter().filter(|x| x.command == ActuatorCommand::Rotate) {
+            self.worker_task_sender
+                .send(WorkerTask::RotateDirection(actuator.clone(), clockwise, self.handle))
+                .unwrap_or_else(|err| error!("queue err {:?}", err));
+        }
+    }
+
     fn try_update(&mut self, speed: &mut Speed) {
         if let Ok(update) = self.update_receiver.try_recv() {
-            *speed = update;
+            self.apply_update(update, speed);
+        }
+    }
+
+    fn apply_update(&mut self, update: SpeedUpdate, speed: &mut Speed) {
+        match update {
+            SpeedUpdate::All(new_speed) => {
+                self.report_speed(new_speed);
+                *speed = new_speed
+            }
+            SpeedUpdate::Lanes(lanes) => self.lanes.extend(lanes),
+            SpeedUpdate::Boost(boost, duration) => self.boost = Some((boost, Instant::now() + duration)),
+            SpeedUpdate::Actuators(actuators) => self.retarget(actuators),
+            SpeedUpdate::LoopGap(gap) => self.set_loop_gap(gap),
+            SpeedUpdate::Settings | SpeedUpdate::Tempo(_) => {}
+        }
+    }
+
+    /// Counts a stroke each time a falling movement of a positional pattern turns into a rising one
+    fn track_stroke(&mut self, pos: f64) {
+        if pos > self.stroke_position && self.falling {
+            self.count_stroke();
+        }
+        if pos != self.stroke_position {
+            self.falling = pos < self.stroke_position;
         }
+        self.stroke_position = pos;
+    }
+
+    fn count_stroke(&self) {
+        if let Some(strokes) = &self.strokes {
+            strokes.record();
+        }
+    }
+
+    fn report_speed(&self, speed: Speed) {
+        *self.task_speed.write().unwrap() = Some(speed);
+    }
+
+    fn lane_speed(&self, actuator: &Actuator, speed: Speed) -> Speed {
+        let speed = *self.lanes.get(actuator.identifier()).unwrap_or(&speed);
+        match self.boost {
+            Some((boost, until)) if Instant::now() < until && boost > speed => boost,
+            _ => speed,
+        }
+    }
+
+    fn config(&self, actuator: &Actuator) -> ActuatorConfig {
+        self.live_configs
+            .read()
+            .unwrap()
+            .get(actuator.identifier())
+            .cloned()
+            .unwrap_or_else(|| actuator.get_config())
+    }
+
+    /// How early pattern commands are sent to make up for the connection latency
+    fn lead(&self) -> Duration {
+        self.lookahead
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|x| x.lead())
+            .unwrap_or_default()
+    }
+
+    /// Position in a pattern that started at 'started', ahead of the clock by the lead
+    fn pattern_time(&self, started: Instant) -> Duration {
+        (Instant::now() + self.lead()).saturating_duration_since(started)
     }
 
     fn external_cancel(&self) -> bool {
@@ -371,6 +1261,10 @@ impl LinearRange {
                 },
                 LinearSpeedScaling::Parabolic(n) => LinearSpeedScaling::Parabolic(n),
             },
+            profile: match settings.profile {
+                StrokeProfile::Constant => self.profile,
+                profile => profile,
+            },
         }
     }
     pub fn get_pos(&self, move_up: bool) -> f64 {
@@ -399,23 +1293,57 @@ impl LinearRange {
         }
     }
     pub fn get_duration_ms(&self, speed: Speed) -> u32 {
-        let factor = (100 - speed.value) as f64 / 100.0;
+        let factor = 1.0 - speed.as_float();
         let ms = self.min_ms as f64 + (self.max_ms - self.min_ms) as f64 * factor;
         ms as u32
     }
 }
 
-fn apply_scalar_settings(speed: Speed, settings: &ActuatorLimits) -> Speed {
-    if speed.value == 0 {
-        return speed;
+/// Rotation speed and direction for a move from position 'from' to 'to',
+/// a full stroke within ROTATE_FULL_SPEED_MS (or faster) rotates at max speed
+fn rotation_for_move(from: f64, to: f64, duration_ms: u32) -> (Speed, bool) {
+    let distance = (to - from).abs();
+    let speed = distance * ROTATE_FULL_SPEED_MS as f64 / duration_ms.max(1) as f64;
+    (Speed::from_float(speed.min(1.0)), to >= from)
+}
+
+/// Whether the actions of 'fscript' take any time, patterns that end at 0 would
+/// repeat without ever waiting
+fn has_duration(fscript: &FScript) -> bool {
+    fscript.actions.iter().any(|x| x.at > 0)
+}
+
+/// 'offset' within the length of the repeating 'fscript'
+fn pattern_offset(fscript: &FScript, offset: Duration) -> Duration {
+    match fscript.actions.last().map(|x| x.at).filter(|x| *x > 0) {
+        Some(length) => Duration::from_millis((offset.as_millis() % length as u128) as u64),
+        None => Duration::ZERO,
+    }
+}
+
+/// Rotation speed and direction for a value of a rotate pattern, 50% stops
+fn rotation_for_value(value: Speed) -> (Speed, bool) {
+    let centered = value.as_float() * 2.0 - 1.0;
+    (Speed::from_float(centered.abs()), centered >= 0.0)
+}
+
+/// Applies the scalar limits, 0 stays 0 unless a pattern plays with `PatternZero::MinSpeed`
+fn apply_scalar_settings(speed: Speed, settings: &ActuatorLimits, is_pattern: bool) -> Speed {
+    if speed == Speed::min() {
+        return match settings {
+            ActuatorLimits::Scalar(settings) if is_pattern && settings.pattern_zero == PatternZero::MinSpeed => {
+                Speed::new(settings.min_speed)
+            }
+            _ => speed,
+        };
     }
     match settings {
         ActuatorLimits::Scalar(settings) => {
             trace!("applying {settings:?}");
             let speed = Speed::from_float(speed.as_float() * settings.factor);
-            if speed.value < settings.min_speed as u16 {
+            if speed < Speed::new(settings.min_speed) {
                 Speed::new(settings.min_speed)
-            } else if speed.value > settings.max_speed as u16 {
+            } else if speed > Speed::new(settings.max_speed) {
                 Speed::new(settings.max_speed)
             } else {
                 speed
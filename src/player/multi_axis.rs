@@ -0,0 +1,225 @@
+use std::{sync::Arc, time::Duration};
+
+use buttplug::core::message::ActuatorType;
+use tokio::time::Instant;
+use tracing::{debug, info, trace};
+
+use crate::{
+    actuator::Actuator,
+    pattern::{AxisChannel, AxisTarget, STROKE_AXIS},
+    speed::Speed,
+};
+
+use super::{rotation_for_move, worker::{combine_results, WorkerResult}, PatternPlayer};
+
+impl PatternPlayer {
+    /// Plays each channel of a multi-axis pattern on the actuators of its target for
+    /// 'duration' and consumes the player. Actuators without a channel stay where they
+    /// are, strokes are counted on the stroke axis
+    pub async fn play_multi_axis(mut self, duration: Duration, channels: Vec<AxisChannel>) -> WorkerResult {
+        info!(?duration, channels = channels.len(), "playing multi-axis");
+        let moves = self.axis_moves(&channels);
+        let end = moves
+            .iter()
+            .map(|x| x.send_at + Duration::from_millis(x.duration_ms as u64))
+            .max()
+            .unwrap_or_default();
+        if end.is_zero() {
+            return self.play_empty_pattern(duration, Speed::max(), true).await;
+        }
+        let waiter = self.stop_after(duration);
+        let mut last_result = Ok(());
+        let mut started = Instant::now();
+        'playing: while !self.external_cancel() {
+            for axis_move in moves.iter() {
+                if let Some(waiting_time) = axis_move.send_at.checked_sub(self.pattern_time(started)) {
+                    match self.positional_wait(waiting_time).await {
+                        Some(paused_for) => started += paused_for,
+                        None => break 'playing,
+                    }
+                }
+                started += self.hold_while_paused().await;
+                if self.external_cancel() {
+                    break 'playing;
+                }
+                if axis_move.stroke {
+                    self.track_stroke(axis_move.pos);
+                    self.last_position = axis_move.pos;
+                }
+                let mut ids = vec![];
+                for actuator in axis_move.actuators.iter() {
+                    let id = self.next_request_id();
+                    if actuator.actuator == ActuatorType::Rotate {
+                        let (speed, clockwise) = rotation_for_move(axis_move.from, axis_move.pos, axis_move.duration_ms);
+                        self.do_rotate(actuator, speed, clockwise, id);
+                    } else {
+                        let pos = self.config(actuator).limits.linear_or_max().apply_pos(axis_move.pos);
+                        trace!(kind = "move", handle = self.handle, actuator_id = %actuator, value = pos, axis_move.duration_ms, "player command");
+                        self.send_move(actuator, pos, axis_move.duration_ms, true, id);
+                    }
+                    ids.push(id);
+                }
+                last_result = combine_results(self.await_results(ids).await);
+            }
+            if let Some(waiting_time) = end.checked_sub(self.pattern_time(started)) {
+                if self.positional_wait(waiting_time).await.is_none() {
+                    break;
+                }
+            }
+            started = Instant::now() + self.lead();
+        }
+        waiter.abort();
+        if let Err(err) = self.finish_positional().await {
+            last_result = Err(err);
+        }
+        info!("done");
+        last_result
+    }
+
+    /// Moves of all 'channels' ordered by the time they are sent, each point of a
+    /// channel is sent when the previous point is reached
+    fn axis_moves(&self, channels: &[AxisChannel]) -> Vec<AxisMove> {
+        let mut moves = vec![];
+        for channel in channels {
+            let actuators = self
+                .actuators
+                .iter()
+                .filter(|actuator| match &channel.target {
+                    AxisTarget::BodyPart(body_part) => self
+                        .config(actuator)
+                        .body_parts
+                        .iter()
+                        .any(|x| x.eq_ignore_ascii_case(body_part.trim())),
+                    AxisTarget::Index(index) => actuator.index_in_device == *index,
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            if actuators.is_empty() {
+                debug!(channel.axis, ?channel.target, "no actuator for axis");
+                continue;
+            }
+            let mut previous = (0, None);
+            for point in channel.fscript.actions.iter() {
+                let pos = self.transposition.apply(point).as_float();
+                let (previous_at, previous_pos) = previous;
+                moves.push(AxisMove {
+                    send_at: Duration::from_millis(previous_at.max(0) as u64),
+                    duration_ms: (point.at - previous_at).max(0) as u32,
+                    from: previous_pos.unwrap_or(pos),
+                    pos,
+                    actuators: actuators.clone(),
+                    stroke: channel.axis == STROKE_AXIS,
+                });
+                previous = (point.at.max(previous_at), Some(pos));
+            }
+        }
+        moves.sort_by_key(|x| x.send_at);
+        moves
+    }
+}
+
+/// Move of the actuators of an axis, see `PatternPlayer::play_multi_axis`
+struct AxisMove {
+    /// time within the pattern
+    send_at: Duration,
+    duration_ms: u32,
+    from: f64,
+    pos: f64,
+    actuators: Vec<Arc<Actuator>>,
+    stroke: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use funscript::{FSPoint, FScript};
+
+    use tokio::runtime::Handle;
+
+    use crate::actuator::{ActuatorConfigLoader, Actuators};
+    use crate::config::actuators::{ActuatorConfig, ActuatorSettings};
+    use crate::pattern::{AxisChannel, AxisTarget, STROKE_AXIS};
+    use crate::tests::{PlayerTest, wait_ms};
+
+    use bp_fakes::*;
+
+    #[tokio::test]
+    async fn test_multi_axis_channels_move_their_targets() {
+        // arrange
+        let client = get_test_client(vec![linear(1, "lin1"), linear(2, "lin2")]).await;
+        let mut settings = ActuatorSettings::default();
+        settings.update_device(ActuatorConfig {
+            body_parts: vec!["penis".into()],
+            ..ActuatorConfig::from_identifier("lin1 (Position)")
+        });
+        settings.update_device(ActuatorConfig {
+            body_parts: vec!["anal".into()],
+            ..ActuatorConfig::from_identifier("lin2 (Position)")
+        });
+        let mut player = PlayerTest::setup(client.created_devices.flatten_actuators().load_config(&mut settings));
+
+        let mut stroke = FScript::default();
+        stroke.actions.push(FSPoint { pos: 0, at: 200 });
+        stroke.actions.push(FSPoint { pos: 100, at: 400 });
+        let mut pitch = FScript::default();
+        pitch.actions.push(FSPoint { pos: 100, at: 100 });
+        let channels = vec![
+            AxisChannel { axis: STROKE_AXIS.into(), target: AxisTarget::BodyPart("penis".into()), fscript: stroke },
+            AxisChannel { axis: "pitch".into(), target: AxisTarget::BodyPart("anal".into()), fscript: pitch },
+        ];
+
+        // act
+        let start = Instant::now();
+        player
+            .get_player()
+            .play_multi_axis(Duration::from_millis(400), channels)
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_pos(0.0).assert_duration(200).assert_time(0, start);
+        calls[1].assert_pos(1.0).assert_duration(200).assert_time(200, start);
+        client.get_device_calls(2)[0]
+            .assert_pos(1.0)
+            .assert_duration(100)
+            .assert_time(0, start);
+    }
+
+    #[tokio::test]
+    async fn test_multi_axis_pause_holds_the_position() {
+        // arrange
+        let client = get_test_client(vec![linear(1, "lin1")]).await;
+        let mut settings = ActuatorSettings::default();
+        settings.update_device(ActuatorConfig {
+            body_parts: vec!["penis".into()],
+            ..ActuatorConfig::from_identifier("lin1 (Position)")
+        });
+        let mut player = PlayerTest::setup(client.created_devices.flatten_actuators().load_config(&mut settings));
+        let mut stroke = FScript::default();
+        stroke.actions.push(FSPoint { pos: 0, at: 200 });
+        stroke.actions.push(FSPoint { pos: 100, at: 400 });
+        let channels = vec![
+            AxisChannel { axis: STROKE_AXIS.into(), target: AxisTarget::BodyPart("penis".into()), fscript: stroke },
+        ];
+        let pattern_player = player.scheduler.create_player(player.actuators.clone(), -1);
+        let handle = pattern_player.handle;
+
+        // act
+        let start = Instant::now();
+        let task = Handle::current().spawn(pattern_player.play_multi_axis(Duration::from_millis(400), channels));
+        wait_ms(100).await;
+        assert!(player.scheduler.pause_task(handle));
+        wait_ms(200).await;
+        assert!(player.scheduler.resume_task(handle));
+        let _ = task.await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_pos(0.0).assert_duration(200).assert_time(0, start);
+        calls[1].assert_pos(1.0).assert_duration(200).assert_time(400, start);
+    }
+}
@@ -0,0 +1,179 @@
+use funscript::FSPoint;
+use serde::{Deserialize, Serialize};
+
+use crate::speed::Speed;
+
+/// What a dispatch does with a funscript that has no actions to play
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EmptyPatternPolicy {
+    /// the task finishes right away
+    #[default]
+    Return,
+    /// the task stays idle until its duration elapsed
+    Wait,
+    /// plays a constant level (percent) for the duration instead,
+    /// linear actuators hold that position
+    Constant(i32),
+}
+
+/// Curve of the intermediate values between two sparse pattern points, see `pattern::upsample`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    Linear,
+    /// eases in and out of each point
+    Cosine,
+}
+
+impl Interpolation {
+    /// Progress along the curve at 't' (0.0 to 1.0) of the way between two points
+    pub fn apply(&self, t: f64) -> f64 {
+        match self {
+            Interpolation::Linear => t,
+            Interpolation::Cosine => (1.0 - (std::f64::consts::PI * t).cos()) / 2.0,
+        }
+    }
+}
+
+/// Gain and offset applied to the funscript values of a single dispatch before
+/// the actuator limits, so the same pattern can be played subtle or intense
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transposition {
+    pub gain: f64,
+    pub offset: i32,
+    /// stretches or compresses the funscripts to play exactly once within the
+    /// duration of the dispatch instead of looping
+    #[serde(default)]
+    pub fit_duration: bool,
+    #[serde(default)]
+    pub empty_pattern: EmptyPatternPolicy,
+    /// fills the gaps of sparse scalar patterns with values at the scalar resolution,
+    /// None plays the points as they are
+    #[serde(default)]
+    pub interpolation: Option<Interpolation>,
+}
+
+impl Default for Transposition {
+    fn default() -> Self {
+        Transposition {
+            gain: 1.0,
+            offset: 0,
+            fit_duration: false,
+            empty_pattern: EmptyPatternPolicy::default(),
+            interpolation: None,
+        }
+    }
+}
+
+impl Transposition {
+    pub fn new(gain: f64, offset: i32) -> Self {
+        Transposition { gain, offset, ..Default::default() }
+    }
+
+    pub fn fit_to_duration(self) -> Self {
+        Transposition { fit_duration: true, ..self }
+    }
+
+    pub fn with_empty_pattern(self, empty_pattern: EmptyPatternPolicy) -> Self {
+        Transposition { empty_pattern, ..self }
+    }
+
+    pub fn with_interpolation(self, interpolation: Interpolation) -> Self {
+        Transposition { interpolation: Some(interpolation), ..self }
+    }
+
+    /// clamp(value * gain + offset)
+    pub fn apply(&self, point: &FSPoint) -> Speed {
+        Speed::new((point.pos as f64 * self.gain + self.offset as f64).round() as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, Instant};
+
+    use funscript::FScript;
+
+    use buttplug::core::message::ActuatorType;
+
+    use crate::actuator::Actuators;
+    use crate::PlayerSettings;
+    use crate::tests::PlayerTest;
+
+    use bp_fakes::*;
+
+    #[test]
+    fn transposition_scales_and_clamps_pattern_values() {
+        let point = FSPoint { pos: 60, at: 0 };
+        assert_eq!(Transposition::default().apply(&point).rounded(), 60);
+        assert_eq!(Transposition::new(0.5, 10).apply(&point).rounded(), 40);
+        assert_eq!(Transposition::new(2.0, 0).apply(&point).rounded(), 100);
+        assert_eq!(Transposition::new(1.0, -80).apply(&point).rounded(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_empty_pattern_policies() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup(client.created_devices.flatten_actuators().clone());
+        let start = Instant::now();
+
+        // act
+        player
+            .get_player()
+            .with_transposition(Transposition::default().with_empty_pattern(EmptyPatternPolicy::Wait))
+            .play_scalar_pattern(Duration::from_millis(100), FScript::default(), Speed::max())
+            .await
+            .unwrap();
+        let waited = start.elapsed();
+        player
+            .get_player()
+            .with_transposition(Transposition::default().with_empty_pattern(EmptyPatternPolicy::Constant(30)))
+            .play_scalar_pattern(Duration::from_millis(100), FScript::default(), Speed::new(50))
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        assert!(waited >= Duration::from_millis(100));
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.15).assert_time(100, start);
+        calls[1].assert_strenth(0.0).assert_time(200, start);
+        assert_eq!(calls.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_pattern_interpolates_at_scalar_resolution() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_with_settings(
+            client.created_devices.flatten_actuators(),
+            PlayerSettings {
+                scalar_resolution_ms: 100,
+                ..Default::default()
+            },
+        );
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 100, at: 0 });
+        fscript.actions.push(FSPoint { pos: 0, at: 400 });
+
+        // act
+        let start = Instant::now();
+        player
+            .get_player()
+            .with_transposition(Transposition::default().with_interpolation(Interpolation::Linear))
+            .play_scalar_pattern(Duration::from_millis(400), fscript, Speed::max())
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.75).assert_time(100, start);
+        calls[2].assert_strenth(0.5).assert_time(200, start);
+        calls[3].assert_strenth(0.25).assert_time(300, start);
+        calls.last().unwrap().assert_strenth(0.0).assert_time(400, start);
+    }
+}
@@ -0,0 +1,303 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    sync::watch,
+    time::{sleep_until, Instant},
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PauseState {
+    since: Option<Instant>,
+    /// time spent in pauses that ended
+    total: Duration,
+    /// timeout and time of the last update of tasks that pause without updates
+    auto: Option<(Duration, Instant)>,
+}
+
+impl PauseState {
+    /// Time the task auto-pauses (or did), None if it does not auto-pause
+    fn auto_pause_at(&self) -> Option<Instant> {
+        self.auto.map(|(timeout, last_update)| last_update + timeout)
+    }
+
+    /// Start of the current pause at 'now', None if the task is not paused
+    fn paused_since(&self, now: Instant) -> Option<Instant> {
+        let auto = self.auto_pause_at().filter(|x| *x <= now);
+        self.since.into_iter().chain(auto).min()
+    }
+
+    /// Adds the current pause to the total if 'change' ends it, or the part of it
+    /// that ended if another pause continues
+    fn end_pause<F: FnOnce(&mut Self)>(&mut self, change: F) {
+        let now = Instant::now();
+        let since = self.paused_since(now);
+        change(self);
+        if let Some(since) = since {
+            self.total += self.paused_since(now).unwrap_or(now).saturating_duration_since(since);
+        }
+    }
+}
+
+/// Pauses and resumes a task, shared between the scheduler and the player. Tasks
+/// can also pause automatically when they receive no updates, see `set_auto_pause`
+#[derive(Debug, Clone)]
+pub struct PauseSwitch {
+    state: Arc<watch::Sender<PauseState>>,
+}
+
+impl Default for PauseSwitch {
+    fn default() -> Self {
+        PauseSwitch {
+            state: Arc::new(watch::channel(PauseState::default()).0),
+        }
+    }
+}
+
+impl PauseSwitch {
+    /// Returns false if the task was already paused
+    pub fn pause(&self) -> bool {
+        self.state.send_if_modified(|state| match state.since {
+            Some(_) => false,
+            None => {
+                state.since = Some(Instant::now());
+                true
+            }
+        })
+    }
+
+    /// Returns false if the task was not paused with `pause`
+    pub fn resume(&self) -> bool {
+        self.state.send_if_modified(|state| {
+            let paused = state.since.is_some();
+            state.end_pause(|state| state.since = None);
+            paused
+        })
+    }
+
+    /// Pauses the task when it received no update for 'timeout' until the next
+    /// update, see `touch`. None turns it off
+    pub fn set_auto_pause(&self, timeout: Option<Duration>) {
+        self.state.send_modify(|state| {
+            state.end_pause(|state| state.auto = timeout.map(|x| (x, Instant::now())));
+        });
+    }
+
+    /// Records an update of the task, which resumes it if it auto-paused
+    pub fn touch(&self) {
+        self.state.send_if_modified(|state| {
+            let now = Instant::now();
+            let auto_paused = state.auto_pause_at().is_some_and(|x| x <= now);
+            state.end_pause(|state| {
+                if let Some((_, last_update)) = &mut state.auto {
+                    *last_update = now;
+                }
+            });
+            auto_paused
+        });
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.borrow().paused_since(Instant::now()).is_some()
+    }
+
+    /// Time spent paused, including the current pause
+    pub fn paused_for(&self) -> Duration {
+        let state = *self.state.borrow();
+        let now = Instant::now();
+        state.total + state.paused_since(now).map(|x| now - x).unwrap_or_default()
+    }
+
+    /// Completes once the task is paused
+    pub async fn paused(&self) {
+        self.wait_until(true).await
+    }
+
+    /// Completes once the task is not paused
+    pub async fn resumed(&self) {
+        self.wait_until(false).await
+    }
+
+    async fn wait_until(&self, paused: bool) {
+        let mut receiver = self.state.subscribe();
+        loop {
+            let (is_paused, auto_pause_at) = {
+                let state = receiver.borrow_and_update();
+                (state.paused_since(Instant::now()).is_some(), state.auto_pause_at())
+            };
+            if is_paused == paused {
+                return;
+            }
+            // auto-pauses begin without a change of the state
+            let auto_pause_at = auto_pause_at.filter(|_| paused);
+            tokio::select! {
+                changed = receiver.changed() => if changed.is_err() {
+                    return;
+                },
+                _ = sleep_until(auto_pause_at.unwrap_or_else(Instant::now)), if auto_pause_at.is_some() => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use funscript::{FSPoint, FScript};
+
+    use buttplug::core::message::ActuatorType;
+
+    use tokio::runtime::Handle;
+
+    use crate::actuator::Actuators;
+    use crate::PlayerSettings;
+    use crate::speed::Speed;
+    use crate::tests::{PlayerTest, wait_ms};
+
+    use bp_fakes::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn paused_time_is_accumulated() {
+        let switch = PauseSwitch::default();
+        assert!(!switch.resume());
+        assert!(switch.pause());
+        assert!(!switch.pause());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(switch.paused_for(), Duration::from_millis(100));
+
+        let waiter = tokio::spawn({
+            let switch = switch.clone();
+            async move { switch.resumed().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(switch.resume());
+        waiter.await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!switch.is_paused());
+        assert_eq!(switch.paused_for(), Duration::from_millis(150));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pauses_without_updates() {
+        let switch = PauseSwitch::default();
+        switch.set_auto_pause(Some(Duration::from_millis(100)));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        switch.touch();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!switch.is_paused());
+
+        let started = Instant::now();
+        switch.paused().await;
+        assert_eq!(started.elapsed(), Duration::from_millis(50));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(switch.pause());
+        switch.touch();
+        assert!(switch.is_paused());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(switch.resume());
+        assert!(!switch.is_paused());
+        assert_eq!(switch.paused_for(), Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn test_paused_pattern_resumes_from_same_point() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 100, at: 0 });
+        fscript.actions.push(FSPoint { pos: 50, at: 100 });
+        fscript.actions.push(FSPoint { pos: 20, at: 300 });
+        let pattern_player = player.scheduler.create_player(player.actuators.clone(), -1);
+        let handle = pattern_player.handle;
+
+        // act
+        let start = std::time::Instant::now();
+        let task = Handle::current().spawn(pattern_player.play_scalar_pattern(
+            Duration::from_millis(200),
+            fscript,
+            Speed::max(),
+        ));
+        wait_ms(50).await;
+        assert!(player.scheduler.pause_task(handle));
+        wait_ms(100).await;
+        assert!(player.scheduler.resume_task(handle));
+        let _ = task.await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.0).assert_time(50, start);
+        calls[2].assert_strenth(1.0).assert_time(150, start);
+        calls[3].assert_strenth(0.5).assert_time(200, start);
+        calls[4].assert_strenth(0.0).assert_time(300, start);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_auto_pauses_without_updates() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let start = std::time::Instant::now();
+        player.play_scalar(Duration::from_millis(400), Speed::new(50));
+        player.scheduler.set_auto_pause(1, Some(Duration::from_millis(100)));
+        wait_ms(200).await;
+        player.scheduler.update_task(1, Speed::new(70));
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        client.get_device_calls(1)[0].assert_strenth(0.5);
+        client.get_device_calls(1)[1].assert_strenth(0.0).assert_time(100, start);
+        client.get_device_calls(1)[2].assert_strenth(0.7).assert_time(200, start);
+        client.get_device_calls(1)[3].assert_strenth(0.0).assert_time(500, start);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_pattern_auto_pauses_with_ramps() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_with_settings(
+            client.created_devices.flatten_actuators(),
+            PlayerSettings {
+                scalar_resolution_ms: 50,
+                ramp_in_ms: 100,
+                ramp_out_ms: 100,
+            },
+        );
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 100, at: 0 });
+        fscript.actions.push(FSPoint { pos: 100, at: 2000 });
+
+        // act
+        let start = std::time::Instant::now();
+        let player_instance = player.scheduler.create_player(player.actuators.clone(), -1);
+        let handle = Handle::current().spawn(async move {
+            player_instance
+                .play_scalar_pattern(Duration::from_millis(600), fscript, Speed::max())
+                .await
+        });
+        player.scheduler.set_auto_pause(1, Some(Duration::from_millis(200)));
+        wait_ms(400).await;
+        player.scheduler.update_task(1, Speed::max());
+        handle.await.unwrap().unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(50, start);
+        calls[2].assert_strenth(1.0).assert_time(100, start);
+        calls[3].assert_strenth(0.5).assert_time(250, start);
+        calls[4].assert_strenth(0.0).assert_time(300, start);
+        calls[5].assert_strenth(0.0).assert_time(400, start);
+        calls[6].assert_strenth(0.5).assert_time(450, start);
+        calls[7].assert_strenth(1.0).assert_time(500, start);
+        calls[8].assert_strenth(0.5).assert_time(750, start);
+        calls[9].assert_strenth(0.0).assert_time(800, start);
+    }
+}
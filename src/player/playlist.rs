@@ -0,0 +1,240 @@
+use std::time::Duration;
+
+use funscript::FScript;
+use tracing::{debug, info};
+
+use crate::{pattern::copy_actions, speed::Speed};
+
+use super::{has_duration, worker::WorkerResult, NextLoop, PatternPlayer};
+
+impl PatternPlayer {
+    /// Plays the linear patterns of 'playlist' back to back for 'duration' and consumes
+    /// the player. The list starts over if 'repeat', otherwise the task ends after its
+    /// last pattern
+    pub async fn play_linear_playlist(self, duration: Duration, playlist: Vec<FScript>, repeat: bool) -> WorkerResult {
+        info!(?duration, patterns = playlist.len(), repeat, "playing linear playlist");
+        let (first, next_loop) = playlist_loop(playlist, repeat);
+        self.play_linear_patterns(duration, first, next_loop).await
+    }
+
+    /// Plays the scalar patterns of 'playlist' back to back, see `play_linear_playlist`
+    pub async fn play_scalar_playlist(
+        self,
+        duration: Duration,
+        playlist: Vec<FScript>,
+        speed: Speed,
+        repeat: bool,
+    ) -> WorkerResult {
+        info!(?duration, patterns = playlist.len(), repeat, "playing scalar playlist");
+        let (first, next_loop) = playlist_loop(playlist, repeat);
+        self.play_scalar_patterns(duration, first, speed, next_loop).await
+    }
+}
+
+/// First pattern of 'playlist' and a `next_loop` function that returns the following ones,
+/// patterns that take no time are skipped. Without 'repeat' it ends the task once the last
+/// pattern was played
+fn playlist_loop(playlist: Vec<FScript>, repeat: bool) -> (FScript, impl FnMut() -> NextLoop) {
+    let entries = playlist.len();
+    let playlist = playlist.into_iter().filter(has_duration).collect::<Vec<_>>();
+    if playlist.len() < entries {
+        debug!(skipped = entries - playlist.len(), "skipping empty playlist entries");
+    }
+    let first = playlist.first().map(copy_actions).unwrap_or_default();
+    let mut current = 0;
+    let next_loop = move || {
+        current += 1;
+        if current >= playlist.len() {
+            if !repeat {
+                return NextLoop::End;
+            }
+            current = 0;
+        }
+        playlist.get(current).map(copy_actions).map_or(NextLoop::End, NextLoop::Play)
+    };
+    (first, next_loop)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use funscript::{FSPoint, FScript};
+
+    use buttplug::core::message::ActuatorType;
+
+    use tokio::time::timeout;
+
+    use crate::speed::Speed;
+    use crate::tests::PlayerTest;
+
+    use bp_fakes::*;
+
+    #[tokio::test]
+    async fn test_scalar_pattern_pauses_between_loops() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        player.scheduler.set_loop_gap(Some(Duration::from_millis(100)));
+
+        // act
+        let mut fs = FScript::default();
+        fs.actions.push(FSPoint { pos: 100, at: 0 });
+        fs.actions.push(FSPoint { pos: 50, at: 100 });
+
+        let start = Instant::now();
+        player
+            .play_scalar_pattern(Duration::from_millis(250), fs, Speed::max())
+            .await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(100, start);
+        calls[2].assert_strenth(0.0).assert_time(100, start);
+        calls[3].assert_strenth(1.0).assert_time(200, start);
+        calls[4].assert_strenth(0.0).assert_time(250, start);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_patterns_replaced_per_loop() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let mut first = FScript::default();
+        first.actions.push(FSPoint { pos: 100, at: 0 });
+        first.actions.push(FSPoint { pos: 50, at: 100 });
+        let mut second = FScript::default();
+        second.actions.push(FSPoint { pos: 20, at: 0 });
+        second.actions.push(FSPoint { pos: 30, at: 100 });
+        let mut next = Some(second);
+
+        let start = Instant::now();
+        let player_instance = player.scheduler.create_player(player.actuators.clone(), -1);
+        player_instance
+            .play_scalar_patterns(Duration::from_millis(350), first, Speed::max(), || next.take())
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(100, start);
+        calls[2].assert_strenth(0.2).assert_time(100, start);
+        calls[3].assert_strenth(0.3).assert_time(200, start);
+        calls[4].assert_strenth(0.2).assert_time(200, start);
+        calls[5].assert_strenth(0.3).assert_time(300, start);
+        calls[6].assert_strenth(0.0).assert_time(350, start);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_playlist_ends_after_last_pattern() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+
+        // act
+        let mut first = FScript::default();
+        first.actions.push(FSPoint { pos: 100, at: 0 });
+        first.actions.push(FSPoint { pos: 50, at: 100 });
+        let mut second = FScript::default();
+        second.actions.push(FSPoint { pos: 20, at: 0 });
+        second.actions.push(FSPoint { pos: 30, at: 100 });
+
+        let start = Instant::now();
+        let player_instance = player.scheduler.create_player(player.actuators.clone(), -1);
+        player_instance
+            .play_scalar_playlist(Duration::from_secs(10), vec![first, second], Speed::max(), false)
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(100, start);
+        calls[2].assert_strenth(0.2).assert_time(100, start);
+        calls[3].assert_strenth(0.3).assert_time(200, start);
+        calls[4].assert_strenth(0.0).assert_time(200, start);
+        assert_eq!(calls.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_linear_playlist_skips_empty_entries_and_finishes() {
+        // arrange
+        let client = get_test_client(vec![linear(1, "lin1")]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let pattern = |points: &[(i32, i32)]| {
+            let mut fscript = FScript::default();
+            for (pos, at) in points {
+                fscript.actions.push(FSPoint { pos: *pos, at: *at });
+            }
+            fscript
+        };
+        let playlist = vec![
+            pattern(&[]),
+            pattern(&[(100, 50), (0, 100)]),
+            pattern(&[(30, 0)]),
+            pattern(&[(50, 50), (20, 100)]),
+        ];
+
+        // act
+        let start = Instant::now();
+        let player_instance = player.scheduler.create_player(player.actuators.clone(), -1);
+        let handle = player_instance.handle;
+        player_instance
+            .play_linear_playlist(Duration::from_secs(10), playlist, false)
+            .await
+            .unwrap();
+
+        // assert
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let calls = client.get_device_calls(1);
+        calls[0].assert_pos(1.0);
+        calls[1].assert_pos(0.0);
+        calls[2].assert_pos(0.5);
+        calls[3].assert_pos(0.2);
+        assert_eq!(calls.len(), 4);
+        let mut events = vec![];
+        while let Ok(event) = player.events.try_recv() {
+            events.push(format!("{:?}", event));
+        }
+        assert_eq!(events, vec![format!("Started({})", handle), format!("Finished({})", handle)]);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_patterns_ignore_next_loops_without_duration() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 100, at: 0 });
+        fscript.actions.push(FSPoint { pos: 50, at: 100 });
+
+        // act
+        let start = Instant::now();
+        let player_instance = player.scheduler.create_player(player.actuators.clone(), -1);
+        let result = timeout(
+            Duration::from_secs(2),
+            player_instance.play_scalar_patterns(Duration::from_millis(350), fscript, Speed::max(), || {
+                let mut instant = FScript::default();
+                instant.actions.push(FSPoint { pos: 80, at: 0 });
+                instant.actions.push(FSPoint { pos: 20, at: 0 });
+                Some(instant)
+            }),
+        )
+        .await;
+
+        // assert
+        client.print_device_calls(start);
+        assert!(result.is_ok());
+        let calls = client.get_device_calls(1);
+        assert!(calls.len() < 10);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(100, start);
+    }
+}
@@ -0,0 +1,247 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::{cancellable_wait, speed::Speed};
+
+use super::PatternPlayer;
+
+impl PatternPlayer {
+    /// Blends from the last point of a loop ('from') to the first point of the next
+    /// one ('to') in the steps of `fade_steps` within the loop crossfade window, the final
+    /// value is left to the pattern. Returns false if the task was cancelled
+    pub(super) async fn crossfade(&mut self, from: Speed, to: Speed, speed: Speed) -> bool {
+        let Some(window) = self.loop_crossfade else {
+            return true;
+        };
+        let steps = self.fade_steps(window);
+        for k in 1..=steps {
+            if !(cancellable_wait(window / steps, &self.cancellation_token).await) {
+                return false;
+            }
+            if k < steps {
+                let progress = k as f64 / steps as f64;
+                let value = from.as_float() + (to.as_float() - from.as_float()) * progress;
+                self.do_update(Speed::from_float(value), speed, true);
+            }
+        }
+        true
+    }
+
+    /// Share of the scalar outputs that the ramps let through once the ramp started, rising
+    /// from zero within the ramp in and falling to zero within the ramp out before the task
+    /// ends. Changes in the steps of `fade_steps`
+    pub(super) fn ramp_factor(&self) -> f64 {
+        if self.ramp_started.is_none() {
+            return 1.0;
+        }
+        let now = Instant::now();
+        let rising = match self.ramp_started.filter(|_| !self.ramp_in.is_zero()) {
+            Some(started) => {
+                let steps = self.fade_steps(self.ramp_in) as f64;
+                (now.duration_since(started).as_secs_f64() / self.ramp_in.as_secs_f64() * steps).floor() / steps
+            }
+            None => 1.0,
+        };
+        let falling = match (*self.deadline.read().unwrap()).filter(|_| !self.ramp_out.is_zero()) {
+            Some(deadline) => {
+                let steps = self.fade_steps(self.ramp_out) as f64;
+                (deadline.saturating_duration_since(now).as_secs_f64() / self.ramp_out.as_secs_f64() * steps).ceil() / steps
+            }
+            None => 1.0,
+        };
+        rising.min(falling).clamp(0.0, 1.0)
+    }
+
+    /// Time the `ramp_factor` changes next, None if it stays the same
+    pub(super) fn next_ramp_step(&self) -> Option<Instant> {
+        let now = Instant::now();
+        let rising = self
+            .ramp_started
+            .filter(|x| *x + self.ramp_in > now)
+            .map(|_| now + self.ramp_in / self.fade_steps(self.ramp_in));
+        let falling = (*self.deadline.read().unwrap())
+            .filter(|_| self.ramp_started.is_some() && !self.ramp_out.is_zero())
+            .and_then(|deadline| {
+                let step = self.ramp_out / self.fade_steps(self.ramp_out);
+                let first = deadline.checked_sub(self.ramp_out).unwrap_or(now) + step;
+                Some(first.max(now + step)).filter(|x| *x < deadline)
+            });
+        rising.into_iter().chain(falling).min()
+    }
+
+    /// Lowers the scalar output to zero within the ramp out, unless the task was halted
+    pub(super) async fn fade_out(&mut self, is_pattern: bool) {
+        let Some((value, speed, _)) = self.scalar_output.filter(|_| !self.ramp_out.is_zero()) else {
+            return;
+        };
+        // ramped tasks that reached their end faded out within their duration already
+        if self.ramp_started.is_some() && self.deadline.read().unwrap().is_some_and(|x| x <= Instant::now()) {
+            return;
+        }
+        debug!(ramp_out = ?self.ramp_out, "fade out");
+        let halt = self.halt.clone();
+        let steps = self.fade_steps(self.ramp_out);
+        // fades from the output that was sent last
+        let value = value * self.ramp_factor();
+        self.ramp_started = None;
+        for k in 1..steps {
+            if !(cancellable_wait(self.ramp_out / steps, &halt).await) {
+                return;
+            }
+            self.do_update(Speed::from_float(value.as_float() * (steps - k) as f64 / steps as f64), speed, is_pattern);
+        }
+        cancellable_wait(self.ramp_out / steps, &halt).await;
+    }
+
+    /// Lowers the scalar output to zero within the ramp out and rests, stops fading
+    /// early when the task is resumed or cancelled
+    pub(super) async fn fade_to_rest(&mut self, speed: Speed, is_pattern: bool) {
+        if let Some((value, _, _)) = self.scalar_output.filter(|_| !self.ramp_out.is_zero() && !self.resting) {
+            debug!(ramp_out = ?self.ramp_out, "fade to pause");
+            let steps = self.fade_steps(self.ramp_out);
+            // fades from the output that was sent last
+            let value = value * self.ramp_factor();
+            let ramp_started = self.ramp_started.take();
+            for k in 1..steps {
+                if !(cancellable_wait(self.ramp_out / steps, &self.cancellation_token).await) || !self.pause.is_paused() {
+                    break;
+                }
+                self.do_update(Speed::from_float(value.as_float() * (steps - k) as f64 / steps as f64), speed, is_pattern);
+            }
+            if self.pause.is_paused() {
+                cancellable_wait(self.ramp_out / steps, &self.cancellation_token).await;
+            }
+            self.ramp_started = ramp_started;
+        }
+        self.do_rest(speed, is_pattern);
+    }
+
+    /// Restarts the ramp in after a pause
+    pub(super) fn ramp_in_again(&mut self) {
+        if self.ramp_started.is_some() || !self.ramp_in.is_zero() {
+            self.ramp_started = Some(Instant::now());
+        }
+    }
+
+    /// Number of steps to change scalar outputs gradually within 'window'
+    pub(super) fn fade_steps(&self, window: Duration) -> u32 {
+        let resolution = self.scalar_resolution_ms.max(1) as u128;
+        (window.as_millis() / resolution).clamp(1, 10) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use funscript::{FSPoint, FScript};
+
+    use buttplug::core::message::ActuatorType;
+
+    use crate::actuator::Actuators;
+    use crate::PlayerSettings;
+    use crate::speed::Speed;
+    use crate::tests::PlayerTest;
+
+    use bp_fakes::*;
+
+    #[tokio::test]
+    async fn test_scalar_pattern_crossfades_loop_seam() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_with_settings(
+            client.created_devices.flatten_actuators(),
+            PlayerSettings {
+                scalar_resolution_ms: 50,
+                ..Default::default()
+            },
+        );
+        player.scheduler.set_loop_crossfade(Some(Duration::from_millis(100)));
+
+        // act
+        let mut fs = FScript::default();
+        fs.actions.push(FSPoint { pos: 100, at: 0 });
+        fs.actions.push(FSPoint { pos: 0, at: 100 });
+
+        let start = Instant::now();
+        player
+            .play_scalar_pattern(Duration::from_millis(250), fs, Speed::max())
+            .await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(1.0).assert_time(0, start);
+        calls[1].assert_strenth(0.0).assert_time(100, start);
+        calls[2].assert_strenth(0.5).assert_time(150, start);
+        calls[3].assert_strenth(1.0).assert_time(200, start);
+        calls[4].assert_strenth(0.0).assert_time(250, start);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_fades_in_and_out() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_with_settings(
+            client.created_devices.flatten_actuators(),
+            PlayerSettings {
+                scalar_resolution_ms: 50,
+                ramp_in_ms: 100,
+                ramp_out_ms: 100,
+            },
+        );
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(300), Speed::max());
+        player.await_last().await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(50, start);
+        calls[2].assert_strenth(1.0).assert_time(100, start);
+        calls[3].assert_strenth(0.5).assert_time(250, start);
+        calls[4].assert_strenth(0.0).assert_time(300, start);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_pattern_ramps_keep_the_pattern_clock() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_with_settings(
+            client.created_devices.flatten_actuators(),
+            PlayerSettings {
+                scalar_resolution_ms: 50,
+                ramp_in_ms: 100,
+                ramp_out_ms: 100,
+            },
+        );
+        let mut fscript = FScript::default();
+        fscript.actions.push(FSPoint { pos: 100, at: 0 });
+        fscript.actions.push(FSPoint { pos: 60, at: 150 });
+        fscript.actions.push(FSPoint { pos: 60, at: 1000 });
+
+        // act
+        let start = Instant::now();
+        let player_instance = player.scheduler.create_player(player.actuators.clone(), -1);
+        player_instance
+            .play_scalar_patterns(Duration::from_millis(300), fscript, Speed::max(), || None)
+            .await
+            .unwrap();
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.0).assert_time(0, start);
+        calls[1].assert_strenth(0.5).assert_time(50, start);
+        calls[2].assert_strenth(1.0).assert_time(100, start);
+        calls[3].assert_strenth(0.6).assert_time(150, start);
+        calls[4].assert_strenth(0.3).assert_time(250, start);
+        calls[5].assert_strenth(0.0).assert_time(300, start);
+        assert_eq!(calls.len(), 6);
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+use crate::{config::expression::BoundExpression, speed::Speed};
+
+use super::{trigger::SamplingTrigger, worker::WorkerResult, PatternPlayer, SpeedUpdate};
+
+impl PatternPlayer {
+    /// Executes a constant movement with 'percentage' updating every 200ms
+    /// or on each signal of the sampling trigger
+    /// for 'duration' and consumes the player
+    pub async fn play_scalar_var(
+        self,
+        duration: Duration,
+        variable: Arc<AtomicI64>,
+    ) -> WorkerResult {
+        info!(?duration, "play scalar variable");
+        self.play_scalar_sampled(duration, move || variable.load(Ordering::Relaxed)).await
+    }
+
+    /// Plays the value of 'expression', re-evaluated at the variable sampling rate
+    pub async fn play_scalar_expression(
+        self,
+        duration: Duration,
+        expression: Arc<BoundExpression>,
+    ) -> WorkerResult {
+        info!(?duration, expression.source, "play scalar expression");
+        self.play_scalar_sampled(duration, move || expression.sample()).await
+    }
+
+    pub(super) async fn play_scalar_sampled(
+        mut self,
+        duration: Duration,
+        sample: impl Fn() -> i64,
+    ) -> WorkerResult {
+        let waiter = self.stop_after(duration);
+        let mut last_var = sample();
+        let mut rising = None;
+        debug!(?last_var, self.handle, "var initialized");
+        self.do_scalar(Speed::new(last_var), Speed::max(), false);
+        let trigger = self.sampling_trigger.clone();
+        let mut held = false;
+        let pause = self.pause.clone();
+        loop {
+            tokio::select! {
+                _ = self.cancellation_token.cancelled() => {
+                    break;
+                }
+                _ = pause.paused(), if !held => {
+                    held = true;
+                    self.fade_to_rest(Speed::max(), false).await;
+                }
+                _ = pause.resumed(), if held => {
+                    held = false;
+                    last_var = sample();
+                    self.ramp_in_again();
+                    self.do_update(Speed::new(last_var), Speed::max(), false);
+                }
+                _ = next_sample(trigger.as_ref()), if !held => {
+                    let var = sample();
+                    let accepted = match self.deadband {
+                        Some(deadband) => deadband.accepts(last_var, var, rising),
+                        None => var != last_var,
+                    };
+                    if accepted {
+                        debug!(?var, self.handle, "var updated");
+                        self.do_update(Speed::new(var), Speed::max(), false);
+                        rising = Some(var > last_var);
+                        last_var = var;
+                    }
+                }
+                Some(update) = self.update_receiver.recv() => {
+                    match update {
+                        SpeedUpdate::Settings if !held => {
+                            self.do_update(Speed::new(last_var), Speed::max(), false);
+                        }
+                        SpeedUpdate::Actuators(actuators) => self.retarget(actuators),
+                        _ => {}
+                    }
+                }
+            };
+        }
+        waiter.abort();
+        let result = self.do_stop(false).await;
+        info!("done");
+        result
+    }
+}
+
+async fn next_sample(trigger: Option<&SamplingTrigger>) {
+    match trigger {
+        Some(trigger) => trigger.wait().await,
+        None => sleep(Duration::from_millis(200)).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::time::{Duration, Instant};
+
+    use buttplug::core::message::ActuatorType;
+
+    use tokio::runtime::Handle;
+
+    use crate::player::trigger::SamplingTrigger;
+    use crate::tests::{PlayerTest, wait_ms};
+
+    use bp_fakes::*;
+
+    #[tokio::test]
+    async fn test_scalar_var_samples_on_trigger() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let trigger = SamplingTrigger::new();
+        player.scheduler.set_sampling_trigger(Some(trigger.clone()));
+        let variable = Arc::new(AtomicI64::new(50));
+
+        // act
+        let start = Instant::now();
+        let task = player.get_player();
+        let var = variable.clone();
+        player.handles.push(Handle::current().spawn(async move {
+            let _ = task.play_scalar_var(Duration::from_millis(100), var).await;
+        }));
+        wait_ms(30).await;
+        variable.store(70, Ordering::Relaxed);
+        wait_ms(20).await;
+        trigger.signal();
+        player.await_all().await;
+
+        // assert
+        client.print_device_calls(start);
+        let calls = client.get_device_calls(1);
+        calls[0].assert_strenth(0.5).assert_time(0, start);
+        calls[1].assert_strenth(0.7).assert_time(50, start);
+        calls[2].assert_strenth(0.0).assert_time(100, start);
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::actuator::Actuator;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SessionCommand {
+    Start { value: f64 },
+    Update { value: f64 },
+    End,
+    Move { position: f64, duration_ms: u32 },
+    Rotate { speed: f64, clockwise: bool },
+    StopAll,
+}
+
+/// A single line of the session log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionLogEntry {
+    /// milliseconds since unix epoch
+    pub timestamp_ms: u128,
+    pub handle: i32,
+    pub action: Option<String>,
+    pub actuator: Option<String>,
+    pub command: SessionCommand,
+}
+
+#[derive(Default)]
+struct SessionLogState {
+    writer: Option<BufWriter<File>>,
+    action_names: HashMap<i32, String>,
+}
+
+/// Opt-in record of every actuator command the worker processes,
+/// written as json-lines so long sessions can be reviewed afterwards
+#[derive(Clone, Default)]
+pub struct SessionLog {
+    state: Arc<Mutex<SessionLogState>>,
+}
+
+impl SessionLog {
+    /// Starts recording into 'path', replacing a file that already exists
+    pub fn enable(&self, path: &Path) -> io::Result<()> {
+        let file = File::create(path)?;
+        let mut state = self.state.lock().unwrap();
+        if let Some(mut writer) = state.writer.replace(BufWriter::new(file)) {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Stops recording and flushes the current file
+    pub fn disable(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.action_names.clear();
+        match state.writer.take() {
+            Some(mut writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Closes the current file and continues recording into 'path'
+    pub fn rotate(&self, path: &Path) -> io::Result<()> {
+        self.enable(path)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state.lock().unwrap().writer.is_some()
+    }
+
+    /// Remembers the action name of 'handle' so that its commands can be attributed
+    pub fn set_action(&self, handle: i32, action_name: &str) {
+        let mut state = self.state.lock().unwrap();
+        if state.writer.is_some() {
+            state.action_names.insert(handle, action_name.to_owned());
+        }
+    }
+
+    pub fn record(&self, handle: i32, actuator: Option<&Actuator>, command: SessionCommand) {
+        let mut state = self.state.lock().unwrap();
+        let action = state.action_names.get(&handle).cloned();
+        if let Some(writer) = state.writer.as_mut() {
+            let entry = SessionLogEntry {
+                timestamp_ms: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|x| x.as_millis())
+                    .unwrap_or_default(),
+                handle,
+                action,
+                actuator: actuator.map(|x| x.identifier().to_owned()),
+                command,
+            };
+            let result = serde_json::to_writer(&mut *writer, &entry)
+                .map_err(io::Error::from)
+                .and_then(|_| writer.write_all(b"\n"));
+            if let Err(err) = result {
+                error!(?err, "failed writing session log");
+            }
+        }
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        match self.state.lock().unwrap().writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Debug for SessionLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionLog")
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn read_entries(path: &Path) -> Vec<SessionLogEntry> {
+        BufReader::new(File::open(path).unwrap())
+            .lines()
+            .map(|x| serde_json::from_str(&x.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn records_only_while_enabled() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let log = SessionLog::default();
+        log.record(1, None, SessionCommand::End);
+
+        log.enable(&path).unwrap();
+        log.set_action(1, "vibrate");
+        log.record(1, None, SessionCommand::Start { value: 0.5 });
+        log.record(2, None, SessionCommand::Move { position: 1.0, duration_ms: 200 });
+        log.disable().unwrap();
+        log.record(1, None, SessionCommand::End);
+
+        let entries = read_entries(&path);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, Some("vibrate".into()));
+        assert_eq!(entries[0].command, SessionCommand::Start { value: 0.5 });
+        assert_eq!(entries[1].action, None);
+    }
+
+    #[test]
+    fn rotate_continues_in_new_file() {
+        let dir = tempdir().unwrap();
+        let first = dir.path().join("1.jsonl");
+        let second = dir.path().join("2.jsonl");
+        let log = SessionLog::default();
+
+        log.enable(&first).unwrap();
+        log.record(1, None, SessionCommand::StopAll);
+        log.rotate(&second).unwrap();
+        log.record(1, None, SessionCommand::End);
+        log.flush().unwrap();
+
+        assert_eq!(read_entries(&first)[0].command, SessionCommand::StopAll);
+        assert_eq!(read_entries(&second)[0].command, SessionCommand::End);
+    }
+}
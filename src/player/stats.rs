@@ -0,0 +1,149 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+pub const ACTION_STATS_FILE: &str = "action_stats.json";
+
+/// Usage of a single action over all sessions
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ActionStats {
+    /// how often the action was started
+    pub count: u64,
+    /// cumulative play time in milliseconds
+    pub total_ms: u64,
+    /// intensity (0.0-1.0) sent to the devices, after the global intensity and caps,
+    /// integrated over the play time
+    pub intensity_ms: f64,
+}
+
+impl ActionStats {
+    /// Time-weighted average of the commanded intensity in 0.0-1.0
+    pub fn average_intensity(&self) -> f64 {
+        if self.total_ms == 0 {
+            return 0.0;
+        }
+        self.intensity_ms / self.total_ms as f64
+    }
+}
+
+#[derive(Debug)]
+struct RunningAction {
+    handle: i32,
+    name: String,
+    started: Instant,
+    value: f64,
+    value_since: Instant,
+    intensity_ms: f64,
+}
+
+impl RunningAction {
+    fn integrate(&mut self, now: Instant) {
+        self.intensity_ms += self.value * now.duration_since(self.value_since).as_millis() as f64;
+        self.value_since = now;
+    }
+}
+
+#[derive(Debug, Default)]
+struct StatsState {
+    actions: HashMap<String, ActionStats>,
+    running: Vec<RunningAction>,
+}
+
+/// Statistics per action name, shared between the scheduler that starts
+/// actions and the worker that sees the commanded values
+#[derive(Clone, Debug, Default)]
+pub struct ActionStatsStore {
+    state: Arc<Mutex<StatsState>>,
+}
+
+impl ActionStatsStore {
+    /// Continues counting from previously stored statistics
+    pub fn load(&self, actions: HashMap<String, ActionStats>) {
+        self.state.lock().unwrap().actions = actions;
+    }
+
+    pub fn start(&self, handle: i32, name: &str) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.actions.entry(name.to_owned()).or_default().count += 1;
+        state.running.push(RunningAction {
+            handle,
+            name: name.to_owned(),
+            started: now,
+            value: 0.0,
+            value_since: now,
+            intensity_ms: 0.0,
+        });
+    }
+
+    /// Intensity that was sent to an actuator of 'handle'
+    pub fn record(&self, handle: i32, value: f64) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        for running in state.running.iter_mut().filter(|x| x.handle == handle) {
+            running.integrate(now);
+            running.value = value;
+        }
+    }
+
+    pub fn finish(&self, handle: i32, name: &str) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let Some(pos) = state.running.iter().position(|x| x.handle == handle && x.name == name) else {
+            return;
+        };
+        let mut running = state.running.remove(pos);
+        running.integrate(now);
+        let stats = state.actions.entry(running.name).or_default();
+        stats.total_ms += now.duration_since(running.started).as_millis() as u64;
+        stats.intensity_ms += running.intensity_ms;
+    }
+
+    /// Statistics by action name, running actions are counted but
+    /// add their play time once they finish
+    pub fn get_all(&self) -> HashMap<String, ActionStats> {
+        self.state.lock().unwrap().actions.clone()
+    }
+
+    pub fn reset(&self) {
+        self.state.lock().unwrap().actions.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::advance;
+
+    use crate::test_util::run_paused;
+
+    use super::*;
+
+    #[test]
+    fn averages_intensity_over_time() {
+        run_paused(async {
+            let stats = ActionStatsStore::default();
+            stats.start(1, "vibrate");
+            stats.record(1, 1.0);
+            advance(Duration::from_millis(100)).await;
+            stats.record(1, 0.5);
+            stats.record(2, 0.0);
+            advance(Duration::from_millis(200)).await;
+            stats.record(1, 0.0);
+            advance(Duration::from_millis(100)).await;
+            stats.finish(1, "vibrate");
+            stats.start(3, "vibrate");
+            stats.finish(3, "vibrate");
+
+            let vibrate = &stats.get_all()["vibrate"];
+            assert_eq!(vibrate.count, 2);
+            assert_eq!(vibrate.total_ms, 400);
+            assert_eq!(vibrate.average_intensity(), 0.5);
+        });
+    }
+}
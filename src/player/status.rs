@@ -0,0 +1,165 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::actuator::Actuator;
+
+/// Most recent value (scalar speed, rotation speed or linear position) that each
+/// task commanded to each of its actuators, shared between the scheduler that
+/// describes the handles and the worker that executes the commands
+#[derive(Clone, Debug, Default)]
+pub struct CommandedValues {
+    values: Arc<Mutex<HashMap<i32, HashMap<String, f64>>>>,
+}
+
+impl CommandedValues {
+    pub fn record(&self, handle: i32, actuator: &Actuator, value: f64) {
+        self.record_identifier(handle, actuator.identifier(), value);
+    }
+
+    fn record_identifier(&self, handle: i32, identifier: &str, value: f64) {
+        self.values
+            .lock()
+            .unwrap()
+            .entry(handle)
+            .or_default()
+            .insert(identifier.to_owned(), value);
+    }
+
+    /// Values of 'handle' by actuator identifier
+    pub fn get(&self, handle: i32) -> HashMap<String, f64> {
+        self.values.lock().unwrap().get(&handle).cloned().unwrap_or_default()
+    }
+
+    /// Drops the values of all handles that 'keep' returns false for
+    pub fn retain<F: Fn(i32) -> bool>(&self, keep: F) {
+        self.values.lock().unwrap().retain(|handle, _| keep(*handle));
+    }
+}
+
+/// A single actuator that is driven by a handle
+#[derive(Debug, Clone)]
+pub struct ActuatorStatus {
+    pub actuator: Arc<Actuator>,
+    /// name of the action that drives the actuator, if it was dispatched by name
+    pub action: Option<String>,
+    /// last commanded value, None if the task did not send anything yet
+    pub value: Option<f64>,
+}
+
+/// What a handle is doing right now, see `ButtplugScheduler::describe_handle`
+#[derive(Debug, Clone)]
+pub struct HandleDescription {
+    pub handle: i32,
+    pub state: TaskState,
+    pub elapsed: Duration,
+    /// time spent playing, pauses excluded
+    pub playing: Duration,
+    /// None while the duration of the task is not known or the task is paused
+    pub remaining: Option<Duration>,
+    /// actuators of the players that are still running
+    pub actuators: Vec<ActuatorStatus>,
+}
+
+impl HandleDescription {
+    pub fn is_running(&self) -> bool {
+        matches!(self.state, TaskState::Running | TaskState::Paused)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    Running,
+    /// see `ButtplugScheduler::pause_task`
+    Paused,
+    /// finished or stopped
+    Finished,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use buttplug::core::message::ActuatorType;
+
+    use tokio::runtime::Handle;
+
+    use crate::speed::Speed;
+    use crate::tests::{PlayerTest, wait_ms};
+
+    use bp_fakes::*;
+
+    #[test]
+    fn keeps_latest_value_per_handle_and_actuator() {
+        let values = CommandedValues::default();
+        values.record_identifier(1, "vib1", 0.5);
+        values.record_identifier(1, "vib1", 0.7);
+        values.record_identifier(1, "lin1", 0.2);
+        values.record_identifier(2, "vib1", 0.1);
+        values.retain(|handle| handle != 2);
+
+        assert_eq!(values.get(1), HashMap::from([("vib1".to_owned(), 0.7), ("lin1".to_owned(), 0.2)]));
+        assert!(values.get(2).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_describe_handle_reports_playback() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let pattern_player = player.scheduler.create_player(player.actuators.clone(), -1);
+        let handle = pattern_player.handle;
+
+        // act
+        let task = Handle::current().spawn(pattern_player.play_scalar(Duration::from_millis(300), Speed::max()));
+        wait_ms(100).await;
+        let running = player.scheduler.describe_handle(handle).unwrap();
+        player.scheduler.pause_task(handle);
+        wait_ms(50).await;
+        let paused = player.scheduler.describe_handle(handle).unwrap();
+        player.scheduler.resume_task(handle);
+        let _ = task.await;
+        let finished = player.scheduler.describe_handle(handle).unwrap();
+
+        // assert
+        assert_eq!(running.state, TaskState::Running);
+        assert_eq!(running.playing, Duration::from_millis(100));
+        assert_eq!(running.remaining, Some(Duration::from_millis(200)));
+        assert_eq!(running.actuators.len(), 1);
+        assert_eq!(paused.state, TaskState::Paused);
+        assert_eq!(paused.playing, Duration::from_millis(100));
+        assert_eq!(paused.elapsed, Duration::from_millis(150));
+        assert_eq!(paused.remaining, None);
+        assert_eq!(finished.state, TaskState::Finished);
+        assert!(finished.actuators.is_empty());
+        assert!(player.scheduler.describe_handle(handle + 1).is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_describe_handle_reports_commanded_values() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let task = player.scheduler.create_action_player(player.actuators.clone(), -1, "vibrate");
+        Handle::current().spawn(async move {
+            let _ = task.play_scalar(Duration::from_secs(10), Speed::new(40)).await;
+        });
+
+        // act
+        wait_ms(4000).await;
+        let description = player.scheduler.describe_handle(1).unwrap();
+
+        // assert
+        assert_eq!(description.handle, 1);
+        assert_eq!(description.state, TaskState::Running);
+        assert_eq!(description.elapsed.as_secs(), 4);
+        assert_eq!(description.remaining.map(|x| x.as_secs()), Some(6));
+        assert_eq!(description.actuators.len(), 1);
+        assert_eq!(description.actuators[0].actuator.identifier(), "vib1 (Vibrate)");
+        assert_eq!(description.actuators[0].action.as_deref(), Some("vibrate"));
+        assert_eq!(description.actuators[0].value, Some(0.4));
+        assert!(player.scheduler.describe_handle(2).is_none());
+    }
+}
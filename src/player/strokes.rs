@@ -0,0 +1,174 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use super::lifecycle::{SchedulerEvent, SchedulerEvents};
+
+/// Stroke counts that a handle reports, see `ButtplugScheduler::set_stroke_milestones`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StrokeMilestones {
+    /// reports every n-th stroke with `SchedulerEvent::StrokeMilestone`
+    pub every: Option<u64>,
+    /// reports `SchedulerEvent::StrokeTarget` once this many strokes were completed
+    pub target: Option<u64>,
+    /// ends the handle when the target is reached
+    pub stop_at_target: bool,
+}
+
+/// Completed strokes of all players of a handle
+#[derive(Debug)]
+pub struct StrokeCounter {
+    handle: i32,
+    count: AtomicU64,
+    milestones: Mutex<StrokeMilestones>,
+    /// players of the handle, cancelled when it stops at its target
+    tokens: Mutex<Vec<CancellationToken>>,
+    event_sender: SchedulerEvents,
+}
+
+impl StrokeCounter {
+    pub fn new(handle: i32, event_sender: SchedulerEvents) -> Arc<Self> {
+        Arc::new(StrokeCounter {
+            handle,
+            count: AtomicU64::new(0),
+            milestones: Mutex::new(StrokeMilestones::default()),
+            tokens: Mutex::new(vec![]),
+            event_sender,
+        })
+    }
+
+    /// Adds a player of the handle
+    pub fn join(&self, cancellation_token: CancellationToken) {
+        self.tokens.lock().unwrap().push(cancellation_token);
+    }
+
+    pub fn set_milestones(&self, milestones: StrokeMilestones) {
+        *self.milestones.lock().unwrap() = milestones;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// Counts a completed stroke and reports the milestones it reached
+    pub fn record(&self) {
+        let count = self.count.fetch_add(1, Ordering::SeqCst) + 1;
+        let milestones = *self.milestones.lock().unwrap();
+        if milestones.every.is_some_and(|every| every > 0 && count.is_multiple_of(every)) {
+            self.event_sender.send(SchedulerEvent::StrokeMilestone(self.handle, count));
+        }
+        if milestones.target == Some(count) {
+            debug!(self.handle, count, milestones.stop_at_target, "stroke target reached");
+            self.event_sender.send(SchedulerEvent::StrokeTarget(self.handle, count));
+            if milestones.stop_at_target {
+                for token in self.tokens.lock().unwrap().iter() {
+                    token.cancel();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, Instant};
+
+    use funscript::{FSPoint, FScript};
+
+    use crate::actuator::Actuators;
+    use crate::config::linear::LinearRange;
+    use crate::speed::Speed;
+    use crate::tests::PlayerTest;
+
+    use bp_fakes::*;
+
+    #[test]
+    fn milestones_are_reported_and_target_stops() {
+        let (sender, mut events) = SchedulerEvents::channel();
+        let counter = StrokeCounter::new(3, sender);
+        let token = CancellationToken::new();
+        counter.join(token.clone());
+        counter.set_milestones(StrokeMilestones {
+            every: Some(2),
+            target: Some(3),
+            stop_at_target: true,
+        });
+
+        for _ in 0..3 {
+            counter.record();
+        }
+
+        assert_eq!(counter.count(), 3);
+        assert!(matches!(events.try_recv(), Ok(SchedulerEvent::StrokeMilestone(3, 2))));
+        assert!(matches!(events.try_recv(), Ok(SchedulerEvent::StrokeTarget(3, 3))));
+        assert!(events.try_recv().is_err());
+        assert!(token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_stroke_target_reports_milestones_and_stops() {
+        // arrange
+        let client = get_test_client(vec![linear(1, "lin1")]).await;
+        let mut player = PlayerTest::setup(client.created_devices.flatten_actuators().clone());
+        let range = LinearRange { min_ms: 50, max_ms: 50, ..LinearRange::max() };
+        let stroke = player.scheduler.create_player(player.actuators.clone(), -1);
+        let handle = stroke.handle;
+        let milestones = StrokeMilestones { every: Some(2), target: Some(3), stop_at_target: true };
+        assert!(player.scheduler.set_stroke_milestones(handle, milestones));
+
+        // act
+        let start = Instant::now();
+        let _ = stroke.play_linear_stroke(Duration::from_secs(10), Speed::max(), range).await;
+
+        // assert
+        assert!(start.elapsed() < Duration::from_millis(1000));
+        assert_eq!(player.scheduler.stroke_count(handle), Some(3));
+        let mut events = vec![];
+        while let Ok(event) = player.events.try_recv() {
+            events.push(format!("{:?}", event));
+        }
+        assert_eq!(
+            events,
+            vec![
+                format!("Started({})", handle),
+                format!("StrokeMilestone({}, 2)", handle),
+                format!("StrokeTarget({}, 3)", handle),
+                format!("Finished({})", handle),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_linear_pattern_strokes_count_once_per_handle() {
+        // arrange
+        let client = get_test_client(vec![linear(1, "lin1"), linear(2, "lin2")]).await;
+        let mut player = PlayerTest::setup(client.created_devices.flatten_actuators().clone());
+        let pattern = || {
+            let mut fscript = FScript::default();
+            for (i, pos) in [100, 0, 100, 0, 100, 0, 100].into_iter().enumerate() {
+                fscript.actions.push(FSPoint { pos, at: i as i32 * 50 });
+            }
+            fscript.actions.push(FSPoint { pos: 100, at: 1000 });
+            fscript
+        };
+        let first = player.scheduler.create_player(vec![player.actuators[0].clone()], -1);
+        let handle = first.handle;
+        let second = player.scheduler.create_player(vec![player.actuators[1].clone()], handle);
+
+        // act
+        let _ = futures::future::join(
+            first.play_linear(Duration::from_millis(500), pattern()),
+            second.play_linear(Duration::from_millis(500), pattern()),
+        )
+        .await;
+
+        // assert
+        assert_eq!(player.scheduler.stroke_count(handle), Some(3));
+    }
+}
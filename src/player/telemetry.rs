@@ -0,0 +1,126 @@
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelemetryFormat {
+    /// one json object per datagram
+    Json,
+    /// OSC message `/bp_scheduler/<kind>` with the actuator id and value as arguments
+    Osc,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TelemetrySettings {
+    /// host:port that receives the datagrams
+    pub target: String,
+    pub format: TelemetryFormat,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TelemetrySample {
+    pub actuator: String,
+    /// "scalar", "position" or "rotate"
+    pub kind: String,
+    pub value: f64,
+}
+
+/// Sends the values that are commanded to the actuators to an external
+/// receiver (visualizers, stream overlays...), errors are only logged
+#[derive(Debug)]
+pub struct Telemetry {
+    socket: UdpSocket,
+    target: SocketAddr,
+    format: TelemetryFormat,
+}
+
+impl Telemetry {
+    pub fn connect(settings: &TelemetrySettings) -> io::Result<Telemetry> {
+        let target = settings
+            .target
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"))?;
+        let bind = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_nonblocking(true)?;
+        debug!(?target, "telemetry connected");
+        Ok(Telemetry {
+            socket,
+            target,
+            format: settings.format,
+        })
+    }
+
+    pub fn emit(&self, actuator: &str, kind: &str, value: f64) {
+        let sample = TelemetrySample {
+            actuator: actuator.into(),
+            kind: kind.into(),
+            value,
+        };
+        let datagram = match self.format {
+            TelemetryFormat::Json => serde_json::to_vec(&sample).unwrap_or_default(),
+            TelemetryFormat::Osc => encode_osc(&sample),
+        };
+        if let Err(err) = self.socket.send_to(&datagram, self.target) {
+            error!(?err, "failed sending telemetry");
+        }
+    }
+}
+
+fn encode_osc(sample: &TelemetrySample) -> Vec<u8> {
+    fn push_str(buf: &mut Vec<u8>, s: &str) {
+        buf.extend_from_slice(s.as_bytes());
+        buf.push(0);
+        buf.resize(buf.len().next_multiple_of(4), 0);
+    }
+    let mut buf = vec![];
+    push_str(&mut buf, &format!("/bp_scheduler/{}", sample.kind));
+    push_str(&mut buf, ",sf");
+    push_str(&mut buf, &sample.actuator);
+    buf.extend_from_slice(&(sample.value as f32).to_be_bytes());
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn receive(format: TelemetryFormat) -> Vec<u8> {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let telemetry = Telemetry::connect(&TelemetrySettings {
+            target: receiver.local_addr().unwrap().to_string(),
+            format,
+        })
+        .unwrap();
+
+        telemetry.emit("vib1 (Vibrate)", "scalar", 0.5);
+
+        let mut buf = [0; 512];
+        let len = receiver.recv(&mut buf).unwrap();
+        buf[..len].to_vec()
+    }
+
+    #[test]
+    fn emits_json_samples() {
+        let sample: TelemetrySample = serde_json::from_slice(&receive(TelemetryFormat::Json)).unwrap();
+        assert_eq!(sample.actuator, "vib1 (Vibrate)");
+        assert_eq!(sample.kind, "scalar");
+        assert_eq!(sample.value, 0.5);
+    }
+
+    #[test]
+    fn emits_osc_messages() {
+        let datagram = receive(TelemetryFormat::Osc);
+        assert!(datagram.starts_with(b"/bp_scheduler/scalar\0\0\0\0,sf\0vib1 (Vibrate)\0\0"));
+        assert_eq!(datagram.len() % 4, 0);
+        assert_eq!(datagram[datagram.len() - 4..], 0.5_f32.to_be_bytes());
+    }
+}
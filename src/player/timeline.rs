@@ -0,0 +1,103 @@
+//! Timeline of all device calls
+//!
+//! The worker logs each command it executes as a debug event with the target
+//! `bp_scheduler::timeline`, so the timeline can be enabled on its own, e.g. with the
+//! filter `bp_scheduler::timeline=debug`. Players log the commands they send with the
+//! message `player command` and the same fields, strokes with the kind `stroke`.
+//! The fields are stable:
+//!
+//! | field         | type | content                                                         |
+//! |---------------|------|-----------------------------------------------------------------|
+//! | `kind`        | str  | see [CallKind]                                                  |
+//! | `handle`      | i32  | task that sent the command                                      |
+//! | `actuator_id` | str  | actuator identifier, redacted according to the logging settings |
+//! | `value`       | f64  | speed (0.0-1.0) or position (0.0-1.0), 0.0 for `end`            |
+//! | `duration_ms` | u32  | duration of a move, 0 for all other kinds                       |
+//! | `clockwise`   | bool | direction of a rotation, true for all other kinds               |
+
+use tracing::debug;
+
+use crate::actuator::Actuator;
+
+pub const TIMELINE_TARGET: &str = "bp_scheduler::timeline";
+
+/// Value of the `kind` field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    /// a task starts a scalar actuator
+    Start,
+    /// a task changes the speed of a scalar actuator
+    Update,
+    /// a task stops a scalar actuator
+    End,
+    /// a linear actuator moves to a position
+    Move,
+    /// a task stops moving a linear actuator
+    EndLinear,
+    /// a rotate actuator rotates, ignoring other tasks
+    Rotate,
+    /// the direction of a rotate actuator that is driven by start and update
+    RotateDirection,
+}
+
+impl CallKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallKind::Start => "start",
+            CallKind::Update => "update",
+            CallKind::End => "end",
+            CallKind::Move => "move",
+            CallKind::EndLinear => "end_linear",
+            CallKind::Rotate => "rotate",
+            CallKind::RotateDirection => "rotate_direction",
+        }
+    }
+}
+
+/// A device call that is part of the timeline
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceCall<'a> {
+    pub kind: CallKind,
+    pub handle: i32,
+    pub actuator: &'a Actuator,
+    pub value: f64,
+    pub duration_ms: u32,
+    pub clockwise: bool,
+}
+
+impl<'a> DeviceCall<'a> {
+    pub fn new(kind: CallKind, handle: i32, actuator: &'a Actuator, value: f64) -> Self {
+        DeviceCall {
+            kind,
+            handle,
+            actuator,
+            value,
+            duration_ms: 0,
+            clockwise: true,
+        }
+    }
+
+    pub fn with_duration_ms(mut self, duration_ms: u32) -> Self {
+        self.duration_ms = duration_ms;
+        self
+    }
+
+    pub fn with_clockwise(mut self, clockwise: bool) -> Self {
+        self.clockwise = clockwise;
+        self
+    }
+
+    /// Logs the call to the timeline target
+    pub fn log(&self) {
+        debug!(
+            target: TIMELINE_TARGET,
+            kind = self.kind.as_str(),
+            handle = self.handle,
+            actuator_id = %self.actuator,
+            value = self.value,
+            duration_ms = self.duration_ms,
+            clockwise = self.clockwise,
+            "device call"
+        );
+    }
+}
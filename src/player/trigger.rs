@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+/// Signalled by the host once per frame, variable-driven players sample
+/// their value on each signal instead of polling at a fixed rate
+#[derive(Debug, Clone, Default)]
+pub struct SamplingTrigger {
+    notify: Arc<Notify>,
+}
+
+impl SamplingTrigger {
+    pub fn new() -> Self {
+        SamplingTrigger::default()
+    }
+
+    /// Wakes all players that wait for the next sample
+    pub fn signal(&self) {
+        self.notify.notify_waiters();
+    }
+
+    pub async fn wait(&self) {
+        self.notify.notified().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::{sleep, Instant};
+
+    use crate::test_util::run_paused;
+
+    use super::*;
+
+    #[test]
+    fn wait_returns_on_signal() {
+        run_paused(async {
+            let trigger = SamplingTrigger::new();
+            let host = trigger.clone();
+            tokio::spawn(async move {
+                sleep(Duration::from_millis(16)).await;
+                host.signal();
+            });
+            let start = Instant::now();
+            trigger.wait().await;
+            assert_eq!(start.elapsed(), Duration::from_millis(16));
+        });
+    }
+}
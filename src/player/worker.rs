@@ -1,16 +1,34 @@
-use buttplug::client::{LinearCommand, ButtplugClientError};
-use std::{collections::HashMap, sync::Arc};
+use buttplug::{
+    client::ButtplugClientError,
+    core::{connector::ButtplugConnectorError, errors::{ButtplugDeviceError, ButtplugError}},
+};
+use std::{collections::{HashMap, VecDeque}, sync::Arc, time::Duration};
 
-use tokio::{runtime::Handle, sync::mpsc::UnboundedReceiver};
-use tracing::{error, info, trace};
+use tokio::{runtime::Handle, sync::mpsc::UnboundedReceiver, time::Instant};
+use tracing::{error, info, trace, warn};
 use tokio::sync::mpsc::UnboundedSender;
 
-use crate::{actuator::Actuator, speed::Speed};
+use crate::{actuator::{Actuator, ActuatorCommand}, config::client::DeviceClass, speed::Speed};
 
-use super::access::DeviceAccess;
+use super::access::{Degradation, DeviceAccess};
+use super::jitter::JitterBuffer;
+use super::session_log::{SessionCommand, SessionLog};
+use super::stats::ActionStatsStore;
+use super::status::CommandedValues;
+use super::timeline::{CallKind, DeviceCall};
 
 pub type WorkerResult<T = ()> = Result<T, WorkerError>;
 
+/// Identifies a single task that reports back a result so that the
+/// issuing player can await exactly the results of its own requests
+pub type RequestId = u64;
+
+#[derive(Debug)]
+pub struct WorkerResponse {
+    pub id: RequestId,
+    pub result: WorkerResult,
+}
+
 /// Process the queue of all device actions from all player threads
 ///
 /// This was introduced so that that the housekeeping and the decision which
@@ -18,6 +36,14 @@ pub type WorkerResult<T = ()> = Result<T, WorkerError>;
 /// its not necessary to introduce Mutex/etc to handle multithreaded access
 pub struct ButtplugWorker {
     pub task_receiver: UnboundedReceiver<WorkerTask>,
+    pub session_log: SessionLog,
+    /// shared with the scheduler, receives the commanded intensities
+    pub action_stats: ActionStatsStore,
+    /// shared with the scheduler, last value sent to each actuator by each task
+    pub commanded: CommandedValues,
+    pub(super) jitter_buffer: Option<JitterBuffer>,
+    /// tasks held back by the jitter buffer with the time they were received
+    pub(super) delayed: VecDeque<(Instant, WorkerTask)>,
 }
 
 #[derive(Clone, Debug)]
@@ -28,56 +54,225 @@ pub enum WorkerTask {
         Arc<Actuator>,
         bool,
         i32,
-        UnboundedSender<WorkerResult>,
+        RequestId,
+        UnboundedSender<WorkerResponse>,
     ),
     Move(
         Arc<Actuator>,
         f64,
         u32,
         bool,
-        UnboundedSender<WorkerResult>,
+        i32,
+        RequestId,
+        UnboundedSender<WorkerResponse>,
+    ),
+    /// the task stops moving the linear actuator
+    EndLinear(Arc<Actuator>, i32),
+    Rotate(
+        Arc<Actuator>,
+        f64,
+        bool,
+        i32,
+        RequestId,
+        UnboundedSender<WorkerResponse>,
     ),
+    /// direction of a rotate actuator that is driven by Start and Update, true is clockwise
+    RotateDirection(Arc<Actuator>, bool, i32),
+    /// a single command outside of any task with the message type of `Actuator::command`,
+    /// the value is a position (moved to within the duration) for linear actuators and a speed
+    /// otherwise, e.g. for self tests and init sequences. Speeds are scaled and capped like those
+    /// of tasks, the probe is refused with an error while a task drives the actuator
+    Probe(
+        Arc<Actuator>,
+        f64,
+        u32,
+        i32,
+        RequestId,
+        UnboundedSender<WorkerResponse>,
+    ),
+    /// caps scalar speeds of all actuators, None lifts the limit
+    SetCeiling(Option<Speed>),
+    /// scales scalar and rotate speeds of all actuators
+    SetIntensity(Speed),
+    #[cfg(feature = "telemetry")]
+    SetTelemetry(Option<Arc<super::telemetry::Telemetry>>),
+    /// limits the scalar commands per second for each device class
+    SetCommandBudgets(HashMap<DeviceClass, u32>),
+    /// blends the speeds of two tasks when one takes over an actuator, None switches instantly
+    SetTakeoverCrossfade(Option<Duration>),
+    /// holds back all following tasks to even out the latency of remote connections
+    SetJitterBuffer(Option<JitterBuffer>),
+    /// limits a single device (by index) to save power, None lifts the limits
+    SetDegradation(u32, Option<Degradation>),
     StopAll, // global but required for resetting device state
 }
 
 impl ButtplugWorker {
+    pub fn new(task_receiver: UnboundedReceiver<WorkerTask>, session_log: SessionLog) -> Self {
+        ButtplugWorker {
+            task_receiver,
+            session_log,
+            action_stats: ActionStatsStore::default(),
+            commanded: CommandedValues::default(),
+            jitter_buffer: None,
+            delayed: VecDeque::new(),
+        }
+    }
+
     pub async fn run_worker_thread(&mut self) {
         let mut device_access = DeviceAccess::default();
         loop {
-            if let Some(next_action) = self.task_receiver.recv().await {
-                trace!("worker exec action {:?}", next_action);
+            if let Some(next_action) = self.next_task().await {
+                trace!(task = ?next_action, "worker task");
                 match next_action {
                     WorkerTask::Start(actuator, speed, is_pattern, handle) => {
+                        DeviceCall::new(CallKind::Start, handle, &actuator, speed.as_float()).log();
+                        let scaled = device_access.scalar_output(&actuator, speed).as_float();
+                        self.session_log.record(handle, Some(&actuator), SessionCommand::Start { value: scaled });
+                        self.action_stats.record(handle, scaled);
+                        self.commanded.record(handle, &actuator, scaled);
                         device_access
                             .start_scalar(actuator, speed, is_pattern, handle)
                             .await;
                     }
                     WorkerTask::Update(actuator, speed, is_pattern, handle) => {
+                        DeviceCall::new(CallKind::Update, handle, &actuator, speed.as_float()).log();
+                        let scaled = device_access.scalar_output(&actuator, speed).as_float();
+                        self.session_log.record(handle, Some(&actuator), SessionCommand::Update { value: scaled });
+                        self.action_stats.record(handle, scaled);
+                        self.commanded.record(handle, &actuator, scaled);
                         device_access.update_scalar(actuator, speed, is_pattern, handle).await;
                     }
-                    WorkerTask::End(actuator, is_pattern, handle, result_sender) => {
+                    WorkerTask::End(actuator, is_pattern, handle, id, result_sender) => {
+                        DeviceCall::new(CallKind::End, handle, &actuator, 0.0).log();
+                        self.session_log.record(handle, Some(&actuator), SessionCommand::End);
+                        self.action_stats.record(handle, 0.0);
+                        self.commanded.record(handle, &actuator, 0.0);
                         let result = device_access
                             .stop_scalar(actuator.clone(), is_pattern, handle)
                             .await;
-                        if let Err(err) = result_sender.send(get_worker_result(result, actuator)) {
+                        let response = WorkerResponse { id, result: get_worker_result(result, actuator) };
+                        if let Err(err) = result_sender.send(response) {
                             error!("failed sending scalar result {:?}", err)
                         }
                     }
-                    WorkerTask::Move(actuator, position, duration_ms, finish, result_sender) => {
-                        let cmd = LinearCommand::LinearMap(HashMap::from([(
-                            actuator.index_in_device,
-                            (duration_ms, position),
-                        )]));
+                    WorkerTask::Move(actuator, position, duration_ms, finish, handle, id, result_sender) => {
+                        let Some(duration_ms) = device_access.acquire_linear(actuator.clone(), handle, duration_ms) else {
+                            trace!(handle, actuator_id = %actuator, "linear actuator preempted");
+                            if finish {
+                                let _ = result_sender.send(WorkerResponse { id, result: Ok(()) });
+                            }
+                            continue;
+                        };
+                        DeviceCall::new(CallKind::Move, handle, &actuator, position)
+                            .with_duration_ms(duration_ms)
+                            .log();
+                        self.session_log.record(handle, Some(&actuator), SessionCommand::Move { position, duration_ms });
+                        self.commanded.record(handle, &actuator, position);
+                        #[cfg(feature = "telemetry")]
+                        if let Some(telemetry) = &device_access.telemetry {
+                            telemetry.emit(actuator.identifier(), "position", position);
+                        }
+                        let command = actuator.linear(duration_ms, position);
                         Handle::current().spawn(async move {
-                            let result = actuator.device.linear(&cmd).await;
+                            let result = command.await;
                             if finish {
-                                if let Err(err) = result_sender.send(get_worker_result(result, actuator)) {
+                                let response = WorkerResponse { id, result: get_worker_result(result, actuator) };
+                                if let Err(err) = result_sender.send(response) {
                                     error!("failed sending linear result {:?}", err)
                                 }
                             }
                         });
                     }
+                    WorkerTask::EndLinear(actuator, handle) => {
+                        DeviceCall::new(CallKind::EndLinear, handle, &actuator, 0.0).log();
+                        device_access.release_linear(actuator, handle);
+                    }
+                    WorkerTask::Rotate(actuator, speed, clockwise, handle, id, result_sender) => {
+                        DeviceCall::new(CallKind::Rotate, handle, &actuator, speed)
+                            .with_clockwise(clockwise)
+                            .log();
+                        let command = device_access.rotate(actuator.clone(), Speed::from_float(speed), clockwise);
+                        let speed = device_access.apply_intensity(Speed::from_float(speed)).as_float();
+                        self.action_stats.record(handle, speed);
+                        self.session_log.record(handle, Some(&actuator), SessionCommand::Rotate { speed, clockwise });
+                        self.commanded.record(handle, &actuator, speed);
+                        #[cfg(feature = "telemetry")]
+                        if let Some(telemetry) = &device_access.telemetry {
+                            telemetry.emit(actuator.identifier(), "rotate", if clockwise { speed } else { -speed });
+                        }
+                        Handle::current().spawn(async move {
+                            let result = command.await;
+                            let response = WorkerResponse { id, result: get_worker_result(result, actuator) };
+                            if let Err(err) = result_sender.send(response) {
+                                error!("failed sending rotate result {:?}", err)
+                            }
+                        });
+                    }
+                    WorkerTask::RotateDirection(actuator, clockwise, handle) => {
+                        DeviceCall::new(CallKind::RotateDirection, handle, &actuator, 0.0)
+                            .with_clockwise(clockwise)
+                            .log();
+                        device_access.set_rotate_direction(actuator, clockwise);
+                    }
+                    WorkerTask::Probe(actuator, value, duration_ms, handle, id, result_sender) => {
+                        let result = if device_access.is_busy(&actuator) {
+                            warn!(%actuator, "probe refused, actuator is controlled by a task");
+                            let message = format!("{} is controlled by a task", actuator);
+                            Err(ButtplugClientError::from(ButtplugError::from(ButtplugDeviceError::UnhandledCommand(message))))
+                        } else {
+                            let (kind, command, sent) = match actuator.command {
+                                ActuatorCommand::Linear => {
+                                    (CallKind::Move, SessionCommand::Move { position: value, duration_ms }, value)
+                                }
+                                ActuatorCommand::Rotate => {
+                                    let speed = device_access.apply_intensity(Speed::from_float(value)).as_float();
+                                    (CallKind::Rotate, SessionCommand::Rotate { speed, clockwise: true }, speed)
+                                }
+                                ActuatorCommand::Scalar => {
+                                    let value = device_access.scalar_output(&actuator, Speed::from_float(value)).as_float();
+                                    (CallKind::Update, SessionCommand::Update { value }, value)
+                                }
+                            };
+                            DeviceCall::new(kind, handle, &actuator, value).with_duration_ms(duration_ms).log();
+                            self.session_log.record(handle, Some(&actuator), command);
+                            self.commanded.record(handle, &actuator, sent);
+                            device_access.probe(actuator.clone(), value, duration_ms).await
+                        };
+                        let response = WorkerResponse { id, result: get_worker_result(result, actuator) };
+                        if let Err(err) = result_sender.send(response) {
+                            error!("failed sending probe result {:?}", err)
+                        }
+                    }
+                    WorkerTask::SetCeiling(ceiling) => {
+                        device_access.set_ceiling(ceiling).await;
+                    }
+                    WorkerTask::SetIntensity(intensity) => {
+                        info!(?intensity, "set intensity");
+                        device_access.set_intensity(intensity).await;
+                    }
+                    #[cfg(feature = "telemetry")]
+                    WorkerTask::SetTelemetry(telemetry) => {
+                        device_access.telemetry = telemetry;
+                    }
+                    WorkerTask::SetCommandBudgets(budgets) => {
+                        info!(?budgets, "set command budgets");
+                        device_access.command_budgets = budgets;
+                    }
+                    WorkerTask::SetTakeoverCrossfade(window) => {
+                        info!(?window, "set takeover crossfade");
+                        device_access.takeover_crossfade = window;
+                    }
+                    WorkerTask::SetJitterBuffer(jitter_buffer) => {
+                        info!(?jitter_buffer, "set jitter buffer");
+                        self.jitter_buffer = jitter_buffer;
+                    }
+                    WorkerTask::SetDegradation(device_index, degradation) => {
+                        info!(device_index, ?degradation, "set degradation");
+                        device_access.set_degradation(device_index, degradation).await;
+                    }
                     WorkerTask::StopAll => {
+                        self.session_log.record(-1, None, SessionCommand::StopAll);
                         device_access.clear_all();
                         info!("stop all action");
                     }
@@ -90,7 +285,53 @@ impl ButtplugWorker {
 #[derive(Debug)]
 pub struct WorkerError {
     pub bp_error: ButtplugClientError,
-    pub actuator: Arc<Actuator>
+    pub actuator: Arc<Actuator>,
+    /// errors of other actuators that failed within the same command
+    pub related: Vec<WorkerError>,
+}
+
+/// Connector errors that can not be cloned are kept as their message
+impl Clone for WorkerError {
+    fn clone(&self) -> Self {
+        let bp_error = match &self.bp_error {
+            ButtplugClientError::ButtplugError(err) => ButtplugClientError::ButtplugError(err.clone()),
+            ButtplugClientError::ButtplugConnectorError(err) => {
+                ButtplugClientError::ButtplugConnectorError(match err {
+                    ButtplugConnectorError::ConnectorNotConnected => ButtplugConnectorError::ConnectorNotConnected,
+                    ButtplugConnectorError::ConnectorChannelClosed => ButtplugConnectorError::ConnectorChannelClosed,
+                    ButtplugConnectorError::ConnectorAlreadyConnected => ButtplugConnectorError::ConnectorAlreadyConnected,
+                    err => ButtplugConnectorError::ConnectorGenericError(err.to_string()),
+                })
+            }
+        };
+        WorkerError {
+            bp_error,
+            actuator: self.actuator.clone(),
+            related: self.related.clone(),
+        }
+    }
+}
+
+impl WorkerError {
+    /// This error followed by all related errors
+    pub fn errors(&self) -> impl Iterator<Item = &WorkerError> {
+        std::iter::once(self).chain(self.related.iter())
+    }
+}
+
+/// Combines the results of all actuators of a command, the first error
+/// carries the errors of all other actuators as `related`
+pub fn combine_results(results: Vec<WorkerResult>) -> WorkerResult {
+    let mut errors = results.into_iter().filter_map(Result::err);
+    let Some(mut first) = errors.next() else {
+        return Ok(());
+    };
+    for mut err in errors {
+        let related = std::mem::take(&mut err.related);
+        first.related.push(err);
+        first.related.extend(related);
+    }
+    Err(first)
 }
 
 fn get_worker_result<T>(bp_result: Result<T, ButtplugClientError>, actuator: Arc<Actuator>) -> Result<T, WorkerError> {
@@ -98,7 +339,111 @@ fn get_worker_result<T>(bp_result: Result<T, ButtplugClientError>, actuator: Arc
         Ok(t) => Ok(t),
         Err(err) => Err(WorkerError { 
             bp_error: err, 
-            actuator: actuator.clone() 
+            actuator: actuator.clone(),
+            related: vec![],
         }),
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use buttplug::core::message::ActuatorType;
+
+    use crate::speed::Speed;
+    use crate::tests::{PlayerTest, wait_ms};
+
+    use bp_fakes::*;
+
+    use super::WorkerTask;
+
+    #[tokio::test]
+    async fn test_worker_channel_sends_custom_tasks() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let mut channel = player.scheduler.worker_channel();
+        let actuator = player.actuators[0].clone();
+
+        // act
+        let start = Instant::now();
+        assert!(channel.send(WorkerTask::Start(actuator.clone(), Speed::new(50), false, channel.handle())));
+        wait_ms(50).await;
+        let (id, result_sender) = channel.request();
+        channel.send(WorkerTask::End(actuator, false, channel.handle(), id, result_sender));
+        let response = channel.next_result().await.unwrap();
+
+        // assert
+        assert_eq!(response.id, id);
+        assert!(response.result.is_ok());
+        client.print_device_calls(start);
+        client.get_device_calls(1)[0].assert_strenth(0.5).assert_time(0, start);
+        client.get_device_calls(1)[1].assert_strenth(0.0).assert_time(50, start);
+    }
+
+    #[tokio::test]
+    async fn test_action_stats_record_the_scaled_speed() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        player.scheduler.set_global_intensity(Speed::new(50));
+        let mut channel = player.scheduler.worker_channel();
+        let actuator = player.actuators[0].clone();
+        player.scheduler.action_stats.start(channel.handle(), "vibrate");
+
+        // act
+        channel.send(WorkerTask::Start(actuator.clone(), Speed::new(80), false, channel.handle()));
+        wait_ms(100).await;
+        let (id, result_sender) = channel.request();
+        channel.send(WorkerTask::End(actuator, false, channel.handle(), id, result_sender));
+        channel.next_result().await.unwrap();
+        player.scheduler.action_stats.finish(channel.handle(), "vibrate");
+
+        // assert
+        let stats = &player.scheduler.action_stats.get_all()["vibrate"];
+        assert!((stats.average_intensity() - 0.4).abs() < 0.05, "{}", stats.average_intensity());
+        client.get_device_calls(1)[0].assert_strenth(0.4);
+    }
+
+    #[tokio::test]
+    async fn test_probe_is_scaled_by_global_intensity() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        player.scheduler.set_global_intensity(Speed::new(50));
+        let mut channel = player.scheduler.worker_channel();
+        let actuator = player.actuators[0].clone();
+
+        // act
+        let start = Instant::now();
+        let result = channel.probe(&actuator, 0.8, 0).await;
+
+        // assert
+        assert!(result.is_ok());
+        client.print_device_calls(start);
+        client.get_device_calls(1)[0].assert_strenth(0.4);
+    }
+
+    #[tokio::test]
+    async fn test_probe_is_refused_while_a_task_drives_the_actuator() {
+        // arrange
+        let client = get_test_client(vec![scalar(1, "vib1", ActuatorType::Vibrate)]).await;
+        let mut player = PlayerTest::setup_no_settings(&client.created_devices);
+        let mut channel = player.scheduler.worker_channel();
+        let actuator = player.actuators[0].clone();
+
+        // act
+        let start = Instant::now();
+        player.play_scalar(Duration::from_millis(100), Speed::new(70));
+        wait_ms(50).await;
+        let result = channel.probe(&actuator, 0.0, 0).await;
+        player.await_all().await;
+
+        // assert
+        assert!(result.is_err());
+        client.print_device_calls(start);
+        client.get_device_calls(1)[0].assert_strenth(0.7).assert_time(0, start);
+        client.get_device_calls(1)[1].assert_strenth(0.0).assert_time(100, start);
+    }
+}
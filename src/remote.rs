@@ -0,0 +1,326 @@
+//! Compact delta protocol to control actuators over constrained links (serial, udp...)
+//!
+//! Both ends agree on a channel index (0-255) for each actuator, e.g. by the order of
+//! the actuators. The encoder only sends the channels that changed since the last frame,
+//! a full keyframe every `keyframe_interval` frames and a heartbeat while nothing changes,
+//! so the receiver can tell an idle link from a dead one.
+//!
+//! Frame layout, all numbers little endian:
+//!
+//! | kind u8 | seq u16 | count u8 | (channel u8, value u16) * count | checksum u8 |
+//!
+//! Values are fractions of 0.0-1.0 in steps of 1/10000, the checksum is the xor of all
+//! preceding bytes.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+const KIND_KEYFRAME: u8 = 0;
+const KIND_DELTA: u8 = 1;
+const KIND_HEARTBEAT: u8 = 2;
+const HEADER_LEN: usize = 4;
+const VALUE_SCALE: f64 = 10_000.0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameKind {
+    /// values of all channels
+    Keyframe,
+    /// values of the channels that changed
+    Delta,
+    /// nothing changed
+    Heartbeat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    Truncated,
+    Checksum,
+    UnknownKind(u8),
+    /// frames were lost, deltas are dropped until the next keyframe
+    Gap { expected: u16, received: u16 },
+    /// a delta arrived after frames were lost
+    WaitingForKeyframe,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "truncated frame"),
+            DecodeError::Checksum => write!(f, "checksum mismatch"),
+            DecodeError::UnknownKind(kind) => write!(f, "unknown frame kind {}", kind),
+            DecodeError::Gap { expected, received } => {
+                write!(f, "expected frame {} but received {}", expected, received)
+            }
+            DecodeError::WaitingForKeyframe => write!(f, "waiting for keyframe"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeltaSettings {
+    /// every n-th frame contains all channels, so receivers recover from lost frames
+    pub keyframe_interval: u16,
+    /// frame that is sent when nothing changed for this long
+    pub heartbeat_interval: Duration,
+}
+
+impl Default for DeltaSettings {
+    fn default() -> Self {
+        Self {
+            keyframe_interval: 50,
+            heartbeat_interval: Duration::from_millis(1_000),
+        }
+    }
+}
+
+/// Turns the latest value of each channel into frames
+#[derive(Debug)]
+pub struct DeltaEncoder {
+    settings: DeltaSettings,
+    /// latest values that were set
+    current: BTreeMap<u8, u16>,
+    /// values the receiver knows about
+    sent: BTreeMap<u8, u16>,
+    seq: u16,
+    frames_since_keyframe: Option<u16>,
+    last_frame: Option<Instant>,
+}
+
+impl DeltaEncoder {
+    pub fn new(settings: DeltaSettings) -> Self {
+        DeltaEncoder {
+            settings,
+            current: BTreeMap::new(),
+            sent: BTreeMap::new(),
+            seq: 0,
+            frames_since_keyframe: None,
+            last_frame: None,
+        }
+    }
+
+    /// Sets the speed or position (0.0-1.0) of 'channel' for the next frame
+    pub fn set(&mut self, channel: u8, value: f64) {
+        self.current.insert(channel, encode_value(value));
+    }
+
+    /// Makes the next frame a keyframe, e.g. after the receiver reported a gap
+    pub fn request_keyframe(&mut self) {
+        self.frames_since_keyframe = None;
+    }
+
+    /// Next frame to send, None if nothing changed and no heartbeat is due
+    pub fn frame(&mut self, now: Instant) -> Option<Vec<u8>> {
+        let keyframe_due = match self.frames_since_keyframe {
+            Some(frames) => frames + 1 >= self.settings.keyframe_interval.max(1),
+            None => true,
+        };
+        let heartbeat_due = match self.last_frame {
+            Some(last_frame) => now.saturating_duration_since(last_frame) >= self.settings.heartbeat_interval,
+            None => true,
+        };
+        let (kind, values) = if keyframe_due {
+            (KIND_KEYFRAME, self.current.clone())
+        } else {
+            let changed = self
+                .current
+                .iter()
+                .filter(|(channel, value)| self.sent.get(channel) != Some(value))
+                .map(|(channel, value)| (*channel, *value))
+                .collect::<BTreeMap<_, _>>();
+            if !changed.is_empty() {
+                (KIND_DELTA, changed)
+            } else if heartbeat_due {
+                (KIND_HEARTBEAT, BTreeMap::new())
+            } else {
+                return None;
+            }
+        };
+        self.frames_since_keyframe = match kind {
+            KIND_KEYFRAME => Some(0),
+            _ => self.frames_since_keyframe.map(|x| x + 1),
+        };
+        self.sent.extend(values.iter().map(|(channel, value)| (*channel, *value)));
+        self.last_frame = Some(now);
+        let frame = encode_frame(kind, self.seq, &values);
+        self.seq = self.seq.wrapping_add(1);
+        Some(frame)
+    }
+}
+
+/// Keeps the values of all channels up to date with the received frames
+#[derive(Debug, Default)]
+pub struct DeltaDecoder {
+    values: BTreeMap<u8, u16>,
+    next_seq: Option<u16>,
+    /// a frame was lost, waiting for the next keyframe
+    stale: bool,
+}
+
+impl DeltaDecoder {
+    /// Applies 'frame' and returns its kind, the values are available through `value`
+    pub fn decode(&mut self, frame: &[u8]) -> Result<FrameKind, DecodeError> {
+        if frame.len() < HEADER_LEN + 1 {
+            return Err(DecodeError::Truncated);
+        }
+        let (content, checksum) = frame.split_at(frame.len() - 1);
+        if content.iter().fold(0, |acc, x| acc ^ x) != checksum[0] {
+            return Err(DecodeError::Checksum);
+        }
+        let seq = u16::from_le_bytes([content[1], content[2]]);
+        let count = content[3] as usize;
+        if content.len() != HEADER_LEN + count * 3 {
+            return Err(DecodeError::Truncated);
+        }
+        let kind = match content[0] {
+            KIND_KEYFRAME => FrameKind::Keyframe,
+            KIND_DELTA => FrameKind::Delta,
+            KIND_HEARTBEAT => FrameKind::Heartbeat,
+            kind => return Err(DecodeError::UnknownKind(kind)),
+        };
+        let expected = self.next_seq;
+        self.next_seq = Some(seq.wrapping_add(1));
+        if kind == FrameKind::Keyframe {
+            self.values.clear();
+            self.stale = false;
+        } else if let Some(expected) = expected.filter(|x| *x != seq) {
+            self.stale = true;
+            return Err(DecodeError::Gap { expected, received: seq });
+        } else if self.stale {
+            return Err(DecodeError::WaitingForKeyframe);
+        }
+        for entry in content[HEADER_LEN..].chunks_exact(3) {
+            self.values.insert(entry[0], u16::from_le_bytes([entry[1], entry[2]]));
+        }
+        Ok(kind)
+    }
+
+    /// Latest value (0.0-1.0) of 'channel', None before it was received
+    /// or while frames were lost
+    pub fn value(&self, channel: u8) -> Option<f64> {
+        if self.stale {
+            return None;
+        }
+        self.values.get(&channel).map(|x| *x as f64 / VALUE_SCALE)
+    }
+}
+
+fn encode_value(value: f64) -> u16 {
+    (value.clamp(0.0, 1.0) * VALUE_SCALE).round() as u16
+}
+
+fn encode_frame(kind: u8, seq: u16, values: &BTreeMap<u8, u16>) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER_LEN + values.len() * 3 + 1);
+    frame.push(kind);
+    frame.extend_from_slice(&seq.to_le_bytes());
+    frame.push(values.len() as u8);
+    for (channel, value) in values {
+        frame.push(*channel);
+        frame.extend_from_slice(&value.to_le_bytes());
+    }
+    frame.push(frame.iter().fold(0, |acc, x| acc ^ x));
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> DeltaSettings {
+        DeltaSettings {
+            keyframe_interval: 4,
+            heartbeat_interval: Duration::from_millis(100),
+        }
+    }
+
+    #[test]
+    fn only_changed_channels_are_sent() {
+        let now = Instant::now();
+        let mut encoder = DeltaEncoder::new(settings());
+        let mut decoder = DeltaDecoder::default();
+        encoder.set(0, 0.5);
+        encoder.set(1, 0.25);
+
+        let keyframe = encoder.frame(now).unwrap();
+        assert_eq!(decoder.decode(&keyframe), Ok(FrameKind::Keyframe));
+        assert_eq!(keyframe.len(), 5 + 2 * 3);
+
+        encoder.set(0, 0.5);
+        encoder.set(1, 0.75);
+        let delta = encoder.frame(now).unwrap();
+        assert_eq!(delta.len(), 5 + 3);
+        assert_eq!(decoder.decode(&delta), Ok(FrameKind::Delta));
+        assert_eq!(decoder.value(0), Some(0.5));
+        assert_eq!(decoder.value(1), Some(0.75));
+        assert_eq!(decoder.value(2), None);
+    }
+
+    #[test]
+    fn heartbeats_are_sent_while_idle() {
+        let now = Instant::now();
+        let mut encoder = DeltaEncoder::new(settings());
+        let mut decoder = DeltaDecoder::default();
+        encoder.set(0, 1.0);
+        decoder.decode(&encoder.frame(now).unwrap()).unwrap();
+
+        assert_eq!(encoder.frame(now + Duration::from_millis(50)), None);
+        let heartbeat = encoder.frame(now + Duration::from_millis(100)).unwrap();
+        assert_eq!(decoder.decode(&heartbeat), Ok(FrameKind::Heartbeat));
+        assert_eq!(decoder.value(0), Some(1.0));
+    }
+
+    #[test]
+    fn lost_frames_are_recovered_by_keyframes() {
+        let now = Instant::now();
+        let mut encoder = DeltaEncoder::new(settings());
+        let mut decoder = DeltaDecoder::default();
+        encoder.set(0, 0.1);
+        decoder.decode(&encoder.frame(now).unwrap()).unwrap();
+        encoder.set(0, 0.2);
+        let _lost = encoder.frame(now).unwrap();
+        encoder.set(0, 0.3);
+
+        assert_eq!(
+            decoder.decode(&encoder.frame(now).unwrap()),
+            Err(DecodeError::Gap { expected: 1, received: 2 })
+        );
+        assert_eq!(decoder.value(0), None);
+        encoder.set(0, 0.35);
+        assert_eq!(decoder.decode(&encoder.frame(now).unwrap()), Err(DecodeError::WaitingForKeyframe));
+
+        encoder.request_keyframe();
+        encoder.set(0, 0.4);
+        assert_eq!(decoder.decode(&encoder.frame(now).unwrap()), Ok(FrameKind::Keyframe));
+        assert_eq!(decoder.value(0), Some(0.4));
+    }
+
+    #[test]
+    fn keyframes_are_sent_periodically() {
+        let now = Instant::now();
+        let mut encoder = DeltaEncoder::new(settings());
+        let kinds = (0..6)
+            .map(|i| {
+                encoder.set(0, i as f64 / 10.0);
+                encoder.frame(now).unwrap()[0]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, vec![KIND_KEYFRAME, KIND_DELTA, KIND_DELTA, KIND_DELTA, KIND_KEYFRAME, KIND_DELTA]);
+    }
+
+    #[test]
+    fn corrupted_frames_are_rejected() {
+        let mut encoder = DeltaEncoder::new(settings());
+        let mut decoder = DeltaDecoder::default();
+        encoder.set(3, 0.5);
+        let mut frame = encoder.frame(Instant::now()).unwrap();
+        frame[5] ^= 0xff;
+
+        assert_eq!(decoder.decode(&frame), Err(DecodeError::Checksum));
+        assert_eq!(decoder.decode(&frame[..3]), Err(DecodeError::Truncated));
+        assert_eq!(decoder.value(3), None);
+    }
+}
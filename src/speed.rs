@@ -1,16 +1,54 @@
-use std::fmt::{Display, self};
+use std::{fmt::{Display, self}, ops::{Add, Mul, Sub}};
 
 use funscript::FSPoint;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// Intensity in percent with a precision of 0.1%
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(from = "SpeedRepr", into = "SpeedRepr")]
 pub struct Speed {
+    /// whole percentage, truncated
     pub value: u16,
+    /// tenths of a percent on top of `value`
+    tenths: u16,
+}
+
+/// Serialized form, whole percentages stay integers like in older configs
+#[derive(Serialize, Deserialize)]
+struct SpeedRepr {
+    value: Percentage,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Percentage {
+    Whole(u16),
+    Fraction(f64),
+}
+
+impl From<SpeedRepr> for Speed {
+    fn from(repr: SpeedRepr) -> Self {
+        match repr.value {
+            Percentage::Whole(percentage) => Speed::new(percentage.into()),
+            Percentage::Fraction(percentage) => Speed::from_percent(percentage),
+        }
+    }
+}
+
+impl From<Speed> for SpeedRepr {
+    fn from(speed: Speed) -> Self {
+        SpeedRepr {
+            value: match speed.tenths {
+                0 => Percentage::Whole(speed.value),
+                _ => Percentage::Fraction(speed.as_percent()),
+            },
+        }
+    }
 }
 
 impl Display for Speed {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.value)
+        write!(f, "{}", self.as_percent())
     }
 }
 
@@ -24,26 +62,181 @@ impl Speed {
         }
         Speed {
             value: percentage as u16,
+            tenths: 0,
         }
     }
+    fn from_permille(permille: u16) -> Speed {
+        Speed {
+            value: permille / 10,
+            tenths: permille % 10,
+        }
+    }
+    fn permille(&self) -> u16 {
+        self.value.saturating_mul(10).saturating_add(self.tenths)
+    }
+    /// Fractional percentage, saturates at 0 and 100 (NaN is 0)
+    pub fn from_percent(percentage: f64) -> Speed {
+        Speed::from_float(percentage / 100.0)
+    }
+    /// Factor between 0.0 and 1.0 truncated to 0.1%, saturates outside of that range (NaN is 0)
     pub fn from_float(factor: f64) -> Speed {
-        Speed::new((factor * 100.0) as i64)    
+        if factor.is_nan() {
+            return Speed::min();
+        }
+        // tolerates the representation error of factors like 0.29
+        Speed::from_permille((factor * 1000.0 + 1e-6).clamp(0.0, 1000.0) as u16)
     }
     pub fn multiply(&self, other: &Speed) -> Speed {
-        let this = self.as_float();
-        let other_f = other.as_float();
-        Speed::new((this * other_f * 100.0).round() as i64) 
+        *self * *other
     }
     pub fn from_fs(point: &FSPoint) -> Speed {
         Speed::new(point.pos.into())
     }
     pub fn min() -> Speed {
-        Speed { value: 0 }
+        Speed::from_permille(0)
     }
     pub fn max() -> Speed {
-        Speed { value: 100 }
+        Speed::from_permille(1000)
+    }
+    /// Percentage rounded to a whole number
+    pub fn rounded(&self) -> u16 {
+        (self.permille() + 5) / 10
+    }
+    pub fn as_percent(&self) -> f64 {
+        self.permille() as f64 / 10.0
     }
     pub fn as_float(self) -> f64 {
-        self.value as f64 / 100.0
+        self.permille() as f64 / 1000.0
+    }
+}
+
+/// Scales one speed by the other, e.g. 50% * 50% = 25%
+impl Mul for Speed {
+    type Output = Speed;
+
+    fn mul(self, rhs: Speed) -> Speed {
+        Speed::from_permille(((self.permille() as u32 * rhs.permille() as u32 + 500) / 1000) as u16)
+    }
+}
+
+/// Scales the speed by a factor, saturating at 0 and 100%
+impl Mul<f64> for Speed {
+    type Output = Speed;
+
+    fn mul(self, rhs: f64) -> Speed {
+        Speed::from_float(self.as_float() * rhs)
+    }
+}
+
+/// Saturating addition
+impl Add for Speed {
+    type Output = Speed;
+
+    fn add(self, rhs: Speed) -> Speed {
+        Speed::from_permille((self.permille() + rhs.permille()).min(1000))
+    }
+}
+
+/// Saturating subtraction
+impl Sub for Speed {
+    type Output = Speed;
+
+    fn sub(self, rhs: Speed) -> Speed {
+        Speed::from_permille(self.permille().saturating_sub(rhs.permille()))
+    }
+}
+
+/// Speeds that tasks step through with `ButtplugScheduler::step_up` and `step_down`,
+/// e.g. for hotkeys
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpeedLadder(pub Vec<Speed>);
+
+impl Default for SpeedLadder {
+    fn default() -> Self {
+        SpeedLadder([10, 25, 50, 75, 100].into_iter().map(Speed::new).collect())
+    }
+}
+
+impl SpeedLadder {
+    /// Lowest step above 'current', the highest step if there is none
+    pub fn step_up(&self, current: Speed) -> Speed {
+        let above = self.0.iter().filter(|x| **x > current).min();
+        above.or(self.0.iter().max()).copied().unwrap_or(current)
     }
-}
\ No newline at end of file
+
+    /// Highest step below 'current', the lowest step if there is none
+    pub fn step_down(&self, current: Speed) -> Speed {
+        let below = self.0.iter().filter(|x| **x < current).max();
+        below.or(self.0.iter().min()).copied().unwrap_or(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ladder_steps_to_the_next_speed() {
+        let ladder = SpeedLadder::default();
+        assert_eq!(ladder.step_up(Speed::new(10)), Speed::new(25));
+        assert_eq!(ladder.step_up(Speed::new(30)), Speed::new(50));
+        assert_eq!(ladder.step_up(Speed::max()), Speed::max());
+        assert_eq!(ladder.step_down(Speed::new(30)), Speed::new(25));
+        assert_eq!(ladder.step_down(Speed::new(10)), Speed::new(10));
+        assert_eq!(ladder.step_down(Speed::min()), Speed::new(10));
+        assert_eq!(SpeedLadder(vec![]).step_up(Speed::new(42)), Speed::new(42));
+    }
+
+    #[test]
+    fn speed_keeps_fractional_percentages() {
+        let speed = Speed::from_percent(2.5);
+        assert_eq!(speed.as_float(), 0.025);
+        assert_eq!(speed.to_string(), "2.5");
+        assert_eq!(Speed::new(40).to_string(), "40");
+        assert_eq!((Speed::new(5) * Speed::new(50)).as_percent(), 2.5);
+        assert_eq!(Speed::from_percent(f64::NAN), Speed::min());
+        assert_eq!(Speed::from_percent(250.0), Speed::max());
+    }
+
+    #[test]
+    fn low_intensities_survive_multiplication() {
+        assert_eq!((Speed::new(10) * Speed::new(10)).as_percent(), 1.0);
+        assert_eq!((Speed::new(10) * Speed::new(4)).as_percent(), 0.4);
+        assert_eq!((Speed::new(10) * Speed::new(4) * Speed::new(50)).as_percent(), 0.2);
+        assert_eq!(Speed::new(1) * Speed::new(1), Speed::min());
+    }
+
+    #[test]
+    fn speed_arithmetic_saturates() {
+        assert_eq!(Speed::new(70) + Speed::new(50), Speed::max());
+        assert_eq!(Speed::new(20) - Speed::new(50), Speed::min());
+        assert_eq!(Speed::new(60) * 0.5, Speed::new(30));
+        assert_eq!(Speed::new(60) * -1.0, Speed::min());
+        assert!(Speed::from_percent(50.1) > Speed::new(50));
+    }
+
+    #[test]
+    fn speed_deserializes_integer_percentages() {
+        let speed: Speed = serde_json::from_str(r#"{ "value": 42 }"#).unwrap();
+        assert_eq!(speed, Speed::new(42));
+        assert_eq!(speed.value, 42);
+        let speed: Speed = serde_json::from_str(&serde_json::to_string(&Speed::from_percent(12.3)).unwrap()).unwrap();
+        assert_eq!(speed.as_percent(), 12.3);
+        assert_eq!(serde_json::to_string(&Speed::from_percent(12.3)).unwrap(), r#"{"value":12.3}"#);
+    }
+
+    #[test]
+    fn speed_round_trips_the_old_format() {
+        let fixture = include_str!("../tests/fixtures/speeds_v1.json").trim();
+        let speeds: Vec<Speed> = serde_json::from_str(fixture).unwrap();
+        assert_eq!(speeds, vec![Speed::new(0), Speed::new(5), Speed::new(42), Speed::new(100)]);
+        assert_eq!(serde_json::to_string(&speeds).unwrap(), fixture);
+    }
+
+    #[test]
+    fn speed_from_float_truncates() {
+        assert_eq!(Speed::from_float(0.4259).as_percent(), 42.5);
+        assert_eq!(Speed::from_float(0.29), Speed::new(29));
+        assert_eq!(Speed::from_percent(12.39).value, 12);
+    }
+}
@@ -0,0 +1,77 @@
+use std::future::Future;
+
+use tokio::{
+    runtime::{Builder, Handle},
+    task::JoinHandle,
+    time::Instant,
+};
+
+use crate::{player::worker::ButtplugWorker, ButtplugScheduler, PlayerSettings};
+
+/// Runs 'future' on a current-thread runtime with a paused clock.
+///
+/// Timers auto-advance whenever all tasks are idle, so players and the worker
+/// run through long durations without actually waiting for them
+pub fn run_paused<F: Future>(future: F) -> F::Output {
+    Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .expect("failed building paused runtime")
+        .block_on(future)
+}
+
+/// Creates a scheduler and runs its worker on the current runtime
+pub fn create_scheduler(settings: PlayerSettings) -> (ButtplugScheduler, JoinHandle<()>) {
+    let (scheduler, mut worker): (ButtplugScheduler, ButtplugWorker) = ButtplugScheduler::create(settings);
+    let worker = Handle::current().spawn(async move {
+        worker.run_worker_thread().await;
+    });
+    (scheduler, worker)
+}
+
+/// Measures elapsed time on the tokio clock, which is the simulated time when paused
+#[derive(Debug, Clone, Copy)]
+pub struct TestClock {
+    start: Instant,
+}
+
+impl TestClock {
+    pub fn start() -> Self {
+        TestClock {
+            start: Instant::now(),
+        }
+    }
+
+    pub fn elapsed_ms(&self) -> u128 {
+        self.start.elapsed().as_millis()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::speed::Speed;
+
+    use super::*;
+
+    #[test]
+    fn paused_clock_skips_waiting_time() {
+        let real_start = std::time::Instant::now();
+
+        let simulated_ms = run_paused(async {
+            let (mut scheduler, _) = create_scheduler(PlayerSettings {
+                scalar_resolution_ms: 1,
+                ..Default::default()
+            });
+            let clock = TestClock::start();
+            let player = scheduler.create_player(vec![], -1);
+            let _ = player.play_scalar(Duration::from_secs(600), Speed::max()).await;
+            clock.elapsed_ms()
+        });
+
+        assert!(simulated_ms >= 600_000);
+        assert!(real_start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,66 @@
+//! Redaction changes the process-wide redactor, so it is tested in its own
+//! binary where it cannot change the identifiers other tests expect
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use bp_scheduler::{
+    client::BpClient,
+    config::{
+        actuators::ActuatorSettings,
+        client::{ClientSettings, InProcessFeatures, LoggingSettings},
+        logging::Redaction,
+    },
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for CapturedLogs {
+    type Writer = CapturedLogs;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[test]
+fn redacted_identifiers_are_not_logged() {
+    let logs = CapturedLogs::default();
+    let subscriber = tracing_subscriber::fmt().with_writer(logs.clone()).with_ansi(false).finish();
+    tracing::subscriber::with_default(subscriber, || {
+        let settings = ClientSettings {
+            in_process_features: InProcessFeatures { bluetooth: false, serial: false, xinput: false },
+            logging: LoggingSettings { redaction: Redaction::Alias, ..Default::default() },
+            ..Default::default()
+        };
+        let mut client = BpClient::connect(settings, ActuatorSettings::default()).unwrap();
+        client.set_enabled("Secret Toy (Vibrate)", true);
+        assert!(logs.text().contains("actuator-1"));
+        assert!(!logs.text().contains("Secret Toy"));
+
+        client.set_logging(LoggingSettings::default());
+        client.set_enabled("Secret Toy (Vibrate)", false);
+        assert!(logs.text().contains("Secret Toy"));
+    });
+}